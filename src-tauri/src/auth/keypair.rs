@@ -108,6 +108,24 @@ pub fn generate_keypair() -> Result<crate::auth::KeypairFiles, String> {
     })
 }
 
+/// Reads the private key from a key file: 32 bytes, hex-encoded.
+pub fn read_private_key_file(path: &std::path::Path) -> Result<[u8; 32], String> {
+    let key_hex =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read key file: {}", e))?;
+    let mut key_bytes_vec = hex::decode(key_hex.trim())
+        .map_err(|_| "Invalid key file: expected hex-encoded private key".to_string())?;
+
+    if key_bytes_vec.len() != 32 {
+        key_bytes_vec.zeroize();
+        return Err("Invalid key file: expected 32-byte (64 hex char) private key".to_string());
+    }
+
+    let mut private_key = [0u8; 32];
+    private_key.copy_from_slice(&key_bytes_vec);
+    key_bytes_vec.zeroize();
+    Ok(private_key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,763 @@
+//! Deduplicating incremental backups.
+//!
+//! A store is a directory with two parts:
+//!
+//! - `chunks/ab/abcd…` — one file per distinct entry row, named by the SHA-256
+//!   of its contents. Rows are stored exactly as they sit in `entries`, so
+//!   titles and texts stay AES-GCM ciphertext. An entry that has not changed
+//!   since the last snapshot keeps its ciphertext and therefore its chunk, so
+//!   each snapshot only writes the rows edited since the previous one.
+//! - `snapshots/snapshot-YYYY-MM-DD-HHhMMmSS.json` — a small manifest per
+//!   snapshot listing the chunk of every entry, plus the auth slots and
+//!   metadata rows needed to rebuild a complete diary.
+//!
+//! Any snapshot can be rebuilt into a standalone `diary.db` that opens with
+//! the same credentials as the diary at the time of the snapshot.
+
+use super::{backups_to_keep, RetentionPolicy};
+use crate::db::schema::{self, DatabaseConnection, SCHEMA_VERSION};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{Local, NaiveDateTime};
+use log::{debug, info};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Manifest format version, bumped on incompatible layout changes.
+const MANIFEST_FORMAT: u32 = 1;
+
+/// Magic prefix of an entry chunk ("Mini Diarium Chunk", layout 1).
+const CHUNK_MAGIC: &[u8; 4] = b"MDC1";
+
+/// Length marker for a NULL blob inside a chunk.
+const NULL_LEN: u32 = u32::MAX;
+
+const SNAPSHOT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d-%Hh%Mm%S";
+
+/// Summary of one snapshot in a store.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    /// Local time the snapshot was taken (`YYYY-MM-DDTHH:MM:SS`)
+    pub created_at: String,
+    pub entries: usize,
+    /// Chunks this snapshot had to write (entries new or changed since the last one)
+    pub new_chunks: usize,
+}
+
+/// Result of pruning a store.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    pub snapshots_deleted: usize,
+    pub chunks_deleted: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    created_at: String,
+    schema_version: i32,
+    new_chunks: usize,
+    entries: Vec<ManifestEntry>,
+    auth_slots: Vec<ManifestAuthSlot>,
+    metadata: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    id: i64,
+    chunk: String,
+}
+
+/// An auth slot row; blobs are base64. Wrapped keys are useless without the
+/// matching password or private key, exactly as in `diary.db`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestAuthSlot {
+    id: i64,
+    #[serde(rename = "type")]
+    slot_type: String,
+    label: String,
+    public_key: Option<String>,
+    wrapped_key: String,
+    created_at: String,
+    last_used: Option<String>,
+}
+
+/// One `entries` row, ciphertext untouched.
+#[derive(Debug, Clone, PartialEq)]
+struct EntryRow {
+    date: String,
+    title_encrypted: Option<Vec<u8>>,
+    text_encrypted: Option<Vec<u8>>,
+    word_count: i32,
+    date_created: String,
+    date_updated: String,
+}
+
+/// Location of a journal's incremental store inside its backups directory.
+pub fn store_dir(backups_dir: &Path) -> PathBuf {
+    backups_dir.join("incremental")
+}
+
+/// Takes a snapshot of the open diary into the store at `store_dir`.
+pub fn create_snapshot(db: &DatabaseConnection, store_dir: &Path) -> Result<SnapshotInfo, String> {
+    create_snapshot_at(db, store_dir, Local::now().naive_local())
+}
+
+fn create_snapshot_at(
+    db: &DatabaseConnection,
+    store_dir: &Path,
+    timestamp: NaiveDateTime,
+) -> Result<SnapshotInfo, String> {
+    let chunks_dir = store_dir.join("chunks");
+    let snapshots_dir = store_dir.join("snapshots");
+    fs::create_dir_all(&chunks_dir).map_err(|e| format!("Failed to create backup store: {}", e))?;
+    fs::create_dir_all(&snapshots_dir)
+        .map_err(|e| format!("Failed to create backup store: {}", e))?;
+
+    // One read transaction so entries, slots and metadata come from the same state
+    let tx = db
+        .conn()
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to start snapshot: {}", e))?;
+    let rows = read_entry_rows(&tx)?;
+    let auth_slots = read_auth_slots(&tx)?;
+    let metadata = read_metadata(&tx)?;
+    tx.commit()
+        .map_err(|e| format!("Failed to finish snapshot read: {}", e))?;
+
+    let mut new_chunks = 0;
+    let mut entries = Vec::with_capacity(rows.len());
+    for (id, row) in rows {
+        let bytes = encode_entry_row(&row);
+        let chunk = hex::encode(Sha256::digest(&bytes));
+        if write_chunk(&chunks_dir, &chunk, &bytes)? {
+            new_chunks += 1;
+        }
+        entries.push(ManifestEntry { id, chunk });
+    }
+
+    let manifest = Manifest {
+        format: MANIFEST_FORMAT,
+        created_at: timestamp.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        schema_version: SCHEMA_VERSION,
+        new_chunks,
+        entries,
+        auth_slots,
+        metadata,
+    };
+    let name = format!("snapshot-{}", timestamp.format(SNAPSHOT_TIMESTAMP_FORMAT));
+    let json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize snapshot manifest: {}", e))?;
+    // Chunks are all on disk before the manifest appears, so a manifest never
+    // references a missing chunk even if the app dies mid-snapshot.
+    write_atomically(&snapshots_dir.join(format!("{}.json", name)), &json)?;
+
+    info!(
+        "Incremental backup {} written ({} entries, {} new chunks)",
+        name,
+        manifest.entries.len(),
+        new_chunks
+    );
+    Ok(SnapshotInfo {
+        name,
+        created_at: manifest.created_at,
+        entries: manifest.entries.len(),
+        new_chunks,
+    })
+}
+
+/// Lists the snapshots in a store, newest first.
+pub fn list_snapshots(store_dir: &Path) -> Result<Vec<SnapshotInfo>, String> {
+    dated_snapshots(store_dir)?
+        .into_iter()
+        .map(|(_, name)| {
+            let manifest = read_manifest(store_dir, &name)?;
+            Ok(SnapshotInfo {
+                name,
+                created_at: manifest.created_at,
+                entries: manifest.entries.len(),
+                new_chunks: manifest.new_chunks,
+            })
+        })
+        .collect()
+}
+
+/// Rebuilds snapshot `name` into a new standalone diary at `dest_path`.
+///
+/// Every chunk is checked against its hash before use. The rebuilt file
+/// opens with the credentials that were valid when the snapshot was taken.
+/// Returns the number of entries restored.
+pub fn restore_snapshot(store_dir: &Path, name: &str, dest_path: &Path) -> Result<usize, String> {
+    if dest_path.exists() {
+        return Err(format!(
+            "Refusing to overwrite existing file: {}",
+            dest_path.display()
+        ));
+    }
+    let manifest = read_manifest(store_dir, name)?;
+    if manifest.schema_version != SCHEMA_VERSION {
+        return Err(format!(
+            "Snapshot uses schema v{} (expected v{})",
+            manifest.schema_version, SCHEMA_VERSION
+        ));
+    }
+
+    let mut tmp_name = dest_path.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+    let _ = fs::remove_file(&tmp_path);
+
+    let result = build_diary(store_dir, &manifest, &tmp_path).and_then(|()| {
+        fs::rename(&tmp_path, dest_path)
+            .map_err(|e| format!("Failed to finalize restored diary: {}", e))
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    info!(
+        "Restored incremental backup {} to {:?} ({} entries)",
+        name,
+        dest_path,
+        manifest.entries.len()
+    );
+    Ok(manifest.entries.len())
+}
+
+/// Deletes snapshots outside `policy`, then every chunk no remaining snapshot uses.
+pub fn prune_snapshots(store_dir: &Path, policy: &RetentionPolicy) -> Result<PruneReport, String> {
+    let snapshots = dated_snapshots(store_dir)?;
+    let timestamps: Vec<NaiveDateTime> = snapshots.iter().map(|(t, _)| *t).collect();
+    let keep = backups_to_keep(&timestamps, policy);
+
+    let mut report = PruneReport::default();
+    let mut referenced = HashSet::new();
+    for ((_, name), keep) in snapshots.iter().zip(keep) {
+        if keep {
+            let manifest = read_manifest(store_dir, name)?;
+            referenced.extend(manifest.entries.into_iter().map(|e| e.chunk));
+        } else {
+            fs::remove_file(manifest_path(store_dir, name))
+                .map_err(|e| format!("Failed to delete old snapshot: {}", e))?;
+            report.snapshots_deleted += 1;
+        }
+    }
+
+    let chunks_dir = store_dir.join("chunks");
+    if chunks_dir.is_dir() {
+        for shard in fs::read_dir(&chunks_dir)
+            .map_err(|e| format!("Failed to read chunk store: {}", e))?
+            .filter_map(|e| e.ok())
+        {
+            let Ok(files) = fs::read_dir(shard.path()) else {
+                continue;
+            };
+            for file in files.filter_map(|e| e.ok()) {
+                let name = file.file_name().to_string_lossy().to_string();
+                if !referenced.contains(&name) {
+                    fs::remove_file(file.path())
+                        .map_err(|e| format!("Failed to delete unused chunk: {}", e))?;
+                    report.chunks_deleted += 1;
+                }
+            }
+        }
+    }
+
+    debug!(
+        "Pruned incremental backups: {} snapshots, {} chunks deleted",
+        report.snapshots_deleted, report.chunks_deleted
+    );
+    Ok(report)
+}
+
+// ─── Private helpers ────────────────────────────────────────────────────────
+
+fn manifest_path(store_dir: &Path, name: &str) -> PathBuf {
+    store_dir.join("snapshots").join(format!("{}.json", name))
+}
+
+fn chunk_path(chunks_dir: &Path, chunk: &str) -> PathBuf {
+    chunks_dir.join(&chunk[..2]).join(chunk)
+}
+
+/// Snapshot names with a valid timestamp, newest first.
+fn dated_snapshots(store_dir: &Path) -> Result<Vec<(NaiveDateTime, String)>, String> {
+    let snapshots_dir = store_dir.join("snapshots");
+    if !snapshots_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots: Vec<(NaiveDateTime, String)> = fs::read_dir(&snapshots_dir)
+        .map_err(|e| format!("Failed to read snapshots directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let file_name = e.file_name().to_str()?.to_string();
+            let name = file_name.strip_suffix(".json")?;
+            let stamp = name.strip_prefix("snapshot-")?;
+            let timestamp = NaiveDateTime::parse_from_str(stamp, SNAPSHOT_TIMESTAMP_FORMAT).ok()?;
+            Some((timestamp, name.to_string()))
+        })
+        .collect();
+    snapshots.sort_by(|a, b| b.cmp(a));
+    Ok(snapshots)
+}
+
+fn read_manifest(store_dir: &Path, name: &str) -> Result<Manifest, String> {
+    if name.contains(['/', '\\']) || !name.starts_with("snapshot-") {
+        return Err(format!("Not a snapshot name: {}", name));
+    }
+    let bytes = fs::read(manifest_path(store_dir, name))
+        .map_err(|e| format!("Failed to read snapshot {}: {}", name, e))?;
+    let manifest: Manifest = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Invalid snapshot manifest {}: {}", name, e))?;
+    if manifest.format != MANIFEST_FORMAT {
+        return Err(format!(
+            "Unsupported snapshot format {} in {}",
+            manifest.format, name
+        ));
+    }
+    Ok(manifest)
+}
+
+/// Writes `bytes` to `path` via a temporary file, so readers never see a partial file.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let mut tmp_name = path.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+    fs::write(&tmp_path, bytes)
+        .and_then(|()| fs::rename(&tmp_path, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            format!("Failed to write {:?}: {}", path, e)
+        })
+}
+
+/// Stores a chunk unless it already exists. Returns true if it was written.
+fn write_chunk(chunks_dir: &Path, chunk: &str, bytes: &[u8]) -> Result<bool, String> {
+    let path = chunk_path(chunks_dir, chunk);
+    if path.is_file() {
+        return Ok(false);
+    }
+    fs::create_dir_all(path.parent().unwrap_or(chunks_dir))
+        .map_err(|e| format!("Failed to create chunk directory: {}", e))?;
+    write_atomically(&path, bytes)?;
+    Ok(true)
+}
+
+fn read_chunk(chunks_dir: &Path, chunk: &str) -> Result<EntryRow, String> {
+    if chunk.len() != 64 || !chunk.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("Invalid chunk id: {}", chunk));
+    }
+    let bytes = fs::read(chunk_path(chunks_dir, chunk))
+        .map_err(|e| format!("Missing chunk {}: {}", chunk, e))?;
+    if hex::encode(Sha256::digest(&bytes)) != chunk {
+        return Err(format!("Chunk {} is corrupted", chunk));
+    }
+    decode_entry_row(&bytes).map_err(|e| format!("Chunk {} is unreadable: {}", chunk, e))
+}
+
+/// Creates a fresh diary at `path` holding exactly what `manifest` describes.
+fn build_diary(store_dir: &Path, manifest: &Manifest, path: &Path) -> Result<(), String> {
+    let chunks_dir = store_dir.join("chunks");
+    let conn =
+        Connection::open(path).map_err(|e| format!("Failed to create restored diary: {}", e))?;
+    schema::create_schema(&conn)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to begin restore: {}", e))?;
+    for entry in &manifest.entries {
+        let row = read_chunk(&chunks_dir, &entry.chunk)?;
+        tx.execute(
+            "INSERT INTO entries (id, date, title_encrypted, text_encrypted, word_count, date_created, date_updated)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                entry.id,
+                row.date,
+                row.title_encrypted,
+                row.text_encrypted,
+                row.word_count,
+                row.date_created,
+                row.date_updated
+            ],
+        )
+        .map_err(|e| format!("Failed to restore entry: {}", e))?;
+    }
+    for slot in &manifest.auth_slots {
+        let public_key = slot
+            .public_key
+            .as_deref()
+            .map(|b| general_purpose::STANDARD.decode(b))
+            .transpose()
+            .map_err(|e| format!("Invalid auth slot in manifest: {}", e))?;
+        let wrapped_key = general_purpose::STANDARD
+            .decode(&slot.wrapped_key)
+            .map_err(|e| format!("Invalid auth slot in manifest: {}", e))?;
+        tx.execute(
+            "INSERT INTO auth_slots (id, type, label, public_key, wrapped_key, created_at, last_used)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                slot.id,
+                slot.slot_type,
+                slot.label,
+                public_key,
+                wrapped_key,
+                slot.created_at,
+                slot.last_used
+            ],
+        )
+        .map_err(|e| format!("Failed to restore auth slot: {}", e))?;
+    }
+    for (key, value) in &manifest.metadata {
+        tx.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )
+        .map_err(|e| format!("Failed to restore metadata: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit restored diary: {}", e))
+}
+
+fn read_entry_rows(conn: &Connection) -> Result<Vec<(i64, EntryRow)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, date, title_encrypted, text_encrypted, word_count, date_created, date_updated \
+             FROM entries ORDER BY id ASC",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                EntryRow {
+                    date: row.get(1)?,
+                    title_encrypted: row.get(2)?,
+                    text_encrypted: row.get(3)?,
+                    word_count: row.get::<_, Option<i32>>(4)?.unwrap_or_default(),
+                    date_created: row.get(5)?,
+                    date_updated: row.get(6)?,
+                },
+            ))
+        })
+        .map_err(|e| format!("Failed to query entries: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read entries: {}", e))?;
+    Ok(rows)
+}
+
+fn read_auth_slots(conn: &Connection) -> Result<Vec<ManifestAuthSlot>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, type, label, public_key, wrapped_key, created_at, last_used \
+             FROM auth_slots ORDER BY id ASC",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let slots = stmt
+        .query_map([], |row| {
+            Ok(ManifestAuthSlot {
+                id: row.get(0)?,
+                slot_type: row.get(1)?,
+                label: row.get(2)?,
+                public_key: row
+                    .get::<_, Option<Vec<u8>>>(3)?
+                    .map(|b| general_purpose::STANDARD.encode(b)),
+                wrapped_key: general_purpose::STANDARD.encode(row.get::<_, Vec<u8>>(4)?),
+                created_at: row.get(5)?,
+                last_used: row.get(6)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query auth slots: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read auth slots: {}", e))?;
+    Ok(slots)
+}
+
+fn read_metadata(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM metadata ORDER BY key ASC")
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to query metadata: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read metadata: {}", e))?;
+    Ok(rows)
+}
+
+/// Layout: magic, then each field as `[u32 LE length][bytes]` (`u32::MAX` = NULL),
+/// with `word_count` as 4 bytes LE in its place.
+fn encode_entry_row(row: &EntryRow) -> Vec<u8> {
+    fn push_field(out: &mut Vec<u8>, field: Option<&[u8]>) {
+        match field {
+            Some(bytes) => {
+                out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                out.extend_from_slice(bytes);
+            }
+            None => out.extend_from_slice(&NULL_LEN.to_le_bytes()),
+        }
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(CHUNK_MAGIC);
+    push_field(&mut out, Some(row.date.as_bytes()));
+    push_field(&mut out, row.title_encrypted.as_deref());
+    push_field(&mut out, row.text_encrypted.as_deref());
+    out.extend_from_slice(&row.word_count.to_le_bytes());
+    push_field(&mut out, Some(row.date_created.as_bytes()));
+    push_field(&mut out, Some(row.date_updated.as_bytes()));
+    out
+}
+
+fn decode_entry_row(bytes: &[u8]) -> Result<EntryRow, String> {
+    struct Reader<'a>(&'a [u8]);
+    impl Reader<'_> {
+        fn take(&mut self, n: usize) -> Result<&[u8], String> {
+            if self.0.len() < n {
+                return Err("truncated".to_string());
+            }
+            let (head, rest) = self.0.split_at(n);
+            self.0 = rest;
+            Ok(head)
+        }
+        fn u32(&mut self) -> Result<u32, String> {
+            let b = self.take(4)?;
+            Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        }
+        fn blob(&mut self) -> Result<Option<Vec<u8>>, String> {
+            match self.u32()? {
+                NULL_LEN => Ok(None),
+                len => Ok(Some(self.take(len as usize)?.to_vec())),
+            }
+        }
+        fn text(&mut self) -> Result<String, String> {
+            let bytes = self.blob()?.ok_or("unexpected NULL")?;
+            String::from_utf8(bytes).map_err(|e| e.to_string())
+        }
+    }
+
+    let mut reader = Reader(bytes);
+    if reader.take(4)? != CHUNK_MAGIC {
+        return Err("bad magic".to_string());
+    }
+    let row = EntryRow {
+        date: reader.text()?,
+        title_encrypted: reader.blob()?,
+        text_encrypted: reader.blob()?,
+        word_count: reader.u32()? as i32,
+        date_created: reader.text()?,
+        date_updated: reader.text()?,
+    };
+    if !reader.0.is_empty() {
+        return Err("trailing bytes".to_string());
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{self, DiaryEntry};
+    use crate::db::schema::{create_database, open_database};
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 0,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: queries::count_words(text),
+            date_created: "2024-01-01T12:00:00Z".to_string(),
+            date_updated: "2024-01-01T12:00:00Z".to_string(),
+        }
+    }
+
+    fn at(stamp: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn count_chunks(store: &Path) -> usize {
+        fs::read_dir(store.join("chunks"))
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|shard| fs::read_dir(shard.path()).unwrap().count())
+            .sum()
+    }
+
+    /// Restores a snapshot and returns its entries as seen through the normal query path.
+    fn restored_entries(store: &Path, name: &str, dir: &Path) -> Vec<DiaryEntry> {
+        let dest = dir.join(format!("{}.db", name));
+        restore_snapshot(store, name, &dest).unwrap();
+        let db = open_database(&dest, "pw".to_string(), dir.join("restore-backups")).unwrap();
+        queries::get_all_entries(&db).unwrap()
+    }
+
+    fn assert_same_entries(actual: &[DiaryEntry], expected: &[DiaryEntry]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert_eq!(
+                (a.id, &a.date, &a.title, &a.text, a.word_count),
+                (e.id, &e.date, &e.title, &e.text, e.word_count)
+            );
+            assert_eq!(
+                (&a.date_created, &a.date_updated),
+                (&e.date_created, &e.date_updated)
+            );
+        }
+    }
+
+    #[test]
+    fn test_entry_row_roundtrip() {
+        let row = EntryRow {
+            date: "2024-01-01".to_string(),
+            title_encrypted: None,
+            text_encrypted: Some(vec![1, 2, 3]),
+            word_count: 42,
+            date_created: "c".to_string(),
+            date_updated: "u".to_string(),
+        };
+        let bytes = encode_entry_row(&row);
+        assert_eq!(decode_entry_row(&bytes).unwrap(), row);
+        assert!(decode_entry_row(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_snapshots_restore_to_matching_entries_and_dedupe() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("incremental");
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+
+        // A large inline image that never changes
+        let image = format!(
+            "<p><img src=\"data:image/png;base64,{}\"></p>",
+            "A".repeat(50_000)
+        );
+        queries::insert_entry(&db, &create_test_entry("2024-01-01", "Photo", &image)).unwrap();
+        queries::insert_entry(
+            &db,
+            &create_test_entry("2024-01-02", "Day two", "<p>one</p>"),
+        )
+        .unwrap();
+        let first_state = queries::get_all_entries(&db).unwrap();
+        let first = create_snapshot_at(&db, &store, at("2024-01-02 20:00:00")).unwrap();
+        assert_eq!(first.new_chunks, 2);
+
+        // Edit one entry, add one, delete none
+        let mut edited = first_state[1].clone();
+        edited.text = "<p>one two</p>".to_string();
+        edited.word_count = 2;
+        queries::update_entry(&db, &edited).unwrap();
+        queries::insert_entry(
+            &db,
+            &create_test_entry("2024-01-03", "Day three", "<p>x</p>"),
+        )
+        .unwrap();
+        let second_state = queries::get_all_entries(&db).unwrap();
+        let second = create_snapshot_at(&db, &store, at("2024-01-03 20:00:00")).unwrap();
+
+        // Only the edited and the new entry were written; the image chunk is shared
+        assert_eq!(second.new_chunks, 2);
+        assert_eq!(count_chunks(&store), 4);
+
+        assert_same_entries(
+            &restored_entries(&store, &first.name, dir.path()),
+            &first_state,
+        );
+        assert_same_entries(
+            &restored_entries(&store, &second.name, dir.path()),
+            &second_state,
+        );
+
+        let listed = list_snapshots(&store).unwrap();
+        let names: Vec<&str> = listed.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [second.name.as_str(), first.name.as_str()]);
+        assert_eq!(listed[0].entries, 3);
+    }
+
+    #[test]
+    fn test_restore_detects_corrupted_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("incremental");
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        queries::insert_entry(
+            &db,
+            &create_test_entry("2024-01-01", "Entry", "<p>text</p>"),
+        )
+        .unwrap();
+        let snapshot = create_snapshot_at(&db, &store, at("2024-01-01 12:00:00")).unwrap();
+
+        let shard = fs::read_dir(store.join("chunks"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let chunk = fs::read_dir(shard.path()).unwrap().next().unwrap().unwrap();
+        let mut bytes = fs::read(chunk.path()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(chunk.path(), bytes).unwrap();
+
+        let dest = dir.path().join("restored.db");
+        let err = restore_snapshot(&store, &snapshot.name, &dest).unwrap_err();
+        assert!(err.contains("corrupted"));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_prune_removes_old_snapshots_and_unused_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("incremental");
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        queries::insert_entry(
+            &db,
+            &create_test_entry("2024-01-01", "Stable", "<p>same</p>"),
+        )
+        .unwrap();
+        queries::insert_entry(&db, &create_test_entry("2024-01-02", "Draft", "<p>v1</p>")).unwrap();
+        create_snapshot_at(&db, &store, at("2024-01-02 12:00:00")).unwrap();
+
+        let mut draft = queries::get_all_entries(&db).unwrap()[1].clone();
+        draft.text = "<p>v2</p>".to_string();
+        queries::update_entry(&db, &draft).unwrap();
+        let latest = create_snapshot_at(&db, &store, at("2024-01-03 12:00:00")).unwrap();
+        let latest_state = queries::get_all_entries(&db).unwrap();
+        assert_eq!(count_chunks(&store), 3);
+
+        let policy = RetentionPolicy {
+            daily: 1,
+            weekly: 0,
+            monthly: 0,
+        };
+        let report = prune_snapshots(&store, &policy).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                snapshots_deleted: 1,
+                chunks_deleted: 1
+            }
+        );
+        assert_eq!(count_chunks(&store), 2);
+        assert_same_entries(
+            &restored_entries(&store, &latest.name, dir.path()),
+            &latest_state,
+        );
+    }
+
+    #[test]
+    fn test_restore_rejects_bad_names_and_existing_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("incremental");
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        let snapshot = create_snapshot_at(&db, &store, at("2024-01-01 12:00:00")).unwrap();
+
+        let existing = dir.path().join("diary.db");
+        assert!(restore_snapshot(&store, &snapshot.name, &existing).is_err());
+        let dest = dir.path().join("out.db");
+        assert!(restore_snapshot(&store, "../../diary", &dest).is_err());
+        assert!(restore_snapshot(&store, "snapshot-1999-01-01-00h00m00", &dest).is_err());
+    }
+}
@@ -0,0 +1,980 @@
+pub mod incremental;
+pub mod schedule;
+
+use crate::db::queries::DiaryEntry;
+use crate::db::salvage;
+use crate::db::schema::{open_database_read_only_with_key, DatabaseConnection};
+use chrono::{Datelike, Local, NaiveDateTime};
+use log::{debug, info, warn};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Pages copied per Online Backup step. Small enough that a step never holds
+/// the source read lock for long, large enough to keep small diaries to a few
+/// steps.
+const PAGES_PER_STEP: i32 = 64;
+
+/// How long to wait before retrying a step when the source is busy or locked.
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// How long the source may stay busy or locked before a backup gives up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Progress of an in-flight backup, reported after every step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct BackupProgress {
+    pub pages_copied: i32,
+    pub total_pages: i32,
+}
+
+/// Creates a backup of the open diary
+/// Returns the path of the created backup file
+pub fn create_backup(db: &DatabaseConnection, backups_dir: &Path) -> Result<PathBuf, String> {
+    create_backup_with_progress(db, backups_dir, |_| {})
+}
+
+/// Creates a backup of the open diary, calling `on_progress` after every step.
+///
+/// Uses SQLite's Online Backup API on the live connection rather than copying
+/// the file, so the backup is a consistent snapshot even in WAL mode or while
+/// another statement is writing. The snapshot is written to a `.tmp` file and
+/// renamed into place once complete, so an interrupted backup never shows up
+/// as `backup-*.db`.
+pub fn create_backup_with_progress(
+    db: &DatabaseConnection,
+    backups_dir: &Path,
+    on_progress: impl FnMut(BackupProgress),
+) -> Result<PathBuf, String> {
+    write_backup(db.conn(), backups_dir, on_progress)
+}
+
+/// Writes a timestamped backup of `src` into `backups_dir`.
+fn write_backup(
+    src: &Connection,
+    backups_dir: &Path,
+    mut on_progress: impl FnMut(BackupProgress),
+) -> Result<PathBuf, String> {
+    // Ensure backups directory exists
+    fs::create_dir_all(backups_dir)
+        .map_err(|e| format!("Failed to create backups directory: {}", e))?;
+
+    // Generate backup filename with current timestamp.
+    // The format is ISO-8601-like (YYYY-MM-DD-HHhMMmSS) so that lexicographic
+    // sort order equals chronological order — rotate_backups() relies on this.
+    let timestamp = Local::now().format(BACKUP_TIMESTAMP_FORMAT);
+    let backup_filename = format!("backup-{}.db", timestamp);
+    let backup_path = backups_dir.join(&backup_filename);
+    let tmp_path = backups_dir.join(format!("{}.tmp", backup_filename));
+
+    let result = copy_database(src, &tmp_path, &mut on_progress).and_then(|()| {
+        fs::rename(&tmp_path, &backup_path).map_err(|e| format!("Failed to finalize backup: {}", e))
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("Failed to create backup: {}", e));
+    }
+
+    debug!("Backup created: {:?}", backup_path);
+    Ok(backup_path)
+}
+
+/// Copies every page of `src` into a new database file at `dest_path`.
+fn copy_database(
+    src: &Connection,
+    dest_path: &Path,
+    on_progress: &mut impl FnMut(BackupProgress),
+) -> Result<(), String> {
+    let mut dest = Connection::open(dest_path).map_err(|e| e.to_string())?;
+    run_online_backup(src, &mut dest, on_progress)
+}
+
+/// Replaces the contents of `dest` with `src`, page by page.
+fn run_online_backup(
+    src: &Connection,
+    dest: &mut Connection,
+    on_progress: &mut impl FnMut(BackupProgress),
+) -> Result<(), String> {
+    run_online_backup_with_timeout(src, dest, on_progress, BUSY_TIMEOUT)
+}
+
+/// Like `run_online_backup`, failing once the source has been busy or
+/// locked for `busy_timeout` without a step getting through.
+fn run_online_backup_with_timeout(
+    src: &Connection,
+    dest: &mut Connection,
+    on_progress: &mut impl FnMut(BackupProgress),
+    busy_timeout: Duration,
+) -> Result<(), String> {
+    let backup = Backup::new(src, dest).map_err(|e| e.to_string())?;
+    let mut busy_since: Option<Instant> = None;
+
+    loop {
+        let step = backup.step(PAGES_PER_STEP).map_err(|e| e.to_string())?;
+        let progress = backup.progress();
+        on_progress(BackupProgress {
+            pages_copied: progress.pagecount - progress.remaining,
+            total_pages: progress.pagecount,
+        });
+        match step {
+            StepResult::Done => return Ok(()),
+            StepResult::More => busy_since = None,
+            StepResult::Busy | StepResult::Locked => {
+                if busy_since.get_or_insert_with(Instant::now).elapsed() >= busy_timeout {
+                    return Err("The diary stayed locked by another connection".to_string());
+                }
+                std::thread::sleep(BUSY_RETRY_DELAY);
+            }
+            _ => return Err("Unexpected backup step result".to_string()),
+        }
+    }
+}
+
+/// Filename timestamp format, e.g. `backup-2024-01-31-09h05m07.db`. Seconds
+/// keep two backups taken in the same minute, such as the safety backups of
+/// back-to-back imports, from overwriting each other.
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d-%Hh%Mm%S";
+
+/// Timestamp format of backups taken before seconds were added.
+const LEGACY_BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d-%Hh%M";
+
+/// Grandfather-father-son retention: how many days, ISO weeks and calendar
+/// months keep their newest backup. The newest backup overall is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RetentionPolicy {
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            daily: 7,
+            weekly: 4,
+            monthly: 12,
+        }
+    }
+}
+
+/// Parses the timestamp encoded in a `backup-YYYY-MM-DD-HHhMMmSS.db` or
+/// older `backup-YYYY-MM-DD-HHhMM.db` filename.
+fn parse_backup_timestamp(file_name: &str) -> Option<NaiveDateTime> {
+    let stamp = file_name.strip_prefix("backup-")?.strip_suffix(".db")?;
+    NaiveDateTime::parse_from_str(stamp, BACKUP_TIMESTAMP_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(stamp, LEGACY_BACKUP_TIMESTAMP_FORMAT))
+        .ok()
+}
+
+/// Lists backup files whose name carries a valid timestamp, newest first.
+/// Anything else in the directory is ignored.
+fn dated_backups(backups_dir: &Path) -> Result<Vec<(NaiveDateTime, PathBuf)>, String> {
+    let mut backups: Vec<(NaiveDateTime, PathBuf)> = fs::read_dir(backups_dir)
+        .map_err(|e| format!("Failed to read backups directory: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let timestamp = parse_backup_timestamp(path.file_name()?.to_str()?)?;
+            Some((timestamp, path))
+        })
+        .collect();
+    backups.sort_by(|a, b| b.cmp(a));
+    Ok(backups)
+}
+
+/// Marks which backups survive `policy`. `timestamps` must be newest first.
+fn backups_to_keep(timestamps: &[NaiveDateTime], policy: &RetentionPolicy) -> Vec<bool> {
+    let mut keep = vec![false; timestamps.len()];
+    if let Some(newest) = keep.first_mut() {
+        *newest = true;
+    }
+    keep_newest_per_period(timestamps, policy.daily, &mut keep, |t| t.date());
+    keep_newest_per_period(timestamps, policy.weekly, &mut keep, |t| {
+        t.date().iso_week()
+    });
+    keep_newest_per_period(timestamps, policy.monthly, &mut keep, |t| {
+        (t.year(), t.month())
+    });
+    keep
+}
+
+/// Keeps the newest backup in each of the `limit` most recent periods.
+fn keep_newest_per_period<K: PartialEq>(
+    timestamps: &[NaiveDateTime],
+    limit: usize,
+    keep: &mut [bool],
+    period: impl Fn(&NaiveDateTime) -> K,
+) {
+    let mut current: Option<K> = None;
+    let mut periods = 0;
+    for (i, timestamp) in timestamps.iter().enumerate() {
+        if periods == limit {
+            break;
+        }
+        let key = period(timestamp);
+        // Sorted newest first, so the first backup seen in a period is its newest.
+        if current.as_ref() != Some(&key) {
+            keep[i] = true;
+            periods += 1;
+            current = Some(key);
+        }
+    }
+}
+
+/// Rotates backups according to a grandfather-father-son `policy`, using the
+/// timestamp in each filename. Files that don't match the backup naming
+/// scheme are never touched.
+pub fn rotate_backups(backups_dir: &Path, policy: &RetentionPolicy) -> Result<(), String> {
+    if !backups_dir.exists() {
+        return Ok(());
+    }
+
+    let backups = dated_backups(backups_dir)?;
+    let timestamps: Vec<NaiveDateTime> = backups.iter().map(|(t, _)| *t).collect();
+    let keep = backups_to_keep(&timestamps, policy);
+
+    let mut deleted = 0;
+    for ((_, backup_file), keep) in backups.iter().zip(keep) {
+        if !keep {
+            fs::remove_file(backup_file)
+                .map_err(|e| format!("Failed to delete old backup: {}", e))?;
+            deleted += 1;
+        }
+    }
+
+    debug!("Rotated backups: deleted {} old backups", deleted);
+    Ok(())
+}
+
+/// An extra backup destination, such as a NAS share or an external drive.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BackupMirror {
+    /// Absolute path of an existing directory
+    pub path: String,
+    /// Retention for this destination; `None` uses the journal's policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
+}
+
+/// Outcome of mirroring a backup to one destination.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MirrorReport {
+    pub destination: String,
+    pub backup_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Writes a fresh backup of the diary file at `diary_path` to every mirror
+/// and rotates each one with its own retention policy.
+///
+/// Works from a separate read-only connection, so it can run on a background
+/// thread while the app keeps using the diary. A mirror that fails (drive not
+/// mounted, disk full, ...) is reported and never stops the others. Mirror
+/// directories are not created: a missing directory usually means an
+/// unmounted drive, and writing to the bare mount point would fill the local
+/// disk instead.
+pub fn backup_to_mirrors(
+    diary_path: &Path,
+    mirrors: &[BackupMirror],
+    default_policy: &RetentionPolicy,
+) -> Vec<MirrorReport> {
+    let src = Connection::open_with_flags(diary_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open journal for mirroring: {}", e));
+
+    mirrors
+        .iter()
+        .map(|mirror| {
+            let dir = Path::new(&mirror.path);
+            let result = src.as_ref().map_err(Clone::clone).and_then(|src| {
+                if !dir.is_dir() {
+                    return Err("Destination is not available".to_string());
+                }
+                let backup_path = write_backup(src, dir, |_| {})?;
+                rotate_backups(dir, &mirror.retention.unwrap_or(*default_policy))?;
+                Ok(backup_path)
+            });
+            match result {
+                Ok(path) => {
+                    debug!("Backup mirrored to {:?}", path);
+                    MirrorReport {
+                        destination: mirror.path.clone(),
+                        backup_path: Some(path.to_string_lossy().to_string()),
+                        error: None,
+                    }
+                }
+                Err(e) => MirrorReport {
+                    destination: mirror.path.clone(),
+                    backup_path: None,
+                    error: Some(e),
+                },
+            }
+        })
+        .collect()
+}
+
+/// A backup file as listed in the backup browser.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    /// Local time the backup was taken, from its filename (`YYYY-MM-DDTHH:MM:SS`)
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+/// Lists the backups in `backups_dir`, newest first.
+pub fn list_backups(backups_dir: &Path) -> Result<Vec<BackupInfo>, String> {
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+    dated_backups(backups_dir)?
+        .into_iter()
+        .map(|(timestamp, path)| {
+            let size_bytes = fs::metadata(&path)
+                .map_err(|e| format!("Failed to read backup metadata: {}", e))?
+                .len();
+            Ok(BackupInfo {
+                file_name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                created_at: timestamp.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                size_bytes,
+            })
+        })
+        .collect()
+}
+
+/// Resolves a backup file name (as returned by [`list_backups`]) to its path.
+///
+/// Only bare `backup-YYYY-MM-DD-HHhMMmSS.db` names are accepted, so a caller can
+/// never point this at a file outside the backups directory.
+pub fn resolve_backup_path(backups_dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    if parse_backup_timestamp(file_name).is_none() {
+        return Err(format!("Not a backup file name: {}", file_name));
+    }
+    let path = backups_dir.join(file_name);
+    if !path.is_file() {
+        return Err(format!("Backup not found: {}", file_name));
+    }
+    Ok(path)
+}
+
+/// Reads every readable entry from a backup of the unlocked diary `live`.
+pub fn read_backup_entries(
+    live: &DatabaseConnection,
+    backup_path: &Path,
+) -> Result<Vec<DiaryEntry>, String> {
+    let backup = open_database_read_only_with_key(backup_path, live.key())?;
+    let (entries, issues) = salvage::salvage_entries(&backup)?;
+    if !issues.is_empty() {
+        warn!(
+            "Skipped {} unreadable row(s) while reading {:?}",
+            issues.len(),
+            backup_path
+        );
+    }
+    Ok(entries)
+}
+
+/// Replaces the whole contents of the unlocked diary `live` with a backup.
+///
+/// A safety backup of the current state is taken first and its path returned,
+/// so a restore can itself be undone. The live diary's auth slots are kept:
+/// restoring an old backup never brings back an old password or a removed key
+/// file.
+pub fn restore_backup(
+    live: &mut DatabaseConnection,
+    backup_path: &Path,
+    backups_dir: &Path,
+) -> Result<PathBuf, String> {
+    // Load the backup into memory before anything is written: the safety
+    // backup below may land on the same filename if both were taken within
+    // the same second.
+    let backup = open_database_read_only_with_key(backup_path, live.key())?;
+    let mut snapshot =
+        Connection::open_in_memory().map_err(|e| format!("Failed to read backup: {}", e))?;
+    run_online_backup(backup.conn(), &mut snapshot, &mut |_| {})
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    drop(backup);
+
+    let safety_backup = create_backup(live, backups_dir)?;
+
+    // Swap the current auth slots into the snapshot so the page copy below
+    // is the only write to the live diary
+    let slots = read_auth_slots(live.conn())?;
+    write_auth_slots(&snapshot, &slots)?;
+    run_online_backup(&snapshot, &mut live.conn, &mut |_| {})
+        .map_err(|e| format!("Failed to restore backup: {}", e))?;
+
+    info!(
+        "Restored {:?} (previous state saved to {:?})",
+        backup_path, safety_backup
+    );
+    Ok(safety_backup)
+}
+
+type AuthSlotRow = Vec<rusqlite::types::Value>;
+
+fn read_auth_slots(conn: &Connection) -> Result<Vec<AuthSlotRow>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, type, label, public_key, wrapped_key, created_at, last_used FROM auth_slots",
+        )
+        .map_err(|e| format!("Failed to read auth slots: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| (0..7).map(|i| row.get(i)).collect())
+        .map_err(|e| format!("Failed to read auth slots: {}", e))?
+        .collect::<Result<Vec<AuthSlotRow>, _>>()
+        .map_err(|e| format!("Failed to read auth slots: {}", e))?;
+    Ok(rows)
+}
+
+fn write_auth_slots(conn: &Connection, slots: &[AuthSlotRow]) -> Result<(), String> {
+    conn.execute_batch("BEGIN IMMEDIATE TRANSACTION; DELETE FROM auth_slots;")
+        .map_err(|e| format!("Failed to restore auth slots: {}", e))?;
+    for slot in slots {
+        if let Err(e) = conn.execute(
+            "INSERT INTO auth_slots (id, type, label, public_key, wrapped_key, created_at, last_used)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params_from_iter(slot),
+        ) {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(format!("Failed to restore auth slots: {}", e));
+        }
+    }
+    conn.execute_batch("COMMIT")
+        .map_err(|e| format!("Failed to restore auth slots: {}", e))
+}
+
+/// Creates a backup, reporting progress to `on_progress`, and rotates old backups
+pub fn backup_and_rotate(
+    db: &DatabaseConnection,
+    backups_dir: &Path,
+    policy: &RetentionPolicy,
+    on_progress: impl FnMut(BackupProgress),
+) -> Result<PathBuf, String> {
+    let backup_path = create_backup_with_progress(db, backups_dir, on_progress)?;
+    rotate_backups(backups_dir, policy)?;
+    Ok(backup_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn create_test_file(path: &Path, content: &str) -> Result<(), std::io::Error> {
+        let mut file = File::create(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn create_test_diary(path: &Path) -> DatabaseConnection {
+        let db = crate::db::schema::create_database(path, "test".to_string()).unwrap();
+        crate::db::queries::insert_entry(
+            &db,
+            &crate::db::queries::DiaryEntry {
+                id: 0,
+                date: "2024-01-01".to_string(),
+                title: "Backed up".to_string(),
+                text: "<p>Entry content</p>".to_string(),
+                word_count: 2,
+                date_created: "2024-01-01T12:00:00Z".to_string(),
+                date_updated: "2024-01-01T12:00:00Z".to_string(),
+            },
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_online_backup_gives_up_while_source_is_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.db");
+        let src = Connection::open(&path).unwrap();
+        src.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")
+            .unwrap();
+        src.busy_timeout(Duration::ZERO).unwrap();
+        let writer = Connection::open(&path).unwrap();
+        writer.execute_batch("BEGIN EXCLUSIVE;").unwrap();
+
+        let mut dest = Connection::open_in_memory().unwrap();
+        let err = run_online_backup_with_timeout(
+            &src,
+            &mut dest,
+            &mut |_| {},
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert!(err.contains("locked"));
+
+        writer.execute_batch("ROLLBACK;").unwrap();
+        run_online_backup(&src, &mut dest, &mut |_| {}).unwrap();
+    }
+
+    fn backup_titles(backup_path: &Path, backups_dir: &Path) -> Vec<String> {
+        let restored =
+            crate::db::schema::open_database(backup_path, "test".to_string(), backups_dir).unwrap();
+        crate::db::queries::get_all_entries(&restored)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_create_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let backups_dir = temp_dir.path().join("backups");
+        let db = create_test_diary(&diary_path);
+
+        // Create backup
+        let backup_path = create_backup(&db, &backups_dir).unwrap();
+
+        // Verify backup exists
+        assert!(backup_path.exists());
+        assert!(backup_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("backup-"));
+        assert!(backup_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .ends_with(".db"));
+
+        // Verify content: the backup opens with the same password and holds the entry
+        assert_eq!(backup_titles(&backup_path, &backups_dir), ["Backed up"]);
+
+        // No temporary file is left behind
+        let names: Vec<_> = fs::read_dir(&backups_dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name())
+            .collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    #[test]
+    fn test_create_backup_reports_progress() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = create_test_diary(&temp_dir.path().join("diary.db"));
+
+        let mut reports = Vec::new();
+        create_backup_with_progress(&db, &temp_dir.path().join("backups"), |p| reports.push(p))
+            .unwrap();
+
+        let last = reports.last().expect("at least one progress report");
+        assert!(last.total_pages > 0);
+        assert_eq!(last.pages_copied, last.total_pages);
+    }
+
+    #[test]
+    fn test_create_backup_includes_uncheckpointed_wal_pages() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let backups_dir = temp_dir.path().join("backups");
+        let db = create_test_diary(&diary_path);
+
+        // In WAL mode, fresh writes live in diary.db-wal until a checkpoint;
+        // a plain file copy of diary.db would miss them.
+        db.conn()
+            .pragma_update(None, "wal_autocheckpoint", 0)
+            .unwrap();
+        let mode: String = db
+            .conn()
+            .query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        crate::db::queries::insert_entry(
+            &db,
+            &crate::db::queries::DiaryEntry {
+                id: 0,
+                date: "2024-01-02".to_string(),
+                title: "Only in WAL".to_string(),
+                text: String::new(),
+                word_count: 0,
+                date_created: "2024-01-02T12:00:00Z".to_string(),
+                date_updated: "2024-01-02T12:00:00Z".to_string(),
+            },
+        )
+        .unwrap();
+
+        let backup_path = create_backup(&db, &backups_dir).unwrap();
+        assert_eq!(
+            backup_titles(&backup_path, &backups_dir),
+            ["Backed up", "Only in WAL"]
+        );
+    }
+
+    fn create_backup_files(backups_dir: &Path, names: &[String]) {
+        fs::create_dir_all(backups_dir).unwrap();
+        for name in names {
+            create_test_file(&backups_dir.join(name), "test").unwrap();
+        }
+    }
+
+    /// Names use the legacy format, which rotation must keep handling
+    fn backup_name(timestamp: NaiveDateTime) -> String {
+        format!(
+            "backup-{}.db",
+            timestamp.format(LEGACY_BACKUP_TIMESTAMP_FORMAT)
+        )
+    }
+
+    #[test]
+    fn test_backup_names_include_seconds() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let backups_dir = temp_dir.path().join("backups");
+        let db = create_test_diary(&diary_path);
+
+        let backup_path = create_backup(&db, &backups_dir).unwrap();
+        let name = backup_path.file_name().unwrap().to_str().unwrap();
+        let stamp = name.strip_prefix("backup-").unwrap();
+        assert_eq!(stamp.len(), "2024-01-31-09h05m07.db".len());
+
+        let new = parse_backup_timestamp("backup-2024-01-31-09h05m07.db").unwrap();
+        let legacy = parse_backup_timestamp("backup-2024-01-31-09h05.db").unwrap();
+        assert!(new > legacy);
+    }
+
+    fn daily_backups(first: &str, days: i64) -> Vec<String> {
+        let start =
+            NaiveDateTime::parse_from_str(&format!("{}-12h00", first), "%Y-%m-%d-%Hh%M").unwrap();
+        (0..days)
+            .map(|d| backup_name(start + chrono::Duration::days(d)))
+            .collect()
+    }
+
+    fn remaining_names(backups_dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(backups_dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_backup_to_mirrors_reports_each_destination() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let db = create_test_diary(&diary_path);
+
+        let nas = temp_dir.path().join("nas");
+        create_backup_files(&nas, &daily_backups("2024-01-01", 5));
+        let usb = temp_dir.path().join("usb");
+        fs::create_dir_all(&usb).unwrap();
+        let unmounted = temp_dir.path().join("unmounted");
+
+        let mirrors = vec![
+            BackupMirror {
+                path: nas.to_string_lossy().to_string(),
+                retention: Some(RetentionPolicy {
+                    daily: 2,
+                    weekly: 0,
+                    monthly: 0,
+                }),
+            },
+            BackupMirror {
+                path: unmounted.to_string_lossy().to_string(),
+                retention: None,
+            },
+            BackupMirror {
+                path: usb.to_string_lossy().to_string(),
+                retention: None,
+            },
+        ];
+        let reports = backup_to_mirrors(&diary_path, &mirrors, &RetentionPolicy::default());
+
+        assert_eq!(reports.len(), 3);
+        // NAS: new backup plus the newest old one, per its own 2-day policy
+        let nas_backup = PathBuf::from(reports[0].backup_path.as_ref().unwrap());
+        assert_eq!(remaining_names(&nas).len(), 2);
+        assert_eq!(backup_titles(&nas_backup, &nas), ["Backed up"]);
+        // A missing destination fails without being created
+        assert!(reports[1].error.is_some());
+        assert!(!unmounted.exists());
+        // ... and does not stop later destinations
+        assert!(reports[2].error.is_none());
+        assert_eq!(remaining_names(&usb).len(), 1);
+
+        drop(db);
+    }
+
+    #[test]
+    fn test_list_backups_newest_first() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 3));
+        create_test_file(&backups_dir.join("notes.txt"), "ignored").unwrap();
+
+        let backups = list_backups(&backups_dir).unwrap();
+        let names: Vec<&str> = backups.iter().map(|b| b.file_name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "backup-2024-01-03-12h00.db",
+                "backup-2024-01-02-12h00.db",
+                "backup-2024-01-01-12h00.db"
+            ]
+        );
+        assert_eq!(backups[0].created_at, "2024-01-03T12:00:00");
+        assert_eq!(backups[0].size_bytes, 4);
+
+        assert!(list_backups(&temp_dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_backup_path_rejects_other_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 1));
+
+        assert!(resolve_backup_path(&backups_dir, "backup-2024-01-01-12h00.db").is_ok());
+        assert!(resolve_backup_path(&backups_dir, "backup-2024-01-02-12h00.db").is_err());
+        assert!(resolve_backup_path(&backups_dir, "../diary.db").is_err());
+        assert!(resolve_backup_path(&backups_dir, "backup-../../x-12h00.db").is_err());
+    }
+
+    #[test]
+    fn test_read_backup_entries_requires_same_key() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        let db = create_test_diary(&temp_dir.path().join("diary.db"));
+        let other = create_test_diary(&temp_dir.path().join("other.db"));
+
+        let backup_path = create_backup(&db, &backups_dir).unwrap();
+        let entries = read_backup_entries(&db, &backup_path).unwrap();
+        assert_eq!(entries[0].title, "Backed up");
+
+        let result = read_backup_entries(&other, &backup_path);
+        assert!(result.unwrap_err().contains("different key"));
+    }
+
+    #[test]
+    fn test_restore_backup_replaces_entries_and_keeps_auth_slots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let backups_dir = temp_dir.path().join("backups");
+        let mut db = create_test_diary(&diary_path);
+
+        let backup_path = create_backup(&db, &backups_dir).unwrap();
+        // Simulate a later edit and a credential change after the backup
+        db.conn().execute("DELETE FROM entries", []).unwrap();
+        db.conn()
+            .execute("UPDATE auth_slots SET label = 'Changed since backup'", [])
+            .unwrap();
+        // Give the backup an older name so the safety backup cannot collide with it
+        let old_path = backups_dir.join("backup-2024-01-01-12h00.db");
+        fs::rename(&backup_path, &old_path).unwrap();
+
+        let safety = restore_backup(&mut db, &old_path, &backups_dir).unwrap();
+
+        let titles: Vec<String> = crate::db::queries::get_all_entries(&db)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.title)
+            .collect();
+        assert_eq!(titles, ["Backed up"]);
+        let label: String = db
+            .conn()
+            .query_row("SELECT label FROM auth_slots", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(label, "Changed since backup");
+
+        // The safety backup holds the pre-restore (empty) state
+        assert!(backup_titles(&safety, &backups_dir).is_empty());
+
+        // The diary still opens from disk with its password
+        drop(db);
+        assert_eq!(backup_titles(&diary_path, &backups_dir), ["Backed up"]);
+    }
+
+    #[test]
+    fn test_rotate_backups_under_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+
+        // 7 consecutive days fit entirely inside the daily tier
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 7));
+
+        rotate_backups(&backups_dir, &RetentionPolicy::default()).unwrap();
+
+        assert_eq!(remaining_names(&backups_dir).len(), 7);
+    }
+
+    #[test]
+    fn test_rotate_backups_keeps_daily_weekly_monthly() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+
+        // One backup a day from 2023-01-01 to 2024-01-31 (Wednesday)
+        create_backup_files(&backups_dir, &daily_backups("2023-01-01", 396));
+
+        rotate_backups(&backups_dir, &RetentionPolicy::default()).unwrap();
+
+        let mut expected: Vec<String> = Vec::new();
+        // Daily: the last 7 days
+        expected.extend(daily_backups("2024-01-25", 7));
+        // Weekly: newest backup of the ISO weeks ending Jan 21 and Jan 14
+        // (the weeks ending Jan 28 and Jan 31 are already covered by the daily tier)
+        expected.push("backup-2024-01-21-12h00.db".to_string());
+        expected.push("backup-2024-01-14-12h00.db".to_string());
+        // Monthly: last day of Feb..Dec 2023 (January 2024 is covered by the daily tier)
+        for date in [
+            "2023-02-28",
+            "2023-03-31",
+            "2023-04-30",
+            "2023-05-31",
+            "2023-06-30",
+            "2023-07-31",
+            "2023-08-31",
+            "2023-09-30",
+            "2023-10-31",
+            "2023-11-30",
+            "2023-12-31",
+        ] {
+            expected.push(format!("backup-{}-12h00.db", date));
+        }
+        expected.sort();
+
+        assert_eq!(remaining_names(&backups_dir), expected);
+    }
+
+    #[test]
+    fn test_rotate_backups_custom_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 60));
+
+        let policy = RetentionPolicy {
+            daily: 2,
+            weekly: 0,
+            monthly: 0,
+        };
+        rotate_backups(&backups_dir, &policy).unwrap();
+
+        assert_eq!(
+            remaining_names(&backups_dir),
+            ["backup-2024-02-28-12h00.db", "backup-2024-02-29-12h00.db"]
+        );
+    }
+
+    #[test]
+    fn test_rotate_backups_always_keeps_newest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 3));
+
+        let policy = RetentionPolicy {
+            daily: 0,
+            weekly: 0,
+            monthly: 0,
+        };
+        rotate_backups(&backups_dir, &policy).unwrap();
+
+        assert_eq!(
+            remaining_names(&backups_dir),
+            ["backup-2024-01-03-12h00.db"]
+        );
+    }
+
+    #[test]
+    fn test_backup_and_rotate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let diary_path = temp_dir.path().join("diary.db");
+        let backups_dir = temp_dir.path().join("backups");
+        let db = create_test_diary(&diary_path);
+
+        // A burst of 30 old backups taken on a single day
+        let start = NaiveDateTime::parse_from_str("2000-01-01-12h00", "%Y-%m-%d-%Hh%M").unwrap();
+        let burst: Vec<String> = (0..30)
+            .map(|i| backup_name(start + chrono::Duration::minutes(i)))
+            .collect();
+        create_backup_files(&backups_dir, &burst);
+
+        let mut last_progress = None;
+        let backup_path = backup_and_rotate(&db, &backups_dir, &RetentionPolicy::default(), |p| {
+            last_progress = Some(p)
+        })
+        .unwrap();
+
+        // The new backup survives its own rotation
+        assert!(backup_path.exists());
+        let last_progress = last_progress.unwrap();
+        assert_eq!(last_progress.pages_copied, last_progress.total_pages);
+
+        // The old burst is thinned to its newest backup
+        let names = remaining_names(&backups_dir);
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"backup-2000-01-01-12h29.db".to_string()));
+    }
+
+    #[test]
+    fn test_rotate_ignores_non_backup_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 35));
+
+        // Files outside the naming scheme, including an unparseable timestamp
+        // and an unfinished Online Backup temp file, must never be deleted
+        create_test_file(&backups_dir.join("readme.txt"), "test").unwrap();
+        create_test_file(&backups_dir.join("other-file.txt"), "test").unwrap();
+        create_test_file(&backups_dir.join("backup-2024-01-60-12h00.db"), "test").unwrap();
+        create_test_file(&backups_dir.join("backup-2024-02-05-12h00.db.tmp"), "test").unwrap();
+
+        rotate_backups(&backups_dir, &RetentionPolicy::default()).unwrap();
+
+        assert!(backups_dir.join("readme.txt").exists());
+        assert!(backups_dir.join("other-file.txt").exists());
+        assert!(backups_dir.join("backup-2024-01-60-12h00.db").exists());
+        assert!(backups_dir.join("backup-2024-02-05-12h00.db.tmp").exists());
+    }
+
+    #[test]
+    fn test_backup_and_rotate_repeated_unlocks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backups_dir = temp_dir.path().join("backups");
+
+        // A month of history, one backup a day
+        create_backup_files(&backups_dir, &daily_backups("2024-01-01", 31));
+
+        // Then 35 rapid unlocks on a single day, one minute apart, rotating after each
+        let start = NaiveDateTime::parse_from_str("2024-02-01-09h00", "%Y-%m-%d-%Hh%M").unwrap();
+        for i in 0..35 {
+            let name = backup_name(start + chrono::Duration::minutes(i));
+            create_test_file(&backups_dir.join(name), "test").unwrap();
+            rotate_backups(&backups_dir, &RetentionPolicy::default()).unwrap();
+        }
+
+        let names = remaining_names(&backups_dir);
+        // Only the newest of the burst is kept for Feb 1 ...
+        assert_eq!(
+            names
+                .iter()
+                .filter(|n| n.starts_with("backup-2024-02-01"))
+                .count(),
+            1
+        );
+        assert!(names.contains(&"backup-2024-02-01-09h34.db".to_string()));
+        // ... and last month's history survives in the daily and weekly tiers.
+        assert!(names.contains(&"backup-2024-01-31-12h00.db".to_string()));
+        assert!(names.contains(&"backup-2024-01-26-12h00.db".to_string()));
+        assert!(names.contains(&"backup-2024-01-21-12h00.db".to_string()));
+        assert!(names.contains(&"backup-2024-01-14-12h00.db".to_string()));
+        assert_eq!(names.len(), 9);
+    }
+}
@@ -0,0 +1,87 @@
+//! Timing rules for backups taken while a journal stays unlocked.
+//!
+//! The scheduler thread itself lives with the rest of the unlock/lock state in
+//! `commands::auth`; this module only decides *when* a backup is due, so the
+//! rules can be tested without a running app.
+
+use std::time::{Duration, Instant};
+
+/// How often the scheduler wakes up to check whether a backup is due.
+pub const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// Records the last backup of the unlocked journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupMark {
+    pub taken_at: Instant,
+    /// The connection's `total_changes()` when the backup was taken
+    pub changes: u64,
+}
+
+impl BackupMark {
+    pub fn new(changes: u64) -> Self {
+        Self {
+            taken_at: Instant::now(),
+            changes,
+        }
+    }
+}
+
+/// True if the journal was written to since `mark`. Without a mark (no backup
+/// taken this session), any change at all counts.
+pub fn has_changes_since(mark: Option<&BackupMark>, current_changes: u64) -> bool {
+    match mark {
+        Some(mark) => current_changes != mark.changes,
+        None => current_changes > 0,
+    }
+}
+
+/// True if a scheduled backup should run now: there are unsaved changes and at
+/// least `interval` has passed since the last backup.
+pub fn is_backup_due(
+    mark: Option<&BackupMark>,
+    current_changes: u64,
+    interval: Duration,
+    now: Instant,
+) -> bool {
+    if !has_changes_since(mark, current_changes) {
+        return false;
+    }
+    match mark {
+        Some(mark) => now.saturating_duration_since(mark.taken_at) >= interval,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_no_backup_without_changes() {
+        let mark = BackupMark::new(5);
+        let later = mark.taken_at + 2 * HOUR;
+        assert!(!has_changes_since(Some(&mark), 5));
+        assert!(!is_backup_due(Some(&mark), 5, HOUR, later));
+        assert!(!is_backup_due(None, 0, HOUR, later));
+    }
+
+    #[test]
+    fn test_backup_due_only_after_interval() {
+        let mark = BackupMark::new(5);
+        assert!(!is_backup_due(
+            Some(&mark),
+            6,
+            HOUR,
+            mark.taken_at + HOUR / 2
+        ));
+        assert!(is_backup_due(Some(&mark), 6, HOUR, mark.taken_at + HOUR));
+    }
+
+    #[test]
+    fn test_backup_due_immediately_without_mark() {
+        assert!(has_changes_since(None, 1));
+        assert!(is_backup_due(None, 1, HOUR, Instant::now()));
+    }
+}
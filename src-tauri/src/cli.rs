@@ -0,0 +1,388 @@
+//! Headless command-line entry points.
+//!
+//! `main.rs` hands the process arguments to [`run_from_args`] before starting
+//! the GUI. Recognised subcommands run to completion and return an exit code;
+//! anything else returns `None` and the app launches normally.
+//!
+//! Usage:
+//! ```text
+//! mini-diarium verify <path/to/diary.db> [--json] [--key-file <file.key>]
+//! mini-diarium salvage <path/to/diary.db> <path/to/recovered.db> [--key-file <file.key>]
+//! ```
+//!
+//! The journal is opened with a key file if `--key-file` is given, otherwise
+//! with its password. Passwords are read from the `MINI_DIARIUM_PASSWORD`
+//! environment variable if set, otherwise from the first line of stdin.
+//! `salvage` protects the new diary with the same password, or, when opened
+//! with a key file, with a password read the same way. Journals that only
+//! unlock automatically on this device cannot be opened from here.
+
+use crate::db::integrity::{self, IntegrityReport};
+use crate::db::salvage;
+use crate::db::schema::{self, DatabaseConnection};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PASSWORD_ENV_VAR: &str = "MINI_DIARIUM_PASSWORD";
+
+/// Exit code: verification ran and found no problems.
+pub const EXIT_OK: i32 = 0;
+/// Exit code: verification (or salvage) ran and found at least one problem.
+pub const EXIT_ISSUES_FOUND: i32 = 1;
+/// Exit code: bad arguments, or the diary could not be opened at all.
+pub const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Verify {
+        db_path: PathBuf,
+        json: bool,
+        key_file: Option<PathBuf>,
+    },
+    Salvage {
+        db_path: PathBuf,
+        output_path: PathBuf,
+        key_file: Option<PathBuf>,
+    },
+}
+
+const VERIFY_USAGE: &str = "Usage: mini-diarium verify <diary.db> [--json] [--key-file <file.key>]";
+const SALVAGE_USAGE: &str =
+    "Usage: mini-diarium salvage <diary.db> <recovered.db> [--key-file <file.key>]";
+const KEY_FILE_FLAG: &str = "--key-file";
+
+/// Parses the arguments after the program name.
+///
+/// Returns `Ok(None)` when the first argument is not a known subcommand, so
+/// that platform-injected arguments never prevent the GUI from starting.
+fn parse_args(args: &[String]) -> Result<Option<Command>, String> {
+    match args.first().map(String::as_str) {
+        Some("verify") => {
+            let (args, key_file) = take_key_file(&args[1..])?;
+            let mut json = false;
+            let paths = split_positional(&args, |flag| match flag {
+                "--json" => {
+                    json = true;
+                    true
+                }
+                _ => false,
+            })?;
+            match paths.as_slice() {
+                [db_path] => Ok(Some(Command::Verify {
+                    db_path: PathBuf::from(db_path),
+                    json,
+                    key_file,
+                })),
+                _ => Err(VERIFY_USAGE.to_string()),
+            }
+        }
+        Some("salvage") => {
+            let (args, key_file) = take_key_file(&args[1..])?;
+            let paths = split_positional(&args, |_| false)?;
+            match paths.as_slice() {
+                [db_path, output_path] => Ok(Some(Command::Salvage {
+                    db_path: PathBuf::from(db_path),
+                    output_path: PathBuf::from(output_path),
+                    key_file,
+                })),
+                _ => Err(SALVAGE_USAGE.to_string()),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Removes `--key-file <path>` from `args`, returning the rest and the path.
+fn take_key_file(args: &[String]) -> Result<(Vec<String>, Option<PathBuf>), String> {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut key_file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == KEY_FILE_FLAG {
+            let path = args
+                .next()
+                .ok_or_else(|| format!("{} needs a path", KEY_FILE_FLAG))?;
+            if key_file.replace(PathBuf::from(path)).is_some() {
+                return Err(format!("{} given more than once", KEY_FILE_FLAG));
+            }
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((rest, key_file))
+}
+
+/// Separates `--flags` from positional arguments. `accept_flag` returns false
+/// for flags the subcommand does not know.
+fn split_positional(
+    args: &[String],
+    mut accept_flag: impl FnMut(&str) -> bool,
+) -> Result<Vec<&str>, String> {
+    let mut positional = Vec::new();
+    for arg in args {
+        if arg.starts_with("--") {
+            if !accept_flag(arg) {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+    Ok(positional)
+}
+
+/// Runs a CLI subcommand if one was given. Returns the process exit code, or
+/// `None` if the GUI should start instead.
+pub fn run_from_args<I: IntoIterator<Item = String>>(args: I) -> Option<i32> {
+    let args: Vec<String> = args.into_iter().collect();
+    let command = match parse_args(&args) {
+        Ok(Some(command)) => command,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Some(EXIT_ERROR);
+        }
+    };
+
+    let result = match command {
+        Command::Verify {
+            db_path,
+            json,
+            key_file,
+        } => run_verify(&db_path, json, key_file.as_deref()),
+        Command::Salvage {
+            db_path,
+            output_path,
+            key_file,
+        } => run_salvage(&db_path, &output_path, key_file.as_deref()),
+    };
+    Some(result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        EXIT_ERROR
+    }))
+}
+
+/// Opens the journal read-only with `key_file`, or else with its password.
+/// Returns the password too, if one was used.
+fn open_journal(
+    db_path: &Path,
+    key_file: Option<&Path>,
+) -> Result<(DatabaseConnection, Option<String>), String> {
+    if !db_path.is_file() {
+        return Err(format!("No journal found at {}", db_path.display()));
+    }
+
+    if let Some(key_file) = key_file {
+        let private_key =
+            zeroize::Zeroizing::new(crate::auth::keypair::read_private_key_file(key_file)?);
+        let db = schema::open_database_read_only_with_keypair(db_path, *private_key)?;
+        return Ok((db, None));
+    }
+
+    if !schema::has_password_slot(db_path)? {
+        return Err(format!(
+            "This journal has no password. If it is unlocked with a key file, pass \
+             {} <file.key>. Journals that only unlock automatically on this device \
+             cannot be opened from the command line; add a password in the app first.",
+            KEY_FILE_FLAG
+        ));
+    }
+    let password = read_password("Password: ")?;
+    let db = schema::open_database_read_only(db_path, password.clone())?;
+    Ok((db, Some(password)))
+}
+
+fn run_verify(db_path: &Path, json: bool, key_file: Option<&Path>) -> Result<i32, String> {
+    let (db, password) = open_journal(db_path, key_file)?;
+    let report = integrity::verify_database(&db, password.as_deref())?;
+
+    if json {
+        let out = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("Failed to serialize report: {}", e))?;
+        println!("{}", out);
+    } else {
+        print!("{}", format_report(&report));
+    }
+
+    Ok(if report.is_healthy() {
+        EXIT_OK
+    } else {
+        EXIT_ISSUES_FOUND
+    })
+}
+
+fn run_salvage(db_path: &Path, output_path: &Path, key_file: Option<&Path>) -> Result<i32, String> {
+    let (db, password) = open_journal(db_path, key_file)?;
+    let password = match password {
+        Some(password) => password,
+        None => read_password("Password for the recovered journal: ")?,
+    };
+    let report = salvage::salvage_to_new_database(&db, output_path, password)?;
+
+    println!(
+        "Recovered {} entries into {}",
+        report.entries_recovered, report.output_path
+    );
+    match &report.quarantine_path {
+        Some(path) => {
+            println!(
+                "{} unreadable entries skipped; details in {}",
+                report.entries_quarantined, path
+            );
+            Ok(EXIT_ISSUES_FOUND)
+        }
+        None => Ok(EXIT_OK),
+    }
+}
+
+fn read_password(prompt: &str) -> Result<String, String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV_VAR) {
+        return Ok(password);
+    }
+    eprint!("{}", prompt);
+    let _ = std::io::stderr().flush();
+    let mut line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read password: {}", e))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Renders a report as human-readable text.
+fn format_report(report: &IntegrityReport) -> String {
+    let mut out = String::new();
+    if report.sqlite_integrity == ["ok"] {
+        out.push_str("SQLite integrity check: ok\n");
+    } else {
+        out.push_str("SQLite integrity check: FAILED\n");
+        for line in &report.sqlite_integrity {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    out.push_str(&format!(
+        "Entries: {}/{} ok\nAuth slots checked: {}\n",
+        report.entries_ok, report.entries_checked, report.auth_slots_checked
+    ));
+
+    if report.issues.is_empty() {
+        out.push_str("No problems found.\n");
+        return out;
+    }
+
+    out.push_str(&format!("{} problem(s) found:\n", report.issues.len()));
+    for issue in &report.issues {
+        let location = match (&issue.date, &issue.field) {
+            (Some(date), Some(field)) => format!(" ({}, {})", date, field),
+            (Some(date), None) => format!(" ({})", date),
+            (None, Some(field)) => format!(" ({})", field),
+            (None, None) => String::new(),
+        };
+        out.push_str(&format!(
+            "  {} #{}{}: {:?} — {}\n",
+            issue.table, issue.row_id, location, issue.kind, issue.message
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::integrity::{IntegrityIssue, IssueKind};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_subcommand_starts_gui() {
+        assert_eq!(parse_args(&args(&[])).unwrap(), None);
+        assert_eq!(parse_args(&args(&["-psn_0_12345"])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        assert_eq!(
+            parse_args(&args(&["verify", "diary.db", "--json"])).unwrap(),
+            Some(Command::Verify {
+                db_path: PathBuf::from("diary.db"),
+                json: true,
+                key_file: None,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["verify", "--key-file", "me.key", "diary.db"])).unwrap(),
+            Some(Command::Verify {
+                db_path: PathBuf::from("diary.db"),
+                json: false,
+                key_file: Some(PathBuf::from("me.key")),
+            })
+        );
+        assert!(parse_args(&args(&["verify", "diary.db", "--key-file"])).is_err());
+        assert!(parse_args(&args(&["verify"])).is_err());
+        assert!(parse_args(&args(&["verify", "a.db", "b.db"])).is_err());
+        assert!(parse_args(&args(&["verify", "a.db", "--fix"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_salvage() {
+        assert_eq!(
+            parse_args(&args(&["salvage", "diary.db", "out.db"])).unwrap(),
+            Some(Command::Salvage {
+                db_path: PathBuf::from("diary.db"),
+                output_path: PathBuf::from("out.db"),
+                key_file: None,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&[
+                "salvage",
+                "diary.db",
+                "out.db",
+                "--key-file",
+                "me.key"
+            ]))
+            .unwrap(),
+            Some(Command::Salvage {
+                db_path: PathBuf::from("diary.db"),
+                output_path: PathBuf::from("out.db"),
+                key_file: Some(PathBuf::from("me.key")),
+            })
+        );
+        assert!(parse_args(&args(&["salvage", "diary.db"])).is_err());
+        assert!(parse_args(&args(&["salvage", "a.db", "b.db", "--json"])).is_err());
+    }
+
+    #[test]
+    fn test_journal_without_password_needs_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("diary.db");
+        schema::create_database_auto(&db_path, &[7u8; 32]).unwrap();
+
+        let err = open_journal(&db_path, None).err().unwrap();
+        assert!(err.contains("no password"));
+        assert!(err.contains(KEY_FILE_FLAG));
+    }
+
+    #[test]
+    fn test_format_report_lists_issues() {
+        let report = IntegrityReport {
+            sqlite_integrity: vec!["ok".to_string()],
+            entries_checked: 2,
+            entries_ok: 1,
+            auth_slots_checked: 1,
+            issues: vec![IntegrityIssue {
+                table: "entries".to_string(),
+                row_id: 7,
+                date: Some("2024-01-02".to_string()),
+                field: Some("title_encrypted".to_string()),
+                kind: IssueKind::DecryptionFailed,
+                message: "aead::Error".to_string(),
+            }],
+        };
+        let text = format_report(&report);
+        assert!(text.contains("Entries: 1/2 ok"));
+        assert!(text.contains("entries #7 (2024-01-02, title_encrypted): DecryptionFailed"));
+    }
+}
@@ -2,7 +2,7 @@ use crate::db::schema::{
     create_database, create_database_auto, open_database, open_database_auto,
     open_database_with_keypair,
 };
-use log::{info, warn};
+use log::info;
 use tauri::{AppHandle, State, Wry};
 use zeroize::{Zeroize, Zeroizing};
 
@@ -62,6 +62,8 @@ pub fn unlock_diary(
 
     let db_conn = open_database(&db_path, password, &backups_dir)?;
 
+    super::backup_after_unlock(&app, &state, &db_conn, &backups_dir);
+
     let mut db_state = state
         .db
         .lock()
@@ -70,10 +72,6 @@ pub fn unlock_diary(
 
     info!("Journal unlocked");
 
-    if let Err(e) = crate::backup::backup_and_rotate(&db_path, &backups_dir) {
-        warn!("Failed to create backup: {}", e);
-    }
-
     crate::menu::update_menu_lock_state(&app, false);
     Ok(())
 }
@@ -100,23 +98,14 @@ pub fn unlock_diary_with_keypair(
         return Err("No journal found. Please create one first.".to_string());
     }
 
-    // Read private key hex from file
-    let key_hex = std::fs::read_to_string(&key_path)
-        .map_err(|e| format!("Failed to read key file: {}", e))?;
-    let mut key_bytes_vec = hex::decode(key_hex.trim())
-        .map_err(|_| "Invalid key file: expected hex-encoded private key".to_string())?;
-
-    if key_bytes_vec.len() != 32 {
-        return Err("Invalid key file: expected 32-byte (64 hex char) private key".to_string());
-    }
-
-    let mut private_key = [0u8; 32];
-    private_key.copy_from_slice(&key_bytes_vec);
-    key_bytes_vec.zeroize();
+    let mut private_key =
+        crate::auth::keypair::read_private_key_file(std::path::Path::new(&key_path))?;
 
     let db_conn = open_database_with_keypair(&db_path, private_key, &backups_dir)?;
     private_key.zeroize();
 
+    super::backup_after_unlock(&app, &state, &db_conn, &backups_dir);
+
     let mut db_state = state
         .db
         .lock()
@@ -125,10 +114,6 @@ pub fn unlock_diary_with_keypair(
 
     info!("Journal unlocked with key file");
 
-    if let Err(e) = crate::backup::backup_and_rotate(&db_path, &backups_dir) {
-        warn!("Failed to create backup: {}", e);
-    }
-
     crate::menu::update_menu_lock_state(&app, false);
     Ok(())
 }
@@ -136,7 +121,7 @@ pub fn unlock_diary_with_keypair(
 /// Locks the diary (closes the database connection)
 #[tauri::command]
 pub fn lock_diary(state: State<DiaryState>, app: AppHandle<Wry>) -> Result<(), String> {
-    if !super::lock_diary_inner(&state)? {
+    if !super::lock_diary_inner(&state, Some(&app))? {
         return Err("Journal is not unlocked".to_string());
     }
 
@@ -346,6 +331,8 @@ pub fn unlock_diary_auto(state: State<DiaryState>, app: AppHandle<Wry>) -> Resul
     let db_conn = open_database_auto(&db_path, &auto_key_bytes, &backups_dir)?;
     // auto_key_bytes zeroizes here on drop
 
+    super::backup_after_unlock(&app, &state, &db_conn, &backups_dir);
+
     let mut db_state = state
         .db
         .lock()
@@ -354,10 +341,6 @@ pub fn unlock_diary_auto(state: State<DiaryState>, app: AppHandle<Wry>) -> Resul
 
     info!("Local-only journal unlocked");
 
-    if let Err(e) = crate::backup::backup_and_rotate(&db_path, &backups_dir) {
-        warn!("Failed to create backup: {}", e);
-    }
-
     crate::menu::update_menu_lock_state(&app, false);
     Ok(())
 }
@@ -367,6 +350,7 @@ pub fn unlock_diary_auto(state: State<DiaryState>, app: AppHandle<Wry>) -> Resul
 #[cfg(test)]
 mod tests {
     use super::super::test_helpers::*;
+    use crate::backup::schedule::BackupMark;
     use crate::db::schema::{create_database, open_database};
 
     #[test]
@@ -415,7 +399,7 @@ mod tests {
             *db = Some(db_conn);
         }
 
-        let did_lock = super::super::lock_diary_inner(&state).unwrap();
+        let did_lock = super::super::lock_diary_inner(&state, None).unwrap();
         assert!(did_lock);
         assert!(state.db.lock().unwrap().is_none());
 
@@ -426,12 +410,136 @@ mod tests {
     fn test_lock_diary_inner_noop_when_already_locked() {
         let (state, db_path, backups_dir) = make_state("lock_inner_locked");
 
-        let did_lock = super::super::lock_diary_inner(&state).unwrap();
+        let did_lock = super::super::lock_diary_inner(&state, None).unwrap();
         assert!(!did_lock);
 
         cleanup(&db_path, &backups_dir);
     }
 
+    fn test_entry(date: &str) -> crate::db::queries::DiaryEntry {
+        crate::db::queries::DiaryEntry {
+            id: 0,
+            date: date.to_string(),
+            title: "Entry".to_string(),
+            text: "<p>Text</p>".to_string(),
+            word_count: 1,
+            date_created: "2024-01-01T00:00:00Z".to_string(),
+            date_updated: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn backup_count(backups_dir: &std::path::Path) -> usize {
+        std::fs::read_dir(backups_dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).count())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_lock_diary_inner_backs_up_only_when_changed() {
+        let (state, db_path, backups_dir) = make_state("lock_inner_backup");
+        let db_conn = create_database(&db_path, "password".to_string()).unwrap();
+        *state.last_backup.lock().unwrap() = Some(BackupMark::new(db_conn.conn().total_changes()));
+        *state.db.lock().unwrap() = Some(db_conn);
+
+        // Nothing written since the last backup
+        assert!(super::super::lock_diary_inner(&state, None).unwrap());
+        assert_eq!(backup_count(&backups_dir), 0);
+
+        let db_conn = open_database(&db_path, "password".to_string(), &backups_dir).unwrap();
+        *state.last_backup.lock().unwrap() = Some(BackupMark::new(db_conn.conn().total_changes()));
+        crate::db::queries::insert_entry(&db_conn, &test_entry("2024-01-01")).unwrap();
+        *state.db.lock().unwrap() = Some(db_conn);
+
+        assert!(super::super::lock_diary_inner(&state, None).unwrap());
+        assert_eq!(backup_count(&backups_dir), 1);
+        assert!(state.last_backup.lock().unwrap().is_none());
+
+        cleanup(&db_path, &backups_dir);
+    }
+
+    #[test]
+    fn test_lock_diary_inner_mirrors_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let mirror_dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("diary.db");
+        let backups_dir = dir.path().join("backups");
+        let journal = crate::config::JournalConfig {
+            id: "mirror1234567890a".to_string(),
+            name: "Journal".to_string(),
+            path: dir.path().to_string_lossy().to_string(),
+            auto_key: None,
+            backup_retention: None,
+            backup_mirrors: vec![crate::backup::BackupMirror {
+                path: mirror_dir.path().to_string_lossy().to_string(),
+                retention: None,
+            }],
+            incremental_backups: false,
+            backup_interval_minutes: None,
+        };
+        crate::config::save_journals(dir.path(), &[journal], "mirror1234567890a").unwrap();
+        let state = super::super::DiaryState::new(
+            db_path.clone(),
+            backups_dir.clone(),
+            dir.path().to_path_buf(),
+        );
+
+        let db_conn = create_database(&db_path, "password".to_string()).unwrap();
+        crate::db::queries::insert_entry(&db_conn, &test_entry("2024-01-01")).unwrap();
+        *state.db.lock().unwrap() = Some(db_conn);
+
+        assert!(super::super::lock_diary_inner(&state, None).unwrap());
+        assert_eq!(backup_count(&backups_dir), 1);
+
+        // Mirrors are written on a background thread
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while backup_count(mirror_dir.path()) == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(backup_count(mirror_dir.path()), 1);
+    }
+
+    #[test]
+    fn test_scheduled_backup_runs_when_due() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("diary.db");
+        let backups_dir = dir.path().join("backups");
+        let journal = crate::config::JournalConfig {
+            id: "sched1234567890ab".to_string(),
+            name: "Journal".to_string(),
+            path: dir.path().to_string_lossy().to_string(),
+            auto_key: None,
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
+        };
+        crate::config::save_journals(dir.path(), &[journal], "sched1234567890ab").unwrap();
+        let state = super::super::DiaryState::new(
+            db_path.clone(),
+            backups_dir.clone(),
+            dir.path().to_path_buf(),
+        );
+
+        let db_conn = create_database(&db_path, "password".to_string()).unwrap();
+        let an_hour_ago = std::time::Instant::now() - std::time::Duration::from_secs(3600);
+        *state.last_backup.lock().unwrap() = Some(BackupMark {
+            taken_at: an_hour_ago,
+            changes: db_conn.conn().total_changes(),
+        });
+        crate::db::queries::insert_entry(&db_conn, &test_entry("2024-01-01")).unwrap();
+        *state.db.lock().unwrap() = Some(db_conn);
+
+        // Scheduled backups are off until an interval is configured
+        assert!(!super::super::run_scheduled_backup(&state, None).unwrap());
+        crate::config::save_journal_backup_interval(dir.path(), "sched1234567890ab", Some(30))
+            .unwrap();
+        assert!(super::super::run_scheduled_backup(&state, None).unwrap());
+        assert_eq!(backup_count(&backups_dir), 1);
+
+        // No changes since, so the next tick does nothing
+        assert!(!super::super::run_scheduled_backup(&state, None).unwrap());
+    }
+
     #[test]
     fn test_wrong_password() {
         let (_, db_path, backups_dir) = make_state("wrong_pw");
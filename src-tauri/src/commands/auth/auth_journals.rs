@@ -1,8 +1,9 @@
 use log::info;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, State, Wry};
 
 use super::DiaryState;
+use crate::backup::{BackupMirror, RetentionPolicy};
 use crate::config::{self, JournalConfig, JournalInfo};
 
 // Note: #[tauri::command] attributes are applied below, after the inner functions.
@@ -47,6 +48,10 @@ fn add_journal_inner(
         name,
         path,
         auto_key: None,
+        backup_retention: None,
+        backup_mirrors: Vec::new(),
+        incremental_backups: false,
+        backup_interval_minutes: None,
     };
     journals.push(journal.clone());
 
@@ -147,6 +152,155 @@ pub fn rename_journal(id: String, name: String, state: State<DiaryState>) -> Res
     rename_journal_inner(id, name, &state.app_data_dir)
 }
 
+fn get_backup_retention_inner(
+    id: String,
+    app_data_dir: &std::path::Path,
+) -> Result<RetentionPolicy, String> {
+    let journals = config::load_journals(app_data_dir);
+    let journal = journals
+        .iter()
+        .find(|j| j.id == id)
+        .ok_or("Journal not found")?;
+    Ok(journal.backup_retention.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_backup_retention(
+    id: String,
+    state: State<DiaryState>,
+) -> Result<RetentionPolicy, String> {
+    get_backup_retention_inner(id, &state.app_data_dir)
+}
+
+/// Sets a journal's backup retention policy. `None` restores the default.
+/// Takes effect at the next rotation (on unlock).
+#[tauri::command]
+pub fn set_backup_retention(
+    id: String,
+    policy: Option<RetentionPolicy>,
+    state: State<DiaryState>,
+) -> Result<(), String> {
+    config::save_journal_backup_retention(&state.app_data_dir, &id, policy)?;
+    info!("Backup retention updated for journal: {}", id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_backup_mirrors(
+    id: String,
+    state: State<DiaryState>,
+) -> Result<Vec<BackupMirror>, String> {
+    let journals = config::load_journals(&state.app_data_dir);
+    let journal = journals
+        .into_iter()
+        .find(|j| j.id == id)
+        .ok_or("Journal not found")?;
+    Ok(journal.backup_mirrors)
+}
+
+fn set_backup_mirrors_inner(
+    id: String,
+    mirrors: Vec<BackupMirror>,
+    app_data_dir: &std::path::Path,
+) -> Result<(), String> {
+    let journals = config::load_journals(app_data_dir);
+    let journal = journals
+        .iter()
+        .find(|j| j.id == id)
+        .ok_or("Journal not found")?;
+    let own_backups = PathBuf::from(&journal.path).join("backups");
+
+    for (i, mirror) in mirrors.iter().enumerate() {
+        let path = PathBuf::from(&mirror.path);
+        if !path.is_absolute() {
+            return Err("Backup destination must be an absolute path".to_string());
+        }
+        if path == own_backups {
+            return Err("Backup destination is the journal's own backups folder".to_string());
+        }
+        if mirrors[..i].iter().any(|m| Path::new(&m.path) == path) {
+            return Err(format!("Backup destination listed twice: {}", mirror.path));
+        }
+        // Rotation in a shared folder would delete the other journal's backups
+        let shared = journals.iter().any(|j| {
+            j.id != id
+                && (PathBuf::from(&j.path).join("backups") == path
+                    || j.backup_mirrors.iter().any(|m| Path::new(&m.path) == path))
+        });
+        if shared {
+            return Err(format!(
+                "Backup destination is already used by another journal: {}",
+                mirror.path
+            ));
+        }
+    }
+
+    config::save_journal_backup_mirrors(app_data_dir, &id, mirrors)?;
+    info!("Backup destinations updated for journal: {}", id);
+    Ok(())
+}
+
+/// Replaces a journal's extra backup destinations. Each destination must be an
+/// absolute path used by no other journal. Takes effect at the next unlock.
+#[tauri::command]
+pub fn set_backup_mirrors(
+    id: String,
+    mirrors: Vec<BackupMirror>,
+    state: State<DiaryState>,
+) -> Result<(), String> {
+    set_backup_mirrors_inner(id, mirrors, &state.app_data_dir)
+}
+
+#[tauri::command]
+pub fn get_incremental_backups(id: String, state: State<DiaryState>) -> Result<bool, String> {
+    let journals = config::load_journals(&state.app_data_dir);
+    let journal = journals
+        .iter()
+        .find(|j| j.id == id)
+        .ok_or("Journal not found")?;
+    Ok(journal.incremental_backups)
+}
+
+/// Switches a journal between full backup copies and deduplicating snapshots.
+/// Existing backups of either kind are kept. Takes effect at the next unlock.
+#[tauri::command]
+pub fn set_incremental_backups(
+    id: String,
+    enabled: bool,
+    state: State<DiaryState>,
+) -> Result<(), String> {
+    config::save_journal_incremental_backups(&state.app_data_dir, &id, enabled)?;
+    info!(
+        "Incremental backups {} for journal: {}",
+        if enabled { "enabled" } else { "disabled" },
+        id
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_backup_interval(id: String, state: State<DiaryState>) -> Result<Option<u32>, String> {
+    let journals = config::load_journals(&state.app_data_dir);
+    let journal = journals
+        .iter()
+        .find(|j| j.id == id)
+        .ok_or("Journal not found")?;
+    Ok(journal.backup_interval_minutes)
+}
+
+/// Sets how often (in minutes) a journal is backed up while it stays unlocked.
+/// `None` turns scheduled backups off; unlock and lock backups still happen.
+#[tauri::command]
+pub fn set_backup_interval(
+    id: String,
+    minutes: Option<u32>,
+    state: State<DiaryState>,
+) -> Result<(), String> {
+    config::save_journal_backup_interval(&state.app_data_dir, &id, minutes)?;
+    info!("Backup interval updated for journal: {}", id);
+    Ok(())
+}
+
 fn switch_journal_inner(id: String, state: &DiaryState) -> Result<(), String> {
     let journals = config::load_journals(&state.app_data_dir);
     let journal = journals
@@ -256,6 +410,82 @@ mod tests {
         cleanup(&app_dir);
     }
 
+    #[test]
+    fn test_get_backup_retention_inner() {
+        let (_state, app_dir) = make_test_env("retention");
+        let journal = add_journal_inner(
+            "Journal".to_string(),
+            std::env::temp_dir().to_str().unwrap().to_string(),
+            &app_dir,
+        )
+        .unwrap();
+
+        let policy = get_backup_retention_inner(journal.id.clone(), &app_dir).unwrap();
+        assert_eq!(policy, RetentionPolicy::default());
+
+        let custom = RetentionPolicy {
+            daily: 14,
+            weekly: 8,
+            monthly: 6,
+        };
+        config::save_journal_backup_retention(&app_dir, &journal.id, Some(custom)).unwrap();
+        assert_eq!(
+            get_backup_retention_inner(journal.id, &app_dir).unwrap(),
+            custom
+        );
+        assert!(get_backup_retention_inner("nope".to_string(), &app_dir).is_err());
+
+        cleanup(&app_dir);
+    }
+
+    #[test]
+    fn test_set_backup_mirrors_validation() {
+        let (_state, app_dir) = make_test_env("mirrors");
+        let base = std::env::temp_dir();
+        let first = add_journal_inner(
+            "First".to_string(),
+            base.to_str().unwrap().to_string(),
+            &app_dir,
+        )
+        .unwrap();
+        let second = add_journal_inner(
+            "Second".to_string(),
+            base.to_str().unwrap().to_string(),
+            &app_dir,
+        )
+        .unwrap();
+        let mirror = |path: PathBuf| BackupMirror {
+            path: path.to_string_lossy().to_string(),
+            retention: None,
+        };
+        let nas = base.join("nas-first");
+
+        set_backup_mirrors_inner(first.id.clone(), vec![mirror(nas.clone())], &app_dir).unwrap();
+        assert_eq!(config::load_journals(&app_dir)[0].backup_mirrors.len(), 1);
+
+        let relative = BackupMirror {
+            path: "relative/dir".to_string(),
+            retention: None,
+        };
+        assert!(set_backup_mirrors_inner(first.id.clone(), vec![relative], &app_dir).is_err());
+        assert!(set_backup_mirrors_inner(
+            first.id.clone(),
+            vec![mirror(base.join("backups"))],
+            &app_dir
+        )
+        .is_err());
+        assert!(set_backup_mirrors_inner(
+            first.id.clone(),
+            vec![mirror(nas.clone()), mirror(nas.clone())],
+            &app_dir
+        )
+        .is_err());
+        // Already used by the first journal
+        assert!(set_backup_mirrors_inner(second.id, vec![mirror(nas)], &app_dir).is_err());
+
+        cleanup(&app_dir);
+    }
+
     #[test]
     fn test_switch_journal_updates_paths() {
         let dir_a = std::env::temp_dir().join("journal_switch_a");
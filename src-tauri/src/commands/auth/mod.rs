@@ -1,8 +1,10 @@
+use crate::backup::schedule::{self, BackupMark};
 use crate::db::schema::DatabaseConnection;
 use log::{info, warn};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, State, Wry};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State, Wry};
 
 /// Shared state for the database connection
 pub struct DiaryState {
@@ -12,6 +14,9 @@ pub struct DiaryState {
     /// App data directory — always the fixed system location, used for config.json.
     /// Never changes after startup, so no Mutex needed.
     pub app_data_dir: PathBuf,
+    /// Last backup of the unlocked journal; `None` while locked or before the
+    /// first backup of the session.
+    pub last_backup: Mutex<Option<BackupMark>>,
 }
 
 impl DiaryState {
@@ -21,6 +26,7 @@ impl DiaryState {
             db_path: Mutex::new(db_path),
             backups_dir: Mutex::new(backups_dir),
             app_data_dir,
+            last_backup: Mutex::new(None),
         }
     }
 }
@@ -30,15 +36,32 @@ struct JournalLockedEventPayload {
     reason: String,
 }
 
-fn lock_diary_inner(state: &DiaryState) -> Result<bool, String> {
+fn lock_diary_inner(state: &DiaryState, app: Option<&AppHandle<Wry>>) -> Result<bool, String> {
     let mut db_state = state
         .db
         .lock()
         .map_err(|_| "Failed to access journal state".to_string())?;
 
-    if db_state.is_none() {
+    let Some(db) = db_state.as_ref() else {
         return Ok(false);
+    };
+
+    // Back up unsaved work before the connection goes away
+    let mut last_backup = state
+        .last_backup
+        .lock()
+        .map_err(|_| "Failed to access journal state".to_string())?;
+    if schedule::has_changes_since(last_backup.as_ref(), db.conn().total_changes()) {
+        match state.backups_dir.lock() {
+            Ok(backups_dir) => {
+                if let Err(e) = take_backup(db, &backups_dir, &state.app_data_dir, app) {
+                    warn!("Failed to create backup on lock: {}", e);
+                }
+            }
+            Err(_) => warn!("Skipping backup on lock: state lock poisoned"),
+        }
     }
+    *last_backup = None;
 
     *db_state = None;
     Ok(true)
@@ -61,13 +84,155 @@ fn emit_diary_locked(app: &AppHandle<Wry>, reason: &str) {
     }
 }
 
+fn emit_backup_progress(app: &AppHandle<Wry>, progress: crate::backup::BackupProgress) {
+    if let Err(error) = app.emit("backup-progress", progress) {
+        warn!("Failed to emit backup-progress event: {}", error);
+    }
+}
+
+/// Backs up the open journal with the active journal's settings: a snapshot
+/// into the incremental store, or a full copy rotated by `backup_and_rotate`.
+/// A full copy reports its progress through `backup-progress` events when an
+/// app handle is given. The journal's mirrors are then refreshed in the background, whether or not
+/// the local backup succeeded.
+fn take_backup(
+    db: &DatabaseConnection,
+    backups_dir: &Path,
+    app_data_dir: &Path,
+    app: Option<&AppHandle<Wry>>,
+) -> Result<(), String> {
+    let result = backup_locally(db, backups_dir, app_data_dir, app);
+    match db.conn().path().filter(|path| !path.is_empty()) {
+        Some(db_path) => spawn_backup_mirrors(app, PathBuf::from(db_path), app_data_dir),
+        None => warn!("Skipping backup mirrors: journal has no file path"),
+    }
+    result
+}
+
+fn backup_locally(
+    db: &DatabaseConnection,
+    backups_dir: &Path,
+    app_data_dir: &Path,
+    app: Option<&AppHandle<Wry>>,
+) -> Result<(), String> {
+    let retention = crate::config::load_active_backup_retention(app_data_dir);
+    if crate::config::load_active_incremental_backups(app_data_dir) {
+        let store_dir = crate::backup::incremental::store_dir(backups_dir);
+        crate::backup::incremental::create_snapshot(db, &store_dir)?;
+        crate::backup::incremental::prune_snapshots(&store_dir, &retention)?;
+    } else {
+        crate::backup::backup_and_rotate(db, backups_dir, &retention, |progress| {
+            if let Some(app) = app {
+                emit_backup_progress(app, progress);
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Backs up a freshly unlocked journal and its mirrors. Failures are logged
+/// and never block the unlock.
+fn backup_after_unlock(
+    app: &AppHandle<Wry>,
+    state: &DiaryState,
+    db: &DatabaseConnection,
+    backups_dir: &Path,
+) {
+    match take_backup(db, backups_dir, &state.app_data_dir, Some(app)) {
+        Ok(()) => {
+            if let Ok(mut last_backup) = state.last_backup.lock() {
+                *last_backup = Some(BackupMark::new(db.conn().total_changes()));
+            }
+        }
+        Err(e) => warn!("Failed to create backup: {}", e),
+    }
+}
+
+/// Starts the thread that backs up the unlocked journal every
+/// `backup_interval_minutes` (per journal, off by default) while there are
+/// changes since the last backup. Mirrors are refreshed with every backup.
+pub fn spawn_backup_scheduler(app: AppHandle<Wry>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(schedule::SCHEDULER_TICK);
+        let state = app.state::<DiaryState>();
+        if let Err(e) = run_scheduled_backup(&state, Some(&app)) {
+            warn!("Scheduled backup failed: {}", e);
+        }
+    });
+}
+
+/// One scheduler tick. Returns `Ok(true)` if a backup was taken.
+fn run_scheduled_backup(state: &DiaryState, app: Option<&AppHandle<Wry>>) -> Result<bool, String> {
+    let Some(interval) = crate::config::load_active_backup_interval(&state.app_data_dir) else {
+        return Ok(false);
+    };
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let Some(db) = db_state.as_ref() else {
+        return Ok(false);
+    };
+    let mut last_backup = state
+        .last_backup
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+
+    let changes = db.conn().total_changes();
+    if !schedule::is_backup_due(last_backup.as_ref(), changes, interval, Instant::now()) {
+        return Ok(false);
+    }
+    let backups_dir = state
+        .backups_dir
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?
+        .clone();
+    take_backup(db, &backups_dir, &state.app_data_dir, app)?;
+    *last_backup = Some(BackupMark::new(changes));
+    info!("Scheduled backup taken");
+    Ok(true)
+}
+
+/// Mirrors a fresh backup of the journal at `db_path` to the active journal's
+/// extra destinations on a background thread, so a slow or missing drive never
+/// delays unlock, lock or editing. Failed destinations are logged and, when an
+/// app handle is given, reported to the frontend through a
+/// `backup-mirror-failed` event.
+fn spawn_backup_mirrors(app: Option<&AppHandle<Wry>>, db_path: PathBuf, app_data_dir: &Path) {
+    let mirrors = crate::config::load_active_backup_mirrors(app_data_dir);
+    if mirrors.is_empty() {
+        return;
+    }
+    let retention = crate::config::load_active_backup_retention(app_data_dir);
+    let app = app.cloned();
+
+    std::thread::spawn(move || {
+        let failed: Vec<_> = crate::backup::backup_to_mirrors(&db_path, &mirrors, &retention)
+            .into_iter()
+            .filter(|report| report.error.is_some())
+            .collect();
+        for report in &failed {
+            warn!(
+                "Failed to mirror backup to {}: {}",
+                report.destination,
+                report.error.as_deref().unwrap_or_default()
+            );
+        }
+        if let Some(app) = app.filter(|_| !failed.is_empty()) {
+            if let Err(error) = app.emit("backup-mirror-failed", failed) {
+                warn!("Failed to emit backup-mirror-failed event: {}", error);
+            }
+        }
+    });
+}
+
 pub(crate) fn auto_lock_diary_if_unlocked(
     state: State<DiaryState>,
     app: AppHandle<Wry>,
     reason: &str,
 ) -> Result<bool, String> {
     emit_diary_locking(&app, reason);
-    let did_lock = lock_diary_inner(&state)?;
+    let did_lock = lock_diary_inner(&state, Some(&app))?;
 
     if did_lock {
         info!("Journal auto-locked ({})", reason);
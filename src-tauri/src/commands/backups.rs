@@ -0,0 +1,205 @@
+use crate::backup::incremental::{self, SnapshotInfo};
+use crate::backup::{self, BackupInfo};
+use crate::commands::auth::DiaryState;
+use crate::db::queries::{self, DiaryEntry};
+use crate::db::schema::DatabaseConnection;
+use log::info;
+use std::path::PathBuf;
+use tauri::State;
+
+/// Result of restoring a whole backup
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RestoreResult {
+    /// Backup of the diary as it was just before the restore
+    pub safety_backup: String,
+}
+
+/// Result of restoring selected entries from a backup
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RestoreEntriesResult {
+    pub entries_restored: usize,
+    /// Entries already present in the live diary with identical content
+    pub entries_skipped: usize,
+}
+
+fn backups_dir(state: &DiaryState) -> Result<PathBuf, String> {
+    Ok(state
+        .backups_dir
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?
+        .clone())
+}
+
+/// Lists the active journal's backups, newest first
+#[tauri::command]
+pub fn list_backups(state: State<DiaryState>) -> Result<Vec<BackupInfo>, String> {
+    backup::list_backups(&backups_dir(&state)?)
+}
+
+/// Opens a backup read-only with the unlocked journal's key and returns its entries
+#[tauri::command]
+pub fn preview_backup(
+    file_name: String,
+    state: State<DiaryState>,
+) -> Result<Vec<DiaryEntry>, String> {
+    let backup_path = backup::resolve_backup_path(&backups_dir(&state)?, &file_name)?;
+
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to preview backups")?;
+
+    backup::read_backup_entries(db, &backup_path)
+}
+
+/// Replaces the live journal with a backup. The current state is backed up first.
+#[tauri::command]
+pub fn restore_backup(
+    file_name: String,
+    state: State<DiaryState>,
+) -> Result<RestoreResult, String> {
+    let backups_dir = backups_dir(&state)?;
+    let backup_path = backup::resolve_backup_path(&backups_dir, &file_name)?;
+
+    let mut db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_mut()
+        .ok_or("Journal must be unlocked to restore a backup")?;
+
+    let safety_backup = backup::restore_backup(db, &backup_path, &backups_dir)?;
+    Ok(RestoreResult {
+        safety_backup: safety_backup.to_string_lossy().to_string(),
+    })
+}
+
+/// Copies the given entries (by their id in the backup) into the live journal
+#[tauri::command]
+pub fn restore_backup_entries(
+    file_name: String,
+    entry_ids: Vec<i64>,
+    state: State<DiaryState>,
+) -> Result<RestoreEntriesResult, String> {
+    let backup_path = backup::resolve_backup_path(&backups_dir(&state)?, &file_name)?;
+
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to restore entries")?;
+
+    let entries = backup::read_backup_entries(db, &backup_path)?;
+    let result = restore_entries(db, entries, &entry_ids)?;
+    info!(
+        "Restored {} entries from {} ({} already present)",
+        result.entries_restored, file_name, result.entries_skipped
+    );
+    Ok(result)
+}
+
+/// Lists the active journal's incremental snapshots, newest first
+#[tauri::command]
+pub fn list_incremental_backups(state: State<DiaryState>) -> Result<Vec<SnapshotInfo>, String> {
+    incremental::list_snapshots(&incremental::store_dir(&backups_dir(&state)?))
+}
+
+/// Rebuilds an incremental snapshot into a new diary file at `output_path`.
+/// The live journal is left untouched; the new file opens with the
+/// credentials that were valid when the snapshot was taken.
+#[tauri::command]
+pub fn restore_incremental_backup(
+    name: String,
+    output_path: String,
+    state: State<DiaryState>,
+) -> Result<usize, String> {
+    let store_dir = incremental::store_dir(&backups_dir(&state)?);
+    incremental::restore_snapshot(&store_dir, &name, &PathBuf::from(output_path))
+}
+
+/// Inserts the selected backup entries as new entries, skipping any that
+/// already exist in the live diary with the same date, title and text.
+fn restore_entries(
+    db: &DatabaseConnection,
+    backup_entries: Vec<DiaryEntry>,
+    entry_ids: &[i64],
+) -> Result<RestoreEntriesResult, String> {
+    let mut entries_restored = 0;
+    let mut entries_skipped = 0;
+
+    for entry in backup_entries
+        .into_iter()
+        .filter(|e| entry_ids.contains(&e.id))
+    {
+        let existing = queries::get_entries_by_date(db, &entry.date)?;
+        if existing
+            .iter()
+            .any(|e| e.title == entry.title && e.text == entry.text)
+        {
+            entries_skipped += 1;
+            continue;
+        }
+        queries::insert_entry(db, &entry)?;
+        entries_restored += 1;
+    }
+
+    Ok(RestoreEntriesResult {
+        entries_restored,
+        entries_skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::create_database;
+
+    fn create_test_entry(date: &str, title: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 0,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: format!("<p>{}</p>", title),
+            word_count: 1,
+            date_created: "2024-01-01T12:00:00Z".to_string(),
+            date_updated: "2024-01-01T12:00:00Z".to_string(),
+        }
+    }
+
+    fn titles(db: &DatabaseConnection) -> Vec<String> {
+        queries::get_all_entries(db)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_restore_selected_entries_skips_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups_dir = dir.path().join("backups");
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        queries::insert_entry(&db, &create_test_entry("2024-01-01", "Kept")).unwrap();
+        queries::insert_entry(&db, &create_test_entry("2024-01-02", "Deleted later")).unwrap();
+        queries::insert_entry(&db, &create_test_entry("2024-01-03", "Not selected")).unwrap();
+
+        let backup_path = backup::create_backup(&db, &backups_dir).unwrap();
+        let all = queries::get_all_entries(&db).unwrap();
+        queries::delete_entry_by_id(&db, all[1].id).unwrap();
+        queries::delete_entry_by_id(&db, all[2].id).unwrap();
+
+        let backup_entries = backup::read_backup_entries(&db, &backup_path).unwrap();
+        assert_eq!(backup_entries.len(), 3);
+
+        let result = restore_entries(&db, backup_entries, &[all[0].id, all[1].id]).unwrap();
+        assert_eq!(result.entries_restored, 1);
+        assert_eq!(result.entries_skipped, 1);
+        assert_eq!(titles(&db), ["Kept", "Deleted later"]);
+    }
+}
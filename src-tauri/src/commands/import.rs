@@ -1,12 +1,15 @@
 use crate::commands::auth::DiaryState;
 use crate::db::queries;
 use crate::db::schema::DatabaseConnection;
-use crate::import::{dayone, dayone_txt, jrnl, minidiary};
+use crate::import::{dayone, dayone_txt, jrnl, minidiary, MAX_IMPORT_FILE_SIZE};
 use log::{debug, error, info};
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io::BufReader;
 use tauri::State;
 
-const MAX_IMPORT_FILE_SIZE: u64 = 100 * 1024 * 1024; // 100 MB
-
 pub(crate) fn read_import_file(file_path: &str) -> Result<String, String> {
     let metadata = std::fs::metadata(file_path).map_err(|e| {
         let err = format!("Cannot access file: {}", e);
@@ -28,17 +31,55 @@ pub(crate) fn read_import_file(file_path: &str) -> Result<String, String> {
     })
 }
 
+/// Opens a file for a streaming import. There is no size limit: streaming
+/// parsers only hold one entry in memory at a time.
+pub(crate) fn open_import_file(file_path: &str) -> Result<BufReader<File>, String> {
+    File::open(file_path).map(BufReader::new).map_err(|e| {
+        let err = format!("Cannot access file: {}", e);
+        error!("{}", err);
+        err
+    })
+}
+
+/// How imported entries are combined with the entries already in the journal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Every imported entry becomes a new entry, even if an identical one exists
+    #[default]
+    Append,
+    /// Entries whose date, title and text match an existing entry are skipped
+    SkipDuplicates,
+    /// An entry with the same date and title as an existing one replaces it
+    /// if its `date_updated` is newer; otherwise it is skipped. An identical
+    /// entry with a newer `date_updated` only updates that timestamp
+    UpdateIfNewer,
+    /// Every date present in the import is cleared before its entries are added
+    ReplaceDate,
+}
+
 /// Import result containing the number of entries imported
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ImportResult {
     pub entries_imported: usize,
+    /// Empty entries, duplicates, and entries older than the existing copy
     pub entries_skipped: usize,
+    /// Existing entries overwritten by a newer imported version
+    pub entries_updated: usize,
+    /// Existing entries deleted because their date was replaced
+    pub entries_removed: usize,
+    /// Id of the import batch, for `undo_last_import`
+    pub batch_id: i64,
+    /// True if the journal's name and backup settings were taken from an
+    /// encrypted archive
+    pub journal_restored: bool,
 }
 
 /// Imports Mini Diary JSON format
 ///
 /// # Arguments
 /// * `file_path` - Path to the JSON file to import
+/// * `mode` - How to merge with existing entries (default: append)
 /// * `state` - Application state containing the database connection
 ///
 /// # Returns
@@ -46,6 +87,7 @@ pub struct ImportResult {
 #[tauri::command]
 pub fn import_minidiary_json(
     file_path: String,
+    mode: Option<ImportMode>,
     state: State<DiaryState>,
 ) -> Result<ImportResult, String> {
     info!("Starting Mini Diary import from file: {}", file_path);
@@ -60,21 +102,15 @@ pub fn import_minidiary_json(
         err.to_string()
     })?;
 
-    // Read file
-    debug!("Reading file...");
-    let json_content = read_import_file(&file_path)?;
+    debug!("Opening file...");
+    let reader = open_import_file(&file_path)?;
 
-    // Parse JSON
-    debug!("Parsing Mini Diary JSON...");
-    let entries = minidiary::parse_minidiary_json(&json_content).map_err(|e| {
-        error!("Parse error: {}", e);
-        e
-    })?;
-    debug!("Parsed {} entries", entries.len());
-
-    // Import entries (each entry always creates a new row)
-    debug!("Importing entries...");
-    let result = import_entries(db, entries).map_err(|e| {
+    // Parse and import entries as they are read, so file size is not limited
+    debug!("Streaming Mini Diary JSON...");
+    let result = backup_and_import_stream(&state, db, mode.unwrap_or_default(), |on_entry| {
+        minidiary::parse_minidiary_json_reader(reader, on_entry)
+    })
+    .map_err(|e| {
         error!("Import error: {}", e);
         e
     })?;
@@ -92,6 +128,7 @@ pub fn import_minidiary_json(
 ///
 /// # Arguments
 /// * `file_path` - Path to the JSON file to import
+/// * `mode` - How to merge with existing entries (default: append)
 /// * `state` - Application state containing the database connection
 ///
 /// # Returns
@@ -99,6 +136,7 @@ pub fn import_minidiary_json(
 #[tauri::command]
 pub fn import_dayone_json(
     file_path: String,
+    mode: Option<ImportMode>,
     state: State<DiaryState>,
 ) -> Result<ImportResult, String> {
     info!("Starting Day One JSON import from file: {}", file_path);
@@ -113,21 +151,15 @@ pub fn import_dayone_json(
         err.to_string()
     })?;
 
-    // Read file
-    debug!("Reading file...");
-    let json_content = read_import_file(&file_path)?;
-
-    // Parse JSON
-    debug!("Parsing Day One JSON...");
-    let entries = dayone::parse_dayone_json(&json_content).map_err(|e| {
-        error!("Parse error: {}", e);
-        e
-    })?;
-    debug!("Parsed {} entries", entries.len());
+    debug!("Opening file...");
+    let reader = open_import_file(&file_path)?;
 
-    // Import entries
-    debug!("Importing entries...");
-    let result = import_entries(db, entries).map_err(|e| {
+    // Parse and import entries as they are read, so file size is not limited
+    debug!("Streaming Day One JSON...");
+    let result = backup_and_import_stream(&state, db, mode.unwrap_or_default(), |on_entry| {
+        dayone::parse_dayone_json_reader(reader, on_entry)
+    })
+    .map_err(|e| {
         error!("Import error: {}", e);
         e
     })?;
@@ -145,6 +177,7 @@ pub fn import_dayone_json(
 ///
 /// # Arguments
 /// * `file_path` - Path to the JSON file to import
+/// * `mode` - How to merge with existing entries (default: append)
 /// * `state` - Application state containing the database connection
 ///
 /// # Returns
@@ -152,6 +185,7 @@ pub fn import_dayone_json(
 #[tauri::command]
 pub fn import_jrnl_json(
     file_path: String,
+    mode: Option<ImportMode>,
     state: State<DiaryState>,
 ) -> Result<ImportResult, String> {
     info!("Starting jrnl import from file: {}", file_path);
@@ -166,21 +200,15 @@ pub fn import_jrnl_json(
         err.to_string()
     })?;
 
-    // Read file
-    debug!("Reading file...");
-    let json_content = read_import_file(&file_path)?;
-
-    // Parse JSON
-    debug!("Parsing jrnl JSON...");
-    let entries = jrnl::parse_jrnl_json(&json_content).map_err(|e| {
-        error!("Parse error: {}", e);
-        e
-    })?;
-    debug!("Parsed {} entries", entries.len());
+    debug!("Opening file...");
+    let reader = open_import_file(&file_path)?;
 
-    // Import entries
-    debug!("Importing entries...");
-    let result = import_entries(db, entries).map_err(|e| {
+    // Parse and import entries as they are read, so file size is not limited
+    debug!("Streaming jrnl JSON...");
+    let result = backup_and_import_stream(&state, db, mode.unwrap_or_default(), |on_entry| {
+        jrnl::parse_jrnl_json_reader(reader, on_entry)
+    })
+    .map_err(|e| {
         error!("Import error: {}", e);
         e
     })?;
@@ -195,6 +223,7 @@ pub fn import_jrnl_json(
 ///
 /// # Arguments
 /// * `file_path` - Path to the TXT file to import
+/// * `mode` - How to merge with existing entries (default: append)
 /// * `state` - Application state containing the database connection
 ///
 /// # Returns
@@ -202,6 +231,7 @@ pub fn import_jrnl_json(
 #[tauri::command]
 pub fn import_dayone_txt(
     file_path: String,
+    mode: Option<ImportMode>,
     state: State<DiaryState>,
 ) -> Result<ImportResult, String> {
     info!("Starting Day One TXT import from file: {}", file_path);
@@ -230,7 +260,7 @@ pub fn import_dayone_txt(
 
     // Import entries
     debug!("Importing entries...");
-    let result = import_entries(db, entries).map_err(|e| {
+    let result = backup_and_import(&state, db, entries, mode.unwrap_or_default()).map_err(|e| {
         error!("Import error: {}", e);
         e
     })?;
@@ -244,31 +274,512 @@ pub fn import_dayone_txt(
     Ok(result)
 }
 
-/// Imports a list of entries into the database
+/// Removes the entries added by the most recent import
 ///
-/// Each entry always creates a new row (AUTOINCREMENT id). No merge logic.
+/// # Returns
+/// The undone batch id, how many entries were removed and how many were kept
+/// because they were edited after the import
+#[tauri::command]
+pub fn undo_last_import(state: State<DiaryState>) -> Result<UndoImportResult, String> {
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to undo an import")?;
+
+    let result = undo_import(db)?;
+    info!(
+        "Undid import batch {}: {} entries removed, {} edited entries kept",
+        result.batch_id, result.entries_removed, result.entries_kept
+    );
+    Ok(result)
+}
+
+/// How many sample titles an import preview shows
+const PREVIEW_SAMPLE_TITLES: usize = 5;
+
+/// Summary of a parsed import file, computed without writing anything
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ImportPreview {
+    pub entry_count: usize,
+    /// Earliest and latest valid entry dates (`YYYY-MM-DD`)
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    /// Dates in the file that already have entries in the journal, sorted
+    pub colliding_dates: Vec<String>,
+    /// Titles of the first few non-empty entries, in file order
+    pub sample_titles: Vec<String>,
+    /// Human-readable problems found in the file
+    pub warnings: Vec<String>,
+}
+
+/// Summarizes `entries` against the journal without modifying it
+pub(crate) fn preview_entries(
+    db: &DatabaseConnection,
+    entries: &[queries::DiaryEntry],
+) -> Result<ImportPreview, String> {
+    let existing = queries::get_all_entries(db)?;
+    let existing_dates: HashSet<&str> = existing.iter().map(|e| e.date.as_str()).collect();
+    let known_hashes: HashSet<Vec<u8>> = existing.iter().map(content_hash).collect();
+
+    let mut preview = ImportPreview {
+        entry_count: entries.len(),
+        ..Default::default()
+    };
+    let mut valid_dates: BTreeSet<&str> = BTreeSet::new();
+    let mut colliding: BTreeSet<&str> = BTreeSet::new();
+    let (mut empty, mut invalid_dates, mut existing_dups, mut file_dups) = (0, 0, 0, 0);
+    let mut seen_in_file: HashSet<Vec<u8>> = HashSet::new();
+
+    for entry in entries {
+        if entry.title.trim().is_empty() && entry.text.trim().is_empty() {
+            empty += 1;
+            continue;
+        }
+        if chrono::NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d").is_ok() {
+            valid_dates.insert(&entry.date);
+        } else {
+            invalid_dates += 1;
+        }
+        if existing_dates.contains(entry.date.as_str()) {
+            colliding.insert(&entry.date);
+        }
+        let hash = content_hash(entry);
+        if known_hashes.contains(&hash) {
+            existing_dups += 1;
+        } else if !seen_in_file.insert(hash) {
+            file_dups += 1;
+        }
+        let title = entry.title.trim();
+        if !title.is_empty() && preview.sample_titles.len() < PREVIEW_SAMPLE_TITLES {
+            preview.sample_titles.push(title.to_string());
+        }
+    }
+
+    preview.first_date = valid_dates.first().map(|d| d.to_string());
+    preview.last_date = valid_dates.last().map(|d| d.to_string());
+    preview.colliding_dates = colliding.into_iter().map(str::to_string).collect();
+
+    if empty > 0 {
+        preview
+            .warnings
+            .push(format!("{} empty entries will be skipped", empty));
+    }
+    if invalid_dates > 0 {
+        preview
+            .warnings
+            .push(format!("{} entries have an invalid date", invalid_dates));
+    }
+    if existing_dups > 0 {
+        preview.warnings.push(format!(
+            "{} entries are already in the journal",
+            existing_dups
+        ));
+    }
+    if file_dups > 0 {
+        preview.warnings.push(format!(
+            "{} entries appear more than once in the file",
+            file_dups
+        ));
+    }
+
+    Ok(preview)
+}
+
+/// Metadata key holding the most recent import batch
+const LAST_IMPORT_BATCH_KEY: &str = "last_import_batch";
+
+/// Metadata key holding the id of the last import batch ever created, so ids
+/// are not reused after an undo clears the batch
+const IMPORT_BATCH_COUNTER_KEY: &str = "import_batch_counter";
+
+/// Rows added by one import, so the import can be undone
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct ImportBatch {
+    id: i64,
+    imported_at: String,
+    entries: Vec<ImportedRow>,
+}
+
+/// A row inserted by an import, with its `date_updated` at import time so
+/// undo can tell whether it was edited since
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct ImportedRow {
+    id: i64,
+    date_updated: String,
+}
+
+/// Result of undoing an import
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UndoImportResult {
+    pub batch_id: i64,
+    pub entries_removed: usize,
+    /// Imported entries edited since the import, which undo leaves in place
+    pub entries_kept: usize,
+}
+
+/// Backs up the journal into its backups folder, then imports `entries` as
+/// one batch. Nothing is imported if the backup fails.
+pub(crate) fn backup_and_import(
+    state: &DiaryState,
+    db: &DatabaseConnection,
+    entries: Vec<queries::DiaryEntry>,
+    mode: ImportMode,
+) -> Result<ImportResult, String> {
+    backup_before_import(state, db)?;
+    import_entries(db, entries, mode)
+}
+
+/// Streaming counterpart of [`backup_and_import`], see [`import_stream`].
+pub(crate) fn backup_and_import_stream(
+    state: &DiaryState,
+    db: &DatabaseConnection,
+    mode: ImportMode,
+    stream: impl FnOnce(&mut dyn FnMut(queries::DiaryEntry) -> Result<(), String>) -> Result<(), String>,
+) -> Result<ImportResult, String> {
+    backup_before_import(state, db)?;
+    import_stream(db, mode, stream)
+}
+
+fn backup_before_import(state: &DiaryState, db: &DatabaseConnection) -> Result<(), String> {
+    let backups_dir = state
+        .backups_dir
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?
+        .clone();
+    let backup_path = crate::backup::create_backup(db, &backups_dir)
+        .map_err(|e| format!("Failed to create pre-import backup: {}", e))?;
+    info!("Pre-import backup created at {:?}", backup_path);
+    Ok(())
+}
+
+/// Imports a list of entries into the database according to `mode`
+///
+/// The whole import runs in one transaction: if any entry fails, the journal
+/// is left exactly as it was. The ids of the inserted rows are recorded as the
+/// last import batch for `undo_last_import`.
+///
+/// Entries with neither title nor text are always skipped. Duplicate detection
+/// compares a hash of each entry's date, title and text (surrounding
+/// whitespace ignored), so it also catches duplicates within the imported file.
 pub(crate) fn import_entries(
     db: &DatabaseConnection,
     entries: Vec<queries::DiaryEntry>,
+    mode: ImportMode,
 ) -> Result<ImportResult, String> {
-    let mut entries_imported = 0;
-    let mut entries_skipped = 0;
+    import_stream(db, mode, |on_entry| {
+        entries.into_iter().try_for_each(on_entry)
+    })
+}
+
+/// Like [`import_entries`], but pulls entries from `stream`, which must call
+/// the function it is given once per entry. Entries are written as they
+/// arrive, so a streaming parser never has to hold the whole file.
+pub(crate) fn import_stream(
+    db: &DatabaseConnection,
+    mode: ImportMode,
+    stream: impl FnOnce(&mut dyn FnMut(queries::DiaryEntry) -> Result<(), String>) -> Result<(), String>,
+) -> Result<ImportResult, String> {
+    db.conn()
+        .execute_batch("BEGIN IMMEDIATE TRANSACTION")
+        .map_err(|e| format!("Failed to begin import transaction: {}", e))?;
+
+    let result = (|| -> Result<ImportResult, String> {
+        let mut importer = EntryImporter::new(db, mode)?;
+        stream(&mut |entry| importer.add(entry))?;
+        let batch = ImportBatch {
+            id: next_import_batch_id(db)?,
+            imported_at: chrono::Utc::now().to_rfc3339(),
+            entries: importer.rows,
+        };
+        save_last_import_batch(db, &batch)?;
+        Ok(ImportResult {
+            batch_id: batch.id,
+            ..importer.result
+        })
+    })();
+
+    match result {
+        Ok(result) => {
+            db.conn()
+                .execute_batch("COMMIT")
+                .map_err(|e| format!("Failed to commit import: {}", e))?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = db.conn().execute_batch("ROLLBACK");
+            Err(e)
+        }
+    }
+}
+
+/// Removes the rows added by the last import, in one transaction.
+/// Rows edited since the import are kept and counted in `entries_kept`.
+/// Entries it updated or replaced are not restored; the pre-import backup has
+/// them.
+pub(crate) fn undo_import(db: &DatabaseConnection) -> Result<UndoImportResult, String> {
+    let batch = load_last_import_batch(db)?.ok_or("There is no import to undo")?;
+
+    db.conn()
+        .execute_batch("BEGIN IMMEDIATE TRANSACTION")
+        .map_err(|e| format!("Failed to begin undo transaction: {}", e))?;
+    let result = (|| -> Result<UndoImportResult, String> {
+        let mut result = UndoImportResult {
+            batch_id: batch.id,
+            entries_removed: 0,
+            entries_kept: 0,
+        };
+        for row in &batch.entries {
+            let date_updated: Option<String> = db
+                .conn()
+                .query_row(
+                    "SELECT date_updated FROM entries WHERE id = ?1",
+                    [row.id],
+                    |r| r.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to read entry: {}", e))?;
+            match date_updated {
+                None => {}
+                Some(date_updated) if date_updated != row.date_updated => {
+                    result.entries_kept += 1;
+                }
+                Some(_) => {
+                    queries::delete_entry_by_id(db, row.id)?;
+                    result.entries_removed += 1;
+                }
+            }
+        }
+        db.conn()
+            .execute(
+                "DELETE FROM metadata WHERE key = ?1",
+                [LAST_IMPORT_BATCH_KEY],
+            )
+            .map_err(|e| format!("Failed to clear import batch: {}", e))?;
+        Ok(result)
+    })();
+
+    match result {
+        Ok(result) => {
+            db.conn()
+                .execute_batch("COMMIT")
+                .map_err(|e| format!("Failed to commit undo: {}", e))?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = db.conn().execute_batch("ROLLBACK");
+            Err(e)
+        }
+    }
+}
+
+fn load_last_import_batch(db: &DatabaseConnection) -> Result<Option<ImportBatch>, String> {
+    let value: Option<String> = db
+        .conn()
+        .query_row(
+            "SELECT value FROM metadata WHERE key = ?1",
+            [LAST_IMPORT_BATCH_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read import batch: {}", e))?;
+    value
+        .map(|v| serde_json::from_str(&v).map_err(|e| format!("Invalid import batch: {}", e)))
+        .transpose()
+}
+
+/// Returns the next import batch id and records it as used.
+fn next_import_batch_id(db: &DatabaseConnection) -> Result<i64, String> {
+    let last: Option<String> = db
+        .conn()
+        .query_row(
+            "SELECT value FROM metadata WHERE key = ?1",
+            [IMPORT_BATCH_COUNTER_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read import batch counter: {}", e))?;
+    let last = match last {
+        Some(value) => value
+            .parse::<i64>()
+            .map_err(|e| format!("Invalid import batch counter: {}", e))?,
+        None => load_last_import_batch(db)?.map_or(0, |b| b.id),
+    };
+    let id = last + 1;
+    db.conn()
+        .execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            [IMPORT_BATCH_COUNTER_KEY, id.to_string().as_str()],
+        )
+        .map_err(|e| format!("Failed to record import batch: {}", e))?;
+    Ok(id)
+}
+
+fn save_last_import_batch(db: &DatabaseConnection, batch: &ImportBatch) -> Result<(), String> {
+    let value = serde_json::to_string(batch)
+        .map_err(|e| format!("Failed to serialize import batch: {}", e))?;
+    db.conn()
+        .execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+            [LAST_IMPORT_BATCH_KEY, value.as_str()],
+        )
+        .map_err(|e| format!("Failed to record import batch: {}", e))?;
+    Ok(())
+}
+
+/// Applies an `ImportMode` to entries one at a time
+struct EntryImporter<'a> {
+    db: &'a DatabaseConnection,
+    mode: ImportMode,
+    /// Journal entries, loaded only for modes that compare against them. In
+    /// update-if-newer mode, rows inserted by this import are added as they go.
+    existing: Vec<queries::DiaryEntry>,
+    known_hashes: HashSet<Vec<u8>>,
+    replaced_dates: HashSet<String>,
+    /// Rows inserted so far, recorded as the import batch
+    rows: Vec<ImportedRow>,
+    result: ImportResult,
+}
+
+impl<'a> EntryImporter<'a> {
+    fn new(db: &'a DatabaseConnection, mode: ImportMode) -> Result<Self, String> {
+        let existing = match mode {
+            ImportMode::SkipDuplicates | ImportMode::UpdateIfNewer => queries::get_all_entries(db)?,
+            ImportMode::Append | ImportMode::ReplaceDate => Vec::new(),
+        };
+        let known_hashes = existing.iter().map(content_hash).collect();
+        Ok(Self {
+            db,
+            mode,
+            existing,
+            known_hashes,
+            replaced_dates: HashSet::new(),
+            rows: Vec::new(),
+            result: ImportResult::default(),
+        })
+    }
+
+    fn add(&mut self, entry: queries::DiaryEntry) -> Result<(), String> {
+        let db = self.db;
 
-    for entry in entries {
         // Skip entries with no meaningful content
         if entry.title.trim().is_empty() && entry.text.trim().is_empty() {
-            entries_skipped += 1;
-            continue;
+            self.result.entries_skipped += 1;
+            return Ok(());
         }
-        // Always insert a new row — AUTOINCREMENT assigns the id
+
+        match self.mode {
+            ImportMode::Append => {}
+            ImportMode::SkipDuplicates => {
+                if !self.known_hashes.insert(content_hash(&entry)) {
+                    self.result.entries_skipped += 1;
+                    return Ok(());
+                }
+            }
+            ImportMode::UpdateIfNewer => {
+                let hash = content_hash(&entry);
+                if self.known_hashes.contains(&hash) {
+                    // Same content: carry over a newer timestamp only
+                    let same = self
+                        .existing
+                        .iter_mut()
+                        .find(|e| e.date == entry.date && content_hash(e) == hash);
+                    match same {
+                        Some(current) if is_newer(&entry.date_updated, &current.date_updated) => {
+                            current.date_updated = entry.date_updated;
+                            queries::update_entry(db, current)?;
+                            refresh_imported_row(&mut self.rows, current);
+                            self.result.entries_updated += 1;
+                        }
+                        _ => self.result.entries_skipped += 1,
+                    }
+                    return Ok(());
+                }
+                let current = self
+                    .existing
+                    .iter_mut()
+                    .find(|e| e.date == entry.date && e.title.trim() == entry.title.trim());
+                if let Some(current) = current {
+                    if is_newer(&entry.date_updated, &current.date_updated) {
+                        current.title = entry.title;
+                        current.text = entry.text;
+                        current.word_count = entry.word_count;
+                        current.date_updated = entry.date_updated;
+                        queries::update_entry(db, current)?;
+                        refresh_imported_row(&mut self.rows, current);
+                        self.known_hashes.insert(hash);
+                        self.result.entries_updated += 1;
+                    } else {
+                        self.result.entries_skipped += 1;
+                    }
+                    return Ok(());
+                }
+                self.known_hashes.insert(hash);
+            }
+            ImportMode::ReplaceDate => {
+                if self.replaced_dates.insert(entry.date.clone()) {
+                    for old in queries::get_entries_by_date(db, &entry.date)? {
+                        queries::delete_entry_by_id(db, old.id)?;
+                        self.result.entries_removed += 1;
+                    }
+                }
+            }
+        }
+
+        // Insert a new row — AUTOINCREMENT assigns the id
         queries::insert_entry(db, &entry)?;
-        entries_imported += 1;
+        let id = db.conn().last_insert_rowid();
+        self.rows.push(ImportedRow {
+            id,
+            date_updated: entry.date_updated.clone(),
+        });
+        self.result.entries_imported += 1;
+        if self.mode == ImportMode::UpdateIfNewer {
+            // Later copies of this entry in the same file update it
+            self.existing.push(queries::DiaryEntry { id, ..entry });
+        }
+        Ok(())
     }
+}
 
-    Ok(ImportResult {
-        entries_imported,
-        entries_skipped,
-    })
+/// Keeps the import batch in step with a row this import inserted and then
+/// updated, so undo still recognises it as untouched since the import.
+fn refresh_imported_row(rows: &mut [ImportedRow], entry: &queries::DiaryEntry) {
+    if let Some(row) = rows.iter_mut().find(|row| row.id == entry.id) {
+        row.date_updated = entry.date_updated.clone();
+    }
+}
+
+/// Hash identifying an entry's content for duplicate detection
+fn content_hash(entry: &queries::DiaryEntry) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for part in [entry.date.as_str(), entry.title.trim(), entry.text.trim()] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().to_vec()
+}
+
+/// True if timestamp `a` is later than `b`. Timestamps are compared as
+/// instants when both parse; anything else falls back to plain string order.
+fn is_newer(a: &str, b: &str) -> bool {
+    match (parse_timestamp(a), parse_timestamp(b)) {
+        (Some(a), Some(b)) => a > b,
+        _ => a > b,
+    }
+}
+
+/// Parses an entry timestamp: RFC 3339, or `YYYY-MM-DD HH:MM:SS` in UTC as
+/// written by the Day One importers.
+fn parse_timestamp(timestamp: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(timestamp) {
+        return Some(dt.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|dt| dt.and_utc())
 }
 
 #[cfg(test)]
@@ -300,7 +811,7 @@ mod tests {
             create_test_entry("2024-01-02", "Entry 2", "Text 2"),
         ];
 
-        let result = import_entries(&db, entries).unwrap();
+        let result = import_entries(&db, entries, ImportMode::Append).unwrap();
 
         assert_eq!(result.entries_imported, 2);
         assert_eq!(result.entries_skipped, 0);
@@ -318,7 +829,7 @@ mod tests {
         // Import entry with same date — should create a second entry (no merge)
         let entries = vec![create_test_entry("2024-01-01", "Evening", "Had dinner")];
 
-        let result = import_entries(&db, entries).unwrap();
+        let result = import_entries(&db, entries, ImportMode::Append).unwrap();
 
         assert_eq!(result.entries_imported, 1);
         assert_eq!(result.entries_skipped, 0);
@@ -328,6 +839,297 @@ mod tests {
         assert_eq!(all.len(), 2);
     }
 
+    fn new_db() -> (tempfile::NamedTempFile, DatabaseConnection) {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path().to_str().unwrap(), "test".to_string()).unwrap();
+        (tmp, db)
+    }
+
+    fn with_updated(mut entry: DiaryEntry, date_updated: &str) -> DiaryEntry {
+        entry.date_updated = date_updated.to_string();
+        entry
+    }
+
+    #[test]
+    fn test_reimport_skip_duplicates() {
+        let (_tmp, db) = new_db();
+        let entries = vec![
+            create_test_entry("2024-01-01", "Entry 1", "Text 1"),
+            create_test_entry("2024-01-02", "Entry 2", "Text 2"),
+        ];
+        import_entries(&db, entries.clone(), ImportMode::SkipDuplicates).unwrap();
+
+        // Same file again, plus one new entry and one in-file duplicate
+        let mut again = entries;
+        again.push(create_test_entry("2024-01-03", "Entry 3", "Text 3"));
+        again.push(create_test_entry("2024-01-03", "Entry 3", "  Text 3\n"));
+        let result = import_entries(&db, again, ImportMode::SkipDuplicates).unwrap();
+
+        assert_eq!(result.entries_imported, 1);
+        assert_eq!(result.entries_skipped, 3);
+        assert_eq!(crate::db::queries::get_all_entries(&db).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_import_update_if_newer() {
+        let (_tmp, db) = new_db();
+        let original = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Day one"),
+            "2024-01-01T10:00:00Z",
+        );
+        crate::db::queries::insert_entry(&db, &original).unwrap();
+
+        let newer = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Day one, edited"),
+            "2024-01-02T10:00:00+02:00",
+        );
+        let older = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Stale copy"),
+            "2023-12-31T10:00:00Z",
+        );
+        let fresh = create_test_entry("2024-01-05", "Other", "New");
+        let result =
+            import_entries(&db, vec![newer, older, fresh], ImportMode::UpdateIfNewer).unwrap();
+
+        assert_eq!(result.entries_updated, 1);
+        assert_eq!(result.entries_skipped, 1);
+        assert_eq!(result.entries_imported, 1);
+        let day = crate::db::queries::get_entries_by_date(&db, "2024-01-01").unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!(day[0].text, "Day one, edited");
+        assert_eq!(day[0].date_created, original.date_created);
+
+        // The same content edited later only moves the timestamp forward
+        let touched = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Day one, edited"),
+            "2024-01-03T10:00:00Z",
+        );
+        let result = import_entries(&db, vec![touched], ImportMode::UpdateIfNewer).unwrap();
+        assert_eq!(result.entries_updated, 1);
+        assert_eq!(result.entries_skipped, 0);
+        let day = crate::db::queries::get_entries_by_date(&db, "2024-01-01").unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!(day[0].date_updated, "2024-01-03T10:00:00Z");
+    }
+
+    #[test]
+    fn test_import_update_if_newer_within_one_file() {
+        let (_tmp, db) = new_db();
+        let first = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Draft"),
+            "2024-01-01T10:00:00Z",
+        );
+        let second = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Final"),
+            "2024-01-02T10:00:00Z",
+        );
+        let stale = with_updated(
+            create_test_entry("2024-01-01", "Trip", "Older draft"),
+            "2023-12-31T10:00:00Z",
+        );
+        let result =
+            import_entries(&db, vec![first, second, stale], ImportMode::UpdateIfNewer).unwrap();
+
+        assert_eq!(result.entries_imported, 1);
+        assert_eq!(result.entries_updated, 1);
+        assert_eq!(result.entries_skipped, 1);
+        let day = crate::db::queries::get_entries_by_date(&db, "2024-01-01").unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!(day[0].text, "Final");
+
+        // The row updated within the import is still undone with it
+        let undone = undo_import(&db).unwrap();
+        assert_eq!(undone.entries_removed, 1);
+        assert_eq!(undone.entries_kept, 0);
+    }
+
+    #[test]
+    fn test_is_newer_compares_day_one_timestamps_as_instants() {
+        // Day One importers write `YYYY-MM-DD HH:MM:SS` (UTC)
+        assert!(is_newer("2024-01-01 10:00:00", "2024-01-01T09:00:00Z"));
+        assert!(!is_newer("2024-01-01T09:00:00Z", "2024-01-01 10:00:00"));
+        assert!(is_newer("2024-01-01T09:00:00+02:00", "2024-01-01 06:00:00"));
+    }
+
+    #[test]
+    fn test_import_replace_date() {
+        let (_tmp, db) = new_db();
+        crate::db::queries::insert_entry(&db, &create_test_entry("2024-01-01", "Old A", "a"))
+            .unwrap();
+        crate::db::queries::insert_entry(&db, &create_test_entry("2024-01-01", "Old B", "b"))
+            .unwrap();
+        crate::db::queries::insert_entry(&db, &create_test_entry("2024-01-02", "Keep", "c"))
+            .unwrap();
+
+        let entries = vec![
+            create_test_entry("2024-01-01", "New A", "x"),
+            create_test_entry("2024-01-01", "New B", "y"),
+        ];
+        let result = import_entries(&db, entries, ImportMode::ReplaceDate).unwrap();
+
+        assert_eq!(result.entries_removed, 2);
+        assert_eq!(result.entries_imported, 2);
+        let titles: Vec<String> = crate::db::queries::get_all_entries(&db)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.title)
+            .collect();
+        assert_eq!(titles.len(), 3);
+        assert!(titles.contains(&"Keep".to_string()));
+        assert!(!titles.contains(&"Old A".to_string()));
+    }
+
+    #[test]
+    fn test_failed_import_rolls_back() {
+        let (_tmp, db) = new_db();
+        db.conn()
+            .execute_batch(
+                "CREATE TRIGGER fail_insert BEFORE INSERT ON entries WHEN NEW.date = '2024-01-03'
+                 BEGIN SELECT RAISE(ABORT, 'simulated failure'); END;",
+            )
+            .unwrap();
+
+        let entries = vec![
+            create_test_entry("2024-01-01", "Entry 1", "Text 1"),
+            create_test_entry("2024-01-02", "Entry 2", "Text 2"),
+            create_test_entry("2024-01-03", "Entry 3", "Text 3"),
+        ];
+        assert!(import_entries(&db, entries, ImportMode::Append).is_err());
+
+        assert!(crate::db::queries::get_all_entries(&db).unwrap().is_empty());
+        assert!(load_last_import_batch(&db).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_undo_last_import_removes_only_that_batch() {
+        let (_tmp, db) = new_db();
+        let first = import_entries(
+            &db,
+            vec![create_test_entry("2024-01-01", "First", "a")],
+            ImportMode::Append,
+        )
+        .unwrap();
+        let second = import_entries(
+            &db,
+            vec![
+                create_test_entry("2024-02-01", "Second", "b"),
+                create_test_entry("2024-02-02", "Third", "c"),
+            ],
+            ImportMode::Append,
+        )
+        .unwrap();
+        assert_eq!((first.batch_id, second.batch_id), (1, 2));
+
+        let undone = undo_import(&db).unwrap();
+        assert_eq!(undone.batch_id, 2);
+        assert_eq!(undone.entries_removed, 2);
+        let remaining = crate::db::queries::get_all_entries(&db).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].title, "First");
+
+        let err = undo_import(&db).unwrap_err();
+        assert!(err.contains("no import to undo"));
+
+        // Batch ids are not reused after an undo
+        let third = import_entries(
+            &db,
+            vec![create_test_entry("2024-03-01", "Fourth", "d")],
+            ImportMode::Append,
+        )
+        .unwrap();
+        assert_eq!(third.batch_id, 3);
+    }
+
+    #[test]
+    fn test_undo_last_import_keeps_entries_edited_since() {
+        let (_tmp, db) = new_db();
+        import_entries(
+            &db,
+            vec![
+                create_test_entry("2024-01-01", "Untouched", "a"),
+                create_test_entry("2024-01-02", "Edited", "b"),
+            ],
+            ImportMode::Append,
+        )
+        .unwrap();
+        let mut edited = crate::db::queries::get_entries_by_date(&db, "2024-01-02")
+            .unwrap()
+            .remove(0);
+        edited.text = "b, revised".to_string();
+        edited.date_updated = "2099-01-01T00:00:00Z".to_string();
+        crate::db::queries::update_entry(&db, &edited).unwrap();
+
+        let undone = undo_import(&db).unwrap();
+        assert_eq!((undone.entries_removed, undone.entries_kept), (1, 1));
+        let remaining = crate::db::queries::get_all_entries(&db).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].text, "b, revised");
+    }
+
+    #[test]
+    fn test_streamed_import_writes_entries_as_parsed() {
+        let (_tmp, db) = new_db();
+        let json = r#"{
+            "entries": [
+                {"creationDate": "2024-01-15T14:30:00Z", "text": "One\n\nFirst body"},
+                {"creationDate": "2024-01-16T09:00:00Z", "text": "Two\n\nSecond body"}
+            ]
+        }"#;
+        let result = import_stream(&db, ImportMode::Append, |on_entry| {
+            dayone::parse_dayone_json_reader(json.as_bytes(), on_entry)
+        })
+        .unwrap();
+
+        assert_eq!(result.entries_imported, 2);
+        assert_eq!(crate::db::queries::get_all_entries(&db).unwrap().len(), 2);
+
+        // A parse error after the first entry leaves nothing behind
+        let truncated =
+            r#"{"entries": [{"creationDate": "2024-02-01T10:00:00Z", "text": "Three"}, {"#;
+        let err = import_stream(&db, ImportMode::Append, |on_entry| {
+            dayone::parse_dayone_json_reader(truncated.as_bytes(), on_entry)
+        })
+        .unwrap_err();
+        assert!(err.contains("Failed to parse Day One JSON"));
+        assert_eq!(crate::db::queries::get_all_entries(&db).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_preview_entries_writes_nothing() {
+        let (_tmp, db) = new_db();
+        crate::db::queries::insert_entry(&db, &create_test_entry("2024-01-02", "Kept", "Same"))
+            .unwrap();
+
+        let entries = vec![
+            create_test_entry("2024-03-01", "Spring", "New"),
+            create_test_entry("2024-01-02", "Kept", "Same"),
+            create_test_entry("2024-01-02", "Another", "Different"),
+            create_test_entry("2024-03-01", "Spring", "New"),
+            create_test_entry("2024-02-30", "Bad date", "x"),
+            create_test_entry("2024-01-05", "", "  "),
+        ];
+        let preview = preview_entries(&db, &entries).unwrap();
+
+        assert_eq!(preview.entry_count, 6);
+        assert_eq!(preview.first_date.as_deref(), Some("2024-01-02"));
+        assert_eq!(preview.last_date.as_deref(), Some("2024-03-01"));
+        assert_eq!(preview.colliding_dates, vec!["2024-01-02".to_string()]);
+        assert_eq!(
+            preview.sample_titles,
+            vec!["Spring", "Kept", "Another", "Spring", "Bad date"]
+        );
+        assert_eq!(preview.warnings.len(), 4);
+        assert!(preview.warnings[0].starts_with("1 empty"));
+        assert_eq!(crate::db::queries::get_all_entries(&db).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_import_mode_deserializes_snake_case() {
+        let mode: ImportMode = serde_json::from_str("\"update_if_newer\"").unwrap();
+        assert_eq!(mode, ImportMode::UpdateIfNewer);
+        assert_eq!(ImportMode::default(), ImportMode::Append);
+    }
+
     #[test]
     fn test_import_file_at_size_limit() {
         use std::io::{Seek, SeekFrom, Write};
@@ -372,7 +1174,7 @@ mod tests {
         let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
         let db = create_database(tmp.path().to_str().unwrap(), "test".to_string()).unwrap();
 
-        let result = import_entries(&db, vec![]).unwrap();
+        let result = import_entries(&db, vec![], ImportMode::Append).unwrap();
 
         assert_eq!(result.entries_imported, 0);
         assert_eq!(result.entries_skipped, 0);
@@ -0,0 +1,56 @@
+use crate::commands::auth::DiaryState;
+use crate::db::integrity::{verify_database, IntegrityReport};
+use crate::db::salvage::{salvage_to_new_database, SalvageReport};
+use log::{info, warn};
+use std::path::Path;
+use tauri::State;
+
+/// Verifies the open journal: SQLite structure, every entry ciphertext, and
+/// the auth slots. If `password` is given, password slots are also checked
+/// against the master key.
+#[tauri::command]
+pub fn verify_diary(
+    password: Option<String>,
+    state: State<DiaryState>,
+) -> Result<IntegrityReport, String> {
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to verify it")?;
+
+    let report = verify_database(db, password.as_deref())?;
+    if report.is_healthy() {
+        info!(
+            "Integrity check passed ({} entries, {} auth slots)",
+            report.entries_checked, report.auth_slots_checked
+        );
+    } else {
+        warn!("Integrity check found {} issue(s)", report.issues.len());
+    }
+    Ok(report)
+}
+
+/// Copies every readable entry of the open journal into a new diary at
+/// `output_path`, protected by `password`. Unreadable rows are skipped and
+/// reported; the open journal is left untouched.
+#[tauri::command]
+pub fn salvage_diary(
+    output_path: String,
+    password: String,
+    state: State<DiaryState>,
+) -> Result<SalvageReport, String> {
+    info!("Starting salvage into {}", output_path);
+
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to salvage it")?;
+
+    salvage_to_new_database(db, Path::new(&output_path), password)
+}
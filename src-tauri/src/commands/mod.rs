@@ -1,9 +1,11 @@
 pub mod auth;
+pub mod backups;
 pub mod debug;
 pub mod entries;
 pub mod export;
 pub mod files;
 pub mod import;
+pub mod integrity;
 pub mod menu;
 pub mod navigation;
 pub mod plugin;
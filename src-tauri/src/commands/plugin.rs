@@ -1,9 +1,13 @@
 use crate::commands::auth::DiaryState;
 use crate::commands::export::ExportResult;
-use crate::commands::import::ImportResult;
+use crate::commands::import::{ImportMode, ImportPreview, ImportResult};
+use crate::config::{self, JournalSettings};
+use crate::db::queries::DiaryEntry;
 use crate::plugin::registry::PluginRegistry;
-use crate::plugin::PluginInfo;
-use log::{debug, error, info};
+use crate::plugin::{ExportOptions, ImportPlugin, PluginInfo};
+use log::{debug, error, info, warn};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::State;
 
@@ -27,10 +31,90 @@ pub fn list_export_plugins(
     Ok(reg.list_exporters())
 }
 
+/// Reads `file_path` and parses it with the import plugin `plugin_id`.
+/// Only the registry lock is held, so no journal needs to be unlocked.
+fn parse_import_file(
+    plugin_id: &str,
+    file_path: &str,
+    passphrase: Option<&str>,
+    registry: &Mutex<PluginRegistry>,
+) -> Result<Vec<DiaryEntry>, String> {
+    let reg = registry
+        .lock()
+        .map_err(|_| "Registry lock poisoned".to_string())?;
+    let plugin = reg
+        .find_importer(plugin_id)
+        .ok_or_else(|| format!("Import plugin '{}' not found", plugin_id))?;
+
+    debug!("Parsing with plugin '{}'...", plugin_id);
+    let mut entries = Vec::new();
+    // Journal settings only matter when the entries are written
+    parse_with_plugin(plugin, file_path, passphrase, &mut |entry| {
+        entries.push(entry);
+        Ok(())
+    })
+    .map_err(|e| {
+        error!("Plugin parse error: {}", e);
+        e
+    })?;
+    debug!("Parsed {} entries", entries.len());
+    Ok(entries)
+}
+
+/// Hands every entry in `file_path` to `on_entry`, decrypting with
+/// `passphrase` when one is given. Returns the journal settings stored in
+/// encrypted archives.
+fn parse_with_plugin(
+    plugin: &dyn ImportPlugin,
+    file_path: &str,
+    passphrase: Option<&str>,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<Option<JournalSettings>, String> {
+    match passphrase {
+        Some(passphrase) => {
+            plugin.parse_path_with_passphrase(Path::new(file_path), passphrase, on_entry)
+        }
+        None => plugin
+            .parse_path(Path::new(file_path), on_entry)
+            .map(|()| None),
+    }
+}
+
+/// Parses a file with an import plugin and summarizes what importing it would
+/// do, without writing anything to the journal.
+#[tauri::command]
+pub fn preview_import(
+    plugin_id: String,
+    file_path: String,
+    passphrase: Option<String>,
+    state: State<DiaryState>,
+    registry: State<Mutex<PluginRegistry>>,
+) -> Result<ImportPreview, String> {
+    info!(
+        "Previewing import plugin '{}' on file: {}",
+        plugin_id, file_path
+    );
+    let entries = parse_import_file(&plugin_id, &file_path, passphrase.as_deref(), &registry)?;
+
+    let db_state = state
+        .db
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let db = db_state
+        .as_ref()
+        .ok_or("Journal must be unlocked to preview an import")?;
+    super::import::preview_entries(db, &entries)
+}
+
+/// Imports a file with an import plugin, writing each entry as the plugin
+/// hands it over. The journal is backed up first and the whole import is one
+/// transaction.
 #[tauri::command]
 pub fn run_import_plugin(
     plugin_id: String,
     file_path: String,
+    passphrase: Option<String>,
+    mode: Option<ImportMode>,
     state: State<DiaryState>,
     registry: State<Mutex<PluginRegistry>>,
 ) -> Result<ImportResult, String> {
@@ -38,28 +122,21 @@ pub fn run_import_plugin(
         "Running import plugin '{}' on file: {}",
         plugin_id, file_path
     );
+    // Fail before the pre-import backup if the file cannot be read
+    std::fs::metadata(&file_path).map_err(|e| {
+        let err = format!("Cannot access file: {}", e);
+        error!("{}", err);
+        err
+    })?;
+    let reg = registry
+        .lock()
+        .map_err(|_| "Registry lock poisoned".to_string())?;
+    let plugin = reg
+        .find_importer(&plugin_id)
+        .ok_or_else(|| format!("Import plugin '{}' not found", plugin_id))?;
 
-    debug!("Reading file...");
-    let content = super::import::read_import_file(&file_path)?;
-
-    // Parse with registry lock only (no DB lock needed for parsing)
-    let entries = {
-        let reg = registry
-            .lock()
-            .map_err(|_| "Registry lock poisoned".to_string())?;
-        let plugin = reg
-            .find_importer(&plugin_id)
-            .ok_or_else(|| format!("Import plugin '{}' not found", plugin_id))?;
-
-        debug!("Parsing with plugin '{}'...", plugin_id);
-        plugin.parse(&content).map_err(|e| {
-            error!("Plugin parse error: {}", e);
-            e
-        })?
-    };
-    debug!("Parsed {} entries", entries.len());
-
-    // Import with DB lock only (registry lock released)
+    // Parsing and writing are interleaved, so the registry and journal locks
+    // are held together (always in that order)
     let db_state = state
         .db
         .lock()
@@ -70,17 +147,36 @@ pub fn run_import_plugin(
         err.to_string()
     })?;
 
-    debug!("Importing entries...");
-    let result = super::import::import_entries(db, entries).map_err(|e| {
-        error!("Import error: {}", e);
-        e
-    })?;
+    debug!("Streaming import with plugin '{}'...", plugin_id);
+    let mut journal = None;
+    let mut result =
+        super::import::backup_and_import_stream(&state, db, mode.unwrap_or_default(), |on_entry| {
+            journal = parse_with_plugin(plugin, &file_path, passphrase.as_deref(), on_entry)?;
+            Ok(())
+        })
+        .map_err(|e| {
+            error!("Import error: {}", e);
+            e
+        })?;
 
-    // Search index hook: call search module's bulk_reindex() here when implemented.
+    // Archives carry the name and backup settings of the journal they came
+    // from; the entries are already in, so a config error only warns
+    if let Some(settings) = journal {
+        match config::save_active_journal_settings(&state.app_data_dir, &settings) {
+            Ok(()) => {
+                info!("Restored journal settings from the archive");
+                result.journal_restored = true;
+            }
+            Err(e) => warn!("Failed to restore journal settings: {}", e),
+        }
+    }
 
     info!(
-        "Plugin import complete: {} imported, {} skipped",
-        result.entries_imported, result.entries_skipped
+        "Plugin import complete: {} imported, {} updated, {} removed, {} skipped",
+        result.entries_imported,
+        result.entries_updated,
+        result.entries_removed,
+        result.entries_skipped
     );
     Ok(result)
 }
@@ -89,6 +185,7 @@ pub fn run_import_plugin(
 pub fn run_export_plugin(
     plugin_id: String,
     file_path: String,
+    options: Option<ExportOptions>,
     state: State<DiaryState>,
     registry: State<Mutex<PluginRegistry>>,
 ) -> Result<ExportResult, String> {
@@ -117,7 +214,7 @@ pub fn run_export_plugin(
     );
 
     // Format with registry lock only (DB lock released)
-    let output = {
+    let (output, directory) = {
         let reg = registry
             .lock()
             .map_err(|_| "Registry lock poisoned".to_string())?;
@@ -125,23 +222,60 @@ pub fn run_export_plugin(
             .find_exporter(&plugin_id)
             .ok_or_else(|| format!("Export plugin '{}' not found", plugin_id))?;
 
-        plugin.export(entries).map_err(|e| {
+        let mut options = options.unwrap_or_default();
+        options.journal = config::load_active_journal_settings(&state.app_data_dir);
+        let output = plugin.export_with_options(entries, &options).map_err(|e| {
             error!("Plugin export error: {}", e);
             e
-        })?
+        })?;
+        (output, plugin.info().directory)
+    };
+
+    let base_dir = if directory {
+        Path::new(&file_path).to_path_buf()
+    } else {
+        Path::new(&file_path)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .to_path_buf()
     };
 
-    std::fs::write(&file_path, &output.content).map_err(|e| {
-        let err = format!("Failed to write file: {}", e);
+    if directory && output.files.is_empty() && !output.content.is_empty() {
+        let err = format!(
+            "Export plugin '{}' writes to a folder but returned no files",
+            plugin_id
+        );
         error!("{}", err);
-        err
-    })?;
+        return Err(err);
+    }
+
+    // Check every extra file before anything touches the disk. Only files an
+    // earlier export of this kind wrote into the folder may be replaced.
+    let previous = if output.overwrite {
+        read_export_manifest(&base_dir)
+    } else {
+        HashSet::new()
+    };
+    let targets = export_file_targets(&base_dir, &output.files, &previous)?;
+
+    if directory {
+        // Folder exporters write only `files`, inside the chosen folder
+        std::fs::create_dir_all(&base_dir)
+            .map_err(|e| format!("Failed to create export folder: {}", e))?;
+    } else {
+        let bytes = output
+            .binary
+            .as_deref()
+            .unwrap_or(output.content.as_bytes());
+        std::fs::write(&file_path, bytes).map_err(|e| {
+            let err = format!("Failed to write file: {}", e);
+            error!("{}", err);
+            err
+        })?;
+    }
 
     if !output.assets.is_empty() {
-        let assets_dir = std::path::Path::new(&file_path)
-            .parent()
-            .unwrap_or(std::path::Path::new("."))
-            .join("assets");
+        let assets_dir = base_dir.join("assets");
         std::fs::create_dir_all(&assets_dir)
             .map_err(|e| format!("Failed to create assets directory: {}", e))?;
         for (filename, bytes) in &output.assets {
@@ -155,6 +289,18 @@ pub fn run_export_plugin(
         );
     }
 
+    if !output.files.is_empty() {
+        write_export_files(&targets, &output.files)?;
+        if output.overwrite {
+            replace_export_manifest(&base_dir, &previous, &output.files)?;
+        }
+        debug!(
+            "Wrote {} file(s) to {}",
+            output.files.len(),
+            base_dir.display()
+        );
+    }
+
     info!(
         "Plugin export complete: {} entries exported to {}",
         entries_exported, file_path
@@ -165,6 +311,101 @@ pub fn run_export_plugin(
     })
 }
 
+/// File listing what an `overwrite` export wrote into its folder, one
+/// relative path per line. Its presence marks the folder as a previous export.
+const EXPORT_MANIFEST: &str = ".mini-diarium-export";
+
+/// Relative paths written by the previous `overwrite` export into `base`, or
+/// an empty set if the folder holds no such export.
+fn read_export_manifest(base: &Path) -> HashSet<String> {
+    std::fs::read_to_string(base.join(EXPORT_MANIFEST))
+        .map(|manifest| manifest.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Removes the files of the previous export that this one no longer writes,
+/// along with folders left empty, then records the new file list.
+fn replace_export_manifest(
+    base: &Path,
+    previous: &HashSet<String>,
+    files: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    let current: HashSet<&str> = files
+        .iter()
+        .map(|(relative, _)| relative.as_str())
+        .collect();
+    for stale in previous.iter().filter(|p| !current.contains(p.as_str())) {
+        if !is_plain_relative_path(stale) {
+            warn!("Ignoring invalid path '{}' in export manifest", stale);
+            continue;
+        }
+        let path = base.join(stale);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove stale export file '{}': {}", stale, e),
+        }
+        // Fails, and stops, at the first folder that still has files
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != base) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    let mut manifest: Vec<&str> = current.into_iter().collect();
+    manifest.sort_unstable();
+    std::fs::write(base.join(EXPORT_MANIFEST), manifest.join("\n"))
+        .map_err(|e| format!("Failed to write export manifest: {}", e))
+}
+
+fn is_plain_relative_path(relative: &str) -> bool {
+    !relative.is_empty()
+        && Path::new(relative)
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// Resolves `(relative path, bytes)` pairs to paths under `base`. Fails if
+/// any path would leave `base` or if any of the files already exists and is
+/// not listed in `previous`, so an export never overwrites existing notes or
+/// files the user put in the folder.
+fn export_file_targets(
+    base: &Path,
+    files: &[(String, Vec<u8>)],
+    previous: &HashSet<String>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut targets = Vec::with_capacity(files.len());
+    for (relative, _) in files {
+        if !is_plain_relative_path(relative) || relative == EXPORT_MANIFEST {
+            return Err(format!("Invalid export file path '{}'", relative));
+        }
+        let target = base.join(relative);
+        if target.exists() && !previous.contains(relative) {
+            return Err(format!(
+                "'{}' already exists. Choose an empty folder for this export.",
+                target.display()
+            ));
+        }
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// Writes each file to the target returned by `export_file_targets`,
+/// creating folders as needed.
+fn write_export_files(targets: &[PathBuf], files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    for (target, (relative, bytes)) in targets.iter().zip(files) {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create folder for '{}': {}", relative, e))?;
+        }
+        std::fs::write(target, bytes)
+            .map_err(|e| format!("Failed to write '{}': {}", relative, e))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,7 +417,7 @@ mod tests {
         let mut registry = PluginRegistry::new();
         builtins::register_all(&mut registry);
         let list = registry.list_importers();
-        assert_eq!(list.len(), 4);
+        assert_eq!(list.len(), 13);
         assert!(list.iter().all(|p| p.builtin));
     }
 
@@ -185,7 +426,7 @@ mod tests {
         let mut registry = PluginRegistry::new();
         builtins::register_all(&mut registry);
         let list = registry.list_exporters();
-        assert_eq!(list.len(), 3);
+        assert_eq!(list.len(), 10);
         assert!(list.iter().all(|p| p.builtin));
     }
 
@@ -221,6 +462,147 @@ mod tests {
         assert!(output.content.contains("2024-01-01"));
     }
 
+    #[test]
+    fn test_parse_import_file_passes_raw_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = "// @name: Bytes\n// @type: import\n// @input: bytes\n\
+                      fn parse(data) { [#{ date: \"2024-01-01\", text: data.len() + \" \" + data[0] }] }";
+        std::fs::write(dir.path().join("bytes.rhai"), script).unwrap();
+        let mut registry = PluginRegistry::new();
+        crate::plugin::rhai_loader::load_plugins(dir.path(), &mut registry);
+        let registry = Mutex::new(registry);
+
+        // Not valid UTF-8, so a text plugin could not read it
+        let input = dir.path().join("input.bin");
+        std::fs::write(&input, [0xff, 0x00, 0x80]).unwrap();
+        let entries =
+            parse_import_file("rhai:bytes", input.to_str().unwrap(), None, &registry).unwrap();
+        assert_eq!(entries[0].text, "3 255");
+    }
+
+    #[test]
+    fn test_parse_import_file_goes_through_parse_path() {
+        let mut registry = PluginRegistry::new();
+        builtins::register_all(&mut registry);
+        let registry = Mutex::new(registry);
+        let dir = tempfile::tempdir().unwrap();
+
+        // A buffered text format is read by the default parse_path
+        let txt = dir.path().join("journal.txt");
+        std::fs::write(&txt, "\tDate:\t15 January 2024\n\nFirst entry content.").unwrap();
+        let entries =
+            parse_import_file("builtin:dayone-txt", txt.to_str().unwrap(), None, &registry)
+                .unwrap();
+        assert_eq!(entries[0].date, "2024-01-15");
+
+        // A folder format only implements parse_path
+        std::fs::write(dir.path().join("2024-02-01.md"), "Folder note").unwrap();
+        let entries = parse_import_file(
+            "builtin:markdown-folder",
+            dir.path().to_str().unwrap(),
+            None,
+            &registry,
+        )
+        .unwrap();
+        assert_eq!(entries[0].date, "2024-02-01");
+    }
+
+    #[test]
+    fn test_parse_import_file_decrypts_archives_from_disk() {
+        let mut registry = PluginRegistry::new();
+        builtins::register_all(&mut registry);
+        let registry = Mutex::new(registry);
+        let entry = DiaryEntry {
+            id: 1,
+            date: "2024-03-01".into(),
+            title: "Moved".into(),
+            text: "<p>Hello</p>".into(),
+            word_count: 1,
+            date_created: "2024-03-01T08:00:00Z".into(),
+            date_updated: "2024-03-01T08:00:00Z".into(),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.mdarchive");
+        std::fs::write(
+            &path,
+            crate::export::archive::tests::export_for_test(vec![entry], "pw"),
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let entries = parse_import_file("builtin:mdarchive", path, Some("pw"), &registry).unwrap();
+        assert_eq!(entries[0].title, "Moved");
+        let err = parse_import_file("builtin:mdarchive", path, None, &registry).unwrap_err();
+        assert!(err.contains("passphrase"));
+    }
+
+    #[test]
+    fn test_export_file_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            ("2024-01-01.md".to_string(), b"note".to_vec()),
+            ("attachments/image-1.png".to_string(), vec![1, 2]),
+        ];
+        let targets = export_file_targets(dir.path(), &files, &HashSet::new()).unwrap();
+        write_export_files(&targets, &files).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("attachments/image-1.png")).unwrap(),
+            vec![1, 2]
+        );
+
+        // A second export into the same folder would overwrite notes
+        let err = export_file_targets(dir.path(), &files, &HashSet::new()).unwrap_err();
+        assert!(err.contains("already exists"));
+        // unless an earlier export wrote them
+        let previous: HashSet<String> = files.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            export_file_targets(dir.path(), &files, &previous)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        for bad in ["../escape.md", "/abs.md", "", EXPORT_MANIFEST] {
+            let err = export_file_targets(dir.path(), &[(bad.to_string(), vec![])], &previous)
+                .unwrap_err();
+            assert!(err.contains("Invalid export file path"));
+        }
+    }
+
+    #[test]
+    fn test_export_manifest_replaces_only_previous_export() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("style.css"), "user's own").unwrap();
+
+        // The user's own file is never replaced by a first export
+        let site = vec![("style.css".to_string(), b"site".to_vec())];
+        let previous = read_export_manifest(dir.path());
+        assert!(export_file_targets(dir.path(), &site, &previous).is_err());
+        std::fs::remove_file(dir.path().join("style.css")).unwrap();
+
+        let first = vec![
+            ("index.html".to_string(), b"v1".to_vec()),
+            ("2023/05/2023-05-01.html".to_string(), b"day".to_vec()),
+        ];
+        let targets = export_file_targets(dir.path(), &first, &previous).unwrap();
+        write_export_files(&targets, &first).unwrap();
+        replace_export_manifest(dir.path(), &previous, &first).unwrap();
+
+        // Exporting again replaces the earlier pages and drops stale ones
+        let second = vec![("index.html".to_string(), b"v2".to_vec())];
+        let previous = read_export_manifest(dir.path());
+        let targets = export_file_targets(dir.path(), &second, &previous).unwrap();
+        write_export_files(&targets, &second).unwrap();
+        replace_export_manifest(dir.path(), &previous, &second).unwrap();
+
+        assert_eq!(std::fs::read(dir.path().join("index.html")).unwrap(), b"v2");
+        assert!(!dir.path().join("2023").exists());
+        assert_eq!(
+            read_export_manifest(dir.path()),
+            HashSet::from(["index.html".to_string()])
+        );
+    }
+
     #[test]
     fn test_import_plugin_not_found() {
         let mut registry = PluginRegistry::new();
@@ -1,5 +1,7 @@
+use crate::backup::{BackupMirror, RetentionPolicy};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 const CONFIG_FILE: &str = "config.json";
 
@@ -10,6 +12,14 @@ pub struct JournalConfig {
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_key: Option<String>, // hex-encoded 32-byte random key; None for password journals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_retention: Option<RetentionPolicy>, // None = RetentionPolicy::default()
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backup_mirrors: Vec<BackupMirror>, // extra destinations besides {path}/backups
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub incremental_backups: bool, // deduplicating snapshots instead of full copies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_interval_minutes: Option<u32>, // None = back up on unlock and lock only
 }
 
 /// Frontend-facing DTO — safe to send over IPC (raw key never included)
@@ -32,6 +42,31 @@ impl From<&JournalConfig> for JournalInfo {
     }
 }
 
+/// The settings of a journal that travel with an encrypted archive: its name
+/// and backup options. Where the journal lives, how it is unlocked and its
+/// backup mirrors (folders on the exporting machine) stay behind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalSettings {
+    pub name: String,
+    #[serde(default)]
+    pub backup_retention: Option<RetentionPolicy>,
+    #[serde(default)]
+    pub incremental_backups: bool,
+    #[serde(default)]
+    pub backup_interval_minutes: Option<u32>,
+}
+
+impl From<&JournalConfig> for JournalSettings {
+    fn from(j: &JournalConfig) -> Self {
+        JournalSettings {
+            name: j.name.clone(),
+            backup_retention: j.backup_retention,
+            incremental_backups: j.incremental_backups,
+            backup_interval_minutes: j.backup_interval_minutes,
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct AppConfig {
     diary_dir: Option<String>,
@@ -107,6 +142,10 @@ pub fn load_journals(app_data_dir: &Path) -> Vec<JournalConfig> {
                 name: "My Journal".to_string(),
                 path: dir.clone(),
                 auto_key: None,
+                backup_retention: None,
+                backup_mirrors: Vec::new(),
+                incremental_backups: false,
+                backup_interval_minutes: None,
             };
             let journals = vec![journal];
             config.journals = Some(journals.clone());
@@ -173,6 +212,158 @@ pub fn save_journal_auto_key(
     save_config(app_data_dir, &config)
 }
 
+/// Returns the backup retention policy for a journal, falling back to the
+/// default when the journal is unknown or has no policy of its own.
+pub fn load_journal_backup_retention(app_data_dir: &Path, journal_id: &str) -> RetentionPolicy {
+    load_journals(app_data_dir)
+        .iter()
+        .find(|j| j.id == journal_id)
+        .and_then(|j| j.backup_retention)
+        .unwrap_or_default()
+}
+
+/// Returns the backup retention policy for the active journal.
+pub fn load_active_backup_retention(app_data_dir: &Path) -> RetentionPolicy {
+    load_active_journal_id(app_data_dir)
+        .map(|id| load_journal_backup_retention(app_data_dir, &id))
+        .unwrap_or_default()
+}
+
+/// Saves the backup retention policy for a specific journal. Pass `None` to
+/// return to the default policy.
+pub fn save_journal_backup_retention(
+    app_data_dir: &Path,
+    journal_id: &str,
+    policy: Option<RetentionPolicy>,
+) -> Result<(), String> {
+    let mut config = load_config(app_data_dir);
+    let journal = config
+        .journals
+        .as_mut()
+        .and_then(|journals| journals.iter_mut().find(|j| j.id == journal_id))
+        .ok_or("Journal not found")?;
+    journal.backup_retention = policy;
+    save_config(app_data_dir, &config)
+}
+
+/// Returns the extra backup destinations configured for the active journal.
+pub fn load_active_backup_mirrors(app_data_dir: &Path) -> Vec<BackupMirror> {
+    let Some(active_id) = load_active_journal_id(app_data_dir) else {
+        return Vec::new();
+    };
+    load_journals(app_data_dir)
+        .into_iter()
+        .find(|j| j.id == active_id)
+        .map(|j| j.backup_mirrors)
+        .unwrap_or_default()
+}
+
+/// Replaces the extra backup destinations for a specific journal.
+pub fn save_journal_backup_mirrors(
+    app_data_dir: &Path,
+    journal_id: &str,
+    mirrors: Vec<BackupMirror>,
+) -> Result<(), String> {
+    let mut config = load_config(app_data_dir);
+    let journal = config
+        .journals
+        .as_mut()
+        .and_then(|journals| journals.iter_mut().find(|j| j.id == journal_id))
+        .ok_or("Journal not found")?;
+    journal.backup_mirrors = mirrors;
+    save_config(app_data_dir, &config)
+}
+
+/// Returns true if the active journal uses incremental backups.
+pub fn load_active_incremental_backups(app_data_dir: &Path) -> bool {
+    let Some(active_id) = load_active_journal_id(app_data_dir) else {
+        return false;
+    };
+    load_journals(app_data_dir)
+        .iter()
+        .any(|j| j.id == active_id && j.incremental_backups)
+}
+
+/// Switches a specific journal between full and incremental backups.
+pub fn save_journal_incremental_backups(
+    app_data_dir: &Path,
+    journal_id: &str,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut config = load_config(app_data_dir);
+    let journal = config
+        .journals
+        .as_mut()
+        .and_then(|journals| journals.iter_mut().find(|j| j.id == journal_id))
+        .ok_or("Journal not found")?;
+    journal.incremental_backups = enabled;
+    save_config(app_data_dir, &config)
+}
+
+/// Returns how often the active journal is backed up while it stays unlocked,
+/// or `None` if scheduled backups are off.
+pub fn load_active_backup_interval(app_data_dir: &Path) -> Option<Duration> {
+    let active_id = load_active_journal_id(app_data_dir)?;
+    load_journals(app_data_dir)
+        .iter()
+        .find(|j| j.id == active_id)
+        .and_then(|j| j.backup_interval_minutes)
+        .map(|minutes| Duration::from_secs(u64::from(minutes) * 60))
+}
+
+/// Sets how often a specific journal is backed up while unlocked. Pass `None`
+/// to turn scheduled backups off.
+pub fn save_journal_backup_interval(
+    app_data_dir: &Path,
+    journal_id: &str,
+    minutes: Option<u32>,
+) -> Result<(), String> {
+    if minutes == Some(0) {
+        return Err("Backup interval must be at least 1 minute".to_string());
+    }
+    let mut config = load_config(app_data_dir);
+    let journal = config
+        .journals
+        .as_mut()
+        .and_then(|journals| journals.iter_mut().find(|j| j.id == journal_id))
+        .ok_or("Journal not found")?;
+    journal.backup_interval_minutes = minutes;
+    save_config(app_data_dir, &config)
+}
+
+/// Returns the name and backup settings of the active journal.
+pub fn load_active_journal_settings(app_data_dir: &Path) -> Option<JournalSettings> {
+    let active_id = load_active_journal_id(app_data_dir)?;
+    load_journals(app_data_dir)
+        .iter()
+        .find(|j| j.id == active_id)
+        .map(JournalSettings::from)
+}
+
+/// Applies `settings` to the active journal, keeping its path, unlock
+/// methods and backup mirrors.
+pub fn save_active_journal_settings(
+    app_data_dir: &Path,
+    settings: &JournalSettings,
+) -> Result<(), String> {
+    let mut config = load_config(app_data_dir);
+    let active_id = config.active_journal_id.clone();
+    let journal = config
+        .journals
+        .as_mut()
+        .and_then(|journals| {
+            journals
+                .iter_mut()
+                .find(|j| Some(&j.id) == active_id.as_ref())
+        })
+        .ok_or("Journal not found")?;
+    journal.name = settings.name.clone();
+    journal.backup_retention = settings.backup_retention;
+    journal.incremental_backups = settings.incremental_backups;
+    journal.backup_interval_minutes = settings.backup_interval_minutes;
+    save_config(app_data_dir, &config)
+}
+
 // ─── Tests ────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -305,6 +496,10 @@ mod tests {
                     .unwrap()
                     .to_string(),
                 auto_key: None,
+                backup_retention: None,
+                backup_mirrors: Vec::new(),
+                incremental_backups: false,
+                backup_interval_minutes: None,
             },
             JournalConfig {
                 id: "eeff00112233aabb".to_string(),
@@ -315,6 +510,10 @@ mod tests {
                     .unwrap()
                     .to_string(),
                 auto_key: None,
+                backup_retention: None,
+                backup_mirrors: Vec::new(),
+                incremental_backups: false,
+                backup_interval_minutes: None,
             },
         ];
         save_journals(&dir, &journals, "aabbccdd11223344").unwrap();
@@ -347,6 +546,10 @@ mod tests {
                     .unwrap()
                     .to_string(),
                 auto_key: None,
+                backup_retention: None,
+                backup_mirrors: Vec::new(),
+                incremental_backups: false,
+                backup_interval_minutes: None,
             },
             JournalConfig {
                 id: "bbbb".to_string(),
@@ -357,6 +560,10 @@ mod tests {
                     .unwrap()
                     .to_string(),
                 auto_key: None,
+                backup_retention: None,
+                backup_mirrors: Vec::new(),
+                incremental_backups: false,
+                backup_interval_minutes: None,
             },
         ];
         save_journals(&dir, &journals, "aaaa").unwrap();
@@ -388,6 +595,10 @@ mod tests {
                 .unwrap()
                 .to_string(),
             auto_key: None,
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
         }];
         save_journals(&dir, &journals, "testid1234567890").unwrap();
 
@@ -410,6 +621,10 @@ mod tests {
                 .unwrap()
                 .to_string(),
             auto_key: Some("deadbeef".to_string()),
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
         }];
         save_journals(&dir, &journals, "testid1234567890").unwrap();
 
@@ -419,4 +634,136 @@ mod tests {
 
         cleanup(&dir);
     }
+
+    #[test]
+    fn test_backup_retention_defaults_when_unset() {
+        let dir = temp_dir("retention_default");
+        // Configs written before retention existed have no backup_retention key
+        fs::write(
+            dir.join(CONFIG_FILE),
+            r#"{"journals":[{"id":"j1","name":"J","path":"/tmp/j"}],"active_journal_id":"j1"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_active_backup_retention(&dir),
+            RetentionPolicy::default()
+        );
+        assert_eq!(
+            load_journal_backup_retention(&dir, "missing"),
+            RetentionPolicy::default()
+        );
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_save_journal_backup_retention_roundtrip() {
+        let dir = temp_dir("retention_roundtrip");
+        let journals = vec![JournalConfig {
+            id: "testid1234567890".to_string(),
+            name: "Journal".to_string(),
+            path: std::env::temp_dir()
+                .join("rj")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            auto_key: None,
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
+        }];
+        save_journals(&dir, &journals, "testid1234567890").unwrap();
+
+        let policy = RetentionPolicy {
+            daily: 3,
+            weekly: 2,
+            monthly: 24,
+        };
+        save_journal_backup_retention(&dir, "testid1234567890", Some(policy)).unwrap();
+        assert_eq!(load_active_backup_retention(&dir), policy);
+
+        save_journal_backup_retention(&dir, "testid1234567890", None).unwrap();
+        assert_eq!(
+            load_active_backup_retention(&dir),
+            RetentionPolicy::default()
+        );
+
+        assert!(save_journal_backup_retention(&dir, "unknown", Some(policy)).is_err());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_active_journal_settings_roundtrip() {
+        let dir = temp_dir("journal_settings");
+        assert!(load_active_journal_settings(&dir).is_none());
+        let journals = vec![JournalConfig {
+            id: "testid1234567890".to_string(),
+            name: "Old name".to_string(),
+            path: "/tmp/js".to_string(),
+            auto_key: Some("ab".repeat(32)),
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
+        }];
+        save_journals(&dir, &journals, "testid1234567890").unwrap();
+
+        let settings = JournalSettings {
+            name: "Travel".to_string(),
+            backup_retention: Some(RetentionPolicy {
+                daily: 3,
+                weekly: 2,
+                monthly: 1,
+            }),
+            incremental_backups: true,
+            backup_interval_minutes: Some(30),
+        };
+        save_active_journal_settings(&dir, &settings).unwrap();
+        assert_eq!(load_active_journal_settings(&dir), Some(settings));
+
+        // Location and unlock method are untouched
+        let journal = &load_journals(&dir)[0];
+        assert_eq!(journal.path, "/tmp/js");
+        assert_eq!(journal.auto_key, Some("ab".repeat(32)));
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_save_journal_backup_mirrors_roundtrip() {
+        let dir = temp_dir("mirrors_roundtrip");
+        let journals = vec![JournalConfig {
+            id: "testid1234567890".to_string(),
+            name: "Journal".to_string(),
+            path: std::env::temp_dir()
+                .join("mj")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            auto_key: None,
+            backup_retention: None,
+            backup_mirrors: Vec::new(),
+            incremental_backups: false,
+            backup_interval_minutes: None,
+        }];
+        save_journals(&dir, &journals, "testid1234567890").unwrap();
+        assert!(load_active_backup_mirrors(&dir).is_empty());
+
+        let mirrors = vec![BackupMirror {
+            path: "/mnt/nas/diary".to_string(),
+            retention: None,
+        }];
+        save_journal_backup_mirrors(&dir, "testid1234567890", mirrors.clone()).unwrap();
+        assert_eq!(load_active_backup_mirrors(&dir), mirrors);
+
+        // Empty lists are omitted from config.json entirely
+        save_journal_backup_mirrors(&dir, "testid1234567890", Vec::new()).unwrap();
+        let raw = fs::read_to_string(dir.join(CONFIG_FILE)).unwrap();
+        assert!(!raw.contains("backup_mirrors"));
+
+        cleanup(&dir);
+    }
 }
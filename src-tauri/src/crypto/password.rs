@@ -2,7 +2,7 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Algorithm, Argon2, Params, Version,
 };
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Argon2id parameters: m=64MB (65536 KiB), t=3, p=4
 const MEMORY_SIZE_KIB: u32 = 65536; // 64 MB
@@ -78,6 +78,50 @@ pub fn derive_key_from_phc_hash(phc_hash: &str) -> Result<Vec<u8>, String> {
     Ok(hash_bytes[..32].to_vec())
 }
 
+/// Argon2id cost parameters stored next to data encrypted with [`derive_key`],
+/// so the key can be re-derived even if the defaults change later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: MEMORY_SIZE_KIB,
+            iterations: ITERATIONS,
+            parallelism: PARALLELISM,
+        }
+    }
+}
+
+/// Derives a 32-byte encryption key from a password and salt with Argon2id.
+///
+/// Only the salt and `params` need to be stored with the encrypted data;
+/// nothing stored reveals the key itself.
+pub fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<Zeroizing<Vec<u8>>, PasswordError> {
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(32),
+    )
+    .map_err(|e| PasswordError::HashingFailed(format!("Invalid parameters: {}", e)))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut key = Zeroizing::new(vec![0u8; 32]);
+    argon2
+        .hash_password_into(password, salt, &mut key)
+        .map_err(|e| PasswordError::HashingFailed(e.to_string()))?;
+    Ok(key)
+}
+
 /// Verifies a password against a stored hash
 ///
 /// # Arguments
@@ -221,6 +265,29 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_derive_key_depends_on_password_and_salt() {
+        let params = KdfParams {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let key = derive_key(b"passphrase", b"0123456789abcdef", &params).unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(
+            key,
+            derive_key(b"passphrase", b"0123456789abcdef", &params).unwrap()
+        );
+        assert_ne!(
+            key,
+            derive_key(b"other", b"0123456789abcdef", &params).unwrap()
+        );
+        assert_ne!(
+            key,
+            derive_key(b"passphrase", b"fedcba9876543210", &params).unwrap()
+        );
+    }
+
     #[test]
     fn test_hash_long_password() {
         let password = "a".repeat(1000);
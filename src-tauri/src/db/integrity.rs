@@ -0,0 +1,612 @@
+use crate::crypto::cipher;
+use crate::db::queries::{self, DiaryEntry};
+use crate::db::schema::DatabaseConnection;
+use log::{debug, warn};
+use serde::Serialize;
+
+/// Category of a single problem found while verifying a diary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// A column could not be read at all (wrong type, truncated page, ...)
+    UnreadableRow,
+    /// An encrypted column is NULL where the app always writes ciphertext
+    MissingData,
+    /// AES-GCM rejected the ciphertext (bad tag, wrong key, truncated blob)
+    DecryptionFailed,
+    /// The plaintext decrypted but is not valid UTF-8
+    InvalidUtf8,
+    /// The `date` column is not a valid YYYY-MM-DD calendar date
+    InvalidDate,
+    /// An auth slot blob does not match the layout of its method
+    MalformedAuthSlot,
+    /// A row the app can never reach (leftover migration tables, keyless slots, ...)
+    OrphanedRow,
+}
+
+/// A single per-row problem found by [`verify_database`].
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityIssue {
+    /// Table the row lives in (`entries`, `auth_slots`, ...)
+    pub table: String,
+    /// SQLite rowid of the affected row
+    pub row_id: i64,
+    /// Entry date, when the row is an entry and the date column was readable
+    pub date: Option<String>,
+    /// Column that failed, if the problem is specific to one column
+    pub field: Option<String>,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Structured result of a full diary verification pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    /// Raw output of `PRAGMA integrity_check` (`["ok"]` for a healthy file)
+    pub sqlite_integrity: Vec<String>,
+    pub entries_checked: usize,
+    pub entries_ok: usize,
+    pub auth_slots_checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// True if SQLite reported no page-level damage and no row failed verification.
+    pub fn is_healthy(&self) -> bool {
+        self.sqlite_integrity == ["ok"] && self.issues.is_empty()
+    }
+}
+
+/// Verifies an unlocked diary end to end.
+///
+/// Runs SQLite's `PRAGMA integrity_check`, then attempts to decrypt every entry
+/// with the master key and checks every auth slot's wrapped-key layout. Unlike
+/// `get_all_entries`, a bad row never aborts the pass — each failure is recorded
+/// as an [`IntegrityIssue`] and verification moves on to the next row.
+///
+/// When `password` is given, every password slot is additionally unwrapped with
+/// it and compared against the master key in use, proving the slot still opens
+/// this diary.
+///
+/// Read-only: nothing is written to the database.
+pub fn verify_database(
+    db: &DatabaseConnection,
+    password: Option<&str>,
+) -> Result<IntegrityReport, String> {
+    let sqlite_integrity = run_integrity_check(db)?;
+    if sqlite_integrity != ["ok"] {
+        warn!(
+            "PRAGMA integrity_check reported {} problem(s)",
+            sqlite_integrity.len()
+        );
+    }
+
+    let mut issues = Vec::new();
+    let (entries_checked, entries_ok) = verify_entries(db, &mut issues)?;
+    let auth_slots_checked = verify_auth_slots(db, password, &mut issues)?;
+    find_orphaned_tables(db, &mut issues)?;
+
+    debug!(
+        "Verification complete: {}/{} entries ok, {} auth slot(s) checked, {} issue(s)",
+        entries_ok,
+        entries_checked,
+        auth_slots_checked,
+        issues.len()
+    );
+
+    Ok(IntegrityReport {
+        sqlite_integrity,
+        entries_checked,
+        entries_ok,
+        auth_slots_checked,
+        issues,
+    })
+}
+
+/// Runs `PRAGMA integrity_check` and returns every message line.
+fn run_integrity_check(db: &DatabaseConnection) -> Result<Vec<String>, String> {
+    let mut stmt = db
+        .conn()
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?;
+    let lines = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read integrity check result: {}", e))?;
+    Ok(lines)
+}
+
+/// Decrypts a single encrypted column, returning the plaintext or the issue that prevented it.
+pub(crate) fn decrypt_field(
+    key: &cipher::Key,
+    blob: Option<Vec<u8>>,
+) -> Result<String, (IssueKind, String)> {
+    let blob = blob.ok_or((IssueKind::MissingData, "Column is NULL".to_string()))?;
+    let bytes =
+        cipher::decrypt(key, &blob).map_err(|e| (IssueKind::DecryptionFailed, e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| (IssueKind::InvalidUtf8, e.to_string()))
+}
+
+/// Checks every row of `entries`. Returns `(checked, ok)`.
+fn verify_entries(
+    db: &DatabaseConnection,
+    issues: &mut Vec<IntegrityIssue>,
+) -> Result<(usize, usize), String> {
+    let mut ok = 0;
+    let checked = scan_entries(db, issues, |_| ok += 1)?;
+    Ok((checked, ok))
+}
+
+/// Walks `entries` in rowid order, decrypting each row independently.
+///
+/// Rows that decrypt cleanly are handed to `on_entry`; every problem on a row
+/// is appended to `issues` and the row is skipped. Returns the number of rows
+/// visited. Shared by verification and salvage so both agree on what counts
+/// as a readable entry.
+pub(crate) fn scan_entries(
+    db: &DatabaseConnection,
+    issues: &mut Vec<IntegrityIssue>,
+    mut on_entry: impl FnMut(DiaryEntry),
+) -> Result<usize, String> {
+    let mut stmt = db
+        .conn()
+        .prepare(
+            "SELECT rowid, date, title_encrypted, text_encrypted, word_count, date_created, date_updated \
+             FROM entries ORDER BY rowid ASC",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let mut rows = stmt
+        .query([])
+        .map_err(|e| format!("Failed to query entries: {}", e))?;
+
+    let mut checked = 0;
+
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(e) => {
+                // A damaged b-tree page ends the scan; SQLite cannot step past it.
+                issues.push(IntegrityIssue {
+                    table: "entries".to_string(),
+                    row_id: -1,
+                    date: None,
+                    field: None,
+                    kind: IssueKind::UnreadableRow,
+                    message: format!("Scan stopped after {} row(s): {}", checked, e),
+                });
+                break;
+            }
+        };
+        checked += 1;
+
+        let row_id: i64 = row.get(0).unwrap_or(-1);
+        let date = row.get::<_, String>(1).ok();
+        let issues_before = issues.len();
+
+        let mut push = |field: Option<&str>, kind: IssueKind, message: String| {
+            issues.push(IntegrityIssue {
+                table: "entries".to_string(),
+                row_id,
+                date: date.clone(),
+                field: field.map(str::to_string),
+                kind,
+                message,
+            });
+        };
+
+        match &date {
+            Some(d) if chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok() => {}
+            Some(d) => push(
+                Some("date"),
+                IssueKind::InvalidDate,
+                format!("'{}' is not a valid date", d),
+            ),
+            None => push(
+                Some("date"),
+                IssueKind::UnreadableRow,
+                "Date column is unreadable".to_string(),
+            ),
+        }
+
+        let mut plaintext = Vec::with_capacity(2);
+        for (index, field) in [(2, "title_encrypted"), (3, "text_encrypted")] {
+            let blob = match row.get::<_, Option<Vec<u8>>>(index) {
+                Ok(b) => b,
+                Err(e) => {
+                    push(Some(field), IssueKind::UnreadableRow, e.to_string());
+                    continue;
+                }
+            };
+            match decrypt_field(db.key(), blob) {
+                Ok(text) => plaintext.push(text),
+                Err((kind, message)) => push(Some(field), kind, message),
+            }
+        }
+
+        if issues.len() != issues_before {
+            continue;
+        }
+
+        let (Some(date), [title, text]) = (date, &mut plaintext[..]) else {
+            continue;
+        };
+        // Bookkeeping columns are NOT NULL in the schema; fall back rather than
+        // drop an entry whose content decrypted fine.
+        let text = std::mem::take(text);
+        let word_count = row
+            .get::<_, i32>(4)
+            .unwrap_or_else(|_| queries::count_words(&text));
+        let date_created = row
+            .get::<_, String>(5)
+            .unwrap_or_else(|_| chrono::Utc::now().to_rfc3339());
+        let date_updated = row
+            .get::<_, String>(6)
+            .unwrap_or_else(|_| date_created.clone());
+        on_entry(DiaryEntry {
+            id: row_id,
+            date,
+            title: std::mem::take(title),
+            text,
+            word_count,
+            date_created,
+            date_updated,
+        });
+    }
+
+    Ok(checked)
+}
+
+/// Checks the layout of every auth slot blob. Returns the number of slots checked.
+fn verify_auth_slots(
+    db: &DatabaseConnection,
+    password: Option<&str>,
+    issues: &mut Vec<IntegrityIssue>,
+) -> Result<usize, String> {
+    let mut stmt = db
+        .conn()
+        .prepare("SELECT id, type, public_key, wrapped_key FROM auth_slots ORDER BY id ASC")
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let slots = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<Vec<u8>>>(2)?,
+                row.get::<_, Option<Vec<u8>>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("Failed to query auth slots: {}", e))?;
+
+    let mut checked = 0;
+    for slot in slots {
+        checked += 1;
+        let (id, slot_type, public_key, wrapped_key) = match slot {
+            Ok(s) => s,
+            Err(e) => {
+                issues.push(slot_issue(-1, IssueKind::UnreadableRow, e.to_string()));
+                continue;
+            }
+        };
+        let Some(wrapped_key) = wrapped_key else {
+            issues.push(slot_issue(
+                id,
+                IssueKind::MissingData,
+                "wrapped_key is NULL".to_string(),
+            ));
+            continue;
+        };
+
+        let result = match slot_type.as_str() {
+            "password" => check_password_slot(&wrapped_key, password, db.key()),
+            "keypair" => check_keypair_slot(public_key.as_deref(), &wrapped_key),
+            // nonce(12) + master key(32) + tag(16)
+            "auto" if wrapped_key.len() == 60 => Ok(()),
+            "auto" => Err((
+                IssueKind::MalformedAuthSlot,
+                format!("Expected 60-byte wrapped key, found {}", wrapped_key.len()),
+            )),
+            other => Err((
+                IssueKind::OrphanedRow,
+                format!("Unknown auth method '{}'", other),
+            )),
+        };
+        if let Err((kind, message)) = result {
+            issues.push(slot_issue(id, kind, message));
+        }
+    }
+
+    Ok(checked)
+}
+
+fn slot_issue(row_id: i64, kind: IssueKind, message: String) -> IntegrityIssue {
+    IntegrityIssue {
+        table: "auth_slots".to_string(),
+        row_id,
+        date: None,
+        field: Some("wrapped_key".to_string()),
+        kind,
+        message,
+    }
+}
+
+/// Validates a password slot: `[phc_len: u32 LE][phc_hash][nonce][ciphertext][tag]`.
+fn check_password_slot(
+    wrapped: &[u8],
+    password: Option<&str>,
+    master_key: &cipher::Key,
+) -> Result<(), (IssueKind, String)> {
+    let malformed = |msg: &str| (IssueKind::MalformedAuthSlot, msg.to_string());
+
+    let header: [u8; 4] = wrapped
+        .get(..4)
+        .and_then(|h| h.try_into().ok())
+        .ok_or_else(|| malformed("Blob is shorter than its header"))?;
+    let phc_len = u32::from_le_bytes(header) as usize;
+    let phc_bytes = wrapped
+        .get(4..4 + phc_len)
+        .ok_or_else(|| malformed("Blob is truncated inside the PHC hash"))?;
+    let phc_hash =
+        std::str::from_utf8(phc_bytes).map_err(|_| malformed("PHC hash is not UTF-8"))?;
+    argon2::PasswordHash::new(phc_hash).map_err(|e| malformed(&e.to_string()))?;
+    if wrapped.len() - 4 - phc_len < 12 + 16 {
+        return Err(malformed("Encrypted master key is truncated"));
+    }
+
+    if let Some(password) = password {
+        let method = crate::auth::password::PasswordMethod::new(password.to_string());
+        let unwrapped = method
+            .unwrap_master_key(wrapped)
+            .map_err(|e| (IssueKind::DecryptionFailed, e))?;
+        if *unwrapped != master_key.as_bytes()[..] {
+            return Err((
+                IssueKind::DecryptionFailed,
+                "Slot unwraps to a different master key".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validates a keypair slot: 32-byte public key plus `[eph_pub(32)][nonce][ciphertext][tag]`.
+fn check_keypair_slot(
+    public_key: Option<&[u8]>,
+    wrapped: &[u8],
+) -> Result<(), (IssueKind, String)> {
+    match public_key {
+        None => {
+            return Err((
+                IssueKind::OrphanedRow,
+                "Keypair slot has no public key and can never be matched".to_string(),
+            ))
+        }
+        Some(k) if k.len() != 32 => {
+            return Err((
+                IssueKind::MalformedAuthSlot,
+                format!("Expected 32-byte public key, found {}", k.len()),
+            ))
+        }
+        Some(_) => {}
+    }
+    if wrapped.len() < 32 + 12 + 16 {
+        return Err((
+            IssueKind::MalformedAuthSlot,
+            format!("Wrapped key is too short ({} bytes)", wrapped.len()),
+        ));
+    }
+    Ok(())
+}
+
+/// Reports rows left behind in tables the current schema no longer reads.
+///
+/// `entries_new` only exists if a v4→v5 migration was interrupted outside its
+/// transaction; `entries_fts` is the plaintext v1–v3 search index that the
+/// v3→v4 migration is supposed to drop.
+fn find_orphaned_tables(
+    db: &DatabaseConnection,
+    issues: &mut Vec<IntegrityIssue>,
+) -> Result<(), String> {
+    for table in ["entries_new", "entries_fts"] {
+        let exists: i64 = db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to inspect schema: {}", e))?;
+        if exists == 0 {
+            continue;
+        }
+
+        let mut stmt = db
+            .conn()
+            .prepare(&format!("SELECT rowid FROM {} ORDER BY rowid ASC", table))
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let row_ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| format!("Failed to query {}: {}", table, e))?
+            .filter_map(|r| r.ok());
+        for row_id in row_ids {
+            issues.push(IntegrityIssue {
+                table: table.to_string(),
+                row_id,
+                date: None,
+                field: None,
+                kind: IssueKind::OrphanedRow,
+                message: format!("Row in leftover table '{}' is not used by the app", table),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::queries::{insert_entry, DiaryEntry};
+    use crate::db::schema::create_database;
+
+    fn create_test_entry(date: &str, title: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 0,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: "<p>Body</p>".to_string(),
+            word_count: 1,
+            date_created: "2024-01-01T12:00:00Z".to_string(),
+            date_updated: "2024-01-01T12:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_healthy_diary_reports_no_issues() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-01", "One")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-02", "Two")).unwrap();
+
+        let report = verify_database(&db, Some("test")).unwrap();
+
+        assert!(
+            report.is_healthy(),
+            "unexpected issues: {:?}",
+            report.issues
+        );
+        assert_eq!(report.entries_checked, 2);
+        assert_eq!(report.entries_ok, 2);
+        assert_eq!(report.auth_slots_checked, 1);
+    }
+
+    #[test]
+    fn test_bad_gcm_tag_and_utf8_are_reported_per_row() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-01", "Good")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-02", "Tampered")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-03", "Binary")).unwrap();
+
+        // Flip the last byte (part of the GCM tag) of row 2's title
+        let mut blob: Vec<u8> = db
+            .conn()
+            .query_row(
+                "SELECT title_encrypted FROM entries WHERE date = '2024-01-02'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        *blob.last_mut().unwrap() ^= 0xFF;
+        db.conn()
+            .execute(
+                "UPDATE entries SET title_encrypted = ?1 WHERE date = '2024-01-02'",
+                [&blob],
+            )
+            .unwrap();
+
+        // Valid ciphertext of invalid UTF-8 for row 3's text
+        let bad_utf8 = cipher::encrypt(db.key(), &[0xFF, 0xFE, 0xFD]).unwrap();
+        db.conn()
+            .execute(
+                "UPDATE entries SET text_encrypted = ?1 WHERE date = '2024-01-03'",
+                [&bad_utf8],
+            )
+            .unwrap();
+
+        let report = verify_database(&db, None).unwrap();
+
+        assert!(!report.is_healthy());
+        assert_eq!(report.entries_checked, 3);
+        assert_eq!(report.entries_ok, 1);
+        assert_eq!(report.issues.len(), 2);
+
+        let tag = &report.issues[0];
+        assert_eq!(tag.kind, IssueKind::DecryptionFailed);
+        assert_eq!(tag.date.as_deref(), Some("2024-01-02"));
+        assert_eq!(tag.field.as_deref(), Some("title_encrypted"));
+
+        let utf8 = &report.issues[1];
+        assert_eq!(utf8.kind, IssueKind::InvalidUtf8);
+        assert_eq!(utf8.date.as_deref(), Some("2024-01-03"));
+        assert_eq!(utf8.field.as_deref(), Some("text_encrypted"));
+    }
+
+    #[test]
+    fn test_null_ciphertext_and_invalid_date() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO entries (date, title_encrypted, text_encrypted, date_created, date_updated)
+                 VALUES ('2024-02-31', NULL, NULL, 'x', 'x')",
+                [],
+            )
+            .unwrap();
+
+        let report = verify_database(&db, None).unwrap();
+
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                IssueKind::InvalidDate,
+                IssueKind::MissingData,
+                IssueKind::MissingData
+            ]
+        );
+        assert_eq!(report.entries_ok, 0);
+    }
+
+    #[test]
+    fn test_malformed_and_orphaned_auth_slots() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+        db.conn()
+            .execute_batch(
+                "INSERT INTO auth_slots (type, label, wrapped_key, created_at)
+                     VALUES ('keypair', 'No pubkey', x'00', 'now');
+                 INSERT INTO auth_slots (type, label, wrapped_key, created_at)
+                     VALUES ('auto', 'Short', x'0102', 'now');",
+            )
+            .unwrap();
+
+        let report = verify_database(&db, None).unwrap();
+
+        assert_eq!(report.auth_slots_checked, 3);
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![IssueKind::OrphanedRow, IssueKind::MalformedAuthSlot]
+        );
+    }
+
+    #[test]
+    fn test_wrong_password_fails_slot_check() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+
+        let report = verify_database(&db, Some("not-the-password")).unwrap();
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].table, "auth_slots");
+        assert_eq!(report.issues[0].kind, IssueKind::DecryptionFailed);
+    }
+
+    #[test]
+    fn test_leftover_fts_rows_are_orphaned() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "test".to_string()).unwrap();
+        db.conn()
+            .execute_batch(
+                "CREATE TABLE entries_fts (title TEXT, text TEXT);
+                 INSERT INTO entries_fts VALUES ('leaked', 'plaintext');",
+            )
+            .unwrap();
+
+        let report = verify_database(&db, None).unwrap();
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].table, "entries_fts");
+        assert_eq!(report.issues[0].kind, IssueKind::OrphanedRow);
+    }
+}
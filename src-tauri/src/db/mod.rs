@@ -1,4 +1,6 @@
+pub mod integrity;
 pub mod queries;
+pub mod salvage;
 pub mod schema;
 
 pub use schema::{create_database, open_database, DatabaseConnection};
@@ -305,7 +305,7 @@ pub fn get_all_entries(db: &DatabaseConnection) -> Result<Vec<DiaryEntry>, Strin
 
 /// Strips HTML tags from `input`, replacing each closing `>` with a space so
 /// that adjacent words separated only by a tag are not concatenated.
-fn strip_html_tags(input: &str) -> String {
+pub(crate) fn strip_html_tags(input: &str) -> String {
     let mut out = String::with_capacity(input.len());
     let mut in_tag = false;
     for ch in input.chars() {
@@ -0,0 +1,282 @@
+use crate::db::integrity::{scan_entries, IntegrityIssue};
+use crate::db::queries::{self, DiaryEntry};
+use crate::db::schema::{create_database, DatabaseConnection};
+use base64::{engine::general_purpose, Engine as _};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Raw contents of an entry row that could not be recovered.
+///
+/// Ciphertext is kept verbatim (base64) so the row can still be inspected or
+/// retried later; nothing from the damaged diary is thrown away.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedRow {
+    pub row_id: i64,
+    pub date: Option<String>,
+    pub title_encrypted: Option<String>,
+    pub text_encrypted: Option<String>,
+}
+
+/// Result of a salvage pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct SalvageReport {
+    /// Path of the freshly created diary holding every readable entry
+    pub output_path: String,
+    /// Path of the JSON quarantine report, written only when rows were skipped
+    pub quarantine_path: Option<String>,
+    pub entries_recovered: usize,
+    pub entries_quarantined: usize,
+    /// Every problem that caused a row to be skipped
+    pub issues: Vec<IntegrityIssue>,
+    pub quarantined_rows: Vec<QuarantinedRow>,
+}
+
+/// Reads every entry that still decrypts, skipping (not aborting on) bad rows.
+///
+/// Returns the readable entries and one issue per failed row/column. Unlike
+/// `get_all_entries`, which fails the whole call on the first row that does
+/// not decrypt, the bad row is skipped and reported.
+pub fn salvage_entries(
+    db: &DatabaseConnection,
+) -> Result<(Vec<DiaryEntry>, Vec<IntegrityIssue>), String> {
+    let mut entries = Vec::new();
+    let mut issues = Vec::new();
+    scan_entries(db, &mut issues, |entry| entries.push(entry))?;
+    Ok((entries, issues))
+}
+
+/// Copies every readable entry of `db` into a new diary at `output_path`.
+///
+/// The new diary is created with [`create_database`] and protected by
+/// `password`. Rows that cannot be read are left out and listed in the
+/// returned report, which is also written next to the new diary as
+/// `<output>.quarantine.json`. The damaged source is never modified.
+pub fn salvage_to_new_database(
+    db: &DatabaseConnection,
+    output_path: &Path,
+    password: String,
+) -> Result<SalvageReport, String> {
+    if output_path.exists() {
+        return Err(format!(
+            "Refusing to overwrite existing file: {}",
+            output_path.display()
+        ));
+    }
+
+    let (entries, issues) = salvage_entries(db)?;
+    let quarantined_rows = collect_quarantined_rows(db, &issues);
+
+    if let Err(e) = write_entries(output_path, password, &entries) {
+        let _ = fs::remove_file(output_path);
+        return Err(e);
+    }
+
+    let mut report = SalvageReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        quarantine_path: None,
+        entries_recovered: entries.len(),
+        entries_quarantined: quarantined_rows.len(),
+        issues,
+        quarantined_rows,
+    };
+
+    if !report.issues.is_empty() {
+        let quarantine_path = quarantine_path_for(output_path);
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("Failed to serialize quarantine report: {}", e))?;
+        fs::write(&quarantine_path, json)
+            .map_err(|e| format!("Failed to write quarantine report: {}", e))?;
+        report.quarantine_path = Some(quarantine_path.to_string_lossy().to_string());
+        warn!(
+            "Salvage skipped {} unreadable row(s); see {}",
+            report.entries_quarantined,
+            quarantine_path.display()
+        );
+    }
+
+    info!(
+        "Salvaged {} entries into {}",
+        report.entries_recovered, report.output_path
+    );
+    Ok(report)
+}
+
+/// Creates the output diary and inserts all entries in a single transaction.
+fn write_entries(
+    output_path: &Path,
+    password: String,
+    entries: &[DiaryEntry],
+) -> Result<(), String> {
+    let new_db = create_database(output_path, password)?;
+    new_db
+        .conn()
+        .execute_batch("BEGIN IMMEDIATE TRANSACTION")
+        .map_err(|e| format!("Failed to begin salvage transaction: {}", e))?;
+    for entry in entries {
+        if let Err(e) = queries::insert_entry(&new_db, entry) {
+            let _ = new_db.conn().execute_batch("ROLLBACK");
+            return Err(e);
+        }
+    }
+    new_db
+        .conn()
+        .execute_batch("COMMIT")
+        .map_err(|e| format!("Failed to commit salvaged entries: {}", e))
+}
+
+/// Best-effort read of the raw columns for every entry row that had an issue.
+fn collect_quarantined_rows(
+    db: &DatabaseConnection,
+    issues: &[IntegrityIssue],
+) -> Vec<QuarantinedRow> {
+    let row_ids: BTreeSet<i64> = issues
+        .iter()
+        .filter(|issue| issue.table == "entries" && issue.row_id >= 0)
+        .map(|issue| issue.row_id)
+        .collect();
+
+    row_ids
+        .into_iter()
+        .map(|row_id| {
+            let raw = db.conn().query_row(
+                "SELECT date, title_encrypted, text_encrypted FROM entries WHERE rowid = ?1",
+                [row_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0).ok().flatten(),
+                        row.get::<_, Option<Vec<u8>>>(1).ok().flatten(),
+                        row.get::<_, Option<Vec<u8>>>(2).ok().flatten(),
+                    ))
+                },
+            );
+            let (date, title, text) = raw.unwrap_or((None, None, None));
+            QuarantinedRow {
+                row_id,
+                date,
+                title_encrypted: title.map(|b| general_purpose::STANDARD.encode(b)),
+                text_encrypted: text.map(|b| general_purpose::STANDARD.encode(b)),
+            }
+        })
+        .collect()
+}
+
+fn quarantine_path_for(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".quarantine.json");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::integrity::IssueKind;
+    use crate::db::queries::insert_entry;
+    use crate::db::schema::open_database;
+
+    fn create_test_entry(date: &str, title: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 0,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: "<p>Body text</p>".to_string(),
+            word_count: 2,
+            date_created: "2024-01-01T12:00:00Z".to_string(),
+            date_updated: "2024-01-01T12:00:00Z".to_string(),
+        }
+    }
+
+    fn corrupt_title(db: &DatabaseConnection, date: &str) {
+        let mut blob: Vec<u8> = db
+            .conn()
+            .query_row(
+                "SELECT title_encrypted FROM entries WHERE date = ?1",
+                [date],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let last = blob.len() - 1;
+        blob[last] ^= 0xFF;
+        db.conn()
+            .execute(
+                "UPDATE entries SET title_encrypted = ?1 WHERE date = ?2",
+                rusqlite::params![blob, date],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_salvage_entries_skips_bad_rows() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        let db = create_database(tmp.path(), "pw".to_string()).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-01", "One")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-02", "Two")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-03", "Three")).unwrap();
+        corrupt_title(&db, "2024-01-02");
+
+        let (entries, issues) = salvage_entries(&db).unwrap();
+        let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["One", "Three"]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::DecryptionFailed);
+        assert_eq!(issues[0].date.as_deref(), Some("2024-01-02"));
+    }
+
+    #[test]
+    fn test_salvage_to_new_database_recovers_readable_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("diary.db");
+        let out_path = dir.path().join("recovered.db");
+
+        let db = create_database(&src_path, "pw".to_string()).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-01", "One")).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-02", "Two")).unwrap();
+        corrupt_title(&db, "2024-01-02");
+
+        let report = salvage_to_new_database(&db, &out_path, "new-pw".to_string()).unwrap();
+        assert_eq!(report.entries_recovered, 1);
+        assert_eq!(report.entries_quarantined, 1);
+        assert_eq!(
+            report.quarantined_rows[0].date.as_deref(),
+            Some("2024-01-02")
+        );
+        assert!(report.quarantined_rows[0].title_encrypted.is_some());
+
+        let quarantine = report.quarantine_path.expect("quarantine report written");
+        let json = fs::read_to_string(&quarantine).unwrap();
+        assert!(json.contains("decryption_failed"));
+
+        let recovered = open_database(&out_path, "new-pw".to_string(), dir.path()).unwrap();
+        let entries = queries::get_all_entries(&recovered).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "One");
+        assert_eq!(entries[0].text, "<p>Body text</p>");
+    }
+
+    #[test]
+    fn test_salvage_healthy_diary_writes_no_quarantine() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        insert_entry(&db, &create_test_entry("2024-01-01", "One")).unwrap();
+
+        let out_path = dir.path().join("copy.db");
+        let report = salvage_to_new_database(&db, &out_path, "pw".to_string()).unwrap();
+        assert_eq!(report.entries_recovered, 1);
+        assert!(report.quarantine_path.is_none());
+        assert!(!quarantine_path_for(&out_path).exists());
+    }
+
+    #[test]
+    fn test_salvage_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = create_database(dir.path().join("diary.db"), "pw".to_string()).unwrap();
+        let out_path = dir.path().join("existing.db");
+        fs::write(&out_path, b"keep me").unwrap();
+
+        let result = salvage_to_new_database(&db, &out_path, "pw".to_string());
+        assert!(result.is_err());
+        assert_eq!(fs::read(&out_path).unwrap(), b"keep me");
+    }
+}
@@ -2,7 +2,7 @@ use crate::crypto::{cipher, password};
 use crate::db::queries;
 use log::{debug, error, info, warn};
 use rand::RngCore;
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
 use std::path::Path;
 use zeroize::Zeroize;
 
@@ -116,11 +116,11 @@ pub fn open_database<P1: AsRef<Path>, P2: AsRef<Path>>(
 
     // Run v1 → v2 migration if needed (FTS restructure only, no re-encryption)
     if current_version < 2 {
-        migrate_v1_to_v2(&db_conn, db_path_ref, backups_dir.as_ref())?;
+        migrate_v1_to_v2(&db_conn, backups_dir.as_ref())?;
     }
 
     // Run v2 → v3 migration (introduce wrapped master key)
-    db_conn = migrate_v2_to_v3(db_conn, db_path_ref, backups_dir.as_ref(), password)?;
+    db_conn = migrate_v2_to_v3(db_conn, backups_dir.as_ref(), password)?;
 
     // Run v3 → v4 migration (drop plaintext FTS table)
     migrate_v3_to_v4(&db_conn)?;
@@ -155,38 +155,7 @@ pub fn open_database_with_keypair<P1: AsRef<Path>, P2: AsRef<Path>>(
             .to_string());
     }
 
-    // Derive the public key from the private key to find the matching slot
-    use x25519_dalek::{PublicKey, StaticSecret};
-    let static_secret = StaticSecret::from(private_key_bytes);
-    let public_key = PublicKey::from(&static_secret);
-    let pub_key_slice: &[u8] = public_key.as_bytes();
-
-    // Find the keypair slot matching this public key
-    let slot_result = conn.query_row(
-        "SELECT id, wrapped_key FROM auth_slots WHERE type = 'keypair' AND public_key = ?1 LIMIT 1",
-        rusqlite::params![pub_key_slice],
-        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
-    );
-
-    let (slot_id, wrapped_key) = match slot_result {
-        Ok(r) => r,
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            return Err("No keypair auth method found for this key file".to_string());
-        }
-        Err(e) => return Err(format!("Database error: {}", e)),
-    };
-
-    // Unwrap master key using the private key
-    let unwrap_method = crate::auth::keypair::PrivateKeyMethod {
-        private_key: private_key_bytes,
-    };
-    let master_key_bytes = unwrap_method
-        .unwrap_master_key(&wrapped_key)
-        .map_err(|e| format!("Failed to unlock with key file: {}", e))?;
-
-    let encryption_key =
-        cipher::Key::from_slice(&master_key_bytes).ok_or("Invalid master key size")?;
-    // master_key_bytes zeroed automatically on drop (SecretBytes)
+    let (slot_id, encryption_key) = unwrap_keypair_slot(&conn, private_key_bytes)?;
 
     // Update last_used
     queries::update_slot_last_used(&conn, slot_id)?;
@@ -300,6 +269,109 @@ pub fn open_database_auto<P1: AsRef<Path>, P2: AsRef<Path>>(
     Ok(db)
 }
 
+/// Opens an existing diary read-only using a password.
+///
+/// No migrations run and `last_used` is not touched, so the file on disk is
+/// never modified. Intended for offline tooling (the `verify` CLI) that must
+/// not write to a possibly damaged diary. Only diaries already at
+/// `SCHEMA_VERSION` are supported.
+pub fn open_database_read_only<P: AsRef<Path>>(
+    db_path: P,
+    password: String,
+) -> Result<DatabaseConnection, String> {
+    let conn = open_current_schema_read_only(db_path.as_ref())?;
+    let (_, encryption_key) = unwrap_password_slot(&conn, password)?;
+
+    Ok(DatabaseConnection {
+        conn,
+        encryption_key,
+    })
+}
+
+/// Opens a diary read-only with a key file's private key, like
+/// [`open_database_read_only`]. The slot's `last_used` is not updated.
+pub fn open_database_read_only_with_keypair<P: AsRef<Path>>(
+    db_path: P,
+    private_key_bytes: [u8; 32],
+) -> Result<DatabaseConnection, String> {
+    let conn = open_current_schema_read_only(db_path.as_ref())?;
+    let (_, encryption_key) = unwrap_keypair_slot(&conn, private_key_bytes)?;
+
+    Ok(DatabaseConnection {
+        conn,
+        encryption_key,
+    })
+}
+
+/// True if the diary at `db_path` can be unlocked with a password. Journals
+/// created with a key file or an automatic device key may have no password.
+pub fn has_password_slot<P: AsRef<Path>>(db_path: P) -> Result<bool, String> {
+    let conn = Connection::open_with_flags(db_path.as_ref(), OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM auth_slots WHERE type = 'password')",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to read auth slots: {}", e))
+}
+
+/// Opens a copy of an unlocked diary (e.g. one of its backups) read-only,
+/// reusing the master key already in memory instead of asking for credentials.
+///
+/// Backups share the master key with the live diary — changing the password
+/// only re-wraps it — so this works without a password. The key is checked
+/// against the first entry; a diary with no entries cannot be checked and is
+/// accepted as-is.
+pub fn open_database_read_only_with_key<P: AsRef<Path>>(
+    db_path: P,
+    key: &cipher::Key,
+) -> Result<DatabaseConnection, String> {
+    let conn = open_current_schema_read_only(db_path.as_ref())?;
+
+    let probe: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT text_encrypted FROM entries ORDER BY id ASC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(format!("Failed to read database: {}", e)),
+        })?;
+    if let Some(blob) = probe {
+        cipher::decrypt(key, &blob)
+            .map_err(|_| "This file was encrypted with a different key".to_string())?;
+    }
+
+    let encryption_key =
+        cipher::Key::from_slice(key.as_bytes()).ok_or("Invalid master key size")?;
+    Ok(DatabaseConnection {
+        conn,
+        encryption_key,
+    })
+}
+
+/// Opens a diary read-only and checks it is already at `SCHEMA_VERSION`.
+fn open_current_schema_read_only(db_path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let current_version: i32 = conn
+        .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+        .unwrap_or(1);
+
+    if current_version != SCHEMA_VERSION {
+        return Err(format!(
+            "Journal is at schema v{} (expected v{}). \
+             Please unlock it in the app first to upgrade.",
+            current_version, SCHEMA_VERSION
+        ));
+    }
+    Ok(conn)
+}
+
 // ─── Private helpers ────────────────────────────────────────────────────────
 
 /// Open a v3 database using the password slot in auth_slots.
@@ -308,6 +380,60 @@ fn open_v3_with_password(
     password: String,
     _backups_dir: &Path,
 ) -> Result<DatabaseConnection, String> {
+    let (slot_id, encryption_key) = unwrap_password_slot(&conn, password)?;
+
+    // Update last_used
+    queries::update_slot_last_used(&conn, slot_id)?;
+
+    Ok(DatabaseConnection {
+        conn,
+        encryption_key,
+    })
+}
+
+/// Unwraps the master key from the first password slot. Returns `(slot_id, master_key)`.
+/// Finds the keypair slot matching `private_key_bytes` and unwraps the master key.
+fn unwrap_keypair_slot(
+    conn: &Connection,
+    private_key_bytes: [u8; 32],
+) -> Result<(i64, cipher::Key), String> {
+    // Derive the public key from the private key to find the matching slot
+    use x25519_dalek::{PublicKey, StaticSecret};
+    let static_secret = StaticSecret::from(private_key_bytes);
+    let public_key = PublicKey::from(&static_secret);
+    let pub_key_slice: &[u8] = public_key.as_bytes();
+
+    // Find the keypair slot matching this public key
+    let slot_result = conn.query_row(
+        "SELECT id, wrapped_key FROM auth_slots WHERE type = 'keypair' AND public_key = ?1 LIMIT 1",
+        rusqlite::params![pub_key_slice],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+    );
+
+    let (slot_id, wrapped_key) = match slot_result {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            return Err("No keypair auth method found for this key file".to_string());
+        }
+        Err(e) => return Err(format!("Database error: {}", e)),
+    };
+
+    // Unwrap master key using the private key
+    let unwrap_method = crate::auth::keypair::PrivateKeyMethod {
+        private_key: private_key_bytes,
+    };
+    let master_key_bytes = unwrap_method
+        .unwrap_master_key(&wrapped_key)
+        .map_err(|e| format!("Failed to unlock with key file: {}", e))?;
+
+    let encryption_key =
+        cipher::Key::from_slice(&master_key_bytes).ok_or("Invalid master key size")?;
+    // master_key_bytes zeroed automatically on drop (SecretBytes)
+
+    Ok((slot_id, encryption_key))
+}
+
+fn unwrap_password_slot(conn: &Connection, password: String) -> Result<(i64, cipher::Key), String> {
     // Find the password slot
     let slot_result = conn.query_row(
         "SELECT id, wrapped_key FROM auth_slots WHERE type = 'password' ORDER BY id ASC LIMIT 1",
@@ -333,17 +459,11 @@ fn open_v3_with_password(
         cipher::Key::from_slice(&master_key_bytes).ok_or("Invalid master key size")?;
     // master_key_bytes zeroed automatically on drop (SecretBytes)
 
-    // Update last_used
-    queries::update_slot_last_used(&conn, slot_id)?;
-
-    Ok(DatabaseConnection {
-        conn,
-        encryption_key,
-    })
+    Ok((slot_id, encryption_key))
 }
 
 /// Creates the database schema (v5)
-fn create_schema(conn: &Connection) -> Result<(), String> {
+pub(crate) fn create_schema(conn: &Connection) -> Result<(), String> {
     conn.execute_batch(
         r#"
         -- Schema version table
@@ -425,15 +545,11 @@ pub(crate) fn derive_key_from_hash(password_hash: &str) -> Result<Vec<u8>, Strin
 /// Migration v1 → v2: Replace external-content FTS with standalone FTS table.
 ///
 /// Does NOT change the encryption key or re-encrypt entries.
-fn migrate_v1_to_v2(
-    db: &DatabaseConnection,
-    db_path: &Path,
-    backups_dir: &Path,
-) -> Result<(), String> {
+fn migrate_v1_to_v2(db: &DatabaseConnection, backups_dir: &Path) -> Result<(), String> {
     info!("Migration v1→v2: starting");
 
     // Create backup before any changes
-    let backup_path = crate::backup::create_backup(db_path, backups_dir)
+    let backup_path = crate::backup::create_backup(db, backups_dir)
         .map_err(|e| format!("Failed to create pre-migration backup: {}", e))?;
     info!("Migration v1→v2: backup created at {:?}", backup_path);
 
@@ -506,14 +622,13 @@ fn migrate_v1_to_v2(
 /// returns a new v3 `DatabaseConnection` (with the master key).
 fn migrate_v2_to_v3(
     mut db: DatabaseConnection,
-    db_path: &Path,
     backups_dir: &Path,
     password: String,
 ) -> Result<DatabaseConnection, String> {
     info!("Migration v2→v3: starting");
 
     // Step 1: Create backup
-    let backup_path = crate::backup::create_backup(db_path, backups_dir)
+    let backup_path = crate::backup::create_backup(&db, backups_dir)
         .map_err(|e| format!("Failed to create pre-migration backup: {}", e))?;
     info!("Migration v2→v3: backup created at {:?}", backup_path);
 
@@ -910,6 +1025,26 @@ mod tests {
         cleanup_backups_dir(&backups_dir);
     }
 
+    #[test]
+    fn test_open_database_read_only() {
+        let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
+        create_database(tmp.path().to_str().unwrap(), "pw".to_string()).unwrap();
+
+        let db = open_database_read_only(tmp.path(), "pw".to_string()).unwrap();
+        let last_used: Option<i64> = db
+            .conn()
+            .query_row("SELECT last_used FROM auth_slots", [], |row| row.get(0))
+            .unwrap();
+        assert!(
+            last_used.is_none(),
+            "read-only open must not touch last_used"
+        );
+        assert!(db.conn().execute("DELETE FROM entries", []).is_err());
+
+        let result = open_database_read_only(tmp.path(), "nope".to_string());
+        assert_eq!(result.err().unwrap(), "Incorrect password");
+    }
+
     #[test]
     fn test_schema_version() {
         let tmp = tempfile::Builder::new().suffix(".db").tempfile().unwrap();
@@ -1205,6 +1340,11 @@ mod tests {
             .unwrap();
         drop(db);
 
+        assert!(has_password_slot(&db_path).unwrap());
+        let read_only = open_database_read_only_with_keypair(&db_path, priv_key).unwrap();
+        assert!(read_only.conn().is_readonly("main").unwrap());
+        drop(read_only);
+
         // Now open with private key
         let db2 = open_database_with_keypair(&db_path, priv_key, &backups_dir).unwrap();
 
@@ -0,0 +1,182 @@
+use crate::config::JournalSettings;
+use crate::crypto::{cipher, password::KdfParams};
+use crate::db::queries::DiaryEntry;
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Value of the `format` field that identifies a `.mdarchive` file.
+pub(crate) const ARCHIVE_FORMAT: &str = "mini-diarium-archive";
+
+/// Current `.mdarchive` layout version.
+pub(crate) const ARCHIVE_VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+
+/// Outer, unencrypted layer of a `.mdarchive` file.
+///
+/// Only what is needed to re-derive the key is stored in the clear: the salt
+/// and Argon2id parameters. Everything else lives in `payload`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ArchiveEnvelope {
+    pub format: String,
+    pub version: u32,
+    pub kdf: ArchiveKdf,
+    /// Base64 of `[nonce][ciphertext][tag]` from `cipher::encrypt`
+    pub payload: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ArchiveKdf {
+    pub algorithm: String,
+    /// Base64 salt
+    pub salt: String,
+    #[serde(flatten)]
+    pub params: KdfParams,
+}
+
+/// Decrypted contents of a `.mdarchive` file.
+///
+/// Tags are hashtags in the entry text and images are embedded in it, so both
+/// travel with the entries. Unlock methods are never included.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ArchivePayload {
+    pub application: String,
+    pub version: String,
+    pub exported_at: String,
+    /// Name and backup settings of the exported journal, if known
+    #[serde(default)]
+    pub journal: Option<JournalSettings>,
+    /// Entries with every field, including creation and update times.
+    /// Images are embedded in the entry HTML and travel with it.
+    pub entries: Vec<DiaryEntry>,
+}
+
+/// Exports diary entries to a passphrase-encrypted `.mdarchive` document.
+///
+/// The entries are serialized to JSON and encrypted with AES-256-GCM under a
+/// key derived from `passphrase` with Argon2id and a fresh random salt. The
+/// result is a small JSON envelope, safe to copy to another machine: without
+/// the passphrase it reveals nothing but the number of bytes.
+pub fn export_entries_to_archive(
+    entries: Vec<DiaryEntry>,
+    journal: Option<JournalSettings>,
+    passphrase: &str,
+) -> Result<String, String> {
+    export_with_params(entries, journal, passphrase, KdfParams::default())
+}
+
+fn export_with_params(
+    entries: Vec<DiaryEntry>,
+    journal: Option<JournalSettings>,
+    passphrase: &str,
+    params: KdfParams,
+) -> Result<String, String> {
+    if passphrase.is_empty() {
+        return Err("An archive passphrase is required".to_string());
+    }
+
+    let payload = ArchivePayload {
+        application: "Mini Diarium".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        journal,
+        entries,
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&payload).map_err(|e| format!("Failed to serialize archive: {}", e))?,
+    );
+
+    let mut salt = [0u8; SALT_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    let key_bytes = crate::crypto::password::derive_key(passphrase.as_bytes(), &salt, &params)
+        .map_err(|e| e.to_string())?;
+    let key = cipher::Key::from_slice(&key_bytes).ok_or("Invalid archive key size")?;
+    let ciphertext = cipher::encrypt(&key, &plaintext)
+        .map_err(|e| format!("Failed to encrypt archive: {}", e))?;
+
+    let envelope = ArchiveEnvelope {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        kdf: ArchiveKdf {
+            algorithm: "argon2id".to_string(),
+            salt: general_purpose::STANDARD.encode(salt),
+            params,
+        },
+        payload: general_purpose::STANDARD.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&envelope)
+        .map_err(|e| format!("Failed to serialize archive: {}", e))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Cheap Argon2id parameters so archive tests stay fast.
+    pub(crate) const TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    pub(crate) fn export_for_test(entries: Vec<DiaryEntry>, passphrase: &str) -> String {
+        export_with_params(entries, None, passphrase, TEST_PARAMS).unwrap()
+    }
+
+    pub(crate) fn export_journal_for_test(
+        entries: Vec<DiaryEntry>,
+        journal: JournalSettings,
+        passphrase: &str,
+    ) -> String {
+        export_with_params(entries, Some(journal), passphrase, TEST_PARAMS).unwrap()
+    }
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 7,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: "2024-01-01T08:00:00Z".to_string(),
+            date_updated: "2024-01-02T09:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_archive_hides_plaintext() {
+        let archive = export_for_test(
+            vec![create_test_entry(
+                "2024-01-01",
+                "Secret title",
+                "<p>Secret body</p>",
+            )],
+            "correct horse",
+        );
+
+        assert!(!archive.contains("Secret"));
+        assert!(!archive.contains("2024-01-01"));
+
+        let envelope: ArchiveEnvelope = serde_json::from_str(&archive).unwrap();
+        assert_eq!(envelope.format, ARCHIVE_FORMAT);
+        assert_eq!(envelope.version, ARCHIVE_VERSION);
+        assert_eq!(envelope.kdf.algorithm, "argon2id");
+        assert_eq!(envelope.kdf.params, TEST_PARAMS);
+    }
+
+    #[test]
+    fn test_archive_uses_fresh_salt() {
+        let a = export_for_test(vec![], "pw");
+        let b = export_for_test(vec![], "pw");
+        let a: ArchiveEnvelope = serde_json::from_str(&a).unwrap();
+        let b: ArchiveEnvelope = serde_json::from_str(&b).unwrap();
+        assert_ne!(a.kdf.salt, b.kdf.salt);
+    }
+
+    #[test]
+    fn test_archive_requires_passphrase() {
+        assert!(export_entries_to_archive(vec![], None, "").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use super::markdown::{html_to_markdown, replace_embedded_images};
+use crate::db::queries::DiaryEntry;
+use crate::import::dayone::{DayOneEntry, DayOneLocation, DayOnePhoto, DayOneWeather};
+use crate::import::metadata::{find_hashtags, split_footer, ImportedMetadata};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use md5::{Digest, Md5};
+
+/// Folder that holds the photos of all entries
+const PHOTOS_DIR: &str = "photos";
+
+/// Exports diary entries in the layout of a Day One JSON export
+///
+/// Writes `Journal.json` and a `photos/` folder. Zipped together, they can be
+/// imported into Day One (and back into this app):
+/// ```json
+/// {
+///   "metadata": { "version": "1.0" },
+///   "entries": [
+///     {
+///       "uuid": "4E6E3D4A1F0B2C8D9E7F6A5B4C3D2E1F",
+///       "creationDate": "2024-01-15T14:30:00Z",
+///       "modifiedDate": "2024-01-15T15:00:00Z",
+///       "timeZone": "UTC",
+///       "text": "# My Title\n\nEntry content with ![](dayone-moment://0A1B…)",
+///       "starred": false,
+///       "tags": ["travel"],
+///       "photos": [{ "identifier": "0A1B…", "md5": "5d41…", "type": "jpeg" }]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// - The title becomes the first line of the Markdown text
+/// - The creation time of day comes from the entry's creation timestamp
+///   (noon if unknown), on the entry's date
+/// - Tags are the entry's `#hashtags`. Starred, location, weather and time
+///   zone lines appended by imports go back into Day One's own fields
+/// - Embedded images are written to `photos/<md5>.<type>`
+///
+/// Returns `(relative path, bytes)` pairs for every file to write.
+pub fn export_entries_to_dayone(
+    entries: Vec<DiaryEntry>,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut photo_files: Vec<(String, Vec<u8>)> = Vec::new();
+    let dayone_entries: Vec<DayOneEntry> = entries
+        .iter()
+        .map(|entry| convert_entry(entry, &mut photo_files))
+        .collect();
+
+    let journal = serde_json::json!({
+        "metadata": { "version": "1.0" },
+        "entries": dayone_entries,
+    });
+    let json = serde_json::to_string_pretty(&journal)
+        .map_err(|e| format!("Failed to serialize Day One JSON: {}", e))?;
+
+    let mut files = vec![("Journal.json".to_string(), json.into_bytes())];
+    files.extend(photo_files);
+    Ok(files)
+}
+
+fn convert_entry(entry: &DiaryEntry, photo_files: &mut Vec<(String, Vec<u8>)>) -> DayOneEntry {
+    let uuid = hex::encode_upper(Md5::digest(format!(
+        "mini-diarium:{}:{}",
+        entry.id, entry.date_created
+    )));
+
+    let (html, metadata) = split_footer(&entry.text);
+    // Day One has no mood field, so a mood stays in the text
+    let mood = ImportedMetadata {
+        mood: metadata.mood.clone(),
+        ..Default::default()
+    };
+    let html = format!("{}{}", html, mood.to_html());
+
+    let mut photos: Vec<DayOnePhoto> = Vec::new();
+    let html = replace_embedded_images(&html, &mut |mime, bytes| {
+        let md5 = hex::encode(Md5::digest(&bytes));
+        let identifier = hex::encode_upper(Md5::digest(format!("{}:{}", uuid, md5)));
+        if !photos.iter().any(|p| p.identifier == identifier) {
+            let kind = mime.strip_prefix("image/").unwrap_or("jpeg").to_string();
+            let path = format!("{}/{}.{}", PHOTOS_DIR, md5, kind);
+            if !photo_files.iter().any(|(existing, _)| *existing == path) {
+                photo_files.push((path, bytes));
+            }
+            photos.push(DayOnePhoto {
+                identifier: identifier.clone(),
+                md5: Some(md5),
+                kind: Some(kind),
+            });
+        }
+        format!("![](dayone-moment://{})", identifier)
+    });
+
+    let body = html_to_markdown(&html);
+    let title = entry.title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = match (title.is_empty(), body.is_empty()) {
+        (true, _) => body.clone(),
+        (false, true) => format!("# {}", title),
+        (false, false) => format!("# {}\n\n{}", title, body),
+    };
+
+    let mut tags = metadata.tags;
+    for tag in find_hashtags(&body) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    DayOneEntry {
+        uuid: Some(uuid),
+        creation_date: creation_date(entry),
+        text,
+        time_zone: Some(metadata.time_zone.unwrap_or_else(|| "UTC".to_string())),
+        modified_date: parse_timestamp(&entry.date_updated)
+            .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        starred: metadata.starred,
+        tags,
+        location: metadata.location.map(|place| DayOneLocation {
+            place_name: Some(place),
+            ..Default::default()
+        }),
+        weather: metadata.weather.as_deref().map(parse_weather),
+        photos,
+    }
+}
+
+/// The entry's date at the UTC time of day it was created
+fn creation_date(entry: &DiaryEntry) -> String {
+    let time = parse_timestamp(&entry.date_created)
+        .map(|dt| dt.format("%H:%M:%S").to_string())
+        .unwrap_or_else(|| "12:00:00".to_string());
+    format!("{}T{}Z", entry.date, time)
+}
+
+/// Reads RFC 3339 timestamps as well as the `YYYY-MM-DD HH:MM:SS` (UTC) form
+/// some importers store
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|dt| dt.and_utc())
+        })
+}
+
+/// Inverse of [`DayOneWeather::describe`]: "Partly Cloudy, 18 °C"
+fn parse_weather(text: &str) -> DayOneWeather {
+    let (conditions, last) = match text.rsplit_once(", ") {
+        Some((conditions, last)) => (Some(conditions), last),
+        None => (None, text),
+    };
+    match last.strip_suffix(" °C").and_then(|t| t.parse::<f64>().ok()) {
+        Some(temperature) => DayOneWeather {
+            conditions_description: conditions.map(str::to_string),
+            temperature_celsius: Some(temperature),
+        },
+        None => DayOneWeather {
+            conditions_description: Some(text.to_string()),
+            temperature_celsius: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::dayone_zip::parse_dayone_zip;
+    use crate::import::zip::tests::build_zip;
+    use base64::{engine::general_purpose, Engine as _};
+    use serde_json::Value;
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 1, 2, 3];
+
+    fn create_test_entry(id: i64, date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: "2024-01-15T08:30:00Z".to_string(),
+            date_updated: "2024-01-16T09:00:00+01:00".to_string(),
+        }
+    }
+
+    fn png_tag() -> String {
+        format!(
+            r#"<img src="data:image/png;base64,{}" alt="">"#,
+            general_purpose::STANDARD.encode(PNG)
+        )
+    }
+
+    fn journal(files: &[(String, Vec<u8>)]) -> Value {
+        assert_eq!(files[0].0, "Journal.json");
+        serde_json::from_slice(&files[0].1).unwrap()
+    }
+
+    #[test]
+    fn test_export_entry_fields() {
+        let text = format!(
+            "<p>Walk with <strong>Anna</strong> #family</p><p>{}</p>\
+             <p>#outdoors</p><p><em>Starred · Mood: Happy · Location: Café Central · \
+             Weather: Cloudy, 4 °C · Time zone: Europe/Vienna</em></p>",
+            png_tag()
+        );
+        let files =
+            export_entries_to_dayone(vec![create_test_entry(7, "2024-01-14", "Sunday", &text)])
+                .unwrap();
+        let json = journal(&files);
+        let entry = &json["entries"][0];
+
+        assert_eq!(json["metadata"]["version"], "1.0");
+        assert_eq!(entry["uuid"].as_str().unwrap().len(), 32);
+        assert_eq!(entry["creationDate"], "2024-01-14T08:30:00Z");
+        assert_eq!(entry["modifiedDate"], "2024-01-16T08:00:00Z");
+        assert_eq!(entry["timeZone"], "Europe/Vienna");
+        assert_eq!(entry["starred"], true);
+        assert_eq!(entry["tags"], serde_json::json!(["outdoors", "family"]));
+        assert_eq!(entry["location"]["placeName"], "Café Central");
+        assert_eq!(entry["weather"]["conditionsDescription"], "Cloudy");
+        assert_eq!(entry["weather"]["temperatureCelsius"], 4.0);
+
+        let md5 = hex::encode(Md5::digest(PNG));
+        let photo = &entry["photos"][0];
+        let identifier = photo["identifier"].as_str().unwrap();
+        assert_eq!(photo["md5"], md5.as_str());
+        assert_eq!(photo["type"], "png");
+        assert_eq!(
+            entry["text"],
+            format!(
+                "# Sunday\n\nWalk with **Anna** #family\n\n![](dayone-moment://{})\n\n*Mood: Happy*",
+                identifier
+            )
+        );
+        assert_eq!(files[1], (format!("photos/{}.png", md5), PNG.to_vec()));
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn test_plain_entry_defaults() {
+        let mut entry = create_test_entry(1, "2024-03-01", "", "<p>Just text</p>");
+        entry.date_created = "unknown".to_string();
+        let files = export_entries_to_dayone(vec![entry]).unwrap();
+        let entry = &journal(&files)["entries"][0];
+
+        assert_eq!(entry["creationDate"], "2024-03-01T12:00:00Z");
+        assert_eq!(entry["timeZone"], "UTC");
+        assert_eq!(entry["text"], "Just text");
+        assert_eq!(entry["starred"], false);
+        assert!(entry.get("tags").is_none());
+        assert!(entry.get("photos").is_none());
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_shared_photo_is_written_once() {
+        let text = format!("<p>{}</p>", png_tag());
+        let files = export_entries_to_dayone(vec![
+            create_test_entry(1, "2024-01-15", "One", &text),
+            create_test_entry(2, "2024-01-16", "Two", &text),
+        ])
+        .unwrap();
+        let json = journal(&files);
+
+        assert_eq!(files.len(), 2);
+        assert_ne!(
+            json["entries"][0]["photos"][0]["identifier"],
+            json["entries"][1]["photos"][0]["identifier"]
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_through_zip_importer() {
+        let text = format!(
+            "<p>Packed <em>everything</em>.</p><p>{}</p><p>#travel</p>\
+             <p><em>Starred · Location: Porto · Weather: Sunny, 24 °C · Time zone: Europe/Lisbon</em></p>",
+            png_tag()
+        );
+        let original = create_test_entry(3, "2024-01-15", "Trip", &text);
+        let files = export_entries_to_dayone(vec![original.clone()]).unwrap();
+
+        let zip_files: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(path, bytes)| (path.as_str(), bytes.as_slice()))
+            .collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), build_zip(&zip_files)).unwrap();
+        let mut imported = Vec::new();
+        parse_dayone_zip(file.path(), &mut |entry| {
+            imported.push(entry);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].date, original.date);
+        assert_eq!(imported[0].title, original.title);
+        assert_eq!(imported[0].text, original.text);
+    }
+}
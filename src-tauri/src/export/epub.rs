@@ -0,0 +1,469 @@
+use super::markdown::extract_images_with;
+use super::zip::ZipWriter;
+use crate::db::queries::DiaryEntry;
+use crate::import::html::{resolve_entity, resolve_reference};
+use crate::import::markdown::escape_html;
+use chrono::{Datelike, NaiveDate, Utc};
+use log::warn;
+use md5::{Digest, Md5};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const STYLE_CSS: &str = r#"body { line-height: 1.5; }
+h1 { margin: 0 0 1.5em; text-align: center; }
+h2 { margin: 2em 0 0.5em; }
+h3 { margin: 1.2em 0 0.4em; font-style: italic; }
+img { max-width: 100%; height: auto; }
+blockquote { margin-left: 1em; padding-left: 1em; border-left: 2px solid #999; }
+nav ol { list-style: none; padding-left: 1em; }
+"#;
+
+/// Image formats every EPUB reader supports: extension and media type
+const CORE_IMAGE_TYPES: [(&str, &str); 5] = [
+    ("jpg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+];
+
+/// One chapter of the book: the entries of one month
+struct Chapter {
+    /// File name inside `OEBPS/`, e.g. `2024-01.xhtml`
+    file: String,
+    title: String,
+    /// Fragment ID and heading of every date in the month
+    dates: Vec<(String, String)>,
+}
+
+/// Exports diary entries as an EPUB 3 e-book
+///
+/// Layout:
+/// ```text
+/// mimetype                 application/epub+zip, stored first
+/// META-INF/container.xml   points to the package document
+/// OEBPS/content.opf        metadata, manifest and reading order
+/// OEBPS/nav.xhtml          table of contents: months and their dates
+/// OEBPS/2024-01.xhtml      one chapter per month
+/// OEBPS/images/image-1.png embedded images
+/// OEBPS/style.css
+/// ```
+///
+/// Each chapter has a heading per date, the entries' titles as subheadings
+/// and their HTML converted to XHTML. Embedded images are extracted like the
+/// Markdown export's assets; formats e-readers do not support are replaced
+/// by "[Image N]". Entries with an invalid date are left out.
+pub fn export_entries_to_epub(entries: Vec<DiaryEntry>) -> Result<Vec<u8>, String> {
+    let mut months: BTreeMap<(i32, u32), BTreeMap<NaiveDate, Vec<&DiaryEntry>>> = BTreeMap::new();
+    for entry in &entries {
+        match NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") {
+            Ok(date) => months
+                .entry((date.year(), date.month()))
+                .or_default()
+                .entry(date)
+                .or_default()
+                .push(entry),
+            Err(_) => warn!("Skipping entry with invalid date '{}'", entry.date),
+        }
+    }
+    let (Some(first), Some(last)) = (months.keys().next(), months.keys().next_back()) else {
+        return Err("There are no entries to export".to_string());
+    };
+    let title = if first.0 == last.0 {
+        format!("Journal {}", first.0)
+    } else {
+        format!("Journal {}–{}", first.0, last.0)
+    };
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut pages: Vec<(String, String)> = Vec::new();
+    let mut images: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut image_counter: usize = 0;
+    for days in months.values() {
+        let Some(first_day) = days.keys().next() else {
+            continue;
+        };
+        let mut chapter = Chapter {
+            file: first_day.format("%Y-%m.xhtml").to_string(),
+            title: first_day.format("%B %Y").to_string(),
+            dates: Vec::new(),
+        };
+        let mut body = format!(
+            "<section epub:type=\"chapter\">\n<h1>{}</h1>\n",
+            escape_html(&chapter.title)
+        );
+        for (date, day_entries) in days {
+            let id = date.format("d%Y-%m-%d").to_string();
+            let heading = date.format("%A, %-d %B %Y").to_string();
+            body.push_str(&format!("<section id=\"{}\">\n<h2>{}</h2>\n", id, heading));
+            for entry in day_entries {
+                if !entry.title.is_empty() {
+                    body.push_str(&format!("<h3>{}</h3>\n", escape_xml(&entry.title)));
+                }
+                let (html, assets) =
+                    extract_images_with(&entry.text, &mut image_counter, &|n, filename| {
+                        if media_type(filename).is_some() {
+                            format!("<img src=\"images/{}\" alt=\"Image {}\">", filename, n)
+                        } else {
+                            format!("[Image {}]", n)
+                        }
+                    });
+                body.push_str(&to_xhtml(&html));
+                body.push('\n');
+                images.extend(
+                    assets
+                        .into_iter()
+                        .filter(|(filename, _)| media_type(filename).is_some()),
+                );
+            }
+            body.push_str("</section>\n");
+            chapter.dates.push((id, heading));
+        }
+        body.push_str("</section>");
+        pages.push((chapter.file.clone(), xhtml_page(&chapter.title, &body)));
+        chapters.push(chapter);
+    }
+
+    let mut zip = ZipWriter::new();
+    // The mimetype must come first and uncompressed so readers can detect it
+    zip.add("mimetype", b"application/epub+zip", false)?;
+    zip.add("META-INF/container.xml", CONTAINER_XML.as_bytes(), true)?;
+    zip.add(
+        "OEBPS/content.opf",
+        package_document(&title, &entries, &chapters, &images).as_bytes(),
+        true,
+    )?;
+    zip.add(
+        "OEBPS/nav.xhtml",
+        navigation_document(&title, &chapters).as_bytes(),
+        true,
+    )?;
+    zip.add("OEBPS/style.css", STYLE_CSS.as_bytes(), true)?;
+    for (file, page) in &pages {
+        zip.add(&format!("OEBPS/{}", file), page.as_bytes(), true)?;
+    }
+    for (filename, bytes) in &images {
+        // Images are compressed already
+        zip.add(&format!("OEBPS/images/{}", filename), bytes, false)?;
+    }
+    zip.finish()
+}
+
+/// `content.opf`: metadata, every file of the book and the reading order
+fn package_document(
+    title: &str,
+    entries: &[DiaryEntry],
+    chapters: &[Chapter],
+    images: &[(String, Vec<u8>)],
+) -> String {
+    // Exporting the same entries again gives the same book ID
+    let mut hasher = Md5::new();
+    for entry in entries {
+        hasher.update(format!(
+            "{}:{}:{};",
+            entry.id, entry.date, entry.date_created
+        ));
+    }
+    let hash = hex::encode(hasher.finalize());
+    let uuid = format!(
+        "{}-{}-{}-{}-{}",
+        &hash[0..8],
+        &hash[8..12],
+        &hash[12..16],
+        &hash[16..20],
+        &hash[20..32]
+    );
+
+    let mut manifest = String::from(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n    \
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::from("    <itemref idref=\"nav\"/>\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.file
+        ));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    for (i, (filename, _)) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"image-{}\" href=\"images/{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            filename,
+            media_type(filename).unwrap_or("application/octet-stream")
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n  \
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n    \
+         <dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n    \
+         <dc:title>{}</dc:title>\n    \
+         <dc:language>en</dc:language>\n    \
+         <meta property=\"dcterms:modified\">{}</meta>\n  \
+         </metadata>\n  <manifest>\n{}  </manifest>\n  <spine>\n{}  </spine>\n</package>\n",
+        uuid,
+        escape_html(title),
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        manifest,
+        spine
+    )
+}
+
+/// `nav.xhtml`: the table of contents, with the dates under each month
+fn navigation_document(title: &str, chapters: &[Chapter]) -> String {
+    let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+    for chapter in chapters {
+        body.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>\n<ol>\n",
+            chapter.file,
+            escape_html(&chapter.title)
+        ));
+        for (id, heading) in &chapter.dates {
+            body.push_str(&format!(
+                "<li><a href=\"{}#{}\">{}</a></li>\n",
+                chapter.file, id, heading
+            ));
+        }
+        body.push_str("</ol>\n</li>\n");
+    }
+    body.push_str("</ol>\n</nav>");
+    xhtml_page(title, &body)
+}
+
+fn xhtml_page(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         xml:lang=\"en\" lang=\"en\">\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+/// Media type of an extracted image if EPUB readers support it
+fn media_type(filename: &str) -> Option<&'static str> {
+    let ext = filename.rsplit('.').next()?;
+    CORE_IMAGE_TYPES
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, media_type)| *media_type)
+}
+
+/// Rewrites the editor's HTML as well-formed XHTML: void elements are
+/// self-closed, unclosed elements are closed, and HTML entities become
+/// characters. Anything after markup that cannot be read is kept as escaped
+/// text.
+fn to_xhtml(html: &str) -> String {
+    let mut xml = Reader::from_str(html);
+    let config = xml.config_mut();
+    config.check_end_names = false;
+    config.allow_unmatched_ends = true;
+
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = Vec::new();
+    loop {
+        match xml.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = element_name(&e);
+                write_start(&mut out, &name, &e);
+                if is_void(&name) {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    open.push(name);
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let name = element_name(&e);
+                write_start(&mut out, &name, &e);
+                out.push_str("/>");
+            }
+            Ok(Event::End(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_ascii_lowercase();
+                // Close elements left open inside this one too
+                if let Some(position) = open.iter().rposition(|n| *n == name) {
+                    for name in open.drain(position..).rev() {
+                        out.push_str(&format!("</{}>", name));
+                    }
+                }
+            }
+            Ok(Event::Text(e)) => out.push_str(&escape_xml(&e.decode().unwrap_or_default())),
+            Ok(Event::CData(e)) => out.push_str(&escape_xml(&e.decode().unwrap_or_default())),
+            Ok(Event::GeneralRef(e)) => out.push_str(&escape_xml(&resolve_reference(&e))),
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                // Keep the rest of the entry as plain text rather than dropping it
+                warn!("Escaping the rest of malformed entry HTML: {}", e);
+                let mut start = (xml.error_position() as usize).min(html.len());
+                while !html.is_char_boundary(start) {
+                    start -= 1;
+                }
+                out.push_str(&escape_xml(&html[start..]));
+                break;
+            }
+        }
+    }
+    for name in open.into_iter().rev() {
+        out.push_str(&format!("</{}>", name));
+    }
+    out
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_ascii_lowercase()
+}
+
+/// Writes `<name attr="value"…` without the closing `>`
+fn write_start(out: &mut String, name: &str, e: &BytesStart) {
+    out.push('<');
+    out.push_str(name);
+    let mut written: Vec<String> = Vec::new();
+    for attribute in e.attributes().with_checks(false).flatten() {
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).to_ascii_lowercase();
+        let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'));
+        // XML allows each attribute only once
+        if !valid_key || written.contains(&key) {
+            continue;
+        }
+        if let Ok(value) = attribute.unescape_value_with(resolve_entity) {
+            out.push_str(&format!(" {}=\"{}\"", key, escape_xml(&value)));
+            written.push(key);
+        }
+    }
+}
+
+fn is_void(name: &str) -> bool {
+    matches!(
+        name,
+        "area" | "br" | "col" | "hr" | "img" | "input" | "meta" | "source" | "wbr"
+    )
+}
+
+/// Escapes text for XML, dropping the control characters XML does not allow
+fn escape_xml(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    escape_html(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::ZipReader;
+    use base64::{engine::general_purpose, Engine as _};
+    use std::io::Cursor;
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 1,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: 0,
+            date_created: "2024-01-01T00:00:00Z".to_string(),
+            date_updated: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    /// File names and contents of the EPUB's ZIP archive
+    fn unzip(epub: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+        let mut reader = ZipReader::new(Cursor::new(epub)).unwrap();
+        let names: Vec<String> = reader.entries().iter().map(|e| e.name.clone()).collect();
+        names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, reader.read(i, 1 << 20).unwrap()))
+            .collect()
+    }
+
+    fn file<'a>(files: &'a [(String, Vec<u8>)], name: &str) -> &'a str {
+        let (_, bytes) = files.iter().find(|(n, _)| n == name).unwrap();
+        std::str::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_book_layout() {
+        let png = general_purpose::STANDARD.encode(b"\x89PNG fake");
+        let entries = vec![
+            create_test_entry("2024-02-03", "", "<p>Snow</p>"),
+            create_test_entry(
+                "2024-01-15",
+                "Skiing & more",
+                &format!(
+                    "<p>Cold<br>day</p><img src=\"data:image/png;base64,{}\">\
+                     <img src=\"data:image/bmp;base64,{}\">",
+                    png, png
+                ),
+            ),
+            create_test_entry("bad-date", "Lost", "<p>Skipped</p>"),
+        ];
+        let files = unzip(export_entries_to_epub(entries).unwrap());
+        let names: Vec<&str> = files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/content.opf",
+                "OEBPS/nav.xhtml",
+                "OEBPS/style.css",
+                "OEBPS/2024-01.xhtml",
+                "OEBPS/2024-02.xhtml",
+                "OEBPS/images/image-1.png",
+            ]
+        );
+        assert_eq!(file(&files, "mimetype"), "application/epub+zip");
+
+        let opf = file(&files, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Journal 2024</dc:title>"));
+        assert!(opf.contains("href=\"images/image-1.png\" media-type=\"image/png\""));
+        assert!(opf.contains("<itemref idref=\"chapter-2\"/>"));
+
+        let nav = file(&files, "OEBPS/nav.xhtml");
+        assert!(nav.contains("<a href=\"2024-01.xhtml\">January 2024</a>"));
+        assert!(nav.contains("<a href=\"2024-01.xhtml#d2024-01-15\">Monday, 15 January 2024</a>"));
+
+        let january = file(&files, "OEBPS/2024-01.xhtml");
+        assert!(january.contains("<h1>January 2024</h1>"));
+        assert!(january.contains("<h2>Monday, 15 January 2024</h2>"));
+        assert!(january.contains("<h3>Skiing &amp; more</h3>"));
+        assert!(january.contains("<p>Cold<br/>day</p>"));
+        assert!(january.contains("<img src=\"images/image-1.png\" alt=\"Image 1\"/>"));
+        assert!(january.contains("[Image 2]"));
+        assert!(!january.contains("Skipped"));
+        assert_eq!(files[7].1, b"\x89PNG fake");
+    }
+
+    #[test]
+    fn test_to_xhtml() {
+        assert_eq!(
+            to_xhtml("<p>A&nbsp;<b>bold <i>x</b> y<hr><a href=\"?a=1&amp;b=2\" HREF=\"z\">l</a>"),
+            "<p>A\u{a0}<b>bold <i>x</i></b> y<hr/><a href=\"?a=1&amp;b=2\">l</a></p>"
+        );
+        assert_eq!(
+            to_xhtml("<p>kept</p><b unclosed & more"),
+            "<p>kept</p>&lt;b unclosed &amp; more"
+        );
+        assert!(export_entries_to_epub(vec![]).is_err());
+    }
+}
@@ -0,0 +1,453 @@
+use super::markdown::{mime_type_to_ext, replace_embedded_images};
+use crate::db::queries::{strip_html_tags, DiaryEntry};
+use crate::import::markdown::escape_html;
+use crate::import::metadata::unescape_html;
+use chrono::{Datelike, Month, NaiveDate};
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Folder that holds the images of all pages
+const ASSETS_DIR: &str = "assets";
+
+const STYLE_CSS: &str = r#"body { margin: 0; font-family: system-ui, sans-serif; line-height: 1.6; color: #222; background: #fafafa; }
+header { display: flex; gap: 1rem; align-items: center; justify-content: space-between; padding: 0.75rem 1.5rem; background: #fff; border-bottom: 1px solid #ddd; }
+header a { font-weight: bold; color: inherit; text-decoration: none; }
+main { max-width: 48rem; margin: 0 auto; padding: 1.5rem; }
+article { background: #fff; border: 1px solid #e5e5e5; border-radius: 6px; padding: 1rem 1.5rem; margin-bottom: 1.5rem; }
+article img { max-width: 100%; height: auto; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1rem; color: #555; }
+nav.pager { display: flex; justify-content: space-between; margin: 1rem 0; }
+.calendars { display: flex; flex-wrap: wrap; gap: 1.5rem; }
+table.calendar { border-collapse: collapse; }
+table.calendar caption { font-weight: bold; padding-bottom: 0.25rem; }
+table.calendar th, table.calendar td { width: 2.2rem; height: 2rem; text-align: center; }
+table.calendar td a { display: block; border-radius: 50%; background: #dbe8ff; text-decoration: none; }
+.results li { margin-bottom: 0.75rem; }
+"#;
+
+const SEARCH_JS: &str = r#"(function () {
+  var query = new URLSearchParams(location.search).get("q") || "";
+  var input = document.getElementById("search-input");
+  var list = document.getElementById("search-results");
+  input.value = query;
+  var terms = query.toLowerCase().split(/\s+/).filter(Boolean);
+  if (terms.length === 0) return;
+  var matches = (window.SEARCH_INDEX || []).filter(function (entry) {
+    var haystack = (entry.title + " " + entry.text).toLowerCase();
+    return terms.every(function (term) { return haystack.indexOf(term) !== -1; });
+  });
+  document.getElementById("search-summary").textContent =
+    matches.length + (matches.length === 1 ? " entry" : " entries") + " found";
+  matches.forEach(function (entry) {
+    var item = document.createElement("li");
+    var link = document.createElement("a");
+    link.href = entry.url;
+    link.textContent = entry.date + (entry.title ? " – " + entry.title : "");
+    var start = Math.max(0, entry.text.toLowerCase().indexOf(terms[0]) - 60);
+    var snippet = document.createElement("div");
+    snippet.textContent = (start > 0 ? "…" : "") + entry.text.substr(start, 160) + "…";
+    item.appendChild(link);
+    item.appendChild(snippet);
+    list.appendChild(item);
+  });
+})();
+"#;
+
+/// One entry of the search index
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    date: &'a str,
+    title: &'a str,
+    /// Day page, relative to the site root
+    url: String,
+    /// Entry text without markup
+    text: String,
+}
+
+/// Exports diary entries as a static website that can be opened from disk
+///
+/// Layout:
+/// ```text
+/// index.html              calendar of the latest month and links to all years
+/// 2024/index.html         calendars of the months with entries
+/// 2024/01/index.html      calendar and list of the days with entries
+/// 2024/01/15.html         all entries of the day
+/// search.html             client-side search
+/// search-index.json       date, title, page and plain text of every entry
+/// search-index.js         the same index, loadable from `file://` pages
+/// style.css
+/// assets/image-1.png      embedded images
+/// ```
+///
+/// Entry HTML is used as stored, except that embedded images are written to
+/// `assets/` and linked. Calendars start on Monday. Entries with an invalid
+/// date are left out.
+///
+/// Returns `(relative path, bytes)` pairs for every file to write.
+pub fn export_entries_to_html_site(entries: Vec<DiaryEntry>) -> Vec<(String, Vec<u8>)> {
+    let mut days: BTreeMap<NaiveDate, Vec<&DiaryEntry>> = BTreeMap::new();
+    for entry in &entries {
+        match NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") {
+            Ok(date) => days.entry(date).or_default().push(entry),
+            Err(_) => warn!("Skipping entry with invalid date '{}'", entry.date),
+        }
+    }
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut image_counter: usize = 0;
+    let mut search_index: Vec<SearchEntry> = Vec::new();
+
+    let dates: Vec<NaiveDate> = days.keys().copied().collect();
+    for (i, (date, day_entries)) in days.iter().enumerate() {
+        let mut body = format!(
+            "<p><a href=\"index.html\">{}</a></p><h1>{}</h1>",
+            month_title(*date),
+            date.format("%A, %-d %B %Y")
+        );
+        for entry in day_entries {
+            let html = replace_embedded_images(&entry.text, &mut |mime, bytes| {
+                image_counter += 1;
+                let name = format!("image-{}.{}", image_counter, mime_type_to_ext(mime));
+                let tag = format!("<img src=\"../../{}/{}\" alt=\"\">", ASSETS_DIR, name);
+                files.push((format!("{}/{}", ASSETS_DIR, name), bytes));
+                tag
+            });
+            body.push_str("<article>");
+            if !entry.title.is_empty() {
+                body.push_str(&format!("<h2>{}</h2>", escape_html(&entry.title)));
+            }
+            body.push_str(&html);
+            body.push_str("</article>");
+
+            search_index.push(SearchEntry {
+                date: &entry.date,
+                title: &entry.title,
+                url: day_path(*date),
+                text: plain_text(&entry.text),
+            });
+        }
+
+        let previous = i.checked_sub(1).map(|p| dates[p]);
+        let next = dates.get(i + 1).copied();
+        body.push_str("<nav class=\"pager\"><span>");
+        if let Some(previous) = previous {
+            body.push_str(&format!(
+                "<a href=\"../../{}\">← {}</a>",
+                day_path(previous),
+                previous.format("%-d %B %Y")
+            ));
+        }
+        body.push_str("</span><span>");
+        if let Some(next) = next {
+            body.push_str(&format!(
+                "<a href=\"../../{}\">{} →</a>",
+                day_path(next),
+                next.format("%-d %B %Y")
+            ));
+        }
+        body.push_str("</span></nav>");
+
+        let title = date.format("%-d %B %Y").to_string();
+        files.push((day_path(*date), page(&title, "../../", &body)));
+    }
+
+    // Month and year archives
+    let mut years: BTreeMap<i32, BTreeMap<u32, Vec<NaiveDate>>> = BTreeMap::new();
+    for date in &dates {
+        years
+            .entry(date.year())
+            .or_default()
+            .entry(date.month())
+            .or_default()
+            .push(*date);
+    }
+    for (year, months) in &years {
+        let mut year_body = format!(
+            "<p><a href=\"../index.html\">All years</a></p><h1>{}</h1><div class=\"calendars\">",
+            year
+        );
+        for (month, month_dates) in months {
+            year_body.push_str(&calendar(*year, *month, &days, "../"));
+
+            let first = month_dates[0];
+            let mut month_body = format!(
+                "<p><a href=\"../index.html\">{}</a></p><h1>{}</h1>{}<ul>",
+                year,
+                month_title(first),
+                calendar(*year, *month, &days, "../../")
+            );
+            for date in month_dates {
+                let titles: Vec<String> = days[date]
+                    .iter()
+                    .filter(|entry| !entry.title.is_empty())
+                    .map(|entry| escape_html(&entry.title))
+                    .collect();
+                month_body.push_str(&format!(
+                    "<li><a href=\"{:02}.html\">{}</a>",
+                    date.day(),
+                    date.format("%A, %-d %B")
+                ));
+                if !titles.is_empty() {
+                    month_body.push_str(&format!(": {}", titles.join(", ")));
+                }
+                month_body.push_str("</li>");
+            }
+            month_body.push_str("</ul>");
+            files.push((
+                format!("{}/{:02}/index.html", year, month),
+                page(&month_title(first), "../../", &month_body),
+            ));
+        }
+        year_body.push_str("</div>");
+        files.push((
+            format!("{}/index.html", year),
+            page(&year.to_string(), "../", &year_body),
+        ));
+    }
+
+    // Index
+    let mut index_body = format!("<h1>Journal</h1><p>{} entries", search_index.len());
+    if let (Some(first), Some(last)) = (dates.first(), dates.last()) {
+        index_body.push_str(&format!(
+            " from {} to {}",
+            first.format("%-d %B %Y"),
+            last.format("%-d %B %Y")
+        ));
+    }
+    index_body.push_str(".</p>");
+    if let Some(last) = dates.last() {
+        index_body.push_str(&calendar(last.year(), last.month(), &days, ""));
+    }
+    index_body.push_str("<h2>Archive</h2><ul>");
+    for (year, months) in years.iter().rev() {
+        let count: usize = months.values().flatten().map(|date| days[date].len()).sum();
+        index_body.push_str(&format!(
+            "<li><a href=\"{0}/index.html\">{0}</a> ({1} entries)<ul>",
+            year, count
+        ));
+        for (month, month_dates) in months {
+            index_body.push_str(&format!(
+                "<li><a href=\"{}/{:02}/index.html\">{}</a></li>",
+                year,
+                month,
+                month_title(month_dates[0])
+            ));
+        }
+        index_body.push_str("</ul></li>");
+    }
+    index_body.push_str("</ul>");
+    files.push(("index.html".to_string(), page("Journal", "", &index_body)));
+
+    // Search
+    let search_body = "<h1>Search</h1>\
+        <form action=\"search.html\"><input id=\"search-input\" type=\"search\" name=\"q\">\
+        <button type=\"submit\">Search</button></form>\
+        <p id=\"search-summary\"></p><ul id=\"search-results\" class=\"results\"></ul>\
+        <script src=\"search-index.js\"></script><script src=\"search.js\"></script>";
+    files.push(("search.html".to_string(), page("Search", "", search_body)));
+    let json = serde_json::to_string(&search_index).unwrap_or_else(|_| "[]".to_string());
+    files.push((
+        "search-index.js".to_string(),
+        format!("window.SEARCH_INDEX = {};\n", json).into_bytes(),
+    ));
+    files.push(("search-index.json".to_string(), json.into_bytes()));
+    files.push(("search.js".to_string(), SEARCH_JS.as_bytes().to_vec()));
+    files.push(("style.css".to_string(), STYLE_CSS.as_bytes().to_vec()));
+
+    files
+}
+
+/// Path of a day page, relative to the site root
+fn day_path(date: NaiveDate) -> String {
+    date.format("%Y/%m/%d.html").to_string()
+}
+
+/// e.g. "January 2024"
+fn month_title(date: NaiveDate) -> String {
+    date.format("%B %Y").to_string()
+}
+
+/// Wraps `body` in a full page. `root` is the relative path to the site root.
+fn page(title: &str, root: &str, body: &str) -> Vec<u8> {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n\
+         </head>\n<body>\n<header><a href=\"{root}index.html\">Journal</a>\
+         <form action=\"{root}search.html\"><input type=\"search\" name=\"q\" \
+         placeholder=\"Search\"></form></header>\n<main>\n{body}\n</main>\n</body>\n</html>\n",
+        title = escape_html(title),
+        root = root,
+        body = body
+    )
+    .into_bytes()
+}
+
+/// A month calendar table whose days with entries link to their pages.
+/// `root` is the relative path from the page to the site root.
+fn calendar(
+    year: i32,
+    month: u32,
+    days: &BTreeMap<NaiveDate, Vec<&DiaryEntry>>,
+    root: &str,
+) -> String {
+    let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+        return String::new();
+    };
+    let name = Month::try_from(month as u8)
+        .map(|m| m.name())
+        .unwrap_or_default();
+    let mut html = format!(
+        "<table class=\"calendar\"><caption><a href=\"{}{}/{:02}/index.html\">{} {}</a></caption>\
+         <tr><th>Mo</th><th>Tu</th><th>We</th><th>Th</th><th>Fr</th><th>Sa</th><th>Su</th></tr><tr>",
+        root, year, month, name, year
+    );
+    let offset = first.weekday().num_days_from_monday();
+    for _ in 0..offset {
+        html.push_str("<td></td>");
+    }
+    let mut column = offset;
+    for date in first.iter_days().take_while(|d| d.month() == month) {
+        if column == 7 {
+            html.push_str("</tr><tr>");
+            column = 0;
+        }
+        if days.contains_key(&date) {
+            html.push_str(&format!(
+                "<td><a href=\"{}{}\">{}</a></td>",
+                root,
+                day_path(date),
+                date.day()
+            ));
+        } else {
+            html.push_str(&format!("<td>{}</td>", date.day()));
+        }
+        column += 1;
+    }
+    html.push_str("</tr></table>");
+    html
+}
+
+/// Entry HTML as plain text for the search index
+fn plain_text(html: &str) -> String {
+    let text = unescape_html(&strip_html_tags(html).replace("&nbsp;", " "));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 1,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: "2024-01-15T08:00:00Z".to_string(),
+            date_updated: "2024-01-15T08:00:00Z".to_string(),
+        }
+    }
+
+    fn file(files: &[(String, Vec<u8>)], name: &str) -> String {
+        let (_, bytes) = files
+            .iter()
+            .find(|(path, _)| path == name)
+            .unwrap_or_else(|| panic!("missing {}", name));
+        String::from_utf8(bytes.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_site_layout() {
+        let files = export_entries_to_html_site(vec![
+            create_test_entry("2024-01-15", "Morning", "<p>Coffee</p>"),
+            create_test_entry("2024-01-15", "Evening", "<p>Tea</p>"),
+            create_test_entry("2023-12-31", "", "<p>Fireworks</p>"),
+            create_test_entry("someday", "Lost", "<p>Nowhere</p>"),
+        ]);
+        let mut paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        paths.sort_unstable();
+
+        assert_eq!(
+            paths,
+            vec![
+                "2023/12/31.html",
+                "2023/12/index.html",
+                "2023/index.html",
+                "2024/01/15.html",
+                "2024/01/index.html",
+                "2024/index.html",
+                "index.html",
+                "search-index.js",
+                "search-index.json",
+                "search.html",
+                "search.js",
+                "style.css",
+            ]
+        );
+
+        let day = file(&files, "2024/01/15.html");
+        assert!(day.contains("<h1>Monday, 15 January 2024</h1>"));
+        assert!(day.contains("<article><h2>Morning</h2><p>Coffee</p></article>"));
+        assert!(day.contains("<article><h2>Evening</h2><p>Tea</p></article>"));
+        assert!(day.contains("<a href=\"../../2023/12/31.html\">← 31 December 2023</a>"));
+        assert!(day.contains("href=\"../../style.css\""));
+
+        let month = file(&files, "2024/01/index.html");
+        assert!(month.contains("<td><a href=\"../../2024/01/15.html\">15</a></td>"));
+        assert!(
+            month.contains("<li><a href=\"15.html\">Monday, 15 January</a>: Morning, Evening</li>")
+        );
+        // January 2024 starts on a Monday
+        assert!(month.contains("</tr><tr><td>1</td>"));
+
+        let index = file(&files, "index.html");
+        assert!(index.contains("<p>3 entries from 31 December 2023 to 15 January 2024.</p>"));
+        assert!(
+            index.contains("<caption><a href=\"2024/01/index.html\">January 2024</a></caption>")
+        );
+        assert!(index.contains("<a href=\"2023/12/index.html\">December 2023</a>"));
+    }
+
+    #[test]
+    fn test_images_are_written_to_assets() {
+        let files = export_entries_to_html_site(vec![create_test_entry(
+            "2024-01-15",
+            "Photo",
+            "<p>Look</p><img src=\"data:image/png;base64,iVBORw==\" alt=\"\">",
+        )]);
+
+        let (_, image) = files
+            .iter()
+            .find(|(path, _)| path == "assets/image-1.png")
+            .unwrap();
+        assert_eq!(image, b"\x89PNG");
+        assert!(file(&files, "2024/01/15.html")
+            .contains("<p>Look</p><img src=\"../../assets/image-1.png\" alt=\"\">"));
+    }
+
+    #[test]
+    fn test_search_index() {
+        let files = export_entries_to_html_site(vec![create_test_entry(
+            "2024-01-15",
+            "Tom & Jerry <3",
+            "<p>Cats &amp; <strong>mice</strong></p><p>Second</p>",
+        )]);
+        let index: serde_json::Value =
+            serde_json::from_str(&file(&files, "search-index.json")).unwrap();
+
+        assert_eq!(
+            index,
+            serde_json::json!([{
+                "date": "2024-01-15",
+                "title": "Tom & Jerry <3",
+                "url": "2024/01/15.html",
+                "text": "Cats & mice Second"
+            }])
+        );
+        assert!(file(&files, "search-index.js").starts_with("window.SEARCH_INDEX = [{"));
+        assert!(file(&files, "2024/01/15.html").contains("<h2>Tom &amp; Jerry &lt;3</h2>"));
+    }
+}
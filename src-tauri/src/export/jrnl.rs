@@ -0,0 +1,106 @@
+use super::markdown::html_to_markdown;
+use crate::db::queries::DiaryEntry;
+use chrono::DateTime;
+
+/// Exports diary entries as a jrnl plain-text journal
+///
+/// Format:
+/// ```text
+/// [2024-01-15 09:30] My Title
+/// Entry content here...
+///
+/// [2024-01-16 14:00] Next Title
+/// ```
+///
+/// The time comes from the entry's creation time (00:00 if unknown). HTML is
+/// converted to Markdown and images are left out. Body lines that would
+/// read as the start of a new entry are indented by one space.
+pub fn export_entries_to_jrnl_txt(entries: Vec<DiaryEntry>) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let time = DateTime::parse_from_rfc3339(&entry.date_created)
+                .map(|dt| dt.format("%H:%M").to_string())
+                .unwrap_or_else(|_| "00:00".to_string());
+            let title = entry.title.split_whitespace().collect::<Vec<_>>().join(" ");
+            let mut text = format!("[{} {}] {}", entry.date, time, title)
+                .trim_end()
+                .to_string();
+            text.push('\n');
+
+            for line in html_to_markdown(&entry.text).trim().lines() {
+                if line.starts_with('[') {
+                    text.push(' ');
+                }
+                text.push_str(line);
+                text.push('\n');
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::jrnl::parse_jrnl_txt;
+
+    fn create_test_entry(date: &str, title: &str, text: &str, created: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 1,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: created.to_string(),
+            date_updated: created.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_export_jrnl_txt() {
+        let output = export_entries_to_jrnl_txt(vec![
+            create_test_entry(
+                "2024-01-15",
+                "Morning",
+                "<p>Coffee <strong>first</strong></p><p>[2024-01-16 10:00] not an entry</p>",
+                "2024-01-15T09:30:00Z",
+            ),
+            create_test_entry("2024-01-16", "", "", "not a timestamp"),
+        ]);
+
+        assert_eq!(
+            output,
+            "[2024-01-15 09:30] Morning\nCoffee **first**\n\n [2024-01-16 10:00] not an entry\n\
+             \n[2024-01-16 00:00]\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_through_importer() {
+        let entries = vec![
+            create_test_entry(
+                "2024-01-15",
+                "Trip",
+                "<p>Packed <em>everything</em>.</p><p>Left at noon.</p>",
+                "2024-01-15T08:05:00Z",
+            ),
+            create_test_entry(
+                "2024-01-15",
+                "Evening",
+                "<p>Arrived</p>",
+                "2024-01-15T21:40:00Z",
+            ),
+        ];
+        let imported = parse_jrnl_txt(&export_entries_to_jrnl_txt(entries.clone())).unwrap();
+
+        assert_eq!(imported.len(), 2);
+        for (original, imported) in entries.iter().zip(&imported) {
+            assert_eq!(imported.date, original.date);
+            assert_eq!(imported.title, original.title);
+            assert_eq!(imported.text, original.text);
+            assert_eq!(imported.date_created, original.date_created);
+        }
+    }
+}
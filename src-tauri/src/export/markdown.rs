@@ -440,8 +440,49 @@ fn extract_and_replace_with_assets(
     html: &str,
     counter: &mut usize,
 ) -> (String, Vec<(String, Vec<u8>)>) {
-    let mut result = String::new();
+    extract_images_to(html, counter, "assets")
+}
+
+/// Like `extract_and_replace_with_assets`, but links images into `dir`
+/// instead of `assets/`.
+pub(crate) fn extract_images_to(
+    html: &str,
+    counter: &mut usize,
+    dir: &str,
+) -> (String, Vec<(String, Vec<u8>)>) {
+    extract_images_with(html, counter, &|n, filename| {
+        format!("![Image {}]({}/{})", n, dir, filename)
+    })
+}
+
+/// Extracts embedded images like `extract_and_replace_with_assets`, replacing
+/// each with `reference(N, filename)` instead of a Markdown image.
+pub(crate) fn extract_images_with(
+    html: &str,
+    counter: &mut usize,
+    reference: &dyn Fn(usize, &str) -> String,
+) -> (String, Vec<(String, Vec<u8>)>) {
     let mut assets: Vec<(String, Vec<u8>)> = Vec::new();
+    let result = replace_embedded_images(html, &mut |mime, bytes| {
+        *counter += 1;
+        let ext = mime_type_to_ext(mime);
+        let filename = format!("image-{}.{}", counter, ext);
+        let text = reference(*counter, &filename);
+        assets.push((filename, bytes));
+        text
+    });
+    (result, assets)
+}
+
+/// Calls `replace` with the MIME type and decoded bytes of every
+/// `<img src="data:image/…;base64,…">` tag in `html`, substituting the tag
+/// with the returned text.
+/// Non-data-URI `<img>` tags and images with corrupted base64 are dropped.
+pub(crate) fn replace_embedded_images(
+    html: &str,
+    replace: &mut dyn FnMut(&str, Vec<u8>) -> String,
+) -> String {
+    let mut result = String::new();
     let mut remaining = html;
 
     while let Some(img_start) = remaining.find("<img") {
@@ -465,17 +506,9 @@ fn extract_and_replace_with_assets(
                 remaining = &remaining[end..];
 
                 if let Some((mime, b64_data)) = extract_src_data_uri(tag) {
-                    match general_purpose::STANDARD.decode(&b64_data) {
-                        Ok(bytes) => {
-                            *counter += 1;
-                            let ext = mime_type_to_ext(&mime);
-                            let filename = format!("image-{}.{}", counter, ext);
-                            result.push_str(&format!("![Image {}](assets/{})", counter, filename));
-                            assets.push((filename, bytes));
-                        }
-                        Err(_) => {
-                            // Corrupted base64 — drop the image silently
-                        }
+                    // Corrupted base64 — drop the image silently
+                    if let Ok(bytes) = general_purpose::STANDARD.decode(&b64_data) {
+                        result.push_str(&replace(&mime, bytes));
                     }
                 }
                 // Non-data-URI <img> tags (e.g. http://) are dropped
@@ -488,7 +521,7 @@ fn extract_and_replace_with_assets(
         }
     }
     result.push_str(remaining);
-    (result, assets)
+    result
 }
 
 /// Scans HTML for `<img src="data:image/…;base64,…">` tags and replaces each
@@ -580,7 +613,7 @@ fn extract_src_data_uri(tag: &str) -> Option<(String, String)> {
 }
 
 /// Maps a MIME type to a file extension for exported image assets.
-fn mime_type_to_ext(mime: &str) -> &'static str {
+pub(crate) fn mime_type_to_ext(mime: &str) -> &'static str {
     match mime {
         "image/jpeg" | "image/jpg" => "jpg",
         "image/png" => "png",
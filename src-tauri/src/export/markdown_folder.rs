@@ -0,0 +1,183 @@
+use super::markdown::{extract_images_to, html_to_markdown};
+use crate::db::queries::DiaryEntry;
+use crate::import::metadata::find_hashtags;
+
+/// Folder that holds the images of all notes
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Exports diary entries as a folder of Markdown notes, one per entry
+///
+/// Notes are named like Obsidian daily notes: `YYYY-MM-DD.md`. On days with
+/// several entries, the first keeps that name and the others get a number,
+/// `YYYY-MM-DD-2.md`, `YYYY-MM-DD-3.md`, ...
+///
+/// Each note starts with YAML front matter:
+/// ```markdown
+/// ---
+/// title: "My Title"
+/// date: 2024-01-15
+/// tags: ["travel", "family"]
+/// created: "2024-01-15T08:00:00Z"
+/// updated: "2024-01-15T09:30:00Z"
+/// word_count: 42
+/// ---
+/// Entry content here...
+/// ```
+///
+/// Tags are the `#hashtags` found in the entry. Embedded images are written
+/// to a shared `attachments/` folder and linked from the notes.
+///
+/// Returns `(relative path, bytes)` pairs for every file to write.
+pub fn export_entries_to_markdown_folder(entries: Vec<DiaryEntry>) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut image_counter: usize = 0;
+    let mut previous_date = String::new();
+    let mut index_in_day = 0;
+
+    for entry in &entries {
+        if entry.date == previous_date {
+            index_in_day += 1;
+        } else {
+            previous_date = entry.date.clone();
+            index_in_day = 1;
+        }
+        let file_name = if index_in_day == 1 {
+            format!("{}.md", entry.date)
+        } else {
+            format!("{}-{}.md", entry.date, index_in_day)
+        };
+
+        let (processed_html, images) =
+            extract_images_to(&entry.text, &mut image_counter, ATTACHMENTS_DIR);
+        let body = html_to_markdown(&processed_html);
+
+        let mut note = front_matter(entry, &find_hashtags(&body));
+        if !body.is_empty() {
+            note.push_str(&body);
+            note.push('\n');
+        }
+
+        files.push((file_name, note.into_bytes()));
+        files.extend(
+            images
+                .into_iter()
+                .map(|(name, bytes)| (format!("{}/{}", ATTACHMENTS_DIR, name), bytes)),
+        );
+    }
+
+    files
+}
+
+fn front_matter(entry: &DiaryEntry, tags: &[String]) -> String {
+    let mut yaml = String::from("---\n");
+    yaml.push_str(&format!("title: {}\n", yaml_string(&entry.title)));
+    yaml.push_str(&format!("date: {}\n", entry.date));
+    let tags: Vec<String> = tags.iter().map(|tag| yaml_string(tag)).collect();
+    yaml.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    yaml.push_str(&format!("created: {}\n", yaml_string(&entry.date_created)));
+    yaml.push_str(&format!("updated: {}\n", yaml_string(&entry.date_updated)));
+    yaml.push_str(&format!("word_count: {}\n", entry.word_count));
+    yaml.push_str("---\n");
+    yaml
+}
+
+/// Double-quoted YAML scalar
+fn yaml_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 1,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: "2024-01-15T08:00:00Z".to_string(),
+            date_updated: "2024-01-15T09:30:00Z".to_string(),
+        }
+    }
+
+    fn note(files: &[(String, Vec<u8>)], name: &str) -> String {
+        let (_, bytes) = files.iter().find(|(path, _)| path == name).unwrap();
+        String::from_utf8(bytes.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_one_note_per_entry_with_front_matter() {
+        let files = export_entries_to_markdown_folder(vec![
+            create_test_entry(
+                "2024-01-15",
+                "Say \"hi\"",
+                "<p>Trip with <strong>friends</strong> #travel</p>",
+            ),
+            create_test_entry("2024-01-15", "", "<p>Second</p>"),
+            create_test_entry("2024-01-16", "Next", ""),
+        ]);
+
+        let names: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["2024-01-15.md", "2024-01-15-2.md", "2024-01-16.md"]
+        );
+        assert_eq!(
+            note(&files, "2024-01-15.md"),
+            "---\ntitle: \"Say \\\"hi\\\"\"\ndate: 2024-01-15\ntags: [\"travel\"]\n\
+             created: \"2024-01-15T08:00:00Z\"\nupdated: \"2024-01-15T09:30:00Z\"\n\
+             word_count: 4\n---\nTrip with **friends** #travel\n"
+        );
+        assert!(note(&files, "2024-01-16.md").ends_with("word_count: 0\n---\n"));
+    }
+
+    #[test]
+    fn test_images_go_to_attachments() {
+        let html = "<p>Look</p><img src=\"data:image/png;base64,iVBORw0KGgo=\">";
+        let files = export_entries_to_markdown_folder(vec![
+            create_test_entry("2024-01-15", "A", html),
+            create_test_entry("2024-01-16", "B", html),
+        ]);
+
+        assert!(note(&files, "2024-01-15.md").contains("![Image 1](attachments/image-1.png)"));
+        assert!(note(&files, "2024-01-16.md").contains("![Image 2](attachments/image-2.png)"));
+        let image = files
+            .iter()
+            .find(|(path, _)| path == "attachments/image-2.png");
+        assert_eq!(image.unwrap().1[..4], [0x89, b'P', b'N', b'G']);
+    }
+
+    #[test]
+    fn test_roundtrip_through_folder_importer() {
+        let html = "<p>Morning swim #sea</p><img src=\"data:image/png;base64,iVBORw0KGgo=\">";
+        let files =
+            export_entries_to_markdown_folder(vec![create_test_entry("2024-01-15", "Beach", html)]);
+        let dir = tempfile::tempdir().unwrap();
+        for (path, bytes) in &files {
+            let target = dir.path().join(path);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::write(target, bytes).unwrap();
+        }
+
+        let mut imported = Vec::new();
+        crate::import::markdown_folder::parse_markdown_folder(dir.path(), &mut |entry| {
+            imported.push(entry);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].title, "Beach");
+        assert_eq!(imported[0].date_updated, "2024-01-15T09:30:00+00:00");
+        assert_eq!(
+            imported[0].text,
+            "<p>Morning swim #sea</p><p><img src=\"data:image/png;base64,iVBORw0KGgo=\" alt=\"Image 1\"></p>"
+        );
+    }
+}
@@ -1,2 +1,10 @@
+pub mod archive;
+pub mod dayone;
+pub mod epub;
+pub mod html_site;
+pub mod jrnl;
 pub mod json;
 pub mod markdown;
+pub mod markdown_folder;
+pub mod pdf;
+pub mod zip;
@@ -0,0 +1,518 @@
+//! Fonts for PDF export: the standard PDF fonts, which every viewer has, or
+//! a TrueType font file embedded in the document.
+//!
+//! Standard fonts only cover Western European text (WinAnsi encoding);
+//! other characters are shown as `?`. An embedded TrueType font covers
+//! whatever the font covers. Its bold and italic styles are synthesized.
+
+use super::writer::{deflate, ObjectId, PdfWriter};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use ttf_parser::{name_id, Face, GlyphId, Permissions};
+
+/// Font families every PDF viewer provides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandardFont {
+    #[default]
+    Helvetica,
+    Times,
+    Courier,
+}
+
+/// Text style, each written with its own font resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    /// Code, always in Courier
+    Mono,
+}
+
+impl FontStyle {
+    pub fn new(bold: bool, italic: bool, mono: bool) -> Self {
+        match (mono, bold, italic) {
+            (true, _, _) => FontStyle::Mono,
+            (false, true, true) => FontStyle::BoldItalic,
+            (false, true, false) => FontStyle::Bold,
+            (false, false, true) => FontStyle::Italic,
+            (false, false, false) => FontStyle::Regular,
+        }
+    }
+
+    pub fn is_bold(self) -> bool {
+        matches!(self, FontStyle::Bold | FontStyle::BoldItalic)
+    }
+
+    pub fn is_italic(self) -> bool {
+        matches!(self, FontStyle::Italic | FontStyle::BoldItalic)
+    }
+}
+
+pub enum Fonts {
+    Standard(StandardFont),
+    TrueType(Box<TrueTypeFont>),
+}
+
+impl Fonts {
+    /// Name of the font resource used for `style` in content streams
+    pub fn resource(&self, style: FontStyle) -> &'static str {
+        match (self, style) {
+            (_, FontStyle::Mono) => "F5",
+            (Fonts::TrueType(_), _) => "F1",
+            (Fonts::Standard(_), FontStyle::Regular) => "F1",
+            (Fonts::Standard(_), FontStyle::Bold) => "F2",
+            (Fonts::Standard(_), FontStyle::Italic) => "F3",
+            (Fonts::Standard(_), FontStyle::BoldItalic) => "F4",
+        }
+    }
+
+    /// Whether bold and italic have to be drawn by thickening and slanting
+    /// the regular font
+    pub fn synthesizes_styles(&self) -> bool {
+        matches!(self, Fonts::TrueType(_))
+    }
+
+    /// Width of `text` in points
+    pub fn width(&mut self, text: &str, style: FontStyle, size: f32) -> f32 {
+        let units: u32 = match (self, style) {
+            (Fonts::TrueType(font), style) if style != FontStyle::Mono => {
+                text.chars().map(|c| font.glyph(c).1 as u32).sum()
+            }
+            (Fonts::Standard(family), style) if style != FontStyle::Mono => text
+                .chars()
+                .map(|c| standard_width(*family, style.is_bold(), c) as u32)
+                .sum(),
+            _ => text.chars().count() as u32 * 600,
+        };
+        units as f32 * size / 1000.0
+    }
+
+    /// `text` as a string operand for the `Tj` operator
+    pub fn encode(&mut self, text: &str, style: FontStyle) -> String {
+        match (self, style) {
+            (Fonts::TrueType(font), style) if style != FontStyle::Mono => {
+                let mut hex = String::from("<");
+                for c in text.chars() {
+                    let (glyph, width) = font.glyph(c);
+                    font.used.entry(glyph).or_insert((width, c));
+                    hex.push_str(&format!("{:04X}", glyph));
+                }
+                hex.push('>');
+                hex
+            }
+            _ => {
+                let mut literal = String::from("(");
+                for c in text.chars() {
+                    match win_ansi(c).unwrap_or(b'?') {
+                        b'(' => literal.push_str("\\("),
+                        b')' => literal.push_str("\\)"),
+                        b'\\' => literal.push_str("\\\\"),
+                        byte @ 32..=126 => literal.push(byte as char),
+                        byte => literal.push_str(&format!("\\{:03o}", byte)),
+                    }
+                }
+                literal.push(')');
+                literal
+            }
+        }
+    }
+
+    /// Writes the font objects and returns the `/Font` resource dictionary
+    pub fn write(&self, pdf: &mut PdfWriter) -> String {
+        let mut resources = Vec::new();
+        match self {
+            Fonts::Standard(family) => {
+                for (resource, bold, italic) in [
+                    ("F1", false, false),
+                    ("F2", true, false),
+                    ("F3", false, true),
+                    ("F4", true, true),
+                ] {
+                    let id = write_standard_font(pdf, standard_font_name(*family, bold, italic));
+                    resources.push(format!("/{} {} 0 R", resource, id));
+                }
+            }
+            Fonts::TrueType(font) => {
+                let id = font.write(pdf);
+                resources.push(format!("/F1 {} 0 R", id));
+            }
+        }
+        let courier = write_standard_font(pdf, "Courier");
+        resources.push(format!("/F5 {} 0 R", courier));
+        format!("<< {} >>", resources.join(" "))
+    }
+}
+
+fn write_standard_font(pdf: &mut PdfWriter, name: &str) -> ObjectId {
+    let id = pdf.reserve();
+    pdf.object(
+        id,
+        &format!(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+            name
+        ),
+    );
+    id
+}
+
+fn standard_font_name(family: StandardFont, bold: bool, italic: bool) -> &'static str {
+    match (family, bold, italic) {
+        (StandardFont::Helvetica, false, false) => "Helvetica",
+        (StandardFont::Helvetica, true, false) => "Helvetica-Bold",
+        (StandardFont::Helvetica, false, true) => "Helvetica-Oblique",
+        (StandardFont::Helvetica, true, true) => "Helvetica-BoldOblique",
+        (StandardFont::Times, false, false) => "Times-Roman",
+        (StandardFont::Times, true, false) => "Times-Bold",
+        (StandardFont::Times, false, true) => "Times-Italic",
+        (StandardFont::Times, true, true) => "Times-BoldItalic",
+        (StandardFont::Courier, false, false) => "Courier",
+        (StandardFont::Courier, true, false) => "Courier-Bold",
+        (StandardFont::Courier, false, true) => "Courier-Oblique",
+        (StandardFont::Courier, true, true) => "Courier-BoldOblique",
+    }
+}
+
+/// Character widths of printable ASCII (32 to 126), in 1/1000 of the font size
+#[rustfmt::skip]
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+#[rustfmt::skip]
+const TIMES: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+#[rustfmt::skip]
+const TIMES_BOLD: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+/// Width of `c` in a standard font. Italic styles are measured with the
+/// upright widths, which differ by a few percent at most.
+fn standard_width(family: StandardFont, bold: bool, c: char) -> u16 {
+    let table = match (family, bold) {
+        (StandardFont::Courier, _) => return 600,
+        (StandardFont::Helvetica, false) => &HELVETICA,
+        (StandardFont::Helvetica, true) => &HELVETICA_BOLD,
+        (StandardFont::Times, false) => &TIMES,
+        (StandardFont::Times, true) => &TIMES_BOLD,
+    };
+    let ascii = |c: char| table[(c as usize) - 32];
+    if (' '..='~').contains(&c) {
+        return ascii(c);
+    }
+    if win_ansi(c).is_none() {
+        return ascii('?');
+    }
+    match c {
+        '\u{a0}' => ascii(' '),
+        '‘' | '’' | '‚' | '‹' | '›' => ascii('\''),
+        '“' | '”' | '„' => ascii('"'),
+        '–' | '«' | '»' => ascii('n'),
+        '—' | '…' | '‰' | '™' => 1000,
+        '•' => 350,
+        '€' | '£' | '¥' | '¢' => ascii('0'),
+        '©' | '®' => 750,
+        '°' => 400,
+        '·' => ascii('.'),
+        '×' | '÷' | '±' => ascii('+'),
+        'Æ' | 'Œ' => ascii('W'),
+        'æ' | 'œ' => ascii('m'),
+        'ß' => ascii('b'),
+        _ => ascii(base_letter(c).unwrap_or('o')),
+    }
+}
+
+/// The unaccented letter of the same width, e.g. `é` → `e`
+fn base_letter(c: char) -> Option<char> {
+    Some(match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ð' => 'D',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' | 'Ÿ' => 'Y',
+        'Þ' => 'P',
+        'Š' => 'S',
+        'Ž' => 'Z',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        // Dotless i is as wide as t in these fonts
+        'ì'..='ï' => 't',
+        'ð' | 'ò'..='ö' | 'ø' => 'o',
+        'ñ' => 'n',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'þ' => 'p',
+        'š' => 's',
+        'ž' => 'z',
+        _ => return None,
+    })
+}
+
+/// Byte of `c` in WinAnsiEncoding, if it has one
+fn win_ansi(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' | '\u{a0}'..='ÿ' => Some(c as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        'ƒ' => Some(0x83),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '†' => Some(0x86),
+        '‡' => Some(0x87),
+        'ˆ' => Some(0x88),
+        '‰' => Some(0x89),
+        'Š' => Some(0x8A),
+        '‹' => Some(0x8B),
+        'Œ' => Some(0x8C),
+        'Ž' => Some(0x8E),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '˜' => Some(0x98),
+        '™' => Some(0x99),
+        'š' => Some(0x9A),
+        '›' => Some(0x9B),
+        'œ' => Some(0x9C),
+        'ž' => Some(0x9E),
+        'Ÿ' => Some(0x9F),
+        _ => None,
+    }
+}
+
+/// A TrueType font file, embedded whole
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    name: String,
+    units_per_em: f32,
+    ascent: i16,
+    descent: i16,
+    cap_height: i16,
+    bbox: [i16; 4],
+    /// Glyph ID and width of each character looked up so far
+    glyphs: HashMap<char, (u16, u16)>,
+    /// Width and character of each glyph written, for the widths array and
+    /// the text extraction map
+    used: BTreeMap<u16, (u16, char)>,
+}
+
+impl TrueTypeFont {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read font file: {}", e))?;
+        let face = Face::parse(&data, 0).map_err(|e| format!("Failed to read font file: {}", e))?;
+        if face.tables().glyf.is_none() {
+            return Err("The font file must be a TrueType font (.ttf)".to_string());
+        }
+        if face.permissions() == Some(Permissions::Restricted) {
+            return Err("The font's license does not allow embedding it in documents".to_string());
+        }
+
+        let name = face
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+            .find_map(|name| name.to_string())
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>();
+        let units_per_em = face.units_per_em() as f32;
+        let scale = |value: i16| (value as f32 * 1000.0 / units_per_em).round() as i16;
+        let bbox = face.global_bounding_box();
+
+        Ok(TrueTypeFont {
+            name: if name.is_empty() {
+                "EmbeddedFont".to_string()
+            } else {
+                name
+            },
+            ascent: scale(face.ascender()),
+            descent: scale(face.descender()),
+            cap_height: scale(face.capital_height().unwrap_or(face.ascender())),
+            bbox: [
+                scale(bbox.x_min),
+                scale(bbox.y_min),
+                scale(bbox.x_max),
+                scale(bbox.y_max),
+            ],
+            units_per_em,
+            glyphs: HashMap::new(),
+            used: BTreeMap::new(),
+            data,
+        })
+    }
+
+    /// Glyph ID and width of `c`. Characters the font lacks get glyph 0,
+    /// which viewers draw as a box.
+    fn glyph(&mut self, c: char) -> (u16, u16) {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let glyph = match Face::parse(&self.data, 0) {
+            Ok(face) => {
+                let id = face.glyph_index(c).unwrap_or(GlyphId(0));
+                let advance = face.glyph_hor_advance(id).unwrap_or(0);
+                (
+                    id.0,
+                    (advance as f32 * 1000.0 / self.units_per_em).round() as u16,
+                )
+            }
+            Err(_) => (0, 500),
+        };
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+
+    /// Writes the font as a composite font addressed by glyph ID
+    fn write(&self, pdf: &mut PdfWriter) -> ObjectId {
+        let font = pdf.reserve();
+        let cid_font = pdf.reserve();
+        let descriptor = pdf.reserve();
+        let file = pdf.reserve();
+        let to_unicode = pdf.reserve();
+
+        pdf.object(
+            font,
+            &format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+                 /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+                self.name, cid_font, to_unicode
+            ),
+        );
+
+        let widths: Vec<String> = self
+            .used
+            .iter()
+            .map(|(glyph, (width, _))| format!("{} [{}]", glyph, width))
+            .collect();
+        pdf.object(
+            cid_font,
+            &format!(
+                "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} \
+                 /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                 /FontDescriptor {} 0 R /CIDToGIDMap /Identity /DW 1000 /W [{}] >>",
+                self.name,
+                descriptor,
+                widths.join(" ")
+            ),
+        );
+        pdf.object(
+            descriptor,
+            &format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags 32 /FontBBox [{} {} {} {}] \
+                 /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 {} 0 R >>",
+                self.name,
+                self.bbox[0],
+                self.bbox[1],
+                self.bbox[2],
+                self.bbox[3],
+                self.ascent,
+                self.descent,
+                self.cap_height,
+                file
+            ),
+        );
+        pdf.stream(
+            file,
+            &format!("/Length1 {} /Filter /FlateDecode", self.data.len()),
+            &deflate(&self.data),
+        );
+
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        let used: Vec<(&u16, &(u16, char))> = self.used.iter().collect();
+        // At most 100 mappings per section
+        for chunk in used.chunks(100) {
+            cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+            for (glyph, (_, c)) in chunk {
+                let utf16: String = c
+                    .encode_utf16(&mut [0; 2])
+                    .iter()
+                    .map(|unit| format!("{:04X}", unit))
+                    .collect();
+                cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, utf16));
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+        pdf.deflated_stream(to_unicode, "", cmap.as_bytes());
+
+        font
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_font_widths_and_encoding() {
+        let mut fonts = Fonts::Standard(StandardFont::Helvetica);
+        assert_eq!(fonts.width("Hi", FontStyle::Regular, 10.0), 9.44);
+        assert_eq!(fonts.width("Hi", FontStyle::Bold, 10.0), 10.0);
+        assert_eq!(
+            fonts.width("é", FontStyle::Regular, 10.0),
+            fonts.width("e", FontStyle::Regular, 10.0)
+        );
+        assert_eq!(fonts.width("abc", FontStyle::Mono, 10.0), 18.0);
+
+        assert_eq!(fonts.encode("a(b)\\", FontStyle::Regular), "(a\\(b\\)\\\\)");
+        assert_eq!(
+            fonts.encode("é – 日", FontStyle::Regular),
+            "(\\351 \\226 ?)"
+        );
+        assert_eq!(fonts.resource(FontStyle::BoldItalic), "F4");
+        assert!(!fonts.synthesizes_styles());
+    }
+
+    #[test]
+    fn test_missing_font_file() {
+        let err = TrueTypeFont::load(Path::new("/nonexistent/font.ttf"))
+            .err()
+            .unwrap();
+        assert!(err.starts_with("Failed to read font file"));
+    }
+}
@@ -0,0 +1,334 @@
+//! Embedding JPEG and PNG images in PDF.
+//!
+//! JPEG data is embedded as is. PNG data without transparency is embedded
+//! as is too, since PDF understands PNG's row filters; PNGs with an alpha
+//! channel are decoded so the alpha can become a separate soft mask.
+
+use super::writer::{deflate, number, ObjectId, PdfWriter};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+/// Largest decoded PNG accepted, in bytes
+const MAX_DECODED_SIZE: usize = 256 * 1024 * 1024;
+
+pub struct PdfImage {
+    pub width: u32,
+    pub height: u32,
+    /// Image dictionary entries besides the size and length
+    dict: String,
+    data: Vec<u8>,
+    /// Alpha channel as a grayscale image: dictionary entries and data
+    mask: Option<(String, Vec<u8>)>,
+}
+
+impl PdfImage {
+    /// Reads a JPEG or PNG image. Returns `None` for other formats and for
+    /// images that cannot be read.
+    pub fn decode(mime: &str, bytes: Vec<u8>) -> Option<Self> {
+        match mime {
+            "image/jpeg" | "image/jpg" => jpeg(bytes),
+            "image/png" => png(&bytes),
+            _ => None,
+        }
+    }
+
+    pub fn write(&self, pdf: &mut PdfWriter) -> ObjectId {
+        let id = pdf.reserve();
+        let mut dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} {}",
+            self.width, self.height, self.dict
+        );
+        if let Some((mask_dict, mask_data)) = &self.mask {
+            let mask = pdf.reserve();
+            pdf.stream(
+                mask,
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} {}",
+                    self.width, self.height, mask_dict
+                ),
+                mask_data,
+            );
+            dict.push_str(&format!(" /SMask {} 0 R", mask));
+        }
+        pdf.stream(id, &dict, &self.data);
+        id
+    }
+}
+
+fn jpeg(bytes: Vec<u8>) -> Option<PdfImage> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    while i + 4 <= bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+        let marker = bytes[i + 1];
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        // Start of frame, except the huffman/arithmetic table markers
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let frame = bytes.get(i + 4..i + 10)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]) as u32;
+            let width = u16::from_be_bytes([frame[3], frame[4]]) as u32;
+            let color_space = match frame[5] {
+                1 => "/DeviceGray",
+                3 => "/DeviceRGB",
+                // Adobe writes CMYK JPEGs inverted
+                4 => "/DeviceCMYK /Decode [1 0 1 0 1 0 1 0]",
+                _ => return None,
+            };
+            if width == 0 || height == 0 {
+                return None;
+            }
+            return Some(PdfImage {
+                width,
+                height,
+                dict: format!(
+                    "/ColorSpace {} /BitsPerComponent 8 /Filter /DCTDecode",
+                    color_space
+                ),
+                data: bytes,
+                mask: None,
+            });
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+fn png(bytes: &[u8]) -> Option<PdfImage> {
+    let mut rest = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    let mut header: Option<&[u8]> = None;
+    let mut palette: &[u8] = &[];
+    let mut idat = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        match kind {
+            b"IHDR" => header = Some(data),
+            b"PLTE" => palette = data,
+            b"IDAT" => idat.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + length..)?;
+    }
+
+    let header = header.filter(|h| h.len() >= 13)?;
+    let width = u32::from_be_bytes(header[0..4].try_into().ok()?);
+    let height = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    let depth = header[8];
+    let color_type = header[9];
+    // Interlaced images would have to be decoded pass by pass
+    if width == 0 || height == 0 || header[12] != 0 || idat.is_empty() {
+        return None;
+    }
+
+    let (colors, color_space) = match color_type {
+        0 | 4 => (1, "/DeviceGray".to_string()),
+        2 | 6 => (3, "/DeviceRGB".to_string()),
+        3 if !palette.is_empty() => (
+            1,
+            format!(
+                "[/Indexed /DeviceRGB {} <{}>]",
+                palette.len() / 3 - 1,
+                hex::encode(palette)
+            ),
+        ),
+        _ => return None,
+    };
+
+    if matches!(color_type, 0 | 2 | 3) {
+        return Some(PdfImage {
+            width,
+            height,
+            dict: format!(
+                "/ColorSpace {} /BitsPerComponent {} /Filter /FlateDecode \
+                 /DecodeParms << /Predictor 15 /Colors {} /BitsPerComponent {} /Columns {} >>",
+                color_space, depth, colors, depth, width
+            ),
+            data: idat,
+            mask: None,
+        });
+    }
+
+    // Gray or RGB with alpha, 8 or 16 bits per sample
+    if depth != 8 && depth != 16 {
+        return None;
+    }
+    let sample = depth as usize / 8;
+    let pixel = (colors + 1) * sample;
+    let stride = (width as usize).checked_mul(pixel)?;
+    let expected = stride.checked_add(1)?.checked_mul(height as usize)?;
+    if expected > MAX_DECODED_SIZE {
+        return None;
+    }
+    let mut filtered = Vec::with_capacity(expected);
+    ZlibDecoder::new(idat.as_slice())
+        .take(expected as u64)
+        .read_to_end(&mut filtered)
+        .ok()?;
+    let pixels = unfilter(&filtered, stride, pixel, height as usize)?;
+
+    let mut color = Vec::with_capacity(pixels.len() / (colors + 1) * colors);
+    let mut alpha = Vec::with_capacity(pixels.len() / (colors + 1));
+    for chunk in pixels.chunks_exact(pixel) {
+        color.extend_from_slice(&chunk[..colors * sample]);
+        alpha.extend_from_slice(&chunk[colors * sample..]);
+    }
+    Some(PdfImage {
+        width,
+        height,
+        dict: format!(
+            "/ColorSpace {} /BitsPerComponent {} /Filter /FlateDecode",
+            color_space, depth
+        ),
+        data: deflate(&color),
+        mask: Some((
+            format!(
+                "/ColorSpace /DeviceGray /BitsPerComponent {} /Filter /FlateDecode",
+                depth
+            ),
+            deflate(&alpha),
+        )),
+    })
+}
+
+/// Reverses PNG's per-row filters
+fn unfilter(data: &[u8], stride: usize, pixel: usize, rows: usize) -> Option<Vec<u8>> {
+    let mut out = vec![0u8; stride * rows];
+    for row in 0..rows {
+        let line = data.get(row * (stride + 1)..(row + 1) * (stride + 1))?;
+        let (filter, line) = (line[0], &line[1..]);
+        let (done, current) = out.split_at_mut(row * stride);
+        let previous = if row > 0 {
+            &done[(row - 1) * stride..]
+        } else {
+            &[][..]
+        };
+        let current = &mut current[..stride];
+        for i in 0..stride {
+            let left = if i >= pixel { current[i - pixel] } else { 0 };
+            let up = previous.get(i).copied().unwrap_or(0);
+            let up_left = if i >= pixel {
+                previous.get(i - pixel).copied().unwrap_or(0)
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return None,
+            };
+            current[i] = line[i].wrapping_add(predictor);
+        }
+    }
+    Some(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Scales an image to fit in `max_width` × `max_height` points without
+/// enlarging it past 72 dpi. Returns the drawn width and height.
+pub fn fit(image: &PdfImage, max_width: f32, max_height: f32) -> (f32, f32) {
+    let (width, height) = (image.width as f32, image.height as f32);
+    let scale = (max_width / width).min(max_height / height).min(1.0);
+    (width * scale, height * scale)
+}
+
+/// Content stream operators drawing the image `name` with its lower left
+/// corner at `x`, `y`
+pub fn draw(name: &str, x: f32, y: f32, width: f32, height: f32) -> String {
+    format!(
+        "q {} 0 0 {} {} {} cm /{} Do Q\n",
+        number(width),
+        number(height),
+        number(x),
+        number(y),
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        // The CRC is not checked
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    fn build_png(color_type: u8, rows: &[u8]) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(chunk(
+            b"IHDR",
+            &[0, 0, 0, 2, 0, 0, 0, 1, 8, color_type, 0, 0, 0],
+        ));
+        png.extend(chunk(b"IDAT", &deflate(rows)));
+        png.extend(chunk(b"IEND", &[]));
+        png
+    }
+
+    #[test]
+    fn test_jpeg_size() {
+        let jpeg = vec![
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00,
+            0x20, 0x00, 0x30, 0x03,
+        ];
+        let image = PdfImage::decode("image/jpeg", jpeg).unwrap();
+        assert_eq!((image.width, image.height), (48, 32));
+        assert!(image.dict.contains("/DeviceRGB"));
+        assert!(PdfImage::decode("image/jpeg", vec![0xFF, 0xD8]).is_none());
+        assert!(PdfImage::decode("image/gif", b"GIF89a".to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_png_with_alpha_is_split() {
+        // Two RGBA pixels, the second row-filtered with "Sub"
+        let rows = [1, 10, 20, 30, 255, 5, 5, 5, 0];
+        let image = PdfImage::decode("image/png", build_png(6, &rows)).unwrap();
+        let (_, mask) = image.mask.as_ref().unwrap();
+
+        let mut color = Vec::new();
+        ZlibDecoder::new(image.data.as_slice())
+            .read_to_end(&mut color)
+            .unwrap();
+        let mut alpha = Vec::new();
+        ZlibDecoder::new(mask.as_slice())
+            .read_to_end(&mut alpha)
+            .unwrap();
+        assert_eq!(color, vec![10, 20, 30, 15, 25, 35]);
+        assert_eq!(alpha, vec![255, 255]);
+
+        let opaque = PdfImage::decode("image/png", build_png(2, &[0, 1, 2, 3, 4, 5, 6])).unwrap();
+        assert!(opaque.mask.is_none());
+        assert!(opaque.dict.contains("/Predictor 15 /Colors 3"));
+    }
+}
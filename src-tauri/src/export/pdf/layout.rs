@@ -0,0 +1,972 @@
+//! Layout of entry HTML on PDF pages.
+//!
+//! The editor's HTML is read into blocks (paragraphs, headings, list items,
+//! quotes, code, rules and images), which are broken into lines and placed
+//! on pages top to bottom. Each page becomes a content stream.
+
+use super::fonts::{FontStyle, Fonts};
+use super::images::{self, PdfImage};
+use super::writer::number;
+use crate::import::html::{attribute, resolve_reference};
+use base64::{engine::general_purpose, Engine as _};
+use log::warn;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Line height, as a multiple of the font size
+pub const LINE_HEIGHT: f32 = 1.4;
+
+/// Inline formatting of a piece of text
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub mono: bool,
+    pub underline: bool,
+    pub strike: bool,
+}
+
+impl Style {
+    pub const BOLD: Style = Style {
+        bold: true,
+        italic: false,
+        mono: false,
+        underline: false,
+        strike: false,
+    };
+
+    fn font(self) -> FontStyle {
+        FontStyle::new(self.bold, self.italic, self.mono)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextKind {
+    Paragraph,
+    Heading(u8),
+    Pre,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Block {
+    Text {
+        /// Styled text; `\n` is a line break
+        spans: Vec<(String, Style)>,
+        kind: TextKind,
+        /// List nesting depth
+        indent: u8,
+        /// Quote nesting depth
+        quote: u8,
+        /// List marker drawn before the first line
+        bullet: Option<String>,
+    },
+    /// An empty paragraph, kept as vertical space
+    Blank,
+    Rule,
+    Image {
+        mime: String,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Reads the editor's HTML into blocks
+pub fn parse_blocks(html: &str) -> Vec<Block> {
+    let mut xml = Reader::from_str(html);
+    let config = xml.config_mut();
+    config.check_end_names = false;
+    config.allow_unmatched_ends = true;
+
+    let mut parser = BlockParser::default();
+    loop {
+        match xml.read_event() {
+            Ok(Event::Start(e)) => parser.start(&e),
+            Ok(Event::Empty(e)) => {
+                parser.start(&e);
+                parser.end(e.local_name().as_ref());
+            }
+            Ok(Event::End(e)) => parser.end(e.local_name().as_ref()),
+            Ok(Event::Text(e)) => parser.text(&e.decode().unwrap_or_default()),
+            Ok(Event::CData(e)) => parser.text(&e.decode().unwrap_or_default()),
+            Ok(Event::GeneralRef(e)) => parser.text(&resolve_reference(&e)),
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Stopped reading malformed entry HTML: {}", e);
+                break;
+            }
+        }
+    }
+    parser.flush();
+    parser.blocks
+}
+
+#[derive(Default)]
+struct BlockParser {
+    blocks: Vec<Block>,
+    spans: Vec<(String, Style)>,
+    heading: Option<u8>,
+    pre: bool,
+    /// Open lists: `None` for bullets, or the next number
+    lists: Vec<Option<u32>>,
+    quote: u8,
+    bullet: Option<String>,
+    bold: u32,
+    italic: u32,
+    underline: u32,
+    strike: u32,
+    code: u32,
+    /// Depth inside elements whose content is not shown
+    skip: u32,
+    /// Number of blocks when the current `<p>` started
+    paragraph_start: Option<usize>,
+}
+
+impl BlockParser {
+    fn style(&self) -> Style {
+        Style {
+            bold: self.bold > 0,
+            italic: self.italic > 0,
+            mono: self.code > 0 || self.pre,
+            underline: self.underline > 0,
+            strike: self.strike > 0,
+        }
+    }
+
+    fn start(&mut self, e: &BytesStart) {
+        let name = e.local_name();
+        if self.skip > 0 {
+            self.skip += u32::from(!is_void(name.as_ref()));
+            return;
+        }
+        match name.as_ref() {
+            b"p" => {
+                self.flush();
+                self.paragraph_start = Some(self.blocks.len());
+            }
+            b"div" => self.flush(),
+            b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+                self.flush();
+                self.heading = Some(name.as_ref()[1] - b'0');
+            }
+            b"pre" => {
+                self.flush();
+                self.pre = true;
+            }
+            b"ul" => {
+                self.flush();
+                self.lists.push(None);
+            }
+            b"ol" => {
+                self.flush();
+                let start = attribute(e, "start").and_then(|s| s.parse().ok());
+                self.lists.push(Some(start.unwrap_or(1)));
+            }
+            b"li" => {
+                self.flush();
+                self.bullet = Some(match attribute(e, "data-checked").as_deref() {
+                    Some("true") => "[x]".to_string(),
+                    Some(_) => "[ ]".to_string(),
+                    None => match self.lists.last_mut() {
+                        Some(Some(n)) => {
+                            *n += 1;
+                            format!("{}.", *n - 1)
+                        }
+                        _ => "•".to_string(),
+                    },
+                });
+            }
+            b"blockquote" => {
+                self.flush();
+                self.quote += 1;
+            }
+            b"br" => {
+                let style = self.style();
+                self.spans.push(("\n".to_string(), style));
+            }
+            b"hr" => {
+                self.flush();
+                self.blocks.push(Block::Rule);
+            }
+            b"img" => {
+                self.flush();
+                match attribute(e, "src").as_deref().and_then(parse_data_uri) {
+                    Some((mime, bytes)) => self.blocks.push(Block::Image { mime, bytes }),
+                    None => self.image_placeholder(e),
+                }
+            }
+            b"strong" | b"b" => self.bold += 1,
+            b"em" | b"i" => self.italic += 1,
+            b"u" => self.underline += 1,
+            b"s" | b"del" | b"strike" => self.strike += 1,
+            b"code" => self.code += 1,
+            b"script" | b"style" => self.skip = 1,
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8]) {
+        if self.skip > 0 {
+            self.skip -= u32::from(!is_void(name));
+            return;
+        }
+        match name {
+            b"p" => {
+                let empty = self.spans.iter().all(|(text, _)| text.trim().is_empty());
+                if empty && self.paragraph_start == Some(self.blocks.len()) {
+                    self.spans.clear();
+                    self.blocks.push(Block::Blank);
+                }
+                self.flush();
+                self.paragraph_start = None;
+            }
+            b"div" => self.flush(),
+            b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+                self.flush();
+                self.heading = None;
+            }
+            b"pre" => {
+                self.flush();
+                self.pre = false;
+            }
+            b"ul" | b"ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            b"li" => {
+                self.flush();
+                self.bullet = None;
+            }
+            b"blockquote" => {
+                self.flush();
+                self.quote = self.quote.saturating_sub(1);
+            }
+            b"strong" | b"b" => self.bold = self.bold.saturating_sub(1),
+            b"em" | b"i" => self.italic = self.italic.saturating_sub(1),
+            b"u" => self.underline = self.underline.saturating_sub(1),
+            b"s" | b"del" | b"strike" => self.strike = self.strike.saturating_sub(1),
+            b"code" => self.code = self.code.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        let style = self.style();
+        if self.pre {
+            self.spans.push((text.to_string(), style));
+            return;
+        }
+        // Collapse whitespace as browsers do, also across formatting
+        let mut collapsed = String::with_capacity(text.len());
+        let mut space = self
+            .spans
+            .last()
+            .is_none_or(|(last, _)| last.ends_with([' ', '\n']));
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                if !space {
+                    collapsed.push(' ');
+                }
+                space = true;
+            } else {
+                collapsed.push(c);
+                space = false;
+            }
+        }
+        if !collapsed.is_empty() {
+            self.spans.push((collapsed, style));
+        }
+    }
+
+    fn image_placeholder(&mut self, e: &BytesStart) {
+        let alt = attribute(e, "alt").filter(|alt| !alt.trim().is_empty());
+        let text = alt.map_or("[Image]".to_string(), |alt| format!("[Image: {}]", alt));
+        let style = Style {
+            italic: true,
+            ..Default::default()
+        };
+        self.spans.push((text, style));
+        self.flush();
+    }
+
+    /// Ends the current text block
+    fn flush(&mut self) {
+        let mut spans = std::mem::take(&mut self.spans);
+        if !self.pre {
+            if let Some((last, _)) = spans.last_mut() {
+                let trimmed = last.trim_end_matches(' ').len();
+                last.truncate(trimmed);
+            }
+        }
+        if spans.iter().all(|(text, _)| text.trim().is_empty()) {
+            return;
+        }
+        let kind = match (self.pre, self.heading) {
+            (true, _) => TextKind::Pre,
+            (false, Some(level)) => TextKind::Heading(level),
+            (false, None) => TextKind::Paragraph,
+        };
+        self.blocks.push(Block::Text {
+            spans,
+            kind,
+            indent: self.lists.len() as u8,
+            quote: self.quote,
+            bullet: self.bullet.take(),
+        });
+    }
+}
+
+fn is_void(name: &[u8]) -> bool {
+    matches!(name, b"br" | b"hr" | b"img" | b"input" | b"wbr")
+}
+
+/// MIME type and bytes of a `data:image/…;base64,…` URI
+fn parse_data_uri(src: &str) -> Option<(String, Vec<u8>)> {
+    let rest = src.strip_prefix("data:")?;
+    let (mime, data) = rest.split_once(";base64,")?;
+    let bytes = general_purpose::STANDARD.decode(data.trim()).ok()?;
+    Some((mime.to_string(), bytes))
+}
+
+/// Page size and margins, in points
+#[derive(Debug, Clone, Copy)]
+pub struct Geometry {
+    pub width: f32,
+    pub height: f32,
+    pub margin: f32,
+    pub font_size: f32,
+}
+
+impl Geometry {
+    fn top(&self) -> f32 {
+        self.height - self.margin
+    }
+
+    fn content_width(&self) -> f32 {
+        self.width - 2.0 * self.margin
+    }
+
+    /// Indentation per list or quote level
+    fn indent_step(&self) -> f32 {
+        self.font_size * 1.8
+    }
+}
+
+/// A position in the laid out pages, for the table of contents and outline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+    pub page: usize,
+    pub y: f32,
+}
+
+/// A clickable area that jumps to an anchor
+pub struct Link {
+    pub rect: [f32; 4],
+    pub target: Anchor,
+}
+
+#[derive(Default)]
+pub struct Page {
+    /// Content stream operators
+    pub content: String,
+    pub links: Vec<Link>,
+}
+
+/// Text of one line, split into runs of one style
+struct Run {
+    text: String,
+    style: Style,
+    x: f32,
+    width: f32,
+}
+
+/// Places blocks on pages from top to bottom
+pub struct PageBuilder<'a> {
+    fonts: &'a mut Fonts,
+    /// Images of the whole document; pages draw them as `/Im<index>`
+    images: &'a mut Vec<PdfImage>,
+    geometry: Geometry,
+    pub pages: Vec<Page>,
+    /// Top of the free space on the current page
+    y: f32,
+}
+
+impl<'a> PageBuilder<'a> {
+    pub fn new(fonts: &'a mut Fonts, images: &'a mut Vec<PdfImage>, geometry: Geometry) -> Self {
+        PageBuilder {
+            fonts,
+            images,
+            geometry,
+            pages: Vec::new(),
+            y: 0.0,
+        }
+    }
+
+    pub fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.y = self.geometry.top();
+    }
+
+    /// Starts a new page unless `height` points fit on the current one
+    fn ensure(&mut self, height: f32) {
+        let at_top = !self.pages.is_empty() && self.y == self.geometry.top();
+        if self.pages.is_empty() || (!at_top && self.y - height < self.geometry.margin) {
+            self.new_page();
+        }
+    }
+
+    /// Continues the current page at `y` points from the bottom
+    pub fn move_to(&mut self, y: f32) {
+        self.y = y;
+    }
+
+    /// Vertical space, left out at the top of a page
+    pub fn space(&mut self, height: f32) {
+        if !self.pages.is_empty() && self.y < self.geometry.top() {
+            self.y -= height;
+        }
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor {
+            page: self.pages.len() - 1,
+            y: self.y,
+        }
+    }
+
+    fn content(&mut self) -> &mut String {
+        &mut self
+            .pages
+            .last_mut()
+            .expect("a page has been started")
+            .content
+    }
+
+    /// A bold heading that stays on the page with at least `keep` points of
+    /// what follows. Returns where it starts.
+    pub fn heading(&mut self, text: &str, size: f32, keep: f32) -> Anchor {
+        self.space(size * 0.6);
+        self.ensure(size * LINE_HEIGHT + keep);
+        let anchor = self.anchor();
+        let spans = [(text.to_string(), Style::BOLD)];
+        self.lines(
+            &spans,
+            TextKind::Paragraph,
+            size,
+            self.geometry.margin,
+            None,
+            0,
+        );
+        self.y -= size * 0.3;
+        anchor
+    }
+
+    /// One line of text centered on the page
+    pub fn centered(&mut self, text: &str, style: Style, size: f32) {
+        self.ensure(size * LINE_HEIGHT);
+        let width = self.fonts.width(text, style.font(), size);
+        let x = (self.geometry.width - width) / 2.0;
+        let baseline = self.y - size * 1.1;
+        self.draw_run(text, style, x, baseline, size, width);
+        self.y -= size * LINE_HEIGHT;
+    }
+
+    /// A table of contents line: `text` on the left, `page` on the right,
+    /// linking to `target`
+    pub fn toc_line(&mut self, text: &str, page: &str, indent: f32, style: Style, target: Anchor) {
+        let size = self.geometry.font_size;
+        self.ensure(size * LINE_HEIGHT);
+        let baseline = self.y - size * 1.1;
+        let left = self.geometry.margin + indent;
+        let right = self.geometry.width - self.geometry.margin;
+        let page_width = self.fonts.width(page, style.font(), size);
+        let text_width = self.fonts.width(text, style.font(), size);
+        self.draw_run(text, style, left, baseline, size, text_width);
+        self.draw_run(page, style, right - page_width, baseline, size, page_width);
+        let rect = [
+            left,
+            baseline - size * 0.3,
+            right,
+            baseline + size * (LINE_HEIGHT - 0.3),
+        ];
+        if let Some(current) = self.pages.last_mut() {
+            current.links.push(Link { rect, target });
+        }
+        self.y -= size * LINE_HEIGHT;
+    }
+
+    /// Lays out one block of entry content
+    pub fn block(&mut self, block: &Block) {
+        let size = self.geometry.font_size;
+        match block {
+            Block::Text {
+                spans,
+                kind,
+                indent,
+                quote,
+                bullet,
+            } => {
+                let (size, spans) = match kind {
+                    TextKind::Heading(level) => {
+                        let scale = [1.4, 1.25, 1.15][(*level as usize).clamp(1, 3) - 1];
+                        self.space(size * 0.3);
+                        let bold: Vec<(String, Style)> = spans
+                            .iter()
+                            .map(|(text, style)| {
+                                (
+                                    text.clone(),
+                                    Style {
+                                        bold: true,
+                                        ..*style
+                                    },
+                                )
+                            })
+                            .collect();
+                        (size * scale, bold)
+                    }
+                    TextKind::Pre => (size * 0.9, spans.clone()),
+                    TextKind::Paragraph => (size, spans.clone()),
+                };
+                let x =
+                    self.geometry.margin + (*indent + *quote) as f32 * self.geometry.indent_step();
+                self.lines(&spans, *kind, size, x, bullet.as_deref(), *quote);
+                let after = if bullet.is_some() || *indent > 0 {
+                    0.2
+                } else {
+                    0.5
+                };
+                self.y -= self.geometry.font_size * after;
+            }
+            Block::Blank => {
+                self.ensure(size * LINE_HEIGHT);
+                self.y -= size * LINE_HEIGHT;
+            }
+            Block::Rule => {
+                self.ensure(size * LINE_HEIGHT);
+                let y = self.y - size * 0.7;
+                let ops = format!(
+                    "q 0.6 G 0.5 w {} {} m {} {} l S Q\n",
+                    number(self.geometry.margin),
+                    number(y),
+                    number(self.geometry.width - self.geometry.margin),
+                    number(y)
+                );
+                self.content().push_str(&ops);
+                self.y -= size * LINE_HEIGHT;
+            }
+            Block::Image { mime, bytes } => match PdfImage::decode(mime, bytes.clone()) {
+                Some(image) => {
+                    let max_height = (self.geometry.top() - self.geometry.margin) * 0.6;
+                    let (width, height) =
+                        images::fit(&image, self.geometry.content_width(), max_height);
+                    self.ensure(height);
+                    let x = self.geometry.margin;
+                    let y = self.y - height;
+                    let name = format!("Im{}", self.images.len());
+                    self.images.push(image);
+                    let ops = images::draw(&name, x, y, width, height);
+                    self.content().push_str(&ops);
+                    self.y = y - size * 0.5;
+                }
+                None => {
+                    let style = Style {
+                        italic: true,
+                        ..Default::default()
+                    };
+                    let spans = [("[Image]".to_string(), style)];
+                    self.lines(
+                        &spans,
+                        TextKind::Paragraph,
+                        size,
+                        self.geometry.margin,
+                        None,
+                        0,
+                    );
+                    self.y -= size * 0.5;
+                }
+            },
+        }
+    }
+
+    /// Breaks `spans` into lines starting at `x` and draws them
+    fn lines(
+        &mut self,
+        spans: &[(String, Style)],
+        kind: TextKind,
+        size: f32,
+        x: f32,
+        bullet: Option<&str>,
+        quote: u8,
+    ) {
+        let max_width = self.geometry.width - self.geometry.margin - x;
+        let lines = if kind == TextKind::Pre {
+            self.break_pre(spans, size, max_width)
+        } else {
+            self.break_lines(spans, size, max_width)
+        };
+        for (i, line) in lines.iter().enumerate() {
+            self.ensure(size * LINE_HEIGHT);
+            let baseline = self.y - size * 1.1;
+            if i == 0 {
+                if let Some(bullet) = bullet {
+                    let width = self.fonts.width(bullet, FontStyle::Regular, size);
+                    self.draw_run(
+                        bullet,
+                        Style::default(),
+                        x - width - size * 0.5,
+                        baseline,
+                        size,
+                        width,
+                    );
+                }
+            }
+            for level in 0..quote {
+                let bar_x =
+                    self.geometry.margin + level as f32 * self.geometry.indent_step() + size * 0.4;
+                let ops = format!(
+                    "q 0.7 G 1.5 w {} {} m {} {} l S Q\n",
+                    number(bar_x),
+                    number(self.y - size * LINE_HEIGHT),
+                    number(bar_x),
+                    number(self.y)
+                );
+                self.content().push_str(&ops);
+            }
+            for run in line {
+                self.draw_run(&run.text, run.style, x + run.x, baseline, size, run.width);
+            }
+            self.y -= size * LINE_HEIGHT;
+        }
+    }
+
+    /// Fills lines word by word. Words longer than a line are split.
+    fn break_lines(
+        &mut self,
+        spans: &[(String, Style)],
+        size: f32,
+        max_width: f32,
+    ) -> Vec<Vec<Run>> {
+        let mut lines: Vec<Vec<Run>> = Vec::new();
+        let mut line: Vec<Run> = Vec::new();
+        let mut x = 0.0;
+        // A word may change style midway, e.g. "**bo**ld"
+        let mut word: Vec<(String, Style)> = Vec::new();
+        let mut space: Option<Style> = None;
+
+        let mut items: Vec<Option<(char, Style)>> = Vec::new();
+        for (text, style) in spans {
+            items.extend(text.chars().map(|c| Some((c, *style))));
+        }
+        items.push(None);
+
+        for item in items {
+            let c = item.map(|(c, _)| c);
+            if matches!(c, Some(' ') | Some('\n') | None) {
+                if !word.is_empty() {
+                    self.place_word(&mut lines, &mut line, &mut x, &word, space, size, max_width);
+                    word.clear();
+                    space = None;
+                }
+                match item {
+                    Some(('\n', _)) => {
+                        lines.push(std::mem::take(&mut line));
+                        x = 0.0;
+                        space = None;
+                    }
+                    Some((' ', style)) if !line.is_empty() => space = Some(style),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some((c, style)) = item {
+                match word.last_mut() {
+                    Some((text, last)) if *last == style => text.push(c),
+                    _ => word.push((c.to_string(), style)),
+                }
+            }
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[allow(clippy::too_many_arguments)] // Line-breaking state threaded through one helper
+    fn place_word(
+        &mut self,
+        lines: &mut Vec<Vec<Run>>,
+        line: &mut Vec<Run>,
+        x: &mut f32,
+        word: &[(String, Style)],
+        space: Option<Style>,
+        size: f32,
+        max_width: f32,
+    ) {
+        let widths: Vec<f32> = word
+            .iter()
+            .map(|(text, style)| self.fonts.width(text, style.font(), size))
+            .collect();
+        let word_width: f32 = widths.iter().sum();
+        let space_width = space.map_or(0.0, |style| self.fonts.width(" ", style.font(), size));
+
+        if !line.is_empty() && *x + space_width + word_width > max_width {
+            lines.push(std::mem::take(line));
+            *x = 0.0;
+        } else if let Some(style) = space.filter(|_| !line.is_empty()) {
+            push_run(line, " ", style, *x, space_width);
+            *x += space_width;
+        }
+
+        if word_width <= max_width {
+            for ((text, style), width) in word.iter().zip(widths) {
+                push_run(line, text, *style, *x, width);
+                *x += width;
+            }
+            return;
+        }
+        // Too long for any line: split between characters
+        for (text, style) in word {
+            for c in text.chars() {
+                let mut buf = [0; 4];
+                let c = c.encode_utf8(&mut buf);
+                let width = self.fonts.width(c, style.font(), size);
+                if *x > 0.0 && *x + width > max_width {
+                    lines.push(std::mem::take(line));
+                    *x = 0.0;
+                }
+                push_run(line, c, *style, *x, width);
+                *x += width;
+            }
+        }
+    }
+
+    /// Keeps the lines of preformatted text, splitting those that are too long
+    fn break_pre(&mut self, spans: &[(String, Style)], size: f32, max_width: f32) -> Vec<Vec<Run>> {
+        let mut lines: Vec<Vec<Run>> = vec![Vec::new()];
+        let mut x = 0.0;
+        for (text, style) in spans {
+            for c in text.chars() {
+                if c == '\n' {
+                    lines.push(Vec::new());
+                    x = 0.0;
+                    continue;
+                }
+                let c = if c == '\t' { ' ' } else { c };
+                let mut buf = [0; 4];
+                let c = c.encode_utf8(&mut buf);
+                let width = self.fonts.width(c, style.font(), size);
+                if x > 0.0 && x + width > max_width {
+                    lines.push(Vec::new());
+                    x = 0.0;
+                }
+                if let Some(line) = lines.last_mut() {
+                    push_run(line, c, *style, x, width);
+                }
+                x += width;
+            }
+        }
+        // A trailing line break does not start another line
+        if lines.len() > 1 && lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    fn draw_run(&mut self, text: &str, style: Style, x: f32, baseline: f32, size: f32, width: f32) {
+        let font = style.font();
+        let operand = self.fonts.encode(text, font);
+        let resource = self.fonts.resource(font);
+        let synthesize = self.fonts.synthesizes_styles() && font != FontStyle::Mono;
+        let skew = if synthesize && font.is_italic() {
+            0.2
+        } else {
+            0.0
+        };
+
+        let mut ops = String::from("BT ");
+        if synthesize && font.is_bold() {
+            // Outline the glyphs as well as filling them
+            ops = format!("q {} w BT 2 Tr ", number(size * 0.03));
+        }
+        ops.push_str(&format!(
+            "/{} {} Tf 1 0 {} 1 {} {} Tm {} Tj ET",
+            resource,
+            number(size),
+            number(skew),
+            number(x),
+            number(baseline),
+            operand
+        ));
+        if synthesize && font.is_bold() {
+            ops.push_str(" Q");
+        }
+        ops.push('\n');
+
+        let line_width = number(size * 0.05);
+        if style.underline {
+            let y = number(baseline - size * 0.15);
+            ops.push_str(&format!(
+                "q {} w {} {} m {} {} l S Q\n",
+                line_width,
+                number(x),
+                y,
+                number(x + width),
+                y
+            ));
+        }
+        if style.strike {
+            let y = number(baseline + size * 0.28);
+            ops.push_str(&format!(
+                "q {} w {} {} m {} {} l S Q\n",
+                line_width,
+                number(x),
+                y,
+                number(x + width),
+                y
+            ));
+        }
+        self.content().push_str(&ops);
+    }
+}
+
+/// Appends text to the line, joining it to the last run if the style matches
+fn push_run(line: &mut Vec<Run>, text: &str, style: Style, x: f32, width: f32) {
+    if let Some(last) = line.last_mut() {
+        if last.style == style {
+            last.text.push_str(text);
+            last.width += width;
+            return;
+        }
+    }
+    line.push(Run {
+        text: text.to_string(),
+        style,
+        x,
+        width,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::pdf::fonts::StandardFont;
+
+    fn text(text: &str) -> (String, Style) {
+        (text.to_string(), Style::default())
+    }
+
+    #[test]
+    fn test_parse_blocks() {
+        let blocks = parse_blocks(
+            "<h2>Plan</h2><p>Go <strong>now</strong>  &amp; then<br>later</p><p></p>\
+             <ol><li><p>One</p></li><li><p>Two</p><ul><li><p>Sub</p></li></ul></li></ol>\
+             <blockquote><p>Quoted</p></blockquote><pre><code>a\n  b</code></pre><hr>\
+             <img src=\"https://example.com/x.png\" alt=\"\">",
+        );
+        let bold = Style::BOLD;
+        let mono = Style {
+            mono: true,
+            ..Default::default()
+        };
+        let paragraph =
+            |spans: Vec<(String, Style)>, indent, quote, bullet: Option<&str>| Block::Text {
+                spans,
+                kind: TextKind::Paragraph,
+                indent,
+                quote,
+                bullet: bullet.map(str::to_string),
+            };
+
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Text {
+                    spans: vec![text("Plan")],
+                    kind: TextKind::Heading(2),
+                    indent: 0,
+                    quote: 0,
+                    bullet: None,
+                },
+                paragraph(
+                    vec![
+                        text("Go "),
+                        ("now".to_string(), bold),
+                        text(" "),
+                        text("&"),
+                        text(" then"),
+                        text("\n"),
+                        text("later"),
+                    ],
+                    0,
+                    0,
+                    None
+                ),
+                Block::Blank,
+                paragraph(vec![text("One")], 1, 0, Some("1.")),
+                paragraph(vec![text("Two")], 1, 0, Some("2.")),
+                paragraph(vec![text("Sub")], 2, 0, Some("•")),
+                paragraph(vec![text("Quoted")], 0, 1, None),
+                Block::Text {
+                    spans: vec![("a\n  b".to_string(), mono)],
+                    kind: TextKind::Pre,
+                    indent: 0,
+                    quote: 0,
+                    bullet: None,
+                },
+                Block::Rule,
+                paragraph(
+                    vec![(
+                        "[Image]".to_string(),
+                        Style {
+                            italic: true,
+                            ..Default::default()
+                        }
+                    )],
+                    0,
+                    0,
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_breaking() {
+        let mut fonts = Fonts::Standard(StandardFont::Courier);
+        let mut images = Vec::new();
+        let geometry = Geometry {
+            width: 200.0,
+            height: 200.0,
+            margin: 20.0,
+            font_size: 10.0,
+        };
+        let mut builder = PageBuilder::new(&mut fonts, &mut images, geometry);
+
+        // Courier is 6 points per character at size 10: 26 characters per line
+        let lines = builder.break_lines(
+            &[
+                text("The quick brown fox jumps over the "),
+                ("lazy".to_string(), Style::BOLD),
+                text(" dog\nAbcdefghijklmnopqrstuvwxyz0123"),
+            ],
+            10.0,
+            160.0,
+        );
+        let texts: Vec<Vec<&str>> = lines
+            .iter()
+            .map(|line| line.iter().map(|run| run.text.as_str()).collect())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                vec!["The quick brown fox jumps"],
+                vec!["over the ", "lazy", " dog"],
+                vec!["Abcdefghijklmnopqrstuvwxyz"],
+                vec!["0123"],
+            ]
+        );
+        assert_eq!(lines[1][1].x, 54.0);
+    }
+}
@@ -0,0 +1,533 @@
+//! PDF export, written without a browser or PDF library.
+//!
+//! Entries are laid out as a book: a title page, a table of contents of
+//! years and months, then one section per month with a heading per date.
+//! Pages are numbered in the footer and the document has an outline
+//! (bookmarks) of months and dates.
+
+mod fonts;
+mod images;
+mod layout;
+mod writer;
+
+pub use fonts::StandardFont;
+
+use crate::db::queries::DiaryEntry;
+use chrono::{Datelike, NaiveDate};
+use fonts::{FontStyle, Fonts, TrueTypeFont};
+use images::PdfImage;
+use layout::{parse_blocks, Anchor, Geometry, Page, PageBuilder, Style, LINE_HEIGHT};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use writer::{number, text_string, ObjectId, PdfWriter};
+
+/// Paper size of the exported pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    A4,
+    A5,
+    Letter,
+}
+
+impl PageSize {
+    /// Width and height in points
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::A5 => (419.53, 595.28),
+            PageSize::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+/// Page and font settings chosen in the export dialog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfLayout {
+    pub page_size: PageSize,
+    /// Standard font, used when no font file is given
+    pub font: StandardFont,
+    /// TrueType font to embed, for text beyond Western European languages
+    pub font_file: Option<String>,
+    /// Body text size in points
+    pub font_size: f32,
+}
+
+impl Default for PdfLayout {
+    fn default() -> Self {
+        PdfLayout {
+            page_size: PageSize::default(),
+            font: StandardFont::default(),
+            font_file: None,
+            font_size: 11.0,
+        }
+    }
+}
+
+/// Where a month and its dates start in the body pages
+struct MonthSection {
+    title: String,
+    year: i32,
+    anchor: Anchor,
+    dates: Vec<(String, Anchor)>,
+}
+
+/// Exports diary entries as a PDF book
+///
+/// Each month starts on a new page. Entries are grouped under a heading
+/// for their date, followed by their title and content. Embedded JPEG and
+/// PNG images are included; other images are shown as "[Image]". Entries
+/// with an invalid date are left out.
+pub fn export_entries_to_pdf(
+    entries: Vec<DiaryEntry>,
+    layout: &PdfLayout,
+) -> Result<Vec<u8>, String> {
+    if !(6.0..=24.0).contains(&layout.font_size) {
+        return Err("Font size must be between 6 and 24 points".to_string());
+    }
+    let mut fonts = match layout.font_file.as_deref().filter(|path| !path.is_empty()) {
+        Some(path) => Fonts::TrueType(Box::new(TrueTypeFont::load(Path::new(path))?)),
+        None => Fonts::Standard(layout.font),
+    };
+
+    let mut days: BTreeMap<NaiveDate, Vec<&DiaryEntry>> = BTreeMap::new();
+    for entry in &entries {
+        match NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") {
+            Ok(date) => days.entry(date).or_default().push(entry),
+            Err(_) => warn!("Skipping entry with invalid date '{}'", entry.date),
+        }
+    }
+
+    let (width, height) = layout.page_size.dimensions();
+    let size = layout.font_size;
+    let geometry = Geometry {
+        width,
+        height,
+        margin: width * 0.1,
+        font_size: size,
+    };
+    let mut images = Vec::new();
+
+    // Body pages, numbered from zero until the front matter is known
+    let mut months: Vec<MonthSection> = Vec::new();
+    let body = {
+        let mut builder = PageBuilder::new(&mut fonts, &mut images, geometry);
+        for (date, day_entries) in &days {
+            let title = date.format("%B %Y").to_string();
+            if months.last().is_none_or(|month| month.title != title) {
+                builder.new_page();
+                let anchor = builder.heading(&title, size * 1.8, size * 3.0);
+                months.push(MonthSection {
+                    title,
+                    year: date.year(),
+                    anchor,
+                    dates: Vec::new(),
+                });
+            }
+            let label = date.format("%A, %-d %B %Y").to_string();
+            let anchor = builder.heading(&label, size * 1.3, size * LINE_HEIGHT * 2.0);
+            for entry in day_entries {
+                if !entry.title.is_empty() {
+                    builder.heading(&entry.title, size * 1.15, size * LINE_HEIGHT);
+                }
+                for block in parse_blocks(&entry.text) {
+                    builder.block(&block);
+                }
+                builder.space(size * 0.8);
+            }
+            if let Some(month) = months.last_mut() {
+                month.dates.push((label, anchor));
+            }
+        }
+        builder.pages
+    };
+
+    // The table of contents is laid out once to count its pages, then again
+    // with the final page numbers
+    let (front, toc) = if months.is_empty() {
+        (1, Vec::new())
+    } else {
+        let count = toc_pages(&mut fonts, &mut images, geometry, &months, 0).len();
+        let front = 1 + count;
+        (
+            front,
+            toc_pages(&mut fonts, &mut images, geometry, &months, front),
+        )
+    };
+
+    let mut pages = vec![title_page(&mut fonts, &mut images, geometry, &days)];
+    pages.extend(toc);
+    pages.extend(body);
+
+    // Page numbers, except on the title page
+    for (i, page) in pages.iter_mut().enumerate().skip(1) {
+        let label = (i + 1).to_string();
+        let label_width = fonts.width(&label, FontStyle::Regular, size * 0.8);
+        page.content.push_str(&format!(
+            "BT /{} {} Tf {} {} Td {} Tj ET\n",
+            fonts.resource(FontStyle::Regular),
+            number(size * 0.8),
+            number((width - label_width) / 2.0),
+            number(geometry.margin / 2.0),
+            fonts.encode(&label, FontStyle::Regular)
+        ));
+    }
+
+    let mut pdf = PdfWriter::new();
+    let catalog = pdf.reserve();
+    let page_tree = pdf.reserve();
+    let page_ids: Vec<ObjectId> = pages.iter().map(|_| pdf.reserve()).collect();
+    let destination = |anchor: Anchor, offset: usize| {
+        format!(
+            "[{} 0 R /XYZ null {} null]",
+            page_ids[anchor.page + offset],
+            number(anchor.y)
+        )
+    };
+
+    let resources = pdf.reserve();
+    let font_dict = fonts.write(&mut pdf);
+    let mut xobjects = String::new();
+    for (i, image) in images.iter().enumerate() {
+        let id = image.write(&mut pdf);
+        xobjects.push_str(&format!("/Im{} {} 0 R ", i, id));
+    }
+    pdf.object(
+        resources,
+        &format!("<< /Font {} /XObject << {}>> >>", font_dict, xobjects),
+    );
+
+    for (page, &id) in pages.iter().zip(&page_ids) {
+        let contents = pdf.reserve();
+        pdf.deflated_stream(contents, "", page.content.as_bytes());
+        let mut annotations = Vec::new();
+        for link in &page.links {
+            let annotation = pdf.reserve();
+            pdf.object(
+                annotation,
+                &format!(
+                    "<< /Type /Annot /Subtype /Link /Rect [{}] /Border [0 0 0] /Dest {} >>",
+                    link.rect.map(number).join(" "),
+                    // Table of contents links already point at final pages
+                    destination(link.target, 0)
+                ),
+            );
+            annotations.push(format!("{} 0 R", annotation));
+        }
+        pdf.object(
+            id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /Contents {} 0 R /Annots [{}] >>",
+                page_tree,
+                contents,
+                annotations.join(" ")
+            ),
+        );
+    }
+    pdf.object(
+        page_tree,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} /MediaBox [0 0 {} {}] /Resources {} 0 R >>",
+            page_ids
+                .iter()
+                .map(|id| format!("{} 0 R", id))
+                .collect::<Vec<_>>()
+                .join(" "),
+            page_ids.len(),
+            number(width),
+            number(height),
+            resources
+        ),
+    );
+
+    let mut catalog_dict = format!("<< /Type /Catalog /Pages {} 0 R", page_tree);
+    if !months.is_empty() {
+        let outline = write_outline(&mut pdf, &months, |anchor| destination(anchor, front));
+        catalog_dict.push_str(&format!(
+            " /Outlines {} 0 R /PageMode /UseOutlines",
+            outline
+        ));
+    }
+    catalog_dict.push_str(" >>");
+    pdf.object(catalog, &catalog_dict);
+
+    let info = pdf.reserve();
+    pdf.object(
+        info,
+        &format!(
+            "<< /Title {} /Producer {} >>",
+            text_string("Journal"),
+            text_string("Mini Diarium")
+        ),
+    );
+    Ok(pdf.finish(catalog, info))
+}
+
+/// "Journal", the covered dates and the number of entries
+fn title_page(
+    fonts: &mut Fonts,
+    images: &mut Vec<PdfImage>,
+    geometry: Geometry,
+    days: &BTreeMap<NaiveDate, Vec<&DiaryEntry>>,
+) -> Page {
+    let size = geometry.font_size;
+    let mut builder = PageBuilder::new(fonts, images, geometry);
+    builder.new_page();
+    builder.move_to(geometry.height * 0.62);
+    builder.centered("Journal", Style::BOLD, size * 2.6);
+    builder.space(size);
+    if let (Some(first), Some(last)) = (days.keys().next(), days.keys().next_back()) {
+        let format = "%-d %B %Y";
+        let range = if first == last {
+            first.format(format).to_string()
+        } else {
+            format!("{} – {}", first.format(format), last.format(format))
+        };
+        builder.centered(&range, Style::default(), size * 1.2);
+    }
+    let count: usize = days.values().map(Vec::len).sum();
+    let count = match count {
+        1 => "1 entry".to_string(),
+        n => format!("{} entries", n),
+    };
+    builder.centered(&count, Style::default(), size);
+    builder.pages.pop().unwrap_or_default()
+}
+
+/// Years and months with their page numbers. `front` is the number of
+/// pages before the body.
+fn toc_pages(
+    fonts: &mut Fonts,
+    images: &mut Vec<PdfImage>,
+    geometry: Geometry,
+    months: &[MonthSection],
+    front: usize,
+) -> Vec<Page> {
+    let size = geometry.font_size;
+    let mut builder = PageBuilder::new(fonts, images, geometry);
+    builder.new_page();
+    builder.heading("Contents", size * 1.8, size * 3.0);
+    let at = |anchor: Anchor| Anchor {
+        page: anchor.page + front,
+        ..anchor
+    };
+    let mut year = None;
+    for month in months {
+        if year != Some(month.year) {
+            year = Some(month.year);
+            builder.space(size * 0.5);
+            let page = (month.anchor.page + front + 1).to_string();
+            builder.toc_line(
+                &month.year.to_string(),
+                &page,
+                0.0,
+                Style::BOLD,
+                at(month.anchor),
+            );
+        }
+        let page = (month.anchor.page + front + 1).to_string();
+        builder.toc_line(
+            &month.title,
+            &page,
+            size * 1.8,
+            Style::default(),
+            at(month.anchor),
+        );
+    }
+    builder.pages
+}
+
+/// Writes bookmarks for every month with its dates below and returns the
+/// outline root
+fn write_outline(
+    pdf: &mut PdfWriter,
+    months: &[MonthSection],
+    destination: impl Fn(Anchor) -> String,
+) -> ObjectId {
+    let root = pdf.reserve();
+    let month_ids: Vec<ObjectId> = months.iter().map(|_| pdf.reserve()).collect();
+    for (i, month) in months.iter().enumerate() {
+        let date_ids: Vec<ObjectId> = month.dates.iter().map(|_| pdf.reserve()).collect();
+        for (j, (label, anchor)) in month.dates.iter().enumerate() {
+            pdf.object(
+                date_ids[j],
+                &outline_item(label, month_ids[i], &date_ids, j, &destination(*anchor), ""),
+            );
+        }
+        let children = match (date_ids.first(), date_ids.last()) {
+            (Some(first), Some(last)) => format!(
+                " /First {} 0 R /Last {} 0 R /Count -{}",
+                first,
+                last,
+                date_ids.len()
+            ),
+            _ => String::new(),
+        };
+        pdf.object(
+            month_ids[i],
+            &outline_item(
+                &month.title,
+                root,
+                &month_ids,
+                i,
+                &destination(month.anchor),
+                &children,
+            ),
+        );
+    }
+    pdf.object(
+        root,
+        &format!(
+            "<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>",
+            month_ids[0],
+            month_ids[month_ids.len() - 1],
+            month_ids.len()
+        ),
+    );
+    root
+}
+
+/// Dictionary of the `index`th of the `siblings` outline items
+fn outline_item(
+    title: &str,
+    parent: ObjectId,
+    siblings: &[ObjectId],
+    index: usize,
+    destination: &str,
+    children: &str,
+) -> String {
+    let mut dict = format!(
+        "<< /Title {} /Parent {} 0 R /Dest {}{}",
+        text_string(title),
+        parent,
+        destination,
+        children
+    );
+    if index > 0 {
+        dict.push_str(&format!(" /Prev {} 0 R", siblings[index - 1]));
+    }
+    if let Some(next) = siblings.get(index + 1) {
+        dict.push_str(&format!(" /Next {} 0 R", next));
+    }
+    dict.push_str(" >>");
+    dict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 1,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: 0,
+            date_created: "2024-01-01T00:00:00Z".to_string(),
+            date_updated: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    /// Page content streams, decompressed
+    fn page_contents(pdf: &[u8]) -> Vec<String> {
+        use flate2::read::ZlibDecoder;
+        use std::io::Read;
+
+        let mut contents = Vec::new();
+        let mut rest = pdf;
+        let marker = b"<< /Filter /FlateDecode /Length ";
+        while let Some(start) = rest.windows(marker.len()).position(|w| w == marker) {
+            let after = &rest[start + marker.len()..];
+            let digits = after.iter().take_while(|b| b.is_ascii_digit()).count();
+            let length: usize = std::str::from_utf8(&after[..digits])
+                .unwrap()
+                .parse()
+                .unwrap();
+            let data_start = digits + " >>\nstream\n".len();
+            let mut text = String::new();
+            ZlibDecoder::new(&after[data_start..data_start + length])
+                .read_to_string(&mut text)
+                .unwrap();
+            contents.push(text);
+            rest = &after[data_start + length..];
+        }
+        contents
+    }
+
+    #[test]
+    fn test_export_book_structure() {
+        let entries = vec![
+            create_test_entry("2024-02-03", "Later", "<p>Second month</p>"),
+            create_test_entry("2024-01-15", "Skiing", "<p>Cold <em>and</em> bright</p>"),
+            create_test_entry("2024-01-15", "", "<p>Evening</p>"),
+            create_test_entry("not-a-date", "Broken", "<p>Skipped</p>"),
+        ];
+        let pdf = export_entries_to_pdf(entries, &PdfLayout::default()).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.7"));
+        assert!(text.ends_with("%%EOF\n"));
+        // Title page, contents, January and February
+        assert!(text.contains("/Type /Pages /Kids [3 0 R 4 0 R 5 0 R 6 0 R] /Count 4"));
+        assert!(text.contains("/MediaBox [0 0 595.28 841.89]"));
+        assert!(text.contains("/PageMode /UseOutlines"));
+        assert!(text.contains(&format!("/Title {}", text_string("January 2024"))));
+        assert!(text.contains(&format!(
+            "/Title {}",
+            text_string("Monday, 15 January 2024")
+        )));
+        assert!(text.contains("/Subtype /Link"));
+
+        let contents = page_contents(&pdf);
+        assert_eq!(contents.len(), 4);
+        assert!(contents[0].contains("(Journal)"));
+        assert!(contents[0].contains("(15 January 2024 \\226 3 February 2024)"));
+        assert!(contents[0].contains("(3 entries)"));
+        assert!(!contents[0].contains("(1) Tj"));
+        assert!(contents[1].contains("(Contents)"));
+        assert!(contents[1].contains("(January 2024)"));
+        assert!(contents[1].contains("(3)"));
+        assert!(contents[1].contains("(4)"));
+        assert!(contents[2].contains("(Monday, 15 January 2024)"));
+        assert!(contents[2].contains("(Skiing)"));
+        assert!(contents[2].contains("/F3"));
+        assert!(contents[2].contains("(Evening)"));
+        assert!(!contents[2].contains("Skipped"));
+        assert!(contents[3].contains("(Second month)"));
+        assert!(contents[3].contains("(4) Tj"));
+    }
+
+    #[test]
+    fn test_layout_options() {
+        let layout: PdfLayout =
+            serde_json::from_str(r#"{"page_size":"letter","font":"times","font_size":10}"#)
+                .unwrap();
+        assert_eq!(layout.page_size, PageSize::Letter);
+        assert_eq!(layout.font_file, None);
+
+        let pdf = export_entries_to_pdf(vec![], &layout).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/MediaBox [0 0 612 792]"));
+        assert!(text.contains("/BaseFont /Times-Roman"));
+        assert!(text.contains("/Count 1"));
+        assert!(!text.contains("/Outlines"));
+
+        let too_small = PdfLayout {
+            font_size: 4.0,
+            ..PdfLayout::default()
+        };
+        assert!(export_entries_to_pdf(vec![], &too_small).is_err());
+        let missing_font = PdfLayout {
+            font_file: Some("/nonexistent/font.ttf".to_string()),
+            ..PdfLayout::default()
+        };
+        assert!(export_entries_to_pdf(vec![], &missing_font)
+            .unwrap_err()
+            .starts_with("Failed to read font file"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Serialization of PDF objects.
+//!
+//! Objects are written in any order once their IDs are reserved, so pages
+//! can refer to fonts and outlines to pages before those are written.
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// Reference to an indirect object
+pub type ObjectId = usize;
+
+pub struct PdfWriter {
+    buf: Vec<u8>,
+    /// Byte offset of each object, by ID (ID 0 is unused)
+    offsets: Vec<Option<usize>>,
+}
+
+impl PdfWriter {
+    pub fn new() -> Self {
+        let mut buf = Vec::new();
+        // The comment with bytes above 127 marks the file as binary
+        buf.extend_from_slice(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n");
+        PdfWriter {
+            buf,
+            offsets: vec![None],
+        }
+    }
+
+    /// Reserves the ID of an object to be written later
+    pub fn reserve(&mut self) -> ObjectId {
+        self.offsets.push(None);
+        self.offsets.len() - 1
+    }
+
+    /// Writes an object whose body is `body`, e.g. a dictionary `<< … >>`
+    pub fn object(&mut self, id: ObjectId, body: &str) {
+        self.offsets[id] = Some(self.buf.len());
+        self.buf
+            .extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", id, body).as_bytes());
+    }
+
+    /// Writes a stream object. `dict` holds the entries besides `/Length`.
+    pub fn stream(&mut self, id: ObjectId, dict: &str, data: &[u8]) {
+        self.offsets[id] = Some(self.buf.len());
+        self.buf.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< {} /Length {} >>\nstream\n",
+                id,
+                dict,
+                data.len()
+            )
+            .as_bytes(),
+        );
+        self.buf.extend_from_slice(data);
+        self.buf.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    /// Writes a stream compressed with `/FlateDecode`
+    pub fn deflated_stream(&mut self, id: ObjectId, dict: &str, data: &[u8]) {
+        let dict = if dict.is_empty() {
+            "/Filter /FlateDecode".to_string()
+        } else {
+            format!("{} /Filter /FlateDecode", dict)
+        };
+        self.stream(id, &dict, &deflate(data));
+    }
+
+    /// Writes the cross-reference table and trailer and returns the file
+    pub fn finish(mut self, catalog: ObjectId, info: ObjectId) -> Vec<u8> {
+        let xref_offset = self.buf.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len());
+        for offset in &self.offsets[1..] {
+            match offset {
+                Some(offset) => xref.push_str(&format!("{:010} 00000 n \n", offset)),
+                None => xref.push_str("0000000000 65535 f \n"),
+            }
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len(),
+            catalog,
+            info,
+            xref_offset
+        ));
+        self.buf.extend_from_slice(xref.as_bytes());
+        self.buf
+    }
+}
+
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+/// A text string for outlines and document info, as UTF-16 with a byte
+/// order mark so any script is shown correctly
+pub fn text_string(text: &str) -> String {
+    let mut hex = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        hex.push_str(&format!("{:04X}", unit));
+    }
+    hex.push('>');
+    hex
+}
+
+/// A number with at most two decimals, e.g. `12.5` or `3`
+pub fn number(value: f32) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded == rounded.trunc() {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_reference_offsets() {
+        let mut pdf = PdfWriter::new();
+        let catalog = pdf.reserve();
+        let info = pdf.reserve();
+        pdf.object(info, "<< /Title (T) >>");
+        pdf.object(catalog, "<< /Type /Catalog >>");
+        let bytes = pdf.finish(catalog, info);
+        let text = String::from_utf8_lossy(&bytes);
+
+        let catalog_offset = bytes.windows(7).position(|w| w == b"1 0 obj").unwrap();
+        assert!(text.contains(&format!(
+            "xref\n0 3\n0000000000 65535 f \n{:010} 00000 n \n",
+            catalog_offset
+        )));
+        assert!(text.ends_with("%%EOF\n"));
+        assert_eq!(text_string("Aé"), "<FEFF004100E9>");
+        assert_eq!(number(12.345), "12.35");
+        assert_eq!(number(3.0), "3");
+    }
+}
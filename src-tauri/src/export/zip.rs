@@ -0,0 +1,144 @@
+//! Writing ZIP archives for exports such as EPUB.
+//!
+//! Archives are built in memory without ZIP64, so the archive and every
+//! file in it must stay under 4 GB.
+
+use chrono::{Datelike, Local, Timelike};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+/// General purpose flag: file names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+const TOO_LARGE: &str = "The export is too large for a ZIP archive";
+
+/// A ZIP archive being written to memory
+pub struct ZipWriter {
+    out: Vec<u8>,
+    central: Vec<u8>,
+    count: u16,
+    /// Modification time of every file, in MS-DOS format
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        let now = Local::now();
+        let year = now.year().clamp(1980, 2107) as u16;
+        ZipWriter {
+            out: Vec::new(),
+            central: Vec::new(),
+            count: 0,
+            dos_time: (now.hour() as u16) << 11
+                | (now.minute() as u16) << 5
+                | (now.second() as u16 / 2),
+            dos_date: (year - 1980) << 9 | (now.month() as u16) << 5 | now.day() as u16,
+        }
+    }
+
+    /// Adds a file, DEFLATE-compressed if `compress` is set
+    pub fn add(&mut self, name: &str, data: &[u8], compress: bool) -> Result<(), String> {
+        let (method, stored) = if compress {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(data)
+                .and_then(|_| encoder.finish())
+                .map(|compressed| (METHOD_DEFLATE, compressed))
+                .map_err(|e| format!("Failed to compress '{}': {}", name, e))?
+        } else {
+            (METHOD_STORED, data.to_vec())
+        };
+        let offset = u32::try_from(self.out.len()).map_err(|_| TOO_LARGE)?;
+        let size = u32::try_from(data.len()).map_err(|_| TOO_LARGE)?;
+        let compressed_size = u32::try_from(stored.len()).map_err(|_| TOO_LARGE)?;
+        let name_len = u16::try_from(name.len()).map_err(|_| TOO_LARGE)?;
+        self.count = self.count.checked_add(1).ok_or(TOO_LARGE)?;
+
+        // Fields shared by the local and central headers, from "version needed"
+        let mut fields = Vec::with_capacity(26);
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        fields.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&self.dos_time.to_le_bytes());
+        fields.extend_from_slice(&self.dos_date.to_le_bytes());
+        fields.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        fields.extend_from_slice(&compressed_size.to_le_bytes());
+        fields.extend_from_slice(&size.to_le_bytes());
+        fields.extend_from_slice(&name_len.to_le_bytes());
+        // No extra field
+        fields.extend_from_slice(&0u16.to_le_bytes());
+
+        self.out
+            .extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        self.out.extend_from_slice(&fields);
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(&stored);
+
+        self.central
+            .extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        // Version made by
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        self.central.extend_from_slice(&fields);
+        // Comment length, disk number, internal and external attributes
+        self.central.extend_from_slice(&[0; 10]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the archive
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        let directory_offset = u32::try_from(self.out.len()).map_err(|_| TOO_LARGE)?;
+        let directory_size = u32::try_from(self.central.len()).map_err(|_| TOO_LARGE)?;
+        self.out.extend_from_slice(&self.central);
+        self.out.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        // This disk and the disk with the central directory
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&directory_size.to_le_bytes());
+        self.out.extend_from_slice(&directory_offset.to_le_bytes());
+        // Comment length
+        self.out.extend_from_slice(&0u16.to_le_bytes());
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::ZipReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_archive_can_be_read_back() {
+        let mut zip = ZipWriter::new();
+        zip.add("mimetype", b"application/epub+zip", false).unwrap();
+        zip.add("OEBPS/ch\u{e4}pter.xhtml", &[b'a'; 5000], true)
+            .unwrap();
+        let bytes = zip.finish().unwrap();
+
+        // A stored first file sits right after its 30-byte header
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..58], b"application/epub+zip");
+
+        let mut reader = ZipReader::new(Cursor::new(bytes)).unwrap();
+        let names: Vec<&str> = reader.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["mimetype", "OEBPS/ch\u{e4}pter.xhtml"]);
+        assert_eq!(reader.read(1, 5000).unwrap(), vec![b'a'; 5000]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::JournalSettings;
+use crate::crypto::{cipher, password::KdfParams};
+use crate::db::queries::DiaryEntry;
+use crate::export::archive::{ArchiveEnvelope, ArchivePayload, ARCHIVE_FORMAT, ARCHIVE_VERSION};
+use base64::{engine::general_purpose, Engine as _};
+use zeroize::Zeroizing;
+
+/// Upper bounds on the Argon2id parameters an archive may ask for, so a
+/// crafted file cannot make the importer allocate gigabytes or spin for minutes.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024; // 1 GB
+const MAX_ITERATIONS: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+
+/// Parse a passphrase-encrypted `.mdarchive` document into diary entries and
+/// the settings of the journal they came from, if the archive holds them.
+///
+/// Entries keep their title, text, word count and creation/update times.
+/// Returns `Err("Incorrect passphrase ...")` when the passphrase is wrong or
+/// the archive was modified.
+pub fn parse_archive(
+    content: &str,
+    passphrase: &str,
+) -> Result<(Vec<DiaryEntry>, Option<JournalSettings>), String> {
+    let envelope: ArchiveEnvelope =
+        serde_json::from_str(content).map_err(|e| format!("Not a Mini Diarium archive: {}", e))?;
+    if envelope.format != ARCHIVE_FORMAT {
+        return Err("Not a Mini Diarium archive".to_string());
+    }
+    if envelope.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Archive version {} is newer than this app supports ({})",
+            envelope.version, ARCHIVE_VERSION
+        ));
+    }
+    if envelope.kdf.algorithm != "argon2id" {
+        return Err(format!(
+            "Unsupported archive key derivation: {}",
+            envelope.kdf.algorithm
+        ));
+    }
+    check_kdf_params(&envelope.kdf.params)?;
+
+    let salt = general_purpose::STANDARD
+        .decode(&envelope.kdf.salt)
+        .map_err(|e| format!("Invalid archive salt: {}", e))?;
+    let ciphertext = general_purpose::STANDARD
+        .decode(&envelope.payload)
+        .map_err(|e| format!("Invalid archive payload: {}", e))?;
+
+    let key_bytes =
+        crate::crypto::password::derive_key(passphrase.as_bytes(), &salt, &envelope.kdf.params)
+            .map_err(|e| e.to_string())?;
+    let key = cipher::Key::from_slice(&key_bytes).ok_or("Invalid archive key size")?;
+    let plaintext = Zeroizing::new(
+        cipher::decrypt(&key, &ciphertext)
+            .map_err(|_| "Incorrect passphrase, or the archive is damaged".to_string())?,
+    );
+
+    let payload: ArchivePayload = serde_json::from_slice(&plaintext)
+        .map_err(|e| format!("Failed to read archive contents: {}", e))?;
+    Ok((payload.entries, payload.journal))
+}
+
+fn check_kdf_params(params: &KdfParams) -> Result<(), String> {
+    if params.memory_kib > MAX_MEMORY_KIB
+        || params.iterations > MAX_ITERATIONS
+        || params.parallelism > MAX_PARALLELISM
+    {
+        return Err("Archive key derivation parameters are out of range".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::archive::tests::{export_for_test, export_journal_for_test};
+
+    fn create_test_entry(date: &str, title: &str, text: &str) -> DiaryEntry {
+        DiaryEntry {
+            id: 3,
+            date: date.to_string(),
+            title: title.to_string(),
+            text: text.to_string(),
+            word_count: crate::db::queries::count_words(text),
+            date_created: "2023-05-01T08:00:00Z".to_string(),
+            date_updated: "2023-06-01T09:30:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let image = "<p><img src=\"data:image/png;base64,iVBORw0KGgo=\"></p>";
+        let entries = vec![
+            create_test_entry("2023-05-01", "First", "<p>Hello world</p>"),
+            create_test_entry("2023-05-01", "Photo", image),
+        ];
+        let journal = JournalSettings {
+            name: "Travel".to_string(),
+            incremental_backups: true,
+            backup_interval_minutes: Some(60),
+            ..Default::default()
+        };
+        let archive = export_journal_for_test(entries.clone(), journal.clone(), "correct horse");
+
+        let (parsed, parsed_journal) = parse_archive(&archive, "correct horse").unwrap();
+        assert_eq!(parsed_journal, Some(journal));
+        assert_eq!(parsed.len(), 2);
+        for (a, e) in parsed.iter().zip(&entries) {
+            assert_eq!(
+                (&a.date, &a.title, &a.text, a.word_count),
+                (&e.date, &e.title, &e.text, e.word_count)
+            );
+            assert_eq!(
+                (&a.date_created, &a.date_updated),
+                (&e.date_created, &e.date_updated)
+            );
+        }
+    }
+
+    #[test]
+    fn test_archive_without_journal_section() {
+        let archive = export_for_test(vec![create_test_entry("2023-05-01", "T", "x")], "pw");
+        let (parsed, journal) = parse_archive(&archive, "pw").unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert!(journal.is_none());
+    }
+
+    #[test]
+    fn test_archive_wrong_passphrase() {
+        let archive = export_for_test(vec![create_test_entry("2023-05-01", "T", "x")], "right");
+        let err = parse_archive(&archive, "wrong").unwrap_err();
+        assert!(err.contains("Incorrect passphrase"));
+    }
+
+    #[test]
+    fn test_archive_tampered_payload() {
+        let archive = export_for_test(vec![create_test_entry("2023-05-01", "T", "x")], "pw");
+        let mut envelope: ArchiveEnvelope = serde_json::from_str(&archive).unwrap();
+        let mut bytes = general_purpose::STANDARD.decode(&envelope.payload).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        envelope.payload = general_purpose::STANDARD.encode(bytes);
+        let tampered = serde_json::to_string(&envelope).unwrap();
+
+        assert!(parse_archive(&tampered, "pw").is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(parse_archive("{\"entries\": []}", "pw").is_err());
+        assert!(parse_archive("not json", "pw").is_err());
+    }
+
+    #[test]
+    fn test_rejects_excessive_kdf_params() {
+        let archive = export_for_test(vec![], "pw");
+        let mut envelope: ArchiveEnvelope = serde_json::from_str(&archive).unwrap();
+        envelope.kdf.params.memory_kib = u32::MAX;
+        let crafted = serde_json::to_string(&envelope).unwrap();
+
+        let err = parse_archive(&crafted, "pw").unwrap_err();
+        assert!(err.contains("out of range"));
+    }
+}
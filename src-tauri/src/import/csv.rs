@@ -0,0 +1,175 @@
+//! CSV reading for spreadsheet-style exports.
+//!
+//! Follows RFC 4180: fields may be quoted with `"`, quoted fields can hold
+//! delimiters, line breaks and doubled quotes (`""`). Exports from different
+//! locales use `,`, `;` or tabs as the delimiter, so it is detected from the
+//! header line. Columns are looked up by header name, which lets importers
+//! map columns instead of relying on their position.
+
+/// A CSV file with a header line
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    /// Parses CSV text whose first line names the columns. A UTF-8 byte
+    /// order mark and blank lines are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let delimiter = detect_delimiter(text);
+        let mut rows = parse_rows(text, delimiter)?.into_iter();
+        let headers = rows
+            .next()
+            .ok_or("The CSV file is empty")?
+            .into_iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+        Ok(CsvTable {
+            headers,
+            rows: rows.collect(),
+        })
+    }
+
+    /// Index of the column with this header, ignoring case
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+    }
+
+    /// Value of the named column in `row`, or `None` if the column does not
+    /// exist or the row is too short
+    pub fn get<'a>(&self, row: &'a [String], name: &str) -> Option<&'a str> {
+        row.get(self.column(name)?).map(String::as_str)
+    }
+}
+
+/// Picks `,`, `;` or tab, whichever appears most often outside quotes in the
+/// first line. Defaults to `,`.
+pub fn detect_delimiter(text: &str) -> char {
+    let mut counts = [(',', 0), (';', 0), ('\t', 0)];
+    let mut in_quotes = false;
+    for c in text.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\n' | '\r' if !in_quotes => break,
+            _ if !in_quotes => {
+                if let Some((_, count)) = counts.iter_mut().find(|(d, _)| *d == c) {
+                    *count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+        .iter()
+        .fold(
+            (',', 0),
+            |best, &(d, n)| if n > best.1 { (d, n) } else { best },
+        )
+        .0
+}
+
+/// Splits CSV text into rows of fields. Line breaks may be `\n` or `\r\n`.
+/// Rows with no content are skipped.
+pub fn parse_rows(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    // Line where the open quoted field started, for error messages
+    let mut quote_start: Option<usize> = None;
+
+    while let Some(c) = chars.next() {
+        if quote_start.is_some() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quote_start = None,
+                '\r' if chars.peek() == Some(&'\n') => {}
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            // A quote only opens a quoted field at its start
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quote_start = Some(line);
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                row.push(std::mem::take(&mut field));
+                push_row(&mut rows, std::mem::take(&mut row));
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    if let Some(start) = quote_start {
+        return Err(format!(
+            "Failed to parse CSV: quoted field starting on line {} is never closed",
+            start
+        ));
+    }
+    row.push(field);
+    push_row(&mut rows, row);
+    Ok(rows)
+}
+
+fn push_row(rows: &mut Vec<Vec<String>>, row: Vec<String>) {
+    if row.iter().any(|field| !field.trim().is_empty()) {
+        rows.push(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoted_fields() {
+        let rows = parse_rows(
+            "a,b,c\r\n\"x, y\",\"say \"\"hi\"\"\",\"two\nlines\"\n\n1,,3",
+            ',',
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["x, y", "say \"hi\"", "two\nlines"],
+                vec!["1", "", "3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_quote() {
+        let err = parse_rows("a,b\n1,\"open\n2,3", ',').unwrap_err();
+        assert!(err.contains("line 2"));
+    }
+
+    #[test]
+    fn test_table_with_detected_delimiter() {
+        let table = CsvTable::parse("\u{feff}Date;Note;Mood\n2024-01-15;\"a;b\";good\n").unwrap();
+        assert_eq!(table.headers, vec!["Date", "Note", "Mood"]);
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(table.get(&table.rows[0], "note"), Some("a;b"));
+        assert_eq!(table.get(&table.rows[0], "missing"), None);
+        assert_eq!(detect_delimiter("a\tb\tc,d"), '\t');
+        assert!(CsvTable::parse("").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use super::csv::CsvTable;
+use super::html::text_to_html;
+use super::metadata::ImportedMetadata;
+use crate::db::queries::{count_words, DiaryEntry};
+use chrono::{NaiveDate, NaiveTime, SecondsFormat};
+use log::warn;
+
+/// Order of day and month in slash-separated dates, which depends on the
+/// phone's locale
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateOrder {
+    DayFirst,
+    MonthFirst,
+}
+
+/// Parse a Daylio CSV export
+///
+/// Expected format:
+/// ```csv
+/// full_date,date,weekday,time,mood,activities,note_title,note
+/// 2024-01-15,January 15,Monday,8:30 PM,rad,friends | movies,Movie night,"Great film<br>Late dinner"
+/// ```
+///
+/// - `full_date` may also be written the locale's way, e.g. `15.01.2024`,
+///   `15/01/2024` or `01/15/2024`. For slashes, the order is worked out from
+///   all rows (a first number above 12 means day first); US order is assumed
+///   when every date is ambiguous
+/// - `time` may be 24-hour (`20:30`) or 12-hour (`8:30 PM`)
+/// - The mood and activities are appended to the text, activities as
+///   `#hashtags`
+/// - Several moods on one day become separate entries
+/// - A row whose date cannot be read is logged and returned without title or
+///   text, so the import counts it as skipped instead of failing
+pub fn parse_daylio_csv(content: &str) -> Result<Vec<DiaryEntry>, String> {
+    let table = CsvTable::parse(content)?;
+    if table.column("full_date").is_none() || table.column("mood").is_none() {
+        return Err(
+            "Not a Daylio export: the CSV file needs 'full_date' and 'mood' columns".to_string(),
+        );
+    }
+
+    let order = detect_date_order(
+        table
+            .rows
+            .iter()
+            .filter_map(|row| table.get(row, "full_date")),
+    );
+
+    Ok(table
+        .rows
+        .iter()
+        .map(|row| {
+            let full_date = table.get(row, "full_date").unwrap_or("").trim();
+            let Some(date) = parse_date(full_date, order) else {
+                warn!("Skipping Daylio row with an invalid date '{}'", full_date);
+                return DiaryEntry {
+                    id: 0,
+                    date: full_date.to_string(),
+                    title: String::new(),
+                    text: String::new(),
+                    word_count: 0,
+                    date_created: String::new(),
+                    date_updated: String::new(),
+                };
+            };
+            let time = table
+                .get(row, "time")
+                .and_then(parse_time)
+                .unwrap_or(NaiveTime::MIN);
+            let timestamp = date
+                .and_time(time)
+                .and_utc()
+                .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+            let note = table.get(row, "note").unwrap_or("");
+            let mut text = text_to_html(&note.replace("<br>", "\n").replace("<br/>", "\n"));
+            let metadata = ImportedMetadata {
+                tags: table
+                    .get(row, "activities")
+                    .unwrap_or("")
+                    .split('|')
+                    .map(str::trim)
+                    .filter(|activity| !activity.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                mood: table
+                    .get(row, "mood")
+                    .map(str::trim)
+                    .filter(|mood| !mood.is_empty())
+                    .map(str::to_string),
+                ..Default::default()
+            };
+            text.push_str(&metadata.to_html());
+
+            DiaryEntry {
+                id: 0,
+                date: date.format("%Y-%m-%d").to_string(),
+                title: table
+                    .get(row, "note_title")
+                    .unwrap_or("")
+                    .trim()
+                    .to_string(),
+                word_count: count_words(&text),
+                text,
+                date_created: timestamp.clone(),
+                date_updated: timestamp,
+            }
+        })
+        .collect())
+}
+
+fn detect_date_order<'a>(dates: impl Iterator<Item = &'a str>) -> DateOrder {
+    for date in dates {
+        let parts: Vec<u32> = date
+            .trim()
+            .split('/')
+            .filter_map(|p| p.parse().ok())
+            .collect();
+        if let [first, second, _] = parts[..] {
+            if first > 12 && first <= 31 {
+                return DateOrder::DayFirst;
+            }
+            if second > 12 && second <= 31 {
+                return DateOrder::MonthFirst;
+            }
+        }
+    }
+    DateOrder::MonthFirst
+}
+
+fn parse_date(value: &str, order: DateOrder) -> Option<NaiveDate> {
+    let slash_format = match order {
+        DateOrder::DayFirst => "%d/%m/%Y",
+        DateOrder::MonthFirst => "%m/%d/%Y",
+    };
+    ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", slash_format]
+        .into_iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    let value = value.trim().to_uppercase();
+    ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p"]
+        .into_iter()
+        .find_map(|format| NaiveTime::parse_from_str(&value, format).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_daylio_export() {
+        let csv = "full_date,date,weekday,time,mood,activities,note_title,note\n\
+                   2024-01-15,January 15,Monday,8:30 PM,rad,friends | movies,Movie night,\"Great film, really<br>Late dinner\"\n\
+                   2024-01-15,January 15,Monday,07:05,meh,,,\n";
+        let entries = parse_daylio_csv(csv).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[0];
+        assert_eq!(entry.date, "2024-01-15");
+        assert_eq!(entry.title, "Movie night");
+        assert_eq!(entry.date_created, "2024-01-15T20:30:00Z");
+        assert_eq!(
+            entry.text,
+            "<p>Great film, really<br>Late dinner</p><p>#friends #movies</p>\
+             <p><em>Mood: rad</em></p>"
+        );
+        assert_eq!(entry.word_count, 9);
+        assert_eq!(entries[1].date_created, "2024-01-15T07:05:00Z");
+        assert_eq!(entries[1].text, "<p><em>Mood: meh</em></p>");
+    }
+
+    #[test]
+    fn test_locale_dates_and_delimiter() {
+        let csv = "full_date;date;weekday;time;mood;activities;note\n\
+                   05/01/2024;5 Jan;Fri;21:00;gut;Sport;\n\
+                   25/01/2024;25 Jan;Thu;21:00;gut;;\n";
+        let entries = parse_daylio_csv(csv).unwrap();
+        assert_eq!(entries[0].date, "2024-01-05");
+        assert_eq!(entries[1].date, "2024-01-25");
+
+        let csv = "full_date,time,mood\n01/05/2024,9:00 am,good\n15.02.2024,,bad\n";
+        let entries = parse_daylio_csv(csv).unwrap();
+        assert_eq!(entries[0].date, "2024-01-05");
+        assert_eq!(entries[0].date_created, "2024-01-05T09:00:00Z");
+        assert_eq!(entries[1].date, "2024-02-15");
+        assert_eq!(entries[1].date_created, "2024-02-15T00:00:00Z");
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(parse_daylio_csv("date,text\n2024-01-01,hi")
+            .unwrap_err()
+            .contains("Not a Daylio export"));
+    }
+
+    #[test]
+    fn test_invalid_date_row_is_skipped() {
+        let entries = parse_daylio_csv("full_date,mood\nsoon,good\n2024-01-02,bad").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "");
+        assert_eq!(entries[0].text, "");
+        assert_eq!(entries[1].date, "2024-01-02");
+
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        let db = crate::db::schema::create_database(db_file.path(), "pw".to_string()).unwrap();
+        let result = crate::commands::import::import_entries(
+            &db,
+            entries,
+            crate::commands::import::ImportMode::Append,
+        )
+        .unwrap();
+        assert_eq!(result.entries_imported, 1);
+        assert_eq!(result.entries_skipped, 1);
+    }
+}
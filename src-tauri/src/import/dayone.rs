@@ -1,5 +1,7 @@
+use super::json_stream;
 use crate::db::queries::DiaryEntry;
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 
 /// Day One JSON export format
 ///
@@ -29,6 +31,10 @@ pub struct DayOneJson {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DayOneEntry {
+    /// Unique ID of the entry, 32 uppercase hex digits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+
     /// ISO 8601 timestamp with timezone
     pub creation_date: String,
 
@@ -38,6 +44,103 @@ pub struct DayOneEntry {
     /// Optional timezone identifier
     #[serde(skip_serializing_if = "Option::is_none")]
     pub time_zone: Option<String>,
+
+    /// ISO 8601 timestamp of the last edit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_date: Option<String>,
+
+    #[serde(default)]
+    pub starred: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<DayOneLocation>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<DayOneWeather>,
+
+    /// Photos referenced from the text as `dayone-moment://<identifier>`.
+    /// Only ZIP exports include the image files themselves.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub photos: Vec<DayOnePhoto>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayOneLocation {
+    pub place_name: Option<String>,
+    pub locality_name: Option<String>,
+    pub administrative_area: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl DayOneLocation {
+    /// Human-readable place, e.g. "Café Central, Vienna, Austria", falling
+    /// back to coordinates when the location has no names.
+    pub fn describe(&self) -> Option<String> {
+        let mut parts: Vec<&str> = Vec::new();
+        for part in [
+            &self.place_name,
+            &self.locality_name,
+            &self.administrative_area,
+            &self.country,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let part = part.trim();
+            if !part.is_empty() && !parts.contains(&part) {
+                parts.push(part);
+            }
+        }
+        if !parts.is_empty() {
+            return Some(parts.join(", "));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => Some(format!("{:.4}, {:.4}", lat, lon)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayOneWeather {
+    pub conditions_description: Option<String>,
+    pub temperature_celsius: Option<f64>,
+}
+
+impl DayOneWeather {
+    /// e.g. "Partly Cloudy, 18 °C"
+    pub fn describe(&self) -> Option<String> {
+        let conditions = self
+            .conditions_description
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty());
+        let temperature = self.temperature_celsius.map(|t| format!("{:.0} °C", t));
+        match (conditions, temperature) {
+            (Some(c), Some(t)) => Some(format!("{}, {}", c, t)),
+            (Some(c), None) => Some(c.to_string()),
+            (None, t) => t,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayOnePhoto {
+    /// Referenced from the entry text as `dayone-moment://<identifier>`
+    pub identifier: String,
+    /// Name of the image file in the export's `photos/` folder, without extension
+    pub md5: Option<String>,
+    /// File type, e.g. "jpeg" or "png"
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
 }
 
 /// Parse Day One JSON export file
@@ -59,37 +162,53 @@ pub struct DayOneEntry {
 /// * JSON parse error
 /// * Invalid date format
 pub fn parse_dayone_json(json_str: &str) -> Result<Vec<DiaryEntry>, String> {
-    // Parse JSON
-    let dayone: DayOneJson = serde_json::from_str(json_str)
-        .map_err(|e| format!("Failed to parse Day One JSON: {}", e))?;
-
     let mut entries = Vec::new();
+    parse_dayone_json_reader(json_str.as_bytes(), &mut |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
 
-    for entry in dayone.entries {
-        // Parse ISO 8601 timestamp to extract date (YYYY-MM-DD)
-        let date = parse_iso8601_to_date(&entry.creation_date)?;
-
-        // Extract title and text from content
-        let (title, text) = extract_title_and_text(&entry.text);
-
-        // Calculate word count
-        let word_count = text.split_whitespace().count() as i32;
-
-        // Parse full timestamp for date_created and date_updated
-        let timestamp = parse_iso8601_to_timestamp(&entry.creation_date)?;
-
-        entries.push(DiaryEntry {
-            id: 0,
-            date,
-            title,
-            text,
-            word_count,
-            date_created: timestamp.clone(),
-            date_updated: timestamp,
-        });
-    }
+/// Streaming variant of [`parse_dayone_json`]: reads the export from `reader`
+/// and calls `on_entry` for each entry as soon as it is parsed, so memory use
+/// does not grow with the size of the file.
+pub fn parse_dayone_json_reader<R: Read>(
+    reader: R,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    json_stream::for_each_array_item(
+        reader,
+        "entries",
+        "Failed to parse Day One JSON",
+        |entry: DayOneEntry| on_entry(convert_entry(entry)?),
+    )?;
+    Ok(())
+}
 
-    Ok(entries)
+/// Converts one Day One entry to a `DiaryEntry`
+fn convert_entry(entry: DayOneEntry) -> Result<DiaryEntry, String> {
+    // Parse ISO 8601 timestamp to extract date (YYYY-MM-DD)
+    let date = parse_iso8601_to_date(&entry.creation_date)?;
+
+    // Extract title and text from content
+    let (title, text) = extract_title_and_text(&entry.text);
+
+    // Calculate word count
+    let word_count = text.split_whitespace().count() as i32;
+
+    // Parse full timestamp for date_created and date_updated
+    let timestamp = parse_iso8601_to_timestamp(&entry.creation_date)?;
+
+    Ok(DiaryEntry {
+        id: 0,
+        date,
+        title,
+        text,
+        word_count,
+        date_created: timestamp.clone(),
+        date_updated: timestamp,
+    })
 }
 
 /// Parse ISO 8601 timestamp to YYYY-MM-DD date string
@@ -100,7 +219,7 @@ pub fn parse_dayone_json(json_str: &str) -> Result<Vec<DiaryEntry>, String> {
 /// - 2024-01-15T14:30:00.123Z (with milliseconds)
 ///
 /// Converts to local date based on timezone offset.
-fn parse_iso8601_to_date(iso_str: &str) -> Result<String, String> {
+pub(crate) fn parse_iso8601_to_date(iso_str: &str) -> Result<String, String> {
     // Parse using chrono
     use chrono::{DateTime, Utc};
 
@@ -115,7 +234,7 @@ fn parse_iso8601_to_date(iso_str: &str) -> Result<String, String> {
 /// Parse ISO 8601 timestamp to standard timestamp format
 ///
 /// Converts ISO 8601 to our standard format: YYYY-MM-DD HH:MM:SS
-fn parse_iso8601_to_timestamp(iso_str: &str) -> Result<String, String> {
+pub(crate) fn parse_iso8601_to_timestamp(iso_str: &str) -> Result<String, String> {
     use chrono::{DateTime, Utc};
 
     let dt = iso_str
@@ -135,9 +254,9 @@ fn parse_iso8601_to_timestamp(iso_str: &str) -> Result<String, String> {
 /// 2. If no "\n\n", split at first "\n":
 ///    - First line = title
 ///    - Rest = body text
-/// 3. If no newline at all, use first 100 chars as title, rest as body
+/// 3. If no newline at all, use first 100 characters as title, rest as body
 /// 4. Trim whitespace from both parts
-fn extract_title_and_text(content: &str) -> (String, String) {
+pub(crate) fn extract_title_and_text(content: &str) -> (String, String) {
     let content = content.trim();
 
     if content.is_empty() {
@@ -159,9 +278,9 @@ fn extract_title_and_text(content: &str) -> (String, String) {
     }
 
     // No newlines - use first 100 chars as title if long enough
-    if content.len() > 100 {
-        let title = content[..100].trim().to_string();
-        let text = content[100..].trim().to_string();
+    if let Some((split, _)) = content.char_indices().nth(100) {
+        let title = content[..split].trim().to_string();
+        let text = content[split..].trim().to_string();
         return (title, text);
     }
 
@@ -267,6 +386,14 @@ mod tests {
         assert_eq!(text.len(), 50);
     }
 
+    #[test]
+    fn test_extract_title_long_non_ascii_content() {
+        let long_text = "é".repeat(150);
+        let (title, text) = extract_title_and_text(&long_text);
+        assert_eq!(title.chars().count(), 100);
+        assert_eq!(text.chars().count(), 50);
+    }
+
     #[test]
     fn test_extract_title_short_content() {
         let (title, text) = extract_title_and_text("Just a short note");
@@ -0,0 +1,229 @@
+use super::dayone::{
+    extract_title_and_text, parse_iso8601_to_date, parse_iso8601_to_timestamp, DayOneEntry,
+};
+use super::json_stream;
+use super::markdown::{image_mime_type, markdown_to_html_with_images, plain_text};
+use super::metadata::ImportedMetadata;
+use super::zip::ZipReader;
+use crate::db::queries::{count_words, DiaryEntry};
+use base64::{engine::general_purpose, Engine as _};
+use log::warn;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+/// Photos larger than this are left out and replaced by their alt text
+const MAX_PHOTO_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Parse a Day One ZIP export
+///
+/// The archive holds one JSON file per journal (e.g. `Journal.json`) in the
+/// same format as a plain Day One JSON export, plus a `photos/` folder whose
+/// files are named by MD5 hash. Entry text is Markdown and refers to photos
+/// as `![](dayone-moment://<identifier>)`.
+///
+/// - Text is converted to HTML, with photos embedded as `data:` URIs
+/// - Tags, starred, location, weather and time zone are appended to the text
+/// - `date_updated` comes from `modifiedDate` when present
+///
+/// Entries are passed to `on_entry` one at a time, so only the entry being
+/// converted (and its photos) is held in memory.
+pub fn parse_dayone_zip(
+    path: &Path,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    // Two handles: one streams the journal JSON while the other reads photos
+    let mut journals = ZipReader::new(open(path)?)?;
+    let mut photos = ZipReader::new(open(path)?)?;
+
+    let journal_files: Vec<usize> = journals
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.name.contains('/') && e.name.to_lowercase().ends_with(".json"))
+        .map(|(i, _)| i)
+        .collect();
+    if journal_files.is_empty() {
+        return Err("No Day One journal found in the ZIP file".to_string());
+    }
+
+    let photo_files: HashMap<String, usize> = photos
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.name.starts_with("photos/") && !e.is_dir())
+        .filter_map(|(i, e)| {
+            let file_name = e.name.rsplit('/').next()?;
+            let stem = file_name.split('.').next()?;
+            Some((stem.to_string(), i))
+        })
+        .collect();
+
+    for index in journal_files {
+        let reader = journals.open(index)?;
+        json_stream::for_each_array_item(
+            reader,
+            "entries",
+            "Failed to parse Day One JSON",
+            |entry: DayOneEntry| {
+                on_entry(convert_entry(entry, &mut |identifier| {
+                    embed_photo(identifier, &mut photos, &photo_files)
+                })?)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Cannot access file: {}", e))
+}
+
+/// Converts one entry, calling `photo_for` with the md5 of each photo the
+/// text refers to.
+fn convert_entry(
+    entry: DayOneEntry,
+    photo_for: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<DiaryEntry, String> {
+    let date = parse_iso8601_to_date(&entry.creation_date)?;
+    let date_created = parse_iso8601_to_timestamp(&entry.creation_date)?;
+    let date_updated = match entry.modified_date.as_deref() {
+        Some(modified) => parse_iso8601_to_timestamp(modified)?,
+        None => date_created.clone(),
+    };
+
+    // An entry that opens with a photo has no title line
+    let (title, body) = match extract_title_and_text(&entry.text) {
+        (first, _) if first.starts_with("![") => (String::new(), entry.text.trim().to_string()),
+        (first, rest) => (plain_text(first.trim_start_matches('#').trim()), rest),
+    };
+
+    let photos: HashMap<&str, &str> = entry
+        .photos
+        .iter()
+        .filter_map(|p| Some((p.identifier.as_str(), p.md5.as_deref()?)))
+        .collect();
+    let mut text = markdown_to_html_with_images(&body, &mut |url| {
+        let identifier = url.strip_prefix("dayone-moment://")?;
+        photo_for(photos.get(identifier)?)
+    });
+
+    let metadata = ImportedMetadata {
+        tags: entry.tags,
+        starred: entry.starred,
+        mood: None,
+        location: entry.location.and_then(|l| l.describe()),
+        weather: entry.weather.and_then(|w| w.describe()),
+        time_zone: entry.time_zone,
+    };
+    text.push_str(&metadata.to_html());
+
+    Ok(DiaryEntry {
+        id: 0,
+        date,
+        title,
+        word_count: count_words(&text),
+        text,
+        date_created,
+        date_updated,
+    })
+}
+
+/// Reads `photos/<md5>.<ext>` and returns it as a `data:` URI.
+fn embed_photo<R: Read + Seek>(
+    md5: &str,
+    photos: &mut ZipReader<R>,
+    photo_files: &HashMap<String, usize>,
+) -> Option<String> {
+    let index = *photo_files.get(md5)?;
+    let name = photos.entries()[index].name.clone();
+    let extension = name.rsplit('.').next().unwrap_or("");
+    let mime = image_mime_type(extension).unwrap_or("application/octet-stream");
+    match photos.read(index, MAX_PHOTO_SIZE) {
+        Ok(bytes) => Some(format!(
+            "data:{};base64,{}",
+            mime,
+            general_purpose::STANDARD.encode(bytes)
+        )),
+        Err(e) => {
+            warn!("Skipping Day One photo: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::tests::build_zip;
+
+    fn parse(files: &[(&str, &[u8])]) -> Result<Vec<DiaryEntry>, String> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), build_zip(files)).unwrap();
+        let mut entries = Vec::new();
+        parse_dayone_zip(file.path(), &mut |entry| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    #[test]
+    fn test_parse_zip_with_photo_and_metadata() {
+        let journal = r##"{"metadata": {"version": "1.0"}, "entries": [{
+            "creationDate": "2024-03-10T09:00:00Z",
+            "modifiedDate": "2024-03-11T10:30:00Z",
+            "text": "# Beach day\n\nWe swam **a lot**.\n\n![](dayone-moment://ABC)",
+            "starred": true,
+            "tags": ["summer", "family"],
+            "timeZone": "Europe/Lisbon",
+            "location": {"placeName": "Praia", "country": "Portugal"},
+            "weather": {"conditionsDescription": "Sunny", "temperatureCelsius": 24.2},
+            "photos": [{"identifier": "ABC", "md5": "0123abcd", "type": "jpeg"}]
+        }]}"##;
+        let entries = parse(&[
+            ("Journal.json", journal.as_bytes()),
+            ("photos/0123abcd.jpeg", &[0xFF, 0xD8, 0xFF]),
+        ])
+        .unwrap();
+
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.date, "2024-03-10");
+        assert_eq!(entry.title, "Beach day");
+        assert_eq!(entry.date_updated, "2024-03-11 10:30:00");
+        assert!(entry
+            .text
+            .starts_with("<p>We swam <strong>a lot</strong>.</p>"));
+        assert!(entry
+            .text
+            .contains("<img src=\"data:image/jpeg;base64,/9j/\""));
+        assert!(entry.text.contains("<p>#summer #family</p>"));
+        assert!(entry.text.contains(
+            "Starred · Location: Praia, Portugal · Weather: Sunny, 24 °C · Time zone: Europe/Lisbon"
+        ));
+        assert_eq!(entry.word_count, 21);
+    }
+
+    #[test]
+    fn test_missing_photo_and_untitled_entry() {
+        let journal = r#"{"entries": [{
+            "creationDate": "2024-03-10T09:00:00Z",
+            "text": "![sunset](dayone-moment://GONE)\n\nNo title here",
+            "photos": [{"identifier": "GONE", "md5": "ffff", "type": "png"}]
+        }]}"#;
+        let entries = parse(&[("Journal.json", journal.as_bytes())]).unwrap();
+
+        assert_eq!(entries[0].title, "");
+        assert_eq!(entries[0].text, "<p>sunset</p><p>No title here</p>");
+    }
+
+    #[test]
+    fn test_zip_without_journal() {
+        let err = parse(&[("photos/a.jpeg", b"x")]).unwrap_err();
+        assert!(err.contains("No Day One journal"));
+    }
+}
@@ -0,0 +1,434 @@
+use super::html::sanitize_html;
+use super::metadata::ImportedMetadata;
+use crate::db::queries::{count_words, DiaryEntry};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// First bytes of every SQLite database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// .NET ticks (100 ns since 0001-01-01) at the Unix epoch
+const TICKS_AT_UNIX_EPOCH: i64 = 621_355_968_000_000_000;
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// One entry of a Diarium JSON export
+#[derive(Debug, Deserialize)]
+struct DiariumEntry {
+    /// Local time, e.g. "2024-01-15T14:30:00"
+    date: String,
+    #[serde(default, alias = "title")]
+    heading: String,
+    #[serde(default, alias = "text")]
+    html: String,
+    /// Star rating, 0 when not rated
+    #[serde(default)]
+    rating: Option<f64>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    people: Vec<String>,
+    #[serde(default)]
+    location: Option<Value>,
+    #[serde(default)]
+    weather: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiariumExport {
+    Entries(Vec<DiariumEntry>),
+    Wrapped { entries: Vec<DiariumEntry> },
+}
+
+/// Parse a Diarium JSON export
+///
+/// Expected format (a bare array, or the same array under `"entries"`):
+/// ```json
+/// [
+///   {
+///     "date": "2024-01-15T14:30:00",
+///     "heading": "My Title",
+///     "html": "<p>Entry content</p>",
+///     "rating": 4,
+///     "tags": ["travel"],
+///     "people": ["Anna"],
+///     "location": "Vienna, Austria",
+///     "weather": "Sunny, 24 °C"
+///   }
+/// ]
+/// ```
+///
+/// - The entry date is the day of `date`, which Diarium writes in local time
+/// - HTML is cleaned up for the editor, keeping embedded `data:` images
+/// - Tags and people are appended as `#hashtags`; the rating becomes the
+///   mood (e.g. "★★★★☆"), followed by location and weather
+pub fn parse_diarium_json(json: &str) -> Result<Vec<DiaryEntry>, String> {
+    let export: DiariumExport =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse Diarium JSON: {}", e))?;
+    let entries = match export {
+        DiariumExport::Entries(entries) | DiariumExport::Wrapped { entries } => entries,
+    };
+    entries.into_iter().map(convert_entry).collect()
+}
+
+/// True if the file at `path` is a SQLite database, such as a Diarium backup
+pub fn is_sqlite_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && &header == SQLITE_HEADER
+}
+
+/// Parse a Diarium backup (`.diary`), which is a SQLite database
+///
+/// Diarium does not publish the layout, so columns are looked up by name
+/// (case-insensitively) and optional ones may be missing:
+/// - `Entries`: `DiaryEntryId` (the entry time as .NET ticks in local time,
+///   unless there is a `Date` column), `Heading`, `Text`, `Rating`,
+///   `Latitude`, `Longitude`
+/// - `Tags` (`TagId`, `Value`) linked through `TagEntries`
+///   (`DiaryEntryId`, `TagId`), and likewise `Persons` (`PersonId`, `Name`)
+///   through `PersonEntries`
+///
+/// Each row is converted like an entry of the JSON export, oldest first.
+/// The file is opened read-only.
+pub fn parse_diarium_sqlite(
+    path: &Path,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open Diarium backup: {}", e))?;
+    let read_err = |e: rusqlite::Error| format!("Failed to read Diarium backup: {}", e);
+
+    let columns = table_columns(&conn, "Entries").map_err(read_err)?;
+    let id = find_column(&columns, &["DiaryEntryId", "Id"])
+        .ok_or("Not a Diarium backup: no Entries table with a DiaryEntryId column")?;
+    let date = find_column(&columns, &["Date"]).unwrap_or(id);
+    let optional = |names: &[&str]| find_column(&columns, names).unwrap_or("NULL");
+    let sql = format!(
+        "SELECT {id}, {date}, {}, {}, {}, {}, {} FROM Entries ORDER BY {date}",
+        optional(&["Heading", "Title"]),
+        optional(&["Text", "Html"]),
+        optional(&["Rating"]),
+        optional(&["Latitude"]),
+        optional(&["Longitude"]),
+    );
+
+    let mut tags = linked_names(&conn, "Tags", "TagEntries", "TagId").map_err(read_err)?;
+    let mut people =
+        linked_names(&conn, "Persons", "PersonEntries", "PersonId").map_err(read_err)?;
+
+    let mut stmt = conn.prepare(&sql).map_err(read_err)?;
+    let mut rows = stmt.query([]).map_err(read_err)?;
+    while let Some(row) = rows.next().map_err(read_err)? {
+        let entry_id: i64 = row.get(0).map_err(read_err)?;
+        let date = match row.get_ref(1).map_err(read_err)? {
+            rusqlite::types::ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+            value => value
+                .as_i64()
+                .ok()
+                .or_else(|| value.as_f64().ok().map(|v| v as i64))
+                .and_then(timestamp_to_text)
+                .ok_or_else(|| format!("Invalid Diarium date in entry {}", entry_id))?,
+        };
+        let latitude: Option<f64> = row.get(5).map_err(read_err)?;
+        let longitude: Option<f64> = row.get(6).map_err(read_err)?;
+        let location = match (latitude, longitude) {
+            (Some(lat), Some(lon)) if (lat, lon) != (0.0, 0.0) => {
+                Some(json!({ "latitude": lat, "longitude": lon }))
+            }
+            _ => None,
+        };
+        let entry = DiariumEntry {
+            date,
+            heading: row
+                .get::<_, Option<String>>(2)
+                .map_err(read_err)?
+                .unwrap_or_default(),
+            html: row
+                .get::<_, Option<String>>(3)
+                .map_err(read_err)?
+                .unwrap_or_default(),
+            rating: row.get(4).map_err(read_err)?,
+            tags: tags.remove(&entry_id).unwrap_or_default(),
+            people: people.remove(&entry_id).unwrap_or_default(),
+            location,
+            weather: None,
+        };
+        on_entry(convert_entry(entry)?)?;
+    }
+    Ok(())
+}
+
+/// Column names of `table`, empty if there is no such table
+fn table_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let columns = stmt.query_map([table], |row| row.get(0))?;
+    columns.collect()
+}
+
+/// The first of `names` that is one of `columns`, ignoring case
+fn find_column<'a>(columns: &'a [String], names: &[&str]) -> Option<&'a str> {
+    names.iter().find_map(|name| {
+        columns
+            .iter()
+            .find(|column| column.eq_ignore_ascii_case(name))
+            .map(String::as_str)
+    })
+}
+
+/// Names from `names_table` linked to entries through `link_table`, by
+/// entry id. Empty if either table or a needed column is missing.
+fn linked_names(
+    conn: &Connection,
+    names_table: &str,
+    link_table: &str,
+    key: &str,
+) -> rusqlite::Result<HashMap<i64, Vec<String>>> {
+    let name_columns = table_columns(conn, names_table)?;
+    let link_columns = table_columns(conn, link_table)?;
+    let (Some(name_key), Some(name), Some(link_key), Some(entry_id)) = (
+        find_column(&name_columns, &[key]),
+        find_column(&name_columns, &["Value", "Name"]),
+        find_column(&link_columns, &[key]),
+        find_column(&link_columns, &["DiaryEntryId"]),
+    ) else {
+        return Ok(HashMap::new());
+    };
+
+    let sql = format!(
+        "SELECT l.{entry_id}, n.{name} FROM {link_table} l \
+         JOIN {names_table} n ON n.{name_key} = l.{link_key} ORDER BY n.{name}"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    let mut by_entry: HashMap<i64, Vec<String>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        if let Some(name) = row.get::<_, Option<String>>(1)? {
+            by_entry.entry(row.get(0)?).or_default().push(name);
+        }
+    }
+    Ok(by_entry)
+}
+
+/// Formats a numeric time as `YYYY-MM-DDTHH:MM:SS`. Large values are .NET
+/// ticks, smaller ones Unix milliseconds or seconds.
+fn timestamp_to_text(value: i64) -> Option<String> {
+    let seconds = if value > 100_000_000_000_000 {
+        (value - TICKS_AT_UNIX_EPOCH) / TICKS_PER_SECOND
+    } else if value > 100_000_000_000 {
+        value / 1000
+    } else {
+        value
+    };
+    DateTime::from_timestamp(seconds, 0).map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+fn convert_entry(entry: DiariumEntry) -> Result<DiaryEntry, String> {
+    let (date, timestamp) = parse_diarium_date(&entry.date)?;
+
+    let mut text = sanitize_html(&entry.html, &mut |_| None);
+    let metadata = ImportedMetadata {
+        tags: entry.tags.into_iter().chain(entry.people).collect(),
+        starred: false,
+        mood: entry.rating.and_then(stars),
+        location: entry.location.as_ref().and_then(describe),
+        weather: entry.weather.as_ref().and_then(describe),
+        time_zone: None,
+    };
+    text.push_str(&metadata.to_html());
+
+    Ok(DiaryEntry {
+        id: 0,
+        date,
+        title: entry.heading.trim().to_string(),
+        word_count: count_words(&text),
+        text,
+        date_created: timestamp.clone(),
+        date_updated: timestamp,
+    })
+}
+
+/// Returns the entry date and an RFC 3339 timestamp. Times without an offset
+/// are local to the writer and are stored as if they were UTC.
+fn parse_diarium_date(value: &str) -> Result<(String, String), String> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok((
+            dt.format("%Y-%m-%d").to_string(),
+            dt.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok((
+                dt.format("%Y-%m-%d").to_string(),
+                dt.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ));
+        }
+    }
+    Err(format!("Invalid Diarium date '{}'", value))
+}
+
+/// e.g. 4 → "★★★★☆"
+fn stars(rating: f64) -> Option<String> {
+    let rating = rating.round().clamp(0.0, 5.0) as usize;
+    (rating > 0).then(|| format!("{}{}", "★".repeat(rating), "☆".repeat(5 - rating)))
+}
+
+/// Describes a location or weather value, which is either plain text or an
+/// object with a name, address, description or coordinates
+fn describe(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+        Value::Object(fields) => {
+            let text_of = |key: &str| {
+                fields
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+            };
+            let mut parts: Vec<String> = ["name", "address", "description"]
+                .into_iter()
+                .filter_map(text_of)
+                .map(str::to_string)
+                .collect();
+            if let Some(temperature) = fields.get("temperature").and_then(Value::as_f64) {
+                parts.push(format!("{:.0} °C", temperature));
+            }
+            let latitude = fields.get("latitude").and_then(Value::as_f64);
+            let longitude = fields.get("longitude").and_then(Value::as_f64);
+            if let (true, Some(lat), Some(lon)) = (parts.is_empty(), latitude, longitude) {
+                parts.push(format!("{:.4}, {:.4}", lat, lon));
+            }
+            Some(parts.join(", ")).filter(|text| !text.is_empty())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entry_with_metadata() {
+        let json = r#"[{
+            "date": "2024-01-15T23:30:00",
+            "heading": " Evening ",
+            "html": "<p>Dinner with <i>Anna</i></p><p><img src=\"data:image/png;base64,iVBO\"></p>",
+            "rating": 4,
+            "tags": ["food"],
+            "people": ["Anna"],
+            "location": {"name": "Café Central", "latitude": 48.21, "longitude": 16.36},
+            "weather": {"description": "Cloudy", "temperature": 3.6}
+        }]"#;
+        let entries = parse_diarium_json(json).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.date, "2024-01-15");
+        assert_eq!(entry.title, "Evening");
+        assert_eq!(entry.date_created, "2024-01-15T23:30:00Z");
+        assert_eq!(
+            entry.text,
+            "<p>Dinner with <em>Anna</em></p><p><img src=\"data:image/png;base64,iVBO\"></p>\
+             <p>#food #Anna</p><p><em>Mood: ★★★★☆ · Location: Café Central · \
+             Weather: Cloudy, 4 °C</em></p>"
+        );
+        assert_eq!(entry.word_count, 16);
+    }
+
+    #[test]
+    fn test_wrapped_export_and_date_formats() {
+        let json = r#"{"entries": [
+            {"date": "2024-02-01 08:15", "text": "<p>Plain</p>", "rating": 0, "location": ""},
+            {"date": "2024-02-02T08:15:00+01:00", "html": ""}
+        ]}"#;
+        let entries = parse_diarium_json(json).unwrap();
+
+        assert_eq!(entries[0].date, "2024-02-01");
+        assert_eq!(entries[0].text, "<p>Plain</p>");
+        assert_eq!(entries[1].date, "2024-02-02");
+        assert_eq!(entries[1].date_created, "2024-02-02T08:15:00+01:00");
+    }
+
+    #[test]
+    fn test_parse_sqlite_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.diary");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Entries (DiaryEntryId INTEGER PRIMARY KEY, Heading TEXT, \
+                 Text TEXT, Rating INTEGER, Latitude REAL, Longitude REAL);
+             CREATE TABLE Tags (TagId INTEGER PRIMARY KEY, Value TEXT);
+             CREATE TABLE TagEntries (DiaryEntryId INTEGER, TagId INTEGER);
+             CREATE TABLE Persons (PersonId INTEGER PRIMARY KEY, Name TEXT);
+             CREATE TABLE PersonEntries (DiaryEntryId INTEGER, PersonId INTEGER);
+             -- 2024-01-15 23:30:00 and 2024-01-14 08:00:00 as .NET ticks
+             INSERT INTO Entries VALUES
+                 (638409582000000000, 'Evening', '<p>Dinner</p>', 4, 48.21, 16.36),
+                 (638408160000000000, NULL, '<p>Morning</p>', 0, 0, 0);
+             INSERT INTO Tags VALUES (1, 'food');
+             INSERT INTO TagEntries VALUES (638409582000000000, 1);
+             INSERT INTO Persons VALUES (1, 'Anna');
+             INSERT INTO PersonEntries VALUES (638409582000000000, 1);",
+        )
+        .unwrap();
+        drop(conn);
+
+        assert!(is_sqlite_file(&path));
+        let mut entries = Vec::new();
+        parse_diarium_sqlite(&path, &mut |entry| {
+            entries.push(entry);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].date, "2024-01-14");
+        assert_eq!(entries[0].title, "");
+        assert_eq!(entries[0].text, "<p>Morning</p>");
+        assert_eq!(entries[1].date, "2024-01-15");
+        assert_eq!(entries[1].date_created, "2024-01-15T23:30:00Z");
+        assert_eq!(entries[1].title, "Evening");
+        assert_eq!(
+            entries[1].text,
+            "<p>Dinner</p><p>#food #Anna</p><p><em>Mood: ★★★★☆ · \
+             Location: 48.2100, 16.3600</em></p>"
+        );
+    }
+
+    #[test]
+    fn test_sqlite_without_entries_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("other.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE notes (id INTEGER)")
+            .unwrap();
+
+        let err = parse_diarium_sqlite(&path, &mut |_| Ok(())).unwrap_err();
+        assert!(err.contains("Not a Diarium backup"));
+        assert!(!is_sqlite_file(&dir.path().join("missing.diary")));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(parse_diarium_json("{}").is_err());
+        assert!(parse_diarium_json(r#"[{"date": "yesterday"}]"#)
+            .unwrap_err()
+            .contains("Invalid Diarium date"));
+    }
+}
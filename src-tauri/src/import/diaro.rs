@@ -0,0 +1,452 @@
+use super::html::{resolve_reference, text_to_html};
+use super::markdown::{escape_html, image_mime_type};
+use super::metadata::ImportedMetadata;
+use super::zip::ZipReader;
+use crate::db::queries::{count_words, DiaryEntry};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use log::warn;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Photos larger than this are left out
+const MAX_PHOTO_SIZE: u64 = 50 * 1024 * 1024;
+
+/// The backup XML inside a ZIP is read whole, so it is capped like other
+/// non-streaming imports
+const MAX_XML_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Name of the backup XML inside a Diaro `.diaro`/`.zip` backup
+const BACKUP_XML: &str = "DiaroBackup.xml";
+
+/// Diaro's built-in moods, by their number in the backup
+const MOODS: [&str; 5] = ["Awesome", "Happy", "Neutral", "Bad", "Awful"];
+
+/// One `<r>` row of a backup table, by column name
+type Row = HashMap<String, String>;
+
+/// Parse a Diaro backup: either `DiaroBackup.xml` or the `.diaro`/`.zip`
+/// file that holds it together with the photos
+///
+/// Expected XML format:
+/// ```xml
+/// <data version="2">
+///   <table name="diaro_tags"><r><uid>t1</uid><title>travel</title></r></table>
+///   <table name="diaro_entries">
+///     <r>
+///       <uid>e1</uid>
+///       <date>1705329000000</date>
+///       <tz_offset>+01:00</tz_offset>
+///       <title>My Title</title>
+///       <text>Entry content</text>
+///       <folder_uid>f1</folder_uid>
+///       <location_uid>l1</location_uid>
+///       <tags>,t1,</tags>
+///       <mood>2</mood>
+///       <weather_temperature>21</weather_temperature>
+///       <weather_description>clear sky</weather_description>
+///     </r>
+///   </table>
+///   <table name="diaro_attachments">
+///     <r><entry_uid>e1</entry_uid><type>photo</type><filename>photo_1.jpg</filename></r>
+///   </table>
+/// </data>
+/// ```
+///
+/// - The entry date is the local day, using the entry's `tz_offset`
+/// - Photos are embedded at the end of the entry. They are read from
+///   `media/photo/` in the ZIP, or next to `DiaroBackup.xml`
+/// - Tags and the folder name are appended as `#hashtags`; mood, location
+///   and weather as one italic line
+pub fn parse_diaro(
+    path: &Path,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+    let mut magic = [0u8; 2];
+    let is_zip = file
+        .read(&mut magic)
+        .map_err(|e| format!("Cannot access file: {}", e))?
+        == 2
+        && &magic == b"PK";
+    file.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Cannot access file: {}", e))?;
+
+    if !is_zip {
+        let tables = read_tables(BufReader::new(file))?;
+        let photo_dir = path
+            .parent()
+            .map(|dir| dir.join("media").join("photo"))
+            .unwrap_or_else(|| PathBuf::from("media/photo"));
+        return convert_entries(&tables, on_entry, &mut |name| {
+            let bytes = std::fs::read(photo_dir.join(name)).ok()?;
+            (bytes.len() as u64 <= MAX_PHOTO_SIZE).then_some(bytes)
+        });
+    }
+
+    let mut zip = ZipReader::new(BufReader::new(file))?;
+    let xml_index = zip
+        .entries()
+        .iter()
+        .position(|e| e.name.rsplit('/').next() == Some(BACKUP_XML))
+        .ok_or_else(|| format!("No {} found in the backup", BACKUP_XML))?;
+    let xml = zip.read(xml_index, MAX_XML_SIZE)?;
+    let tables = read_tables(xml.as_slice())?;
+    drop(xml);
+
+    let photos: HashMap<String, usize> = zip
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.is_dir() && e.name.contains("photo/"))
+        .filter_map(|(i, e)| Some((e.name.rsplit('/').next()?.to_string(), i)))
+        .collect();
+    convert_entries(&tables, on_entry, &mut |name| {
+        read_zip_photo(&mut zip, photos.get(name).copied()?)
+    })
+}
+
+fn read_zip_photo<R: Read + Seek>(zip: &mut ZipReader<R>, index: usize) -> Option<Vec<u8>> {
+    zip.read(index, MAX_PHOTO_SIZE)
+        .map_err(|e| warn!("Skipping Diaro photo: {}", e))
+        .ok()
+}
+
+/// Reads every `<table>` of the backup into rows of column values
+fn read_tables<R: BufRead>(reader: R) -> Result<HashMap<String, Vec<Row>>, String> {
+    let mut xml = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut tables: HashMap<String, Vec<Row>> = HashMap::new();
+    let mut table: Option<String> = None;
+    let mut row: Option<Row> = None;
+    let mut column: Option<String> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = xml
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Failed to parse Diaro backup: {}", e))?;
+        match event {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if row.is_some() {
+                    column = Some(name);
+                    text.clear();
+                } else if name == "r" && table.is_some() {
+                    row = Some(Row::new());
+                } else if name == "table" {
+                    let attribute = e.try_get_attribute("name").ok().flatten();
+                    table = attribute
+                        .and_then(|a| a.unescape_value().ok())
+                        .map(|name| name.into_owned());
+                }
+            }
+            Event::Text(e) if column.is_some() => text.push_str(
+                &e.decode()
+                    .map_err(|e| format!("Failed to parse Diaro backup: {}", e))?,
+            ),
+            Event::CData(e) if column.is_some() => text.push_str(
+                &e.decode()
+                    .map_err(|e| format!("Failed to parse Diaro backup: {}", e))?,
+            ),
+            Event::GeneralRef(e) if column.is_some() => text.push_str(&resolve_reference(&e)),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"r" if row.is_some() => {
+                    if let (Some(table), Some(row)) = (&table, row.take()) {
+                        tables.entry(table.clone()).or_default().push(row);
+                    }
+                }
+                b"table" => table = None,
+                _ => {
+                    if let (Some(row), Some(column)) = (row.as_mut(), column.take()) {
+                        row.insert(column, std::mem::take(&mut text));
+                    }
+                }
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(tables)
+}
+
+fn convert_entries(
+    tables: &HashMap<String, Vec<Row>>,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    read_photo: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<(), String> {
+    let entries = tables
+        .get("diaro_entries")
+        .ok_or("Not a Diaro backup: no entries table found")?;
+    let tags = titles(tables, "diaro_tags");
+    let folders = titles(tables, "diaro_folders");
+    let moods = titles(tables, "diaro_moods");
+    let locations: HashMap<&str, String> = tables
+        .get("diaro_locations")
+        .into_iter()
+        .flatten()
+        .filter_map(|row| Some((field(row, "uid")?, describe_location(row)?)))
+        .collect();
+
+    let mut photos: HashMap<&str, Vec<(i64, &str)>> = HashMap::new();
+    for row in tables.get("diaro_attachments").into_iter().flatten() {
+        if let (Some(entry), Some(file_name), Some("photo")) = (
+            field(row, "entry_uid"),
+            field(row, "filename"),
+            field(row, "type"),
+        ) {
+            let position = field(row, "position")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(0);
+            photos.entry(entry).or_default().push((position, file_name));
+        }
+    }
+
+    for row in entries {
+        let millis: i64 = field(row, "date")
+            .and_then(|d| d.parse().ok())
+            .ok_or_else(|| {
+                format!(
+                    "Diaro entry '{}' has no valid date",
+                    field(row, "uid").unwrap_or("?")
+                )
+            })?;
+        let created = DateTime::<Utc>::from_timestamp_millis(millis)
+            .ok_or_else(|| format!("Invalid Diaro timestamp: {}", millis))?;
+        let offset = field(row, "tz_offset")
+            .and_then(|o| o.parse::<FixedOffset>().ok())
+            .unwrap_or_else(|| FixedOffset::east_opt(0).expect("zero offset is valid"));
+        let date_created = created.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let mut text = text_to_html(field(row, "text").unwrap_or(""));
+        let mut entry_photos = field(row, "uid")
+            .and_then(|uid| photos.get(uid))
+            .cloned()
+            .unwrap_or_default();
+        entry_photos.sort();
+        for (_, file_name) in entry_photos {
+            if let Some(src) = embed_photo(file_name, read_photo) {
+                text.push_str(&format!("<p><img src=\"{}\"></p>", escape_html(&src)));
+            }
+        }
+
+        let mut entry_tags: Vec<String> = field(row, "tags")
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|uid| tags.get(uid.trim()))
+            .map(|title| title.to_string())
+            .collect();
+        if let Some(folder) = field(row, "folder_uid").and_then(|uid| folders.get(uid)) {
+            entry_tags.push(folder.to_string());
+        }
+        let mood = field(row, "mood").and_then(|mood| {
+            moods.get(mood).map(|title| title.to_string()).or_else(|| {
+                let number: usize = mood.parse().ok()?;
+                MOODS.get(number.checked_sub(1)?).map(|m| m.to_string())
+            })
+        });
+        let metadata = ImportedMetadata {
+            tags: entry_tags,
+            starred: false,
+            mood,
+            location: field(row, "location_uid").and_then(|uid| locations.get(uid).cloned()),
+            weather: describe_weather(row),
+            time_zone: None,
+        };
+        text.push_str(&metadata.to_html());
+
+        on_entry(DiaryEntry {
+            id: 0,
+            date: created
+                .with_timezone(&offset)
+                .format("%Y-%m-%d")
+                .to_string(),
+            title: field(row, "title").unwrap_or("").trim().to_string(),
+            word_count: count_words(&text),
+            text,
+            date_updated: date_created.clone(),
+            date_created,
+        })?;
+    }
+    Ok(())
+}
+
+/// A non-empty column value
+fn field<'a>(row: &'a Row, column: &str) -> Option<&'a str> {
+    row.get(column)
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// `uid` → `title` for a table of named things (tags, folders, moods)
+fn titles<'a>(tables: &'a HashMap<String, Vec<Row>>, table: &str) -> HashMap<&'a str, &'a str> {
+    tables
+        .get(table)
+        .into_iter()
+        .flatten()
+        .filter_map(|row| Some((field(row, "uid")?, field(row, "title")?)))
+        .collect()
+}
+
+fn describe_location(row: &Row) -> Option<String> {
+    match (field(row, "title"), field(row, "address")) {
+        (Some(title), Some(address)) if title != address => {
+            return Some(format!("{}, {}", title, address))
+        }
+        (Some(name), _) | (None, Some(name)) => return Some(name.to_string()),
+        (None, None) => {}
+    }
+    let lat: f64 = field(row, "lat")?.parse().ok()?;
+    let lng: f64 = field(row, "lng")?.parse().ok()?;
+    Some(format!("{:.4}, {:.4}", lat, lng))
+}
+
+/// e.g. "clear sky, 21 °C"
+fn describe_weather(row: &Row) -> Option<String> {
+    let conditions = field(row, "weather_description");
+    let temperature = field(row, "weather_temperature")
+        .and_then(|t| t.parse::<f64>().ok())
+        .map(|t| format!("{:.0} °C", t));
+    match (conditions, temperature) {
+        (Some(c), Some(t)) => Some(format!("{}, {}", c, t)),
+        (Some(c), None) => Some(c.to_string()),
+        (None, t) => t,
+    }
+}
+
+fn embed_photo(
+    file_name: &str,
+    read_photo: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+) -> Option<String> {
+    // Only plain file names are looked up, never paths
+    if file_name.contains(['/', '\\']) || file_name.starts_with('.') {
+        warn!("Skipping Diaro photo with an invalid name '{}'", file_name);
+        return None;
+    }
+    let mime = image_mime_type(file_name.rsplit('.').next().unwrap_or(""))?;
+    let Some(bytes) = read_photo(file_name) else {
+        warn!("Skipping missing Diaro photo '{}'", file_name);
+        return None;
+    };
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::tests::build_zip;
+
+    const BACKUP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<data version="2">
+  <table name="diaro_folders"><r><uid>f1</uid><title>Personal</title><color>#ff0000</color></r></table>
+  <table name="diaro_tags">
+    <r><uid>t1</uid><title>travel</title></r>
+    <r><uid>t2</uid><title>road trip</title></r>
+  </table>
+  <table name="diaro_locations">
+    <r><uid>l1</uid><title>Home</title><address>Main St 1, Springfield</address><lat>1.5</lat><lng>2.5</lng></r>
+  </table>
+  <table name="diaro_entries">
+    <r>
+      <uid>e1</uid>
+      <date>1705356000000</date>
+      <tz_offset>+02:00</tz_offset>
+      <title>Late night</title>
+      <text>First line &amp; more
+second line
+
+Next paragraph</text>
+      <folder_uid>f1</folder_uid>
+      <location_uid>l1</location_uid>
+      <tags>,t1,t2,</tags>
+      <mood>2</mood>
+      <weather_temperature>-3.4</weather_temperature>
+      <weather_description>light snow</weather_description>
+    </r>
+    <r><uid>e2</uid><date>1705356000000</date><title></title><text>Plain</text><tags></tags></r>
+  </table>
+  <table name="diaro_attachments">
+    <r><uid>a2</uid><entry_uid>e1</entry_uid><type>photo</type><filename>photo_2.png</filename><position>2</position></r>
+    <r><uid>a1</uid><entry_uid>e1</entry_uid><type>photo</type><filename>photo_1.jpg</filename><position>1</position></r>
+    <r><uid>a3</uid><entry_uid>e1</entry_uid><type>photo</type><filename>../secret.jpg</filename><position>3</position></r>
+  </table>
+</data>"#;
+
+    fn parse(path: &Path) -> Result<Vec<DiaryEntry>, String> {
+        let mut entries = Vec::new();
+        parse_diaro(path, &mut |entry| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    #[test]
+    fn test_parse_backup_zip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let zip = build_zip(&[
+            ("DiaroBackup.xml", BACKUP.as_bytes()),
+            ("media/photo/photo_1.jpg", &[0xFF, 0xD8, 0xFF]),
+            ("media/photo/photo_2.png", &[0x89, b'P', b'N', b'G']),
+        ]);
+        std::fs::write(file.path(), zip).unwrap();
+
+        let entries = parse(file.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[0];
+        // 22:00 UTC is already the next day at +02:00
+        assert_eq!(entry.date, "2024-01-16");
+        assert_eq!(entry.date_created, "2024-01-15T22:00:00Z");
+        assert_eq!(entry.title, "Late night");
+        assert_eq!(
+            entry.text,
+            "<p>First line &amp; more<br>second line</p><p>Next paragraph</p>\
+             <p><img src=\"data:image/jpeg;base64,/9j/\"></p>\
+             <p><img src=\"data:image/png;base64,iVBORw==\"></p>\
+             <p>#travel #road_trip #Personal</p>\
+             <p><em>Mood: Happy · Location: Home, Main St 1, Springfield · \
+             Weather: light snow, -3 °C</em></p>"
+        );
+        assert_eq!(entry.word_count, 26);
+        assert_eq!(entries[1].date, "2024-01-15");
+        assert_eq!(entries[1].text, "<p>Plain</p>");
+    }
+
+    #[test]
+    fn test_parse_extracted_xml_with_photo_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = dir.path().join("DiaroBackup.xml");
+        std::fs::write(&xml, BACKUP).unwrap();
+        std::fs::create_dir_all(dir.path().join("media/photo")).unwrap();
+        std::fs::write(
+            dir.path().join("media/photo/photo_1.jpg"),
+            [0xFF, 0xD8, 0xFF],
+        )
+        .unwrap();
+
+        let entries = parse(&xml).unwrap();
+        assert!(entries[0]
+            .text
+            .contains("<img src=\"data:image/jpeg;base64,/9j/\">"));
+        assert!(!entries[0].text.contains("image/png"));
+    }
+
+    #[test]
+    fn test_not_a_diaro_backup() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "<data><table name=\"other\"></table></data>").unwrap();
+        assert!(parse(file.path()).unwrap_err().contains("no entries table"));
+
+        std::fs::write(file.path(), build_zip(&[("notes.txt", b"x")])).unwrap();
+        assert!(parse(file.path()).unwrap_err().contains("DiaroBackup.xml"));
+    }
+}
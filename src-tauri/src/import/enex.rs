@@ -0,0 +1,341 @@
+use super::html::{resolve_reference, sanitize_html, Embed};
+use super::metadata::ImportedMetadata;
+use crate::db::queries::{count_words, DiaryEntry};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{NaiveDateTime, SecondsFormat};
+use log::warn;
+use md5::{Digest, Md5};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+
+/// Images larger than this are left out of the entry
+const MAX_IMAGE_SIZE: usize = 50 * 1024 * 1024;
+
+/// Parse an Evernote export (`.enex`), as written by Evernote and Joplin
+///
+/// Expected format:
+/// ```xml
+/// <en-export>
+///   <note>
+///     <title>My Title</title>
+///     <content><![CDATA[<en-note><div>Text</div><en-media hash="…" type="image/png"/></en-note>]]></content>
+///     <created>20240115T143000Z</created>
+///     <updated>20240116T090000Z</updated>
+///     <tag>travel</tag>
+///     <resource>
+///       <data encoding="base64">iVBORw0KGgo…</data>
+///       <mime>image/png</mime>
+///     </resource>
+///   </note>
+/// </en-export>
+/// ```
+///
+/// - The note content (ENML) is converted to the editor's HTML
+/// - `<en-media>` images are embedded as `data:` URIs, matched to their
+///   `<resource>` by the MD5 hash of the data
+/// - Tags are appended to the text as `#hashtags`
+pub fn parse_enex(content: &str) -> Result<Vec<DiaryEntry>, String> {
+    let mut entries = Vec::new();
+    parse_enex_reader(content.as_bytes(), &mut |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Streaming variant of [`parse_enex`]: notes are passed to `on_entry` one
+/// at a time, so only the note being converted is held in memory.
+pub fn parse_enex_reader<R: Read>(
+    reader: R,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut xml = Reader::from_reader(BufReader::new(reader));
+    let mut buf = Vec::new();
+    let mut found_export = false;
+    let mut note: Option<Note> = None;
+    let mut resource: Option<Resource> = None;
+    let mut capturing = false;
+    let mut text = String::new();
+
+    loop {
+        let event = xml
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Failed to parse ENEX file: {}", e))?;
+        match event {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"en-export" => found_export = true,
+                b"note" => note = Some(Note::default()),
+                b"resource" if note.is_some() => resource = Some(Resource::default()),
+                b"title" | b"content" | b"created" | b"updated" | b"tag" | b"data" | b"mime"
+                | b"file-name"
+                    if note.is_some() =>
+                {
+                    capturing = true;
+                    text.clear();
+                }
+                _ => {}
+            },
+            Event::Text(e) if capturing => {
+                let decoded = e
+                    .decode()
+                    .map_err(|e| format!("Failed to parse ENEX file: {}", e))?;
+                text.push_str(&decoded);
+            }
+            Event::CData(e) if capturing => {
+                let decoded = e
+                    .decode()
+                    .map_err(|e| format!("Failed to parse ENEX file: {}", e))?;
+                text.push_str(&decoded);
+            }
+            Event::GeneralRef(e) if capturing => text.push_str(&resolve_reference(&e)),
+            Event::End(e) => {
+                let name = e.local_name();
+                capturing = false;
+                let value = std::mem::take(&mut text);
+                match (name.as_ref(), note.as_mut(), resource.as_mut()) {
+                    (b"data", Some(_), Some(resource)) => resource.data = value,
+                    (b"mime", Some(_), Some(resource)) => resource.mime = value.trim().to_string(),
+                    (b"file-name", Some(_), Some(resource)) => {
+                        resource.file_name = Some(value.trim().to_string())
+                    }
+                    (b"resource", Some(note), Some(_)) => {
+                        if let Some(resource) = resource.take() {
+                            note.add_resource(resource);
+                        }
+                    }
+                    (b"title", Some(note), None) => note.title = value.trim().to_string(),
+                    (b"content", Some(note), None) => note.content = value,
+                    (b"created", Some(note), None) => note.created = Some(value),
+                    (b"updated", Some(note), None) => note.updated = Some(value),
+                    (b"tag", Some(note), None) => note.tags.push(value.trim().to_string()),
+                    (b"note", Some(_), _) => {
+                        if let Some(note) = note.take() {
+                            on_entry(note.into_entry()?)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !found_export {
+        return Err("Not an Evernote export: missing <en-export> element".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Note {
+    title: String,
+    content: String,
+    created: Option<String>,
+    updated: Option<String>,
+    tags: Vec<String>,
+    /// Attachments by the hex MD5 hash of their data
+    resources: HashMap<String, Resource>,
+}
+
+#[derive(Default)]
+struct Resource {
+    data: String,
+    mime: String,
+    file_name: Option<String>,
+    bytes: Vec<u8>,
+}
+
+impl Note {
+    fn add_resource(&mut self, mut resource: Resource) {
+        let data: String = resource
+            .data
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        match general_purpose::STANDARD.decode(data) {
+            Ok(bytes) => {
+                let hash = hex::encode(Md5::digest(&bytes));
+                resource.data = String::new();
+                resource.bytes = bytes;
+                self.resources.insert(hash, resource);
+            }
+            Err(e) => warn!("Skipping ENEX attachment with invalid data: {}", e),
+        }
+    }
+
+    fn into_entry(self) -> Result<DiaryEntry, String> {
+        let created = self
+            .created
+            .as_deref()
+            .or(self.updated.as_deref())
+            .ok_or_else(|| format!("Note '{}' has no creation date", self.title))?;
+        let created = parse_enex_timestamp(created)?;
+        let date_created = created.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let date_updated = match self.updated.as_deref() {
+            Some(updated) => parse_enex_timestamp(updated)?
+                .and_utc()
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => date_created.clone(),
+        };
+
+        let resources = &self.resources;
+        let mut text = sanitize_html(&self.content, &mut |hash| {
+            let Some(resource) = resources.get(&hash.to_lowercase()) else {
+                warn!("Skipping ENEX media without a matching resource");
+                return None;
+            };
+            if resource.mime.starts_with("image/") && resource.bytes.len() <= MAX_IMAGE_SIZE {
+                return Some(Embed::Image(format!(
+                    "data:{};base64,{}",
+                    resource.mime,
+                    general_purpose::STANDARD.encode(&resource.bytes)
+                )));
+            }
+            let name = resource.file_name.as_deref().unwrap_or(&resource.mime);
+            warn!("Skipping ENEX attachment '{}'", name);
+            Some(Embed::Text(format!("[Attachment: {}]", name)))
+        });
+        let metadata = ImportedMetadata {
+            tags: self.tags,
+            ..Default::default()
+        };
+        text.push_str(&metadata.to_html());
+
+        Ok(DiaryEntry {
+            id: 0,
+            date: created.format("%Y-%m-%d").to_string(),
+            title: self.title,
+            word_count: count_words(&text),
+            text,
+            date_created,
+            date_updated,
+        })
+    }
+}
+
+/// Parses an ENEX timestamp (`20240115T143000Z`, always UTC)
+fn parse_enex_timestamp(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ")
+        .map_err(|e| format!("Invalid ENEX timestamp '{}': {}", value.trim(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    fn enex(notes: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE en-export SYSTEM \"http://xml.evernote.com/pub/evernote-export3.dtd\">\n\
+             <en-export export-date=\"20240201T120000Z\" application=\"Evernote\">{}</en-export>",
+            notes
+        )
+    }
+
+    #[test]
+    fn test_parse_note_with_image_and_tags() {
+        let hash = hex::encode(Md5::digest(PNG));
+        let data = general_purpose::STANDARD.encode(PNG);
+        let content = enex(&format!(
+            r#"<note>
+              <title>Trip &amp; notes</title>
+              <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>We went <b>hiking</b>&nbsp;today.</div><div><br/></div>
+<div><en-media hash="{}" type="image/png"/></div></en-note>]]></content>
+              <created>20240115T143000Z</created>
+              <updated>20240116T090000Z</updated>
+              <tag>travel</tag>
+              <tag>road trip</tag>
+              <resource>
+                <data encoding="base64">
+{}
+                </data>
+                <mime>image/png</mime>
+              </resource>
+            </note>"#,
+            hash, data
+        ));
+
+        let entries = parse_enex(&content).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.date, "2024-01-15");
+        assert_eq!(entry.title, "Trip & notes");
+        assert_eq!(entry.date_created, "2024-01-15T14:30:00Z");
+        assert_eq!(entry.date_updated, "2024-01-16T09:00:00Z");
+        assert_eq!(
+            entry.text,
+            format!(
+                "<p>We went <strong>hiking</strong>\u{a0}today.</p><p></p>\
+                 <p><img src=\"data:image/png;base64,{}\"></p><p>#travel #road_trip</p>",
+                data
+            )
+        );
+        assert_eq!(entry.word_count, 6);
+    }
+
+    #[test]
+    fn test_non_image_attachment_becomes_text() {
+        let bytes = b"%PDF-1.4";
+        let hash = hex::encode(Md5::digest(bytes));
+        let content = enex(&format!(
+            "<note><title>Doc</title><content>&lt;en-note&gt;&lt;en-media hash=\"{}\" \
+             type=\"application/pdf\"/&gt;&lt;/en-note&gt;</content>\
+             <created>20240115T143000Z</created><resource><data>{}</data>\
+             <mime>application/pdf</mime><resource-attributes><file-name>scan.pdf</file-name>\
+             </resource-attributes></resource></note>",
+            hash,
+            general_purpose::STANDARD.encode(bytes)
+        ));
+
+        let entries = parse_enex(&content).unwrap();
+        assert_eq!(entries[0].text, "<p>[Attachment: scan.pdf]</p>");
+        assert_eq!(entries[0].date_updated, entries[0].date_created);
+    }
+
+    #[test]
+    fn test_multiple_notes_stream_in_order() {
+        let content = enex(
+            "<note><title>A</title><content>&lt;en-note&gt;x&lt;/en-note&gt;</content>\
+             <created>20240101T000000Z</created></note>\
+             <note><title>B</title><content>&lt;en-note&gt;y&lt;/en-note&gt;</content>\
+             <created>20240102T235959Z</created></note>",
+        );
+        let mut titles = Vec::new();
+        parse_enex_reader(content.as_bytes(), &mut |entry| {
+            titles.push((entry.title, entry.date));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            titles,
+            vec![
+                ("A".to_string(), "2024-01-01".to_string()),
+                ("B".to_string(), "2024-01-02".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(parse_enex("{\"entries\": []}")
+            .unwrap_err()
+            .contains("en-export"));
+        assert!(parse_enex(&enex("<note><title>A</title></note>"))
+            .unwrap_err()
+            .contains("no creation date"));
+        assert!(parse_enex(&enex(
+            "<note><title>A</title><created>yesterday</created></note>"
+        ))
+        .unwrap_err()
+        .contains("Invalid ENEX timestamp"));
+    }
+}
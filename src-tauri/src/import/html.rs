@@ -0,0 +1,467 @@
+//! Conversion of HTML from other journaling apps to the editor's HTML.
+//!
+//! Imported HTML can contain anything, so it is rebuilt from an allow-list
+//! of elements instead of being stored as is.
+
+use super::markdown::{escape_html, is_safe_url};
+use log::warn;
+use quick_xml::events::{BytesRef, BytesStart, Event};
+use quick_xml::Reader;
+
+/// What an image reference in imported HTML resolves to
+pub enum Embed {
+    /// An image, as a `data:` URI
+    Image(String),
+    /// Text shown instead, e.g. for an attachment that is not an image
+    Text(String),
+}
+
+/// Converts plain text to HTML: blank lines separate paragraphs and other
+/// line breaks are kept as `<br>`.
+pub fn text_to_html(text: &str) -> String {
+    let normalized = text.replace("\r\n", "\n");
+    let mut html = String::new();
+    for paragraph in normalized.split("\n\n") {
+        let lines: Vec<String> = paragraph
+            .trim_matches('\n')
+            .lines()
+            .map(escape_html)
+            .collect();
+        if lines.iter().any(|line| !line.trim().is_empty()) {
+            html.push_str(&format!("<p>{}</p>", lines.join("<br>")));
+        }
+    }
+    html
+}
+
+/// What an element becomes in the editor's HTML
+enum Element {
+    /// Ends the current paragraph (`div`, `p`, table cells, ...)
+    Paragraph,
+    /// Block container written as is (`ul`, `li`, `blockquote`, ...)
+    Block(&'static str),
+    /// Block whose text is not wrapped in a paragraph (`h1`, `pre`, ...)
+    TextBlock(&'static str),
+    /// Inline formatting mark
+    Mark(&'static str),
+    Link,
+    LineBreak,
+    Rule,
+    /// `<img>`, or `<en-media>` in Evernote notes
+    Image,
+    Todo,
+    /// Dropped together with everything inside it
+    Skip,
+    /// Dropped, but its content is kept
+    Unwrap,
+}
+
+fn classify(name: &[u8]) -> Element {
+    match name {
+        b"div" | b"p" | b"table" | b"thead" | b"tbody" | b"tfoot" | b"tr" | b"td" | b"th"
+        | b"dl" | b"dt" | b"dd" | b"center" | b"address" | b"caption" => Element::Paragraph,
+        b"ul" => Element::Block("ul"),
+        b"ol" => Element::Block("ol"),
+        b"li" => Element::Block("li"),
+        b"blockquote" => Element::Block("blockquote"),
+        b"h1" => Element::TextBlock("h1"),
+        b"h2" => Element::TextBlock("h2"),
+        b"h3" => Element::TextBlock("h3"),
+        b"h4" => Element::TextBlock("h4"),
+        b"h5" => Element::TextBlock("h5"),
+        b"h6" => Element::TextBlock("h6"),
+        b"pre" => Element::TextBlock("pre"),
+        b"b" | b"strong" => Element::Mark("strong"),
+        b"i" | b"em" | b"cite" => Element::Mark("em"),
+        b"u" | b"ins" => Element::Mark("u"),
+        b"s" | b"strike" | b"del" => Element::Mark("s"),
+        b"code" | b"tt" | b"kbd" => Element::Mark("code"),
+        b"a" => Element::Link,
+        b"br" => Element::LineBreak,
+        b"hr" => Element::Rule,
+        b"img" | b"en-media" => Element::Image,
+        b"en-todo" => Element::Todo,
+        b"en-crypt" | b"script" | b"style" | b"object" | b"embed" | b"iframe" | b"applet"
+        | b"map" => Element::Skip,
+        _ => Element::Unwrap,
+    }
+}
+
+/// Converts HTML from another app, or Evernote's ENML, to the editor's HTML
+///
+/// Paragraphs, headings, lists, quotes, code, links (http, https and mailto)
+/// and basic formatting are kept. Anything else is unwrapped to its text, and
+/// scripts, styles and encrypted Evernote text are dropped. Evernote
+/// checkboxes become ☐ and ☑.
+///
+/// `embed` is called with the `src` of each `<img>` and the `hash` of each
+/// `<en-media>`. `data:image/` sources are kept without asking. An image
+/// `embed` cannot resolve is replaced by its alt text.
+pub fn sanitize_html(html: &str, embed: &mut dyn FnMut(&str) -> Option<Embed>) -> String {
+    let mut xml = Reader::from_str(html);
+    let config = xml.config_mut();
+    config.check_end_names = false;
+    config.allow_unmatched_ends = true;
+
+    let mut writer = HtmlWriter::default();
+    loop {
+        match xml.read_event() {
+            Ok(Event::Start(e)) => writer.start(&e, embed),
+            Ok(Event::Empty(e)) => {
+                if writer.skip == 0 && !matches!(classify(e.local_name().as_ref()), Element::Skip) {
+                    writer.start(&e, embed);
+                    writer.end(e.local_name().as_ref());
+                }
+            }
+            Ok(Event::End(e)) => writer.end(e.local_name().as_ref()),
+            Ok(Event::Text(e)) => writer.text(&e.decode().unwrap_or_default()),
+            Ok(Event::CData(e)) => writer.text(&e.decode().unwrap_or_default()),
+            Ok(Event::GeneralRef(e)) => writer.text(&resolve_reference(&e)),
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Stopped reading malformed HTML: {}", e);
+                break;
+            }
+        }
+    }
+    writer.finish()
+}
+
+/// Writes block and inline HTML, wrapping loose inline content in `<p>`
+#[derive(Default)]
+struct HtmlWriter {
+    out: String,
+    /// Inline content waiting for the end of its paragraph
+    paragraph: String,
+    has_text: bool,
+    has_break: bool,
+    /// Open inline marks as (tag, opening HTML), reopened after a paragraph break
+    marks: Vec<(&'static str, String)>,
+    /// Depth inside headings and `pre`, where inline content is written directly
+    text_blocks: usize,
+    pre: usize,
+    /// Depth inside a dropped element
+    skip: usize,
+}
+
+impl HtmlWriter {
+    fn target(&mut self) -> &mut String {
+        if self.text_blocks > 0 {
+            &mut self.out
+        } else {
+            &mut self.paragraph
+        }
+    }
+
+    fn start(&mut self, e: &BytesStart, embed: &mut dyn FnMut(&str) -> Option<Embed>) {
+        if self.skip > 0 {
+            self.skip += 1;
+            return;
+        }
+        match classify(e.local_name().as_ref()) {
+            Element::Paragraph => self.flush(),
+            Element::Block(tag) => {
+                self.flush();
+                self.out.push_str(&format!("<{}>", tag));
+            }
+            Element::TextBlock(tag) => {
+                self.flush();
+                self.out.push_str(&format!("<{}>", tag));
+                self.text_blocks += 1;
+                if tag == "pre" {
+                    self.pre += 1;
+                }
+            }
+            Element::Mark(tag) => self.open_mark(tag, format!("<{}>", tag)),
+            Element::Link => {
+                if let Some(href) = attribute(e, "href").filter(|href| is_safe_url(href)) {
+                    self.open_mark("a", format!("<a href=\"{}\">", escape_html(&href)));
+                }
+            }
+            Element::LineBreak => {
+                self.target().push_str("<br>");
+                self.has_break = true;
+            }
+            Element::Rule => {
+                self.flush();
+                self.out.push_str("<hr>");
+            }
+            Element::Image => self.image(e, embed),
+            Element::Todo => {
+                let checked = attribute(e, "checked").is_some_and(|v| v == "true");
+                self.text(if checked { "☑ " } else { "☐ " });
+            }
+            Element::Skip => {
+                if e.local_name().as_ref() == b"en-crypt" {
+                    self.text("[Encrypted text]");
+                }
+                self.skip = 1;
+            }
+            Element::Unwrap => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8]) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        match classify(name) {
+            Element::Paragraph => self.flush(),
+            Element::Block(tag) => {
+                self.flush();
+                self.out.push_str(&format!("</{}>", tag));
+            }
+            Element::TextBlock(tag) => {
+                self.close_all_marks();
+                self.out.push_str(&format!("</{}>", tag));
+                self.text_blocks = self.text_blocks.saturating_sub(1);
+                if tag == "pre" {
+                    self.pre = self.pre.saturating_sub(1);
+                }
+                // Its text was written directly, not into the next paragraph
+                self.has_text = false;
+                self.has_break = false;
+            }
+            Element::Mark(tag) => self.close_mark(tag),
+            Element::Link => self.close_mark("a"),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 || text.is_empty() {
+            return;
+        }
+        if text.chars().any(|c| !c.is_ascii_whitespace()) {
+            self.has_text = true;
+        }
+        if self.pre > 0 {
+            let text = escape_html(text);
+            self.target().push_str(&text);
+            return;
+        }
+        // Source line breaks and indentation are not part of the text
+        let target = self.target();
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                if !target.ends_with(' ') {
+                    target.push(' ');
+                }
+            } else {
+                target.push_str(&escape_html(c.encode_utf8(&mut [0; 4])));
+            }
+        }
+    }
+
+    fn image(&mut self, e: &BytesStart, embed: &mut dyn FnMut(&str) -> Option<Embed>) {
+        let source = if e.local_name().as_ref() == b"en-media" {
+            attribute(e, "hash")
+        } else {
+            attribute(e, "src")
+        };
+        let source = source.unwrap_or_default();
+        let resolved = if source.starts_with("data:image/") {
+            Some(Embed::Image(source))
+        } else {
+            embed(&source)
+        };
+        match resolved {
+            Some(Embed::Image(src)) => {
+                let img = format!("<img src=\"{}\">", escape_html(&src));
+                self.target().push_str(&img);
+                self.has_text = true;
+            }
+            Some(Embed::Text(text)) => self.text(&text),
+            None => {
+                if let Some(alt) = attribute(e, "alt") {
+                    self.text(&alt);
+                }
+            }
+        }
+    }
+
+    fn open_mark(&mut self, tag: &'static str, opening: String) {
+        self.target().push_str(&opening);
+        self.marks.push((tag, opening));
+    }
+
+    /// Closes `tag`, reopening any marks that were opened inside it
+    fn close_mark(&mut self, tag: &'static str) {
+        let Some(position) = self.marks.iter().rposition(|(t, _)| *t == tag) else {
+            return;
+        };
+        let reopen = self.marks.split_off(position + 1);
+        let (_, opening) = self.marks.pop().unwrap_or_default();
+        if reopen.is_empty() && self.target().ends_with(&opening) {
+            // Nothing was written inside the mark
+            let target = self.target();
+            target.truncate(target.len() - opening.len());
+            return;
+        }
+        let mut html = String::new();
+        for (t, _) in reopen.iter().rev() {
+            html.push_str(&format!("</{}>", t));
+        }
+        html.push_str(&format!("</{}>", tag));
+        for (_, opening) in &reopen {
+            html.push_str(opening);
+        }
+        self.target().push_str(&html);
+        self.marks.extend(reopen);
+    }
+
+    fn close_all_marks(&mut self) {
+        let closing: String = self
+            .marks
+            .iter()
+            .rev()
+            .map(|(tag, _)| format!("</{}>", tag))
+            .collect();
+        self.marks.clear();
+        self.target().push_str(&closing);
+    }
+
+    /// Ends the current paragraph. Marks still open carry over to the next.
+    fn flush(&mut self) {
+        if self.text_blocks > 0 {
+            return;
+        }
+        let reopen: String = self
+            .marks
+            .iter()
+            .map(|(_, opening)| opening.as_str())
+            .collect();
+        if self.has_text {
+            let closing: String = self
+                .marks
+                .iter()
+                .rev()
+                .map(|(tag, _)| format!("</{}>", tag))
+                .collect();
+            let mut paragraph = std::mem::take(&mut self.paragraph);
+            while let Some(trimmed) = paragraph.trim_end_matches(' ').strip_suffix("<br>") {
+                paragraph = trimmed.to_string();
+            }
+            self.out.push_str(&format!(
+                "<p>{}{}</p>",
+                paragraph.trim_matches(' '),
+                closing
+            ));
+        } else if self.has_break {
+            self.out.push_str("<p></p>");
+        }
+        self.paragraph = reopen;
+        self.has_text = false;
+        self.has_break = false;
+    }
+
+    fn finish(mut self) -> String {
+        self.marks.clear();
+        self.flush();
+        while let Some(trimmed) = self.out.strip_suffix("<p></p>") {
+            self.out.truncate(trimmed.len());
+        }
+        self.out
+    }
+}
+
+pub(crate) fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    let attribute = e.try_get_attribute(name).ok()??;
+    let value = attribute.unescape_value_with(resolve_entity).ok()?;
+    Some(value.into_owned())
+}
+
+/// Resolves `&name;` and `&#N;` references, keeping unknown ones as text
+pub fn resolve_reference(reference: &BytesRef) -> String {
+    if let Ok(Some(c)) = reference.resolve_char_ref() {
+        return c.to_string();
+    }
+    let name = reference.decode().unwrap_or_default();
+    match resolve_entity(&name) {
+        Some(value) => value.to_string(),
+        None => format!("&{};", name),
+    }
+}
+
+/// The XML entities plus the HTML ones the ENML DTD allows and notes use
+pub(crate) fn resolve_entity(name: &str) -> Option<&'static str> {
+    quick_xml::escape::resolve_xml_entity(name).or(match name {
+        "nbsp" => Some("\u{a0}"),
+        "ndash" => Some("–"),
+        "mdash" => Some("—"),
+        "hellip" => Some("…"),
+        "lsquo" => Some("‘"),
+        "rsquo" => Some("’"),
+        "ldquo" => Some("“"),
+        "rdquo" => Some("”"),
+        "laquo" => Some("«"),
+        "raquo" => Some("»"),
+        "bull" => Some("•"),
+        "middot" => Some("·"),
+        "deg" => Some("°"),
+        "copy" => Some("©"),
+        "reg" => Some("®"),
+        "trade" => Some("™"),
+        "euro" => Some("€"),
+        "pound" => Some("£"),
+        "times" => Some("×"),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enml_blocks_and_sanitizing() {
+        let enml = r#"<en-note><h1>Plan</h1><ul><li><div><en-todo checked="true"/>Pack</div></li>
+            <li><en-todo/>Leave</li></ul><div>See <a href="https://example.com">this</a>
+            and <a href="javascript:alert(1)">that</a><script>alert(2)</script></div>
+            <table><tr><td>a</td><td><span style="color:red">b</span></td></tr></table>
+            <div><en-crypt cipher="AES">c2VjcmV0</en-crypt></div></en-note>"#;
+
+        assert_eq!(
+            sanitize_html(enml, &mut |_| None),
+            "<h1>Plan</h1><ul><li><p>☑ Pack</p></li><li><p>☐ Leave</p></li></ul>\
+             <p>See <a href=\"https://example.com\">this</a> and that</p>\
+             <p>a</p><p>b</p><p>[Encrypted text]</p>"
+        );
+    }
+
+    #[test]
+    fn test_marks_across_paragraphs() {
+        let enml = "<en-note><b>one<div>two</div></b><i>three</i></en-note>";
+        assert_eq!(
+            sanitize_html(enml, &mut |_| None),
+            "<p><strong>one</strong></p><p><strong>two</strong></p><p><em>three</em></p>"
+        );
+    }
+
+    #[test]
+    fn test_text_to_html() {
+        assert_eq!(
+            text_to_html("Line one\r\nline <two>\n\n\n\nNext"),
+            "<p>Line one<br>line &lt;two&gt;</p><p>Next</p>"
+        );
+        assert_eq!(text_to_html("  \n"), "");
+    }
+
+    #[test]
+    fn test_images() {
+        let html = "<p>A <img src=\"data:image/png;base64,iVBO\"> <img src=\"a.jpg\" alt=\"cat\">\
+                    <img src=\"b.jpg\"> <img src=\"missing.jpg\" alt=\"gone\"></p>";
+        let result = sanitize_html(html, &mut |src| match src {
+            "a.jpg" => Some(Embed::Image("data:image/jpeg;base64,/9j/".to_string())),
+            "b.jpg" => Some(Embed::Text("[b.jpg]".to_string())),
+            _ => None,
+        });
+        assert_eq!(
+            result,
+            "<p>A <img src=\"data:image/png;base64,iVBO\"> \
+             <img src=\"data:image/jpeg;base64,/9j/\">[b.jpg] gone</p>"
+        );
+    }
+}
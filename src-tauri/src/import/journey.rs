@@ -0,0 +1,318 @@
+use super::html::sanitize_html;
+use super::markdown::{escape_html, image_mime_type, markdown_to_html};
+use super::metadata::ImportedMetadata;
+use super::zip::ZipReader;
+use crate::db::queries::{count_words, DiaryEntry};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::warn;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+/// Photos larger than this are left out
+const MAX_PHOTO_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Entry files larger than this are skipped
+const MAX_ENTRY_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Journey stores "no temperature" as the largest double
+const NO_TEMPERATURE: f64 = 1e300;
+
+/// One entry file from a Journey export
+#[derive(Debug, Deserialize)]
+struct JourneyEntry {
+    #[serde(default)]
+    text: String,
+    /// Milliseconds since the Unix epoch
+    date_journal: i64,
+    #[serde(default)]
+    date_modified: Option<i64>,
+    /// "html" or "markdown"
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    favourite: bool,
+    /// 0 when no mood was picked
+    #[serde(default)]
+    mood: i64,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    lat: Option<f64>,
+    #[serde(default)]
+    lon: Option<f64>,
+    #[serde(default)]
+    weather: Option<JourneyWeather>,
+    /// File names of the photos in the export
+    #[serde(default)]
+    photos: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JourneyWeather {
+    #[serde(default)]
+    degree_c: Option<f64>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+impl JourneyWeather {
+    /// e.g. "Clear, 21 °C"
+    fn describe(&self) -> Option<String> {
+        let conditions = self
+            .description
+            .as_deref()
+            .map(str::trim)
+            .filter(|d| !d.is_empty());
+        let temperature = self
+            .degree_c
+            .filter(|t| t.abs() < NO_TEMPERATURE)
+            .map(|t| format!("{:.0} °C", t));
+        match (conditions, temperature) {
+            (Some(c), Some(t)) => Some(format!("{}, {}", c, t)),
+            (Some(c), None) => Some(c.to_string()),
+            (None, t) => t,
+        }
+    }
+}
+
+/// Parse a Journey ZIP export
+///
+/// The archive holds one JSON file per entry plus the photo files they name.
+/// Expected entry format:
+/// ```json
+/// {
+///   "text": "<p>Entry content</p>",
+///   "type": "html",
+///   "date_journal": 1705329000000,
+///   "date_modified": 1705333000000,
+///   "timezone": "Europe/Berlin",
+///   "favourite": true,
+///   "mood": 2,
+///   "tags": ["travel"],
+///   "address": "Berlin, Germany",
+///   "weather": {"degree_c": 21.0, "description": "Clear"},
+///   "photos": ["1705329000000-abc.jpg"]
+/// }
+/// ```
+///
+/// - Journey entries have no title, so the title is left empty
+/// - HTML is cleaned up for the editor; Markdown entries are converted
+/// - Photos are embedded at the end of the entry
+/// - Tags, favourite, mood, address, weather and time zone are appended
+/// - The entry date is the UTC day of `date_journal`
+pub fn parse_journey_zip(
+    path: &Path,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+    let mut zip = ZipReader::new(BufReader::new(file))?;
+
+    let entry_files: Vec<usize> = zip
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.is_dir() && e.name.to_lowercase().ends_with(".json"))
+        .map(|(i, _)| i)
+        .collect();
+    if entry_files.is_empty() {
+        return Err("No Journey entries found in the ZIP file".to_string());
+    }
+
+    let photo_files: HashMap<String, usize> = zip
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.is_dir())
+        .filter_map(|(i, e)| Some((e.name.rsplit('/').next()?.to_string(), i)))
+        .collect();
+
+    for index in entry_files {
+        let name = zip.entries()[index].name.clone();
+        let bytes = match zip.read(index, MAX_ENTRY_SIZE) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping Journey entry '{}': {}", name, e);
+                continue;
+            }
+        };
+        let entry: JourneyEntry = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Failed to parse Journey entry '{}': {}", name, e))?;
+        on_entry(convert_entry(entry, &mut zip, &photo_files)?)?;
+    }
+    Ok(())
+}
+
+fn convert_entry<R: Read + Seek>(
+    entry: JourneyEntry,
+    zip: &mut ZipReader<R>,
+    photo_files: &HashMap<String, usize>,
+) -> Result<DiaryEntry, String> {
+    let created = timestamp(entry.date_journal)?;
+    let date_created = created.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let date_updated = match entry.date_modified.filter(|&m| m > 0) {
+        Some(modified) => timestamp(modified)?.to_rfc3339_opts(SecondsFormat::Secs, true),
+        None => date_created.clone(),
+    };
+
+    let mut text = if entry.kind.eq_ignore_ascii_case("markdown") {
+        markdown_to_html(&entry.text)
+    } else {
+        sanitize_html(&entry.text, &mut |_| None)
+    };
+    for photo in &entry.photos {
+        if let Some(src) = embed_photo(photo, zip, photo_files) {
+            text.push_str(&format!("<p><img src=\"{}\"></p>", escape_html(&src)));
+        }
+    }
+
+    let location = entry
+        .address
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .or(match (entry.lat, entry.lon) {
+            (Some(lat), Some(lon)) if lat.abs() <= 90.0 && (lat, lon) != (0.0, 0.0) => {
+                Some(format!("{:.4}, {:.4}", lat, lon))
+            }
+            _ => None,
+        });
+    let metadata = ImportedMetadata {
+        tags: entry.tags,
+        starred: entry.favourite,
+        mood: (entry.mood != 0).then(|| entry.mood.to_string()),
+        location,
+        weather: entry.weather.and_then(|w| w.describe()),
+        time_zone: entry.timezone.filter(|tz| !tz.trim().is_empty()),
+    };
+    text.push_str(&metadata.to_html());
+
+    Ok(DiaryEntry {
+        id: 0,
+        date: created.format("%Y-%m-%d").to_string(),
+        title: String::new(),
+        word_count: count_words(&text),
+        text,
+        date_created,
+        date_updated,
+    })
+}
+
+fn timestamp(millis: i64) -> Result<DateTime<Utc>, String> {
+    DateTime::<Utc>::from_timestamp_millis(millis)
+        .ok_or_else(|| format!("Invalid Journey timestamp: {}", millis))
+}
+
+/// Reads a photo from the archive and returns it as a `data:` URI.
+fn embed_photo<R: Read + Seek>(
+    name: &str,
+    zip: &mut ZipReader<R>,
+    photo_files: &HashMap<String, usize>,
+) -> Option<String> {
+    let Some(&index) = photo_files.get(name) else {
+        warn!("Skipping missing Journey photo '{}'", name);
+        return None;
+    };
+    let mime = image_mime_type(name.rsplit('.').next().unwrap_or(""))?;
+    match zip.read(index, MAX_PHOTO_SIZE) {
+        Ok(bytes) => Some(format!(
+            "data:{};base64,{}",
+            mime,
+            general_purpose::STANDARD.encode(bytes)
+        )),
+        Err(e) => {
+            warn!("Skipping Journey photo '{}': {}", name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::zip::tests::build_zip;
+
+    fn parse(files: &[(&str, &[u8])]) -> Result<Vec<DiaryEntry>, String> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), build_zip(files)).unwrap();
+        let mut entries = Vec::new();
+        parse_journey_zip(file.path(), &mut |entry| {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    #[test]
+    fn test_parse_entry_with_photo_and_metadata() {
+        let entry = r#"{
+            "text": "<p>Lunch by the <b>river</b></p><script>x()</script>",
+            "type": "html",
+            "date_journal": 1705329000000,
+            "date_modified": 1705333000000,
+            "timezone": "Europe/Berlin",
+            "favourite": true,
+            "mood": 2,
+            "tags": ["food"],
+            "address": "Berlin, Germany",
+            "lat": 52.52, "lon": 13.40,
+            "weather": {"degree_c": 21.2, "description": "Clear", "icon": "01d"},
+            "photos": ["1705329000000-abc.jpg"],
+            "sentiment": 0.0
+        }"#;
+        let entries = parse(&[
+            ("1705329000000-abc.json", entry.as_bytes()),
+            ("1705329000000-abc.jpg", &[0xFF, 0xD8, 0xFF]),
+        ])
+        .unwrap();
+
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.date, "2024-01-15");
+        assert_eq!(entry.title, "");
+        assert_eq!(entry.date_created, "2024-01-15T14:30:00Z");
+        assert_eq!(entry.date_updated, "2024-01-15T15:36:40Z");
+        assert_eq!(
+            entry.text,
+            "<p>Lunch by the <strong>river</strong></p>\
+             <p><img src=\"data:image/jpeg;base64,/9j/\"></p><p>#food</p>\
+             <p><em>Starred · Mood: 2 · Location: Berlin, Germany · \
+             Weather: Clear, 21 °C · Time zone: Europe/Berlin</em></p>"
+        );
+        assert_eq!(entry.word_count, 22);
+    }
+
+    #[test]
+    fn test_markdown_entry_without_extras() {
+        let entry = r#"{
+            "text": "Quiet **day**",
+            "type": "markdown",
+            "date_journal": 1705329000000,
+            "lat": 0.0, "lon": 0.0,
+            "weather": {"degree_c": 1.7976931348623157e308, "description": ""},
+            "photos": ["missing.jpg"]
+        }"#;
+        let entries = parse(&[("a.json", entry.as_bytes())]).unwrap();
+
+        assert_eq!(entries[0].text, "<p>Quiet <strong>day</strong></p>");
+        assert_eq!(entries[0].date_updated, entries[0].date_created);
+    }
+
+    #[test]
+    fn test_zip_without_entries() {
+        let err = parse(&[("photo.jpg", b"x")]).unwrap_err();
+        assert!(err.contains("No Journey entries"));
+    }
+
+    #[test]
+    fn test_invalid_entry_names_the_file() {
+        let err = parse(&[("broken.json", b"{\"text\": 1}")]).unwrap_err();
+        assert!(err.contains("broken.json"));
+    }
+}
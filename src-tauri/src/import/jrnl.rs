@@ -1,15 +1,11 @@
-use crate::db::queries::DiaryEntry;
-use chrono::{Datelike, Utc};
+use super::json_stream;
+use super::markdown::markdown_to_html;
+use super::metadata::ImportedMetadata;
+use crate::db::queries::{count_words, DiaryEntry};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
 use log::warn;
 use serde::Deserialize;
-
-/// Root structure of a jrnl JSON export
-#[derive(Debug, Deserialize)]
-struct JrnlJson {
-    entries: Vec<JrnlEntry>,
-    #[allow(dead_code)] // jrnl JSON schema field; the tags summary is not used by Mini Diarium
-    tags: serde_json::Value,
-}
+use std::io::Read;
 
 /// Individual jrnl entry
 #[derive(Debug, Deserialize)]
@@ -55,26 +51,37 @@ struct JrnlEntry {
 /// * `Ok(Vec<DiaryEntry>)` - Successfully parsed entries
 /// * `Err(String)` - Parse error message
 pub fn parse_jrnl_json(json: &str) -> Result<Vec<DiaryEntry>, String> {
-    let jrnl_data: JrnlJson =
-        serde_json::from_str(json).map_err(|e| format!("Failed to parse jrnl JSON: {}", e))?;
+    let mut entries = Vec::new();
+    parse_jrnl_json_reader(json.as_bytes(), &mut |entry| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
 
-    let mut diary_entries = Vec::new();
+/// Streaming variant of [`parse_jrnl_json`]: reads the export from `reader`
+/// and calls `on_entry` for each valid entry as soon as it is parsed.
+pub fn parse_jrnl_json_reader<R: Read>(
+    reader: R,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    const CONTEXT: &str = "Failed to parse jrnl JSON";
     let now = Utc::now().to_rfc3339();
 
-    for entry in jrnl_data.entries {
+    let keys = json_stream::for_each_array_item(reader, "entries", CONTEXT, |entry: JrnlEntry| {
         // Validate date format (YYYY-MM-DD)
         if !is_valid_date_format(&entry.date) {
             warn!(
                 "Skipping jrnl entry with invalid date format: {}",
                 entry.date
             );
-            continue;
+            return Ok(());
         }
 
         // Calculate word count from body only (title is separate)
         let word_count = entry.body.split_whitespace().count() as i32;
 
-        diary_entries.push(DiaryEntry {
+        on_entry(DiaryEntry {
             id: 0,
             date: entry.date,
             title: entry.title,
@@ -82,10 +89,101 @@ pub fn parse_jrnl_json(json: &str) -> Result<Vec<DiaryEntry>, String> {
             word_count,
             date_created: now.clone(),
             date_updated: now.clone(),
-        });
+        })
+    })?;
+
+    // jrnl always writes its tag summary; without it this is not a jrnl export
+    if !keys.contains("tags") {
+        return Err(format!("{}: missing field `tags`", CONTEXT));
+    }
+    Ok(())
+}
+
+/// Parse a jrnl plain-text journal (the `.txt` file jrnl keeps entries in)
+///
+/// jrnl format:
+/// ```text
+/// [2024-01-15 09:30] Entry title
+/// Entry body text, which may span
+/// several lines and paragraphs.
+///
+/// [2024-01-16 14:00 PM] Starred entry *
+/// ```
+///
+/// - An entry starts at a line with `[YYYY-MM-DD time]`, where the time is
+///   24-hour or 12-hour; the rest of the line is the title
+/// - A title ending in ` *` marks a starred entry
+/// - The body is Markdown and runs until the next entry line
+pub fn parse_jrnl_txt(content: &str) -> Result<Vec<DiaryEntry>, String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut entries = Vec::new();
+    let mut current: Option<(NaiveDateTime, &str, Vec<&str>)> = None;
+
+    for line in content.lines() {
+        if let Some((timestamp, title)) = parse_jrnl_header(line) {
+            if let Some((timestamp, title, body)) = current.take() {
+                entries.push(jrnl_txt_entry(timestamp, title, &body));
+            }
+            current = Some((timestamp, title, Vec::new()));
+        } else if let Some((_, _, body)) = current.as_mut() {
+            body.push(line);
+        } else if !line.trim().is_empty() {
+            return Err(
+                "Not a jrnl journal: entries must start with a line like '[2024-01-15 09:30] Title'"
+                    .to_string(),
+            );
+        }
     }
+    if let Some((timestamp, title, body)) = current {
+        entries.push(jrnl_txt_entry(timestamp, title, &body));
+    }
+    Ok(entries)
+}
 
-    Ok(diary_entries)
+/// Splits `[2024-01-15 09:30] Title` into its timestamp and title
+fn parse_jrnl_header(line: &str) -> Option<(NaiveDateTime, &str)> {
+    let rest = line.strip_prefix('[')?;
+    let (stamp, title) = rest.split_once(']')?;
+    let date = NaiveDate::parse_from_str(stamp.get(..10)?, "%Y-%m-%d").ok()?;
+    let time = stamp[10..].trim().to_uppercase();
+    let time = ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p"]
+        .into_iter()
+        .find_map(|format| NaiveTime::parse_from_str(&time, format).ok())?;
+    Some((
+        date.and_time(time),
+        title.strip_prefix(' ').unwrap_or(title),
+    ))
+}
+
+fn jrnl_txt_entry(timestamp: NaiveDateTime, title: &str, body: &[&str]) -> DiaryEntry {
+    let title = title.trim_end();
+    let (title, starred) = match title.strip_suffix(" *") {
+        Some(title) => (title.trim_end(), true),
+        None => (title, title == "*"),
+    };
+    let title = if title == "*" { "" } else { title };
+
+    let mut text = markdown_to_html(body.join("\n").trim_matches('\n'));
+    text.push_str(
+        &ImportedMetadata {
+            starred,
+            ..Default::default()
+        }
+        .to_html(),
+    );
+    let timestamp = timestamp
+        .and_utc()
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    DiaryEntry {
+        id: 0,
+        date: timestamp[..10].to_string(),
+        title: title.to_string(),
+        word_count: count_words(&text),
+        text,
+        date_created: timestamp.clone(),
+        date_updated: timestamp,
+    }
 }
 
 /// Validate date format is YYYY-MM-DD with calendar accuracy
@@ -425,4 +523,51 @@ mod tests {
         assert_eq!(entries[2].date, "2024-01-17");
         assert_eq!(entries[2].title, "Weekend Plans");
     }
+
+    #[test]
+    fn test_parse_jrnl_txt() {
+        let txt = "[2024-01-15 09:30] First day\nStarted using **jrnl**.\n\nSecond paragraph.\n\n\
+                   [2024-01-16 02:15 PM] Meeting *\n\n[2024-01-17 18:45]\nNo title\n";
+        let entries = parse_jrnl_txt(txt).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].date, "2024-01-15");
+        assert_eq!(entries[0].title, "First day");
+        assert_eq!(entries[0].date_created, "2024-01-15T09:30:00Z");
+        assert_eq!(
+            entries[0].text,
+            "<p>Started using <strong>jrnl</strong>.</p><p>Second paragraph.</p>"
+        );
+        assert_eq!(entries[0].word_count, 6);
+        assert_eq!(entries[1].title, "Meeting");
+        assert_eq!(entries[1].date_created, "2024-01-16T14:15:00Z");
+        assert_eq!(entries[1].text, "<p><em>Starred</em></p>");
+        assert_eq!(entries[2].title, "");
+        assert_eq!(entries[2].text, "<p>No title</p>");
+    }
+
+    #[test]
+    fn test_parse_jrnl_txt_rejects_other_text() {
+        assert!(parse_jrnl_txt("Just some notes\n[2024-01-15 09:30] Title")
+            .unwrap_err()
+            .contains("Not a jrnl journal"));
+        assert!(parse_jrnl_txt("").unwrap().is_empty());
+        // A bracketed line that is not a valid timestamp is part of the body
+        let entries = parse_jrnl_txt("[2024-01-15 09:30] A\n[2024-02-31 10:00] b").unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_jrnl_txt_sample_fixture() {
+        let txt = include_str!("../../test-fixtures/jrnl-sample.txt");
+        let entries = parse_jrnl_txt(txt).unwrap();
+
+        assert_eq!(entries.len(), 3, "Expected 3 entries in sample fixture");
+        assert_eq!(entries[0].date, "2024-01-15");
+        assert_eq!(entries[0].title, "First Day");
+        assert!(entries[0].text.contains("Started using jrnl"));
+        assert_eq!(entries[1].title, "Project Meeting");
+        assert!(entries[1].text.contains("Starred"));
+        assert_eq!(entries[2].date, "2024-01-17");
+    }
 }
@@ -0,0 +1,255 @@
+//! Incremental reading of JSON exports.
+//!
+//! Exports keep their entries in one big array or object under a top-level
+//! key. These helpers hand each element to a callback as soon as it has been
+//! parsed, so only one entry is held in memory at a time no matter how large
+//! the file is.
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+
+/// Reads a top-level JSON object and calls `on_item` for every element of the
+/// array stored under `field`. Other keys are skipped without being kept.
+///
+/// Errors from `on_item` are returned unchanged; JSON errors are prefixed
+/// with `context`. Returns the top-level keys that were present, so callers
+/// can check for other required fields.
+pub fn for_each_array_item<T, R>(
+    reader: R,
+    field: &str,
+    context: &str,
+    mut on_item: impl FnMut(T) -> Result<(), String>,
+) -> Result<HashSet<String>, String>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    stream_field(reader, field, context, Shape::Array, &mut |_, item| {
+        on_item(item)
+    })
+}
+
+/// Reads a top-level JSON object and calls `on_item` with the key and value of
+/// every member of the object stored under `field`.
+///
+/// Errors are reported as for [`for_each_array_item`]. Returns the top-level
+/// keys that were present.
+pub fn for_each_map_entry<T, R>(
+    reader: R,
+    field: &str,
+    context: &str,
+    mut on_item: impl FnMut(String, T) -> Result<(), String>,
+) -> Result<HashSet<String>, String>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    stream_field(reader, field, context, Shape::Map, &mut |key, item| {
+        on_item(key.unwrap_or_default(), item)
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Shape {
+    Array,
+    Map,
+}
+
+type ItemCallback<'a, T> = dyn FnMut(Option<String>, T) -> Result<(), String> + 'a;
+
+fn stream_field<T, R>(
+    reader: R,
+    field: &str,
+    context: &str,
+    shape: Shape,
+    on_item: &mut ItemCallback<'_, T>,
+) -> Result<HashSet<String>, String>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    let mut keys = HashSet::new();
+    // An error from `on_item` is stashed here so it reaches the caller as-is
+    // instead of being wrapped in a JSON syntax error.
+    let mut failure = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let parsed = RootSeed {
+        field,
+        shape,
+        on_item,
+        keys: &mut keys,
+        failure: &mut failure,
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|()| deserializer.end());
+
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    parsed.map_err(|e| format!("{}: {}", context, e))?;
+    Ok(keys)
+}
+
+struct RootSeed<'a, 'f, T> {
+    field: &'a str,
+    shape: Shape,
+    on_item: &'a mut ItemCallback<'f, T>,
+    keys: &'a mut HashSet<String>,
+    failure: &'a mut Option<String>,
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for RootSeed<'_, '_, T> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for RootSeed<'_, '_, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON object with an `{}` field", self.field)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut found = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.field && !found {
+                map.next_value_seed(ItemsSeed {
+                    shape: self.shape,
+                    on_item: &mut *self.on_item,
+                    failure: &mut *self.failure,
+                    _item: PhantomData,
+                })?;
+                found = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+            self.keys.insert(key);
+        }
+        if !found {
+            return Err(de::Error::custom(format!("missing field `{}`", self.field)));
+        }
+        Ok(())
+    }
+}
+
+struct ItemsSeed<'a, 'f, T> {
+    shape: Shape,
+    on_item: &'a mut ItemCallback<'f, T>,
+    failure: &'a mut Option<String>,
+    _item: PhantomData<T>,
+}
+
+impl<T> ItemsSeed<'_, '_, T> {
+    fn emit<E: de::Error>(&mut self, key: Option<String>, item: T) -> Result<(), E> {
+        (self.on_item)(key, item).map_err(|e| {
+            *self.failure = Some(e);
+            E::custom("import stopped")
+        })
+    }
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for ItemsSeed<'_, '_, T> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.shape {
+            Shape::Array => deserializer.deserialize_seq(self),
+            Shape::Map => deserializer.deserialize_map(self),
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for ItemsSeed<'_, '_, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.shape {
+            Shape::Array => f.write_str("an array of entries"),
+            Shape::Map => f.write_str("an object of entries"),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(item) = seq.next_element::<T>()? {
+            self.emit(None, item)?;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some((key, item)) = map.next_entry::<String, T>()? {
+            self.emit(Some(key), item)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streams_array_items_and_reports_keys() {
+        let json = r#"{"tags": {"a": 1}, "entries": [1, 2, 3], "other": [true]}"#;
+        let mut items = Vec::new();
+        let keys = for_each_array_item(json.as_bytes(), "entries", "Bad", |n: u32| {
+            items.push(n);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(items, vec![1, 2, 3]);
+        assert!(keys.contains("tags") && keys.contains("other"));
+    }
+
+    #[test]
+    fn test_streams_map_entries() {
+        let json = r#"{"entries": {"2024-01-01": "a", "2024-01-02": "b"}}"#;
+        let mut items = Vec::new();
+        for_each_map_entry(json.as_bytes(), "entries", "Bad", |key, value: String| {
+            items.push((key, value));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1], ("2024-01-02".to_string(), "b".to_string()));
+    }
+
+    #[test]
+    fn test_missing_field_and_bad_json() {
+        let ok = |_: u32| Ok(());
+        let parse = |json: &str| for_each_array_item(json.as_bytes(), "entries", "Bad JSON", ok);
+        let err = parse(r#"{"other": []}"#).unwrap_err();
+        assert!(err.starts_with("Bad JSON: missing field `entries`"));
+        assert!(parse(r#"{"entries": [1,"#).is_err());
+        assert!(parse(r#"{"entries": []} x"#).is_err());
+    }
+
+    #[test]
+    fn test_callback_error_stops_parsing() {
+        let mut seen = 0;
+        let err = for_each_array_item(
+            r#"{"entries": [1, 2, 3]}"#.as_bytes(),
+            "entries",
+            "Bad",
+            |_: u32| {
+                seen += 1;
+                Err("database is full".to_string())
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(err, "database is full");
+        assert_eq!(seen, 1);
+    }
+}
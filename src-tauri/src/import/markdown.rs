@@ -0,0 +1,559 @@
+//! Markdown to HTML conversion for imported entries.
+//!
+//! Covers the Markdown that journaling apps write: headings, paragraphs,
+//! lists, block quotes, code, rules, emphasis, links and images. Raw HTML in
+//! the source is escaped rather than passed through, so an imported file can
+//! never inject markup into the editor.
+
+/// Converts Markdown to HTML. Images are replaced by their alt text.
+pub fn markdown_to_html(markdown: &str) -> String {
+    markdown_to_html_with_images(markdown, &mut |_| None)
+}
+
+/// Converts Markdown to HTML, calling `resolve_image` with the URL of every
+/// image. It returns the `src` to embed (usually a `data:` URI), or `None` to
+/// replace the image with its alt text.
+pub fn markdown_to_html_with_images(
+    markdown: &str,
+    resolve_image: &mut dyn FnMut(&str) -> Option<String>,
+) -> String {
+    let normalized = markdown.replace("\r\n", "\n").replace('\t', "    ");
+    let lines: Vec<&str> = normalized.lines().collect();
+    let mut html = String::new();
+    render_blocks(&lines, resolve_image, &mut html);
+    html
+}
+
+fn render_blocks(
+    lines: &[&str],
+    resolve_image: &mut dyn FnMut(&str) -> Option<String>,
+    out: &mut String,
+) {
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            i += 1;
+            continue;
+        }
+
+        // Setext heading: a paragraph line underlined with === or ---
+        if paragraph.len() == 1 && is_setext_underline(trimmed) {
+            let level = if trimmed.starts_with('=') { 1 } else { 2 };
+            let text = paragraph.pop().unwrap_or_default();
+            push_heading(level, text.trim(), resolve_image, out);
+            i += 1;
+            continue;
+        }
+
+        if let Some(fence) = code_fence(trimmed) {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                code.push(lines[i]);
+                i += 1;
+            }
+            out.push_str("<pre><code>");
+            out.push_str(&escape_html(&code.join("\n")));
+            out.push_str("</code></pre>");
+            i += 1;
+            continue;
+        }
+
+        if let Some((level, text)) = atx_heading(trimmed) {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            push_heading(level, text, resolve_image, out);
+            i += 1;
+            continue;
+        }
+
+        if is_thematic_break(trimmed) {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            out.push_str("<hr>");
+            i += 1;
+            continue;
+        }
+
+        if trimmed.starts_with('>') {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            let mut quoted = Vec::new();
+            while i < lines.len() && lines[i].trim_start().starts_with('>') {
+                let rest = &lines[i].trim_start()[1..];
+                quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                i += 1;
+            }
+            out.push_str("<blockquote>");
+            render_blocks(&quoted, resolve_image, out);
+            out.push_str("</blockquote>");
+            continue;
+        }
+
+        if let Some(marker) = list_marker(line) {
+            flush_paragraph(&mut paragraph, resolve_image, out);
+            i = render_list(lines, i, marker.ordered, resolve_image, out);
+            continue;
+        }
+
+        paragraph.push(trimmed);
+        i += 1;
+    }
+    flush_paragraph(&mut paragraph, resolve_image, out);
+}
+
+fn flush_paragraph(
+    paragraph: &mut Vec<&str>,
+    resolve_image: &mut dyn FnMut(&str) -> Option<String>,
+    out: &mut String,
+) {
+    if paragraph.is_empty() {
+        return;
+    }
+    // Journaling apps treat every newline as a line break
+    let lines: Vec<String> = paragraph
+        .drain(..)
+        .map(|line| render_inline(line.trim_end(), resolve_image))
+        .collect();
+    out.push_str("<p>");
+    out.push_str(&lines.join("<br>"));
+    out.push_str("</p>");
+}
+
+fn push_heading(
+    level: usize,
+    text: &str,
+    resolve_image: &mut dyn FnMut(&str) -> Option<String>,
+    out: &mut String,
+) {
+    out.push_str(&format!(
+        "<h{level}>{}</h{level}>",
+        render_inline(text, resolve_image)
+    ));
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+fn is_setext_underline(line: &str) -> bool {
+    let line = line.trim_end();
+    !line.is_empty() && (line.bytes().all(|b| b == b'=') || line.bytes().all(|b| b == b'-'))
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && matches!(chars[0], '-' | '*' | '_') && chars.iter().all(|&c| c == chars[0])
+}
+
+fn code_fence(line: &str) -> Option<&'static str> {
+    if line.starts_with("```") {
+        Some("```")
+    } else if line.starts_with("~~~") {
+        Some("~~~")
+    } else {
+        None
+    }
+}
+
+struct ListMarker {
+    ordered: bool,
+    /// Column where the item's content starts
+    content_indent: usize,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let (ordered, marker_len) = if rest.starts_with(['-', '*', '+']) {
+        (false, 1)
+    } else {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || digits > 9 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        (true, digits + 1)
+    };
+    let after = &rest[marker_len..];
+    if !after.is_empty() && !after.starts_with(' ') {
+        return None;
+    }
+    let spaces = after.len() - after.trim_start().len();
+    Some(ListMarker {
+        ordered,
+        content_indent: indent + marker_len + spaces.clamp(1, 4),
+    })
+}
+
+/// Renders the list starting at `lines[start]` and returns the index of the
+/// first line after it.
+fn render_list(
+    lines: &[&str],
+    start: usize,
+    ordered: bool,
+    resolve_image: &mut dyn FnMut(&str) -> Option<String>,
+    out: &mut String,
+) -> usize {
+    let base_indent = lines[start].len() - lines[start].trim_start().len();
+    let tag = if ordered { "ol" } else { "ul" };
+    out.push_str(&format!("<{}>", tag));
+
+    let mut i = start;
+    while i < lines.len() {
+        let Some(marker) = list_marker(lines[i]) else {
+            break;
+        };
+        let indent = lines[i].len() - lines[i].trim_start().len();
+        if marker.ordered != ordered || indent != base_indent {
+            break;
+        }
+
+        // The item is its first line plus every following line that is
+        // indented past the marker (or blank and followed by such a line)
+        let mut item = vec![lines[i].get(marker.content_indent..).unwrap_or("")];
+        i += 1;
+        while i < lines.len() {
+            let line = lines[i];
+            let line_indent = line.len() - line.trim_start().len();
+            if line.trim().is_empty() {
+                let continues = lines
+                    .get(i + 1)
+                    .is_some_and(|next| next.len() - next.trim_start().len() > base_indent);
+                if !continues {
+                    break;
+                }
+                item.push("");
+            } else if line_indent > base_indent {
+                item.push(&line[line_indent.min(marker.content_indent)..]);
+            } else if item.last().is_some_and(|l| !l.is_empty()) && list_marker(line).is_none() {
+                // Lazy continuation of the item's paragraph
+                item.push(line.trim_start());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+
+        let mut content = String::new();
+        render_blocks(&item, resolve_image, &mut content);
+        out.push_str("<li>");
+        out.push_str(&content);
+        out.push_str("</li>");
+
+        // A blank line between items of the same list does not end it
+        if i + 1 < lines.len()
+            && lines[i].trim().is_empty()
+            && list_marker(lines[i + 1]).is_some_and(|m| m.ordered == ordered)
+        {
+            i += 1;
+        }
+    }
+
+    out.push_str(&format!("</{}>", tag));
+    i
+}
+
+fn render_inline(text: &str, resolve_image: &mut dyn FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\\' => {
+                let next = rest[1..].chars().next();
+                match next {
+                    Some(escaped) if escaped.is_ascii_punctuation() => {
+                        push_escaped(&mut out, escaped);
+                        rest = &rest[2..];
+                    }
+                    _ => {
+                        out.push('\\');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            '`' => {
+                let ticks = rest.bytes().take_while(|&b| b == b'`').count();
+                let fence = &rest[..ticks];
+                match rest[ticks..].find(fence) {
+                    Some(end) => {
+                        let code = rest[ticks..ticks + end].trim();
+                        out.push_str("<code>");
+                        out.push_str(&escape_html(code));
+                        out.push_str("</code>");
+                        rest = &rest[2 * ticks + end..];
+                    }
+                    None => {
+                        out.push_str(fence);
+                        rest = &rest[ticks..];
+                    }
+                }
+            }
+            '!' if rest[1..].starts_with('[') => match parse_link(&rest[1..]) {
+                Some((alt, url, len)) => {
+                    let alt = plain_text(alt);
+                    match resolve_image(url) {
+                        Some(src) => out.push_str(&format!(
+                            "<img src=\"{}\" alt=\"{}\">",
+                            escape_html(&src),
+                            escape_html(&alt)
+                        )),
+                        None => out.push_str(&escape_html(&alt)),
+                    }
+                    rest = &rest[1 + len..];
+                }
+                None => {
+                    out.push('!');
+                    rest = &rest[1..];
+                }
+            },
+            '[' => match parse_link(rest) {
+                Some((label, url, len)) => {
+                    let label = render_inline(label, resolve_image);
+                    if is_safe_url(url) {
+                        out.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(url), label));
+                    } else {
+                        out.push_str(&label);
+                    }
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push('[');
+                    rest = &rest[1..];
+                }
+            },
+            '<' => match rest.find('>').map(|end| &rest[1..end]) {
+                Some(url) if is_safe_url(url) && !url.contains(char::is_whitespace) => {
+                    let escaped = escape_html(url);
+                    out.push_str(&format!("<a href=\"{}\">{}</a>", escaped, escaped));
+                    rest = &rest[url.len() + 2..];
+                }
+                _ => {
+                    out.push_str("&lt;");
+                    rest = &rest[1..];
+                }
+            },
+            '*' | '_' | '~' => match parse_emphasis(rest, &out) {
+                Some((tag, inner, len)) => {
+                    out.push_str(&format!(
+                        "<{tag}>{}</{tag}>",
+                        render_inline(inner, resolve_image)
+                    ));
+                    rest = &rest[len..];
+                }
+                None => {
+                    push_escaped(&mut out, c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            },
+            _ => {
+                push_escaped(&mut out, c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Parses `[label](url)` at the start of `text`, returning the label, the URL
+/// and the length of the whole construct.
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let mut depth = 0;
+    let mut label_end = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    label_end = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let label_end = label_end?;
+    let after = &text[label_end + 1..];
+    if !after.starts_with('(') {
+        return None;
+    }
+    let close = after.find(')')?;
+    // Drop an optional "title" after the URL
+    let url = after[1..close].split_whitespace().next().unwrap_or("");
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    Some((&text[1..label_end], url, label_end + 1 + close + 1))
+}
+
+/// Parses emphasis at the start of `text`, returning the HTML tag, the inner
+/// text and the length of the whole construct.
+fn parse_emphasis<'a>(text: &'a str, before: &str) -> Option<(&'static str, &'a str, usize)> {
+    let (delimiter, tag) = if text.starts_with("**") {
+        ("**", "strong")
+    } else if text.starts_with("__") {
+        ("__", "strong")
+    } else if text.starts_with("~~") {
+        ("~~", "s")
+    } else if text.starts_with('*') {
+        ("*", "em")
+    } else if text.starts_with('_') {
+        ("_", "em")
+    } else {
+        return None;
+    };
+    // Underscores inside words (snake_case) are not emphasis
+    if delimiter.starts_with('_') && before.ends_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+    let body = &text[delimiter.len()..];
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut search = 0;
+    while let Some(pos) = body[search..].find(delimiter) {
+        let end = search + pos;
+        let inner = &body[..end];
+        let escaped = inner.ends_with('\\');
+        if !inner.is_empty() && !inner.ends_with(char::is_whitespace) && !escaped {
+            return Some((tag, inner, 2 * delimiter.len() + end));
+        }
+        search = end + delimiter.len();
+    }
+    None
+}
+
+/// Strips Markdown syntax, for text that ends up in attributes or titles.
+pub fn plain_text(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut chars = markdown.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(char::is_ascii_punctuation) => {
+                out.extend(chars.next());
+            }
+            '*' | '_' | '`' | '~' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// MIME type of an image file, by extension, for embedding it as a `data:` URI
+pub fn image_mime_type(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "bmp" => Some("image/bmp"),
+        "heic" => Some("image/heic"),
+        "tif" | "tiff" => Some("image/tiff"),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_safe_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+}
+
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        _ => out.push(c),
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_escaped(&mut out, c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs_and_line_breaks() {
+        assert_eq!(
+            markdown_to_html("First line\nsecond line\n\nNext paragraph"),
+            "<p>First line<br>second line</p><p>Next paragraph</p>"
+        );
+    }
+
+    #[test]
+    fn test_headings_rules_and_quotes() {
+        assert_eq!(markdown_to_html("# Title #"), "<h1>Title</h1>");
+        assert_eq!(markdown_to_html("Sub\n---"), "<h2>Sub</h2>");
+        assert_eq!(markdown_to_html("a\n\n***\n\nb"), "<p>a</p><hr><p>b</p>");
+        assert_eq!(
+            markdown_to_html("> quoted\n> more"),
+            "<blockquote><p>quoted<br>more</p></blockquote>"
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            markdown_to_html("- one\n- two\n  - nested\n\n1. first\n2. second"),
+            "<ul><li><p>one</p></li><li><p>two</p><ul><li><p>nested</p></li></ul></li></ul>\
+             <ol><li><p>first</p></li><li><p>second</p></li></ol>"
+        );
+    }
+
+    #[test]
+    fn test_inline_formatting() {
+        assert_eq!(
+            markdown_to_html("**bold** *it* ~~gone~~ `x < y` snake_case_name"),
+            "<p><strong>bold</strong> <em>it</em> <s>gone</s> <code>x &lt; y</code> snake_case_name</p>"
+        );
+        assert_eq!(markdown_to_html("1\\. not a list"), "<p>1. not a list</p>");
+    }
+
+    #[test]
+    fn test_links_and_images() {
+        assert_eq!(
+            markdown_to_html("[site](https://example.com) [bad](javascript:alert(1))"),
+            "<p><a href=\"https://example.com\">site</a> bad)</p>"
+        );
+        let html = markdown_to_html_with_images("![cat](photo://1) ![](photo://2)", &mut |url| {
+            (url == "photo://1").then(|| "data:image/png;base64,AA==".to_string())
+        });
+        assert_eq!(
+            html,
+            "<p><img src=\"data:image/png;base64,AA==\" alt=\"cat\"> </p>"
+        );
+    }
+
+    #[test]
+    fn test_raw_html_is_escaped() {
+        assert_eq!(
+            markdown_to_html("<script>alert(1)</script>\n\n```\n<b>\n```"),
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt;</p><pre><code>&lt;b&gt;</code></pre>"
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use super::markdown::{image_mime_type, markdown_to_html_with_images, plain_text};
+use super::metadata::{find_hashtags, ImportedMetadata};
+use crate::db::queries::{count_words, DiaryEntry};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Notes and images larger than this are skipped
+const MAX_NOTE_SIZE: u64 = 100 * 1024 * 1024;
+const MAX_IMAGE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Parse a folder of Markdown notes, such as an Obsidian vault
+///
+/// Every `.md` file in the folder and its subfolders becomes an entry if its
+/// date is known: from a `date:` field in the YAML front matter, or from a
+/// file name of the form `YYYY-MM-DD.md` (Obsidian daily notes). Other notes
+/// are skipped. Hidden folders such as `.obsidian` and `.trash` are ignored.
+///
+/// - Title comes from front matter `title:`, else a leading `# Heading`, else
+///   the file name unless it is the date
+/// - Markdown is converted to HTML; images linked with `![](path)` or
+///   embedded with `![[name]]` are read from the folder and embedded
+/// - Front matter `tags:` are appended to the text as `#hashtags`
+/// - Front matter `created:` / `updated:` set the timestamps, falling back
+///   to the file's modification time
+///
+/// Notes are read and passed to `on_entry` one at a time.
+pub fn parse_markdown_folder(
+    root: &Path,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    if !root.is_dir() {
+        return Err("Please choose a folder of Markdown files".to_string());
+    }
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Cannot access folder: {}", e))?;
+
+    let mut notes = Vec::new();
+    let mut images = HashMap::new();
+    collect_files(&root, &mut notes, &mut images)?;
+    if notes.is_empty() {
+        return Err("No Markdown files found in the folder".to_string());
+    }
+    notes.sort();
+
+    let vault = Vault {
+        root: &root,
+        images: &images,
+    };
+    for path in notes {
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_NOTE_SIZE {
+            warn!("Skipping {}: larger than 100 MB", path.display());
+            continue;
+        }
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        match convert_note(&path, &content, &vault) {
+            Some(entry) => on_entry(entry)?,
+            None => debug!("Skipping {}: no date", path.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Walks `dir`, collecting Markdown notes and indexing images by file name.
+/// Hidden entries and symbolic links are skipped.
+fn collect_files(
+    dir: &Path,
+    notes: &mut Vec<PathBuf>,
+    images: &mut HashMap<String, PathBuf>,
+) -> Result<(), String> {
+    let read_dir = std::fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read folder '{}': {}", dir.display(), e))?;
+    for item in read_dir.flatten() {
+        let name = item.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = item.file_type() else {
+            continue;
+        };
+        let path = item.path();
+        if file_type.is_dir() {
+            collect_files(&path, notes, images)?;
+        } else if file_type.is_file() {
+            let extension = extension_of(&name);
+            if extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown") {
+                notes.push(path);
+            } else if image_mime_type(extension).is_some() {
+                images.entry(name.to_lowercase()).or_insert(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Vault<'a> {
+    root: &'a Path,
+    /// Image files by lower-case file name, for Obsidian's `![[name]]` embeds
+    images: &'a HashMap<String, PathBuf>,
+}
+
+impl Vault<'_> {
+    /// Finds the image a note refers to and returns it as a `data:` URI.
+    ///
+    /// Links are tried relative to the note, then to the vault root, then by
+    /// file name anywhere in the vault. Files outside the vault are never read.
+    fn embed_image(&self, note_dir: &Path, url: &str) -> Option<String> {
+        if url.contains(':') {
+            return None;
+        }
+        let link = percent_decode(url);
+        let link = link.trim_start_matches('/');
+        let by_name = link
+            .rsplit('/')
+            .next()
+            .and_then(|name| self.images.get(&name.to_lowercase()));
+
+        let candidates = [note_dir.join(link), self.root.join(link)];
+        let path = candidates
+            .iter()
+            .chain(by_name)
+            .filter_map(|path| path.canonicalize().ok())
+            .find(|path| path.starts_with(self.root) && path.is_file())?;
+
+        let extension = extension_of(path.to_str()?);
+        let mime = image_mime_type(extension)?;
+        let size = std::fs::metadata(&path).ok()?.len();
+        if size > MAX_IMAGE_SIZE {
+            warn!("Skipping image {}: larger than 50 MB", path.display());
+            return None;
+        }
+        match std::fs::read(&path) {
+            Ok(bytes) => Some(format!(
+                "data:{};base64,{}",
+                mime,
+                general_purpose::STANDARD.encode(bytes)
+            )),
+            Err(e) => {
+                warn!("Skipping image {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+fn convert_note(path: &Path, content: &str, vault: &Vault) -> Option<DiaryEntry> {
+    let (front_matter, body) = split_front_matter(content);
+    let stem = path.file_stem()?.to_string_lossy().into_owned();
+
+    let date = front_matter
+        .date
+        .as_deref()
+        .and_then(parse_date)
+        .or_else(|| parse_date(&stem))?;
+
+    // A leading `# Heading` is the title unless front matter names one
+    let (heading, body) = match leading_heading(body) {
+        Some((heading, rest)) if front_matter.title.is_none() => (Some(heading), rest),
+        _ => (None, body),
+    };
+    let title = match (front_matter.title, heading) {
+        (Some(title), _) => title,
+        (None, Some(heading)) => plain_text(heading),
+        (None, None) if stem != date => stem,
+        (None, None) => String::new(),
+    };
+
+    let note_dir = path.parent().unwrap_or(vault.root);
+    let mut text = markdown_to_html_with_images(&rewrite_wikilinks(body), &mut |url| {
+        vault.embed_image(note_dir, url)
+    });
+    // Tags already written as #hashtags in the note are not repeated
+    let inline_tags = find_hashtags(body);
+    let metadata = ImportedMetadata {
+        tags: front_matter
+            .tags
+            .into_iter()
+            .filter(|tag| !inline_tags.contains(tag))
+            .collect(),
+        ..Default::default()
+    };
+    text.push_str(&metadata.to_html());
+
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|t| DateTime::<Utc>::from(t).to_rfc3339())
+        .unwrap_or_else(|_| Utc::now().to_rfc3339());
+    let date_created = front_matter
+        .created
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or_else(|| modified.clone());
+    let date_updated = front_matter
+        .updated
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or(modified);
+
+    Some(DiaryEntry {
+        id: 0,
+        date,
+        title,
+        word_count: count_words(&text),
+        text,
+        date_created,
+        date_updated,
+    })
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct FrontMatter {
+    date: Option<String>,
+    title: Option<String>,
+    tags: Vec<String>,
+    created: Option<String>,
+    updated: Option<String>,
+}
+
+/// Splits YAML front matter (between `---` lines at the top of the note)
+/// from the body. Only the flat `key: value` and list forms notes use are
+/// understood; anything else in the block is ignored.
+fn split_front_matter(content: &str) -> (FrontMatter, &str) {
+    let content = content.trim_start_matches('\u{feff}');
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (FrontMatter::default(), content);
+    };
+
+    let mut front_matter = FrontMatter::default();
+    let mut current_key = String::new();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return (front_matter, &rest[offset..]);
+        }
+
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            // Block list item belonging to the last key
+            if current_key == "tags" || current_key == "tag" {
+                push_tag(&mut front_matter.tags, item);
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        current_key = key.trim().to_lowercase();
+        let value = unquote(value.trim());
+        if value.is_empty() {
+            continue;
+        }
+        match current_key.as_str() {
+            "date" => front_matter.date = Some(value),
+            "title" => front_matter.title = Some(value),
+            "tags" | "tag" => match value.strip_prefix('[') {
+                // Flow list: [a, "b c"]
+                Some(list) => list
+                    .trim_end_matches(']')
+                    .split(',')
+                    .for_each(|tag| push_tag(&mut front_matter.tags, tag)),
+                // Plain string: "a b" or "a, b"
+                None => value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .for_each(|tag| push_tag(&mut front_matter.tags, tag)),
+            },
+            "created" | "date_created" | "created_at" => front_matter.created = Some(value),
+            "updated" | "modified" | "date_updated" | "updated_at" => {
+                front_matter.updated = Some(value)
+            }
+            _ => {}
+        }
+    }
+    // No closing line: this was not front matter after all
+    (FrontMatter::default(), content)
+}
+
+fn push_tag(tags: &mut Vec<String>, tag: &str) {
+    let tag = unquote(tag);
+    let tag = tag.trim_start_matches('#');
+    if !tag.is_empty() {
+        tags.push(tag.to_string());
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut out = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        }
+        return out;
+    }
+    value.to_string()
+}
+
+/// Returns the text of a `# Heading` on the first non-blank line, and the rest.
+fn leading_heading(body: &str) -> Option<(&str, &str)> {
+    let body = body.trim_start();
+    let first = body.lines().next()?;
+    let heading = first.strip_prefix("# ")?.trim();
+    Some((heading, &body[first.len()..]))
+}
+
+/// Reads a `YYYY-MM-DD` date, ignoring any time after it.
+fn parse_date(value: &str) -> Option<String> {
+    let date = value.get(..10)?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.to_string())
+}
+
+/// Converts a front matter timestamp to RFC 3339. Times without an offset
+/// are taken as UTC.
+fn parse_timestamp(value: &str) -> Option<String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc).to_rfc3339());
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.and_utc().to_rfc3339());
+        }
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().to_rfc3339())
+}
+
+/// Rewrites Obsidian wikilinks into plain Markdown: `![[photo.png|300]]`
+/// becomes an image link and `[[Note|alias]]` becomes its text.
+fn rewrite_wikilinks(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(start) = rest.find("[[") {
+        let Some(len) = rest[start..].find("]]") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + len];
+        let embed = rest[..start].ends_with('!');
+        out.push_str(&rest[..if embed { start - 1 } else { start }]);
+
+        let (target, alias) = match inner.split_once('|') {
+            Some((target, alias)) => (target.trim(), Some(alias.trim())),
+            None => (inner.trim(), None),
+        };
+        let target = target.split('#').next().unwrap_or(target);
+        if embed && image_mime_type(extension_of(target)).is_some() {
+            out.push_str(&format!("![]({})", percent_encode(target)));
+        } else {
+            let text = alias.filter(|a| !embed && !a.is_empty()).unwrap_or(target);
+            out.push_str(text);
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn extension_of(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((_, extension)) if !extension.contains('/') => extension,
+        _ => "",
+    }
+}
+
+/// Encodes the characters that would end a Markdown link target
+fn percent_encode(path: &str) -> String {
+    path.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+fn percent_decode(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn parse(root: &Path) -> Vec<DiaryEntry> {
+        let mut entries = Vec::new();
+        parse_markdown_folder(root, &mut |entry| {
+            entries.push(entry);
+            Ok(())
+        })
+        .unwrap();
+        entries
+    }
+
+    #[test]
+    fn test_imports_daily_notes_and_front_matter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("Daily")).unwrap();
+        fs::create_dir_all(root.join("attachments")).unwrap();
+        fs::create_dir_all(root.join(".obsidian")).unwrap();
+        fs::write(
+            root.join("attachments/My Photo.png"),
+            [0x89, b'P', b'N', b'G'],
+        )
+        .unwrap();
+        fs::write(
+            root.join("Daily/2024-02-01.md"),
+            "# Quiet day\n\nRead a **book**.\n\n![[My Photo.png]]\nSee [[Other Note|the plan]].\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("Trip.md"),
+            "---\ndate: 2024-02-03\ntitle: \"Off to Porto\"\ntags:\n  - travel\n  - \"#family\"\ncreated: 2024-02-03T08:00:00Z\n---\nPacked the car.\n",
+        )
+        .unwrap();
+        fs::write(root.join("Ideas.md"), "No date here").unwrap();
+        fs::write(root.join(".obsidian/2024-01-01.md"), "Hidden").unwrap();
+
+        let entries = parse(root);
+        assert_eq!(entries.len(), 2);
+
+        let daily = &entries[0];
+        assert_eq!(daily.date, "2024-02-01");
+        assert_eq!(daily.title, "Quiet day");
+        assert!(daily
+            .text
+            .starts_with("<p>Read a <strong>book</strong>.</p>"));
+        assert!(daily
+            .text
+            .contains("<img src=\"data:image/png;base64,iVBORw==\""));
+        assert!(daily.text.contains("See the plan."));
+
+        let trip = &entries[1];
+        assert_eq!(trip.date, "2024-02-03");
+        assert_eq!(trip.title, "Off to Porto");
+        assert_eq!(trip.text, "<p>Packed the car.</p><p>#travel #family</p>");
+        assert_eq!(trip.date_created, "2024-02-03T08:00:00+00:00");
+    }
+
+    #[test]
+    fn test_images_outside_folder_are_not_read() {
+        let outer = tempfile::tempdir().unwrap();
+        let root = outer.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(outer.path().join("secret.png"), b"secret").unwrap();
+        fs::write(root.join("2024-02-01.md"), "![x](../secret.png)").unwrap();
+
+        let entries = parse(&root);
+        assert_eq!(entries[0].text, "<p>x</p>");
+    }
+
+    #[test]
+    fn test_front_matter_parsing() {
+        let (fm, body) =
+            split_front_matter("---\ndate: 2024-05-06 09:30\ntags: [a, \"b c\"]\n---\nBody");
+        assert_eq!(fm.date.as_deref(), Some("2024-05-06 09:30"));
+        assert_eq!(fm.tags, vec!["a", "b c"]);
+        assert_eq!(body, "Body");
+
+        let (fm, _) = split_front_matter("---\ntitle: \"Say \\\"hi\\\"\"\n---\n");
+        assert_eq!(fm.title.as_deref(), Some("Say \"hi\""));
+
+        let (fm, body) = split_front_matter("---\nnot closed\nBody");
+        assert_eq!(fm, FrontMatter::default());
+        assert_eq!(body, "---\nnot closed\nBody");
+    }
+
+    #[test]
+    fn test_rejects_file_or_empty_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = parse_markdown_folder(dir.path(), &mut |_| Ok(())).unwrap_err();
+        assert!(err.contains("No Markdown files"));
+
+        let file = dir.path().join("2024-01-01.md");
+        fs::write(&file, "x").unwrap();
+        let err = parse_markdown_folder(&file, &mut |_| Ok(())).unwrap_err();
+        assert!(err.contains("choose a folder"));
+    }
+}
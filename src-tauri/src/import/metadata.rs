@@ -0,0 +1,224 @@
+//! Entry metadata carried by other journaling apps.
+//!
+//! Journal entries here are a title and rich text, so tags, favourites,
+//! places and weather from an import are kept by appending them to the entry
+//! text: tags as a line of `#hashtags`, everything else as one italic line.
+//! Exporters to formats with such fields read them back with [`split_footer`].
+
+use super::markdown::escape_html;
+
+/// Metadata from an imported entry that has no column of its own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedMetadata {
+    pub tags: Vec<String>,
+    pub starred: bool,
+    /// Mood or rating, as the app names it (e.g. "Happy" or "★★★★☆")
+    pub mood: Option<String>,
+    pub location: Option<String>,
+    pub weather: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+impl ImportedMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && !self.starred
+            && self.mood.is_none()
+            && self.location.is_none()
+            && self.weather.is_none()
+            && self.time_zone.is_none()
+    }
+
+    /// Renders the metadata as HTML paragraphs to append to the entry text.
+    /// Returns an empty string if there is nothing to add.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|tag| tag.split_whitespace().collect::<Vec<_>>().join("_"))
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| format!("#{}", escape_html(tag.trim_start_matches('#'))))
+            .collect();
+        if !tags.is_empty() {
+            html.push_str(&format!("<p>{}</p>", tags.join(" ")));
+        }
+
+        let mut details = Vec::new();
+        if self.starred {
+            details.push("Starred".to_string());
+        }
+        for (label, value) in [
+            ("Mood", &self.mood),
+            ("Location", &self.location),
+            ("Weather", &self.weather),
+            ("Time zone", &self.time_zone),
+        ] {
+            if let Some(value) = value.as_deref().filter(|v| !v.trim().is_empty()) {
+                details.push(format!("{}: {}", label, escape_html(value.trim())));
+            }
+        }
+        if !details.is_empty() {
+            html.push_str(&format!("<p><em>{}</em></p>", details.join(" · ")));
+        }
+        html
+    }
+}
+
+/// Splits the lines written by [`ImportedMetadata::to_html`] off the end of
+/// an entry's HTML. Returns the HTML before them and the metadata they hold,
+/// which is empty if the entry does not end with them.
+pub fn split_footer(html: &str) -> (&str, ImportedMetadata) {
+    let mut rest = html;
+    let mut metadata = ImportedMetadata::default();
+
+    if let Some((before, details)) = last_paragraph(rest) {
+        if let Some(details) = details
+            .strip_prefix("<em>")
+            .and_then(|d| d.strip_suffix("</em>"))
+        {
+            if parse_details(details, &mut metadata) {
+                rest = before;
+            } else {
+                metadata = ImportedMetadata::default();
+            }
+        }
+    }
+
+    if let Some((before, line)) = last_paragraph(rest) {
+        let tags: Vec<&str> = line.split(' ').collect();
+        if !line.contains('<') && tags.iter().all(|tag| tag.len() > 1 && tag.starts_with('#')) {
+            metadata.tags = tags.iter().map(|tag| unescape_html(&tag[1..])).collect();
+            rest = before;
+        }
+    }
+    (rest, metadata)
+}
+
+/// The content of the final `<p>` of `html`, and everything before it
+fn last_paragraph(html: &str) -> Option<(&str, &str)> {
+    let without_end = html.strip_suffix("</p>")?;
+    let start = without_end.rfind("<p>")?;
+    Some((&html[..start], &without_end[start + 3..]))
+}
+
+/// Reads `Starred · Location: … · …`, returning false if any item is not one
+/// that [`ImportedMetadata::to_html`] writes
+fn parse_details(details: &str, metadata: &mut ImportedMetadata) -> bool {
+    for item in details.split(" · ") {
+        if item == "Starred" {
+            metadata.starred = true;
+            continue;
+        }
+        let Some((label, value)) = item.split_once(": ") else {
+            return false;
+        };
+        let value = Some(unescape_html(value));
+        match label {
+            "Mood" => metadata.mood = value,
+            "Location" => metadata.location = value,
+            "Weather" => metadata.weather = value,
+            "Time zone" => metadata.time_zone = value,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Turns the entities written by `escape_html` (and `&#39;`) back into
+/// characters.
+pub(crate) fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Finds `#hashtags` in plain text or Markdown, in order and without
+/// duplicates. A hashtag starts at a word boundary and is not all digits,
+/// so headings (`# Title`) and issue numbers (`#12`) are not matched.
+pub fn find_hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            let tag: String = text[i + 1..]
+                .chars()
+                .take_while(|&c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect();
+            let tag = tag.trim_end_matches(['-', '/']);
+            if !tag.chars().all(|c| c.is_ascii_digit()) && !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        previous = c;
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_hashtags() {
+        assert_eq!(
+            find_hashtags("# Title\n#travel day, #road_trip #12 a#b #travel #x/y-"),
+            vec!["travel", "road_trip", "x/y"]
+        );
+    }
+
+    #[test]
+    fn test_split_footer_reads_back_rendered_metadata() {
+        let metadata = ImportedMetadata {
+            tags: vec!["travel".into(), "road_trip".into()],
+            starred: true,
+            mood: None,
+            location: Some("Tom & Jerry's <place>".into()),
+            weather: Some("Sunny, 24 °C".into()),
+            time_zone: Some("Europe/Lisbon".into()),
+        };
+        let html = format!("<p>Body</p>{}", metadata.to_html());
+
+        assert_eq!(split_footer(&html), ("<p>Body</p>", metadata));
+    }
+
+    #[test]
+    fn test_split_footer_leaves_other_text_alone() {
+        let html = "<p>Body</p><p><em>Just emphasis</em></p>";
+        assert_eq!(split_footer(html), (html, ImportedMetadata::default()));
+
+        let html = "<p>Body #travel</p>";
+        assert_eq!(split_footer(html), (html, ImportedMetadata::default()));
+
+        let (rest, metadata) = split_footer("<p>#only #tags</p>");
+        assert_eq!(rest, "");
+        assert_eq!(metadata.tags, vec!["only", "tags"]);
+    }
+
+    #[test]
+    fn test_empty_metadata_renders_nothing() {
+        let metadata = ImportedMetadata::default();
+        assert!(metadata.is_empty());
+        assert_eq!(metadata.to_html(), "");
+    }
+
+    #[test]
+    fn test_renders_tags_and_details() {
+        let metadata = ImportedMetadata {
+            tags: vec!["travel".into(), "#family".into(), "road trip".into()],
+            starred: true,
+            mood: Some("Happy".into()),
+            location: Some("Lisbon, Portugal".into()),
+            weather: None,
+            time_zone: Some("Europe/Lisbon".into()),
+        };
+        assert_eq!(
+            metadata.to_html(),
+            "<p>#travel #family #road_trip</p>\
+             <p><em>Starred · Mood: Happy · Location: Lisbon, Portugal · Time zone: Europe/Lisbon</em></p>"
+        );
+    }
+}
@@ -1,7 +1,9 @@
+use super::json_stream;
 use crate::db::queries::DiaryEntry;
 use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::Read;
 
 /// Mini Diary JSON export format schema (actual format from Mini Diary app)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,47 +44,54 @@ pub struct Entry {
 /// # Returns
 /// A vector of DiaryEntry structs ready for database insertion
 pub fn parse_minidiary_json(json_str: &str) -> Result<Vec<DiaryEntry>, String> {
-    // Parse JSON
-    let mini_diary: MiniDiaryJson =
-        serde_json::from_str(json_str).map_err(|e| format!("Failed to parse JSON: {}", e))?;
-
-    debug!(
-        "Parsed Mini Diary format version: {}",
-        mini_diary.metadata.version
-    );
-    debug!("Found {} entries", mini_diary.entries.len());
+    let mut diary_entries = Vec::new();
+    parse_minidiary_json_reader(json_str.as_bytes(), &mut |entry| {
+        diary_entries.push(entry);
+        Ok(())
+    })?;
+    debug!("Successfully parsed {} valid entries", diary_entries.len());
+    Ok(diary_entries)
+}
 
-    // Convert entries to DiaryEntry format
+/// Streaming variant of [`parse_minidiary_json`]: reads the export from
+/// `reader` and calls `on_entry` for each valid entry as soon as it is parsed.
+pub fn parse_minidiary_json_reader<R: Read>(
+    reader: R,
+    on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+) -> Result<(), String> {
+    const CONTEXT: &str = "Failed to parse JSON";
     let now = chrono::Utc::now().to_rfc3339();
-    let mut diary_entries: Vec<DiaryEntry> = Vec::new();
-
-    for (date, entry) in mini_diary.entries {
-        // Validate date format
-        if !is_valid_date_format(&date) {
-            warn!("Invalid date format '{}', skipping", date);
-            continue;
-        }
-
-        // Calculate word count
-        let word_count = crate::db::queries::count_words(&entry.text);
-
-        // Convert human-readable timestamp to ISO 8601 if possible
-        // If conversion fails, use current timestamp
-        let date_updated = parse_timestamp(&entry.date_updated).unwrap_or_else(|| now.clone());
-
-        diary_entries.push(DiaryEntry {
-            id: 0,
-            date: date.clone(),
-            title: entry.title,
-            text: entry.text,
-            word_count,
-            date_created: now.clone(), // We don't have this in the source, use import time
-            date_updated,
-        });
-    }
 
-    debug!("Successfully parsed {} valid entries", diary_entries.len());
-    Ok(diary_entries)
+    let keys =
+        json_stream::for_each_map_entry(reader, "entries", CONTEXT, |date, entry: Entry| {
+            // Validate date format
+            if !is_valid_date_format(&date) {
+                warn!("Invalid date format '{}', skipping", date);
+                return Ok(());
+            }
+
+            // Calculate word count
+            let word_count = crate::db::queries::count_words(&entry.text);
+
+            // Convert human-readable timestamp to ISO 8601 if possible
+            // If conversion fails, use current timestamp
+            let date_updated = parse_timestamp(&entry.date_updated).unwrap_or_else(|| now.clone());
+
+            on_entry(DiaryEntry {
+                id: 0,
+                date,
+                title: entry.title,
+                text: entry.text,
+                word_count,
+                date_created: now.clone(), // We don't have this in the source, use import time
+                date_updated,
+            })
+        })?;
+
+    if !keys.contains("metadata") {
+        return Err(format!("{}: missing field `metadata`", CONTEXT));
+    }
+    Ok(())
 }
 
 /// Attempts to parse a human-readable timestamp to ISO 8601
@@ -1,4 +1,21 @@
+pub mod archive;
+pub mod csv;
+pub mod daylio;
 pub mod dayone;
 pub mod dayone_txt;
+pub mod dayone_zip;
+pub mod diarium;
+pub mod diaro;
+pub mod enex;
+pub mod html;
+pub mod journey;
 pub mod jrnl;
+pub mod json_stream;
+pub mod markdown;
+pub mod markdown_folder;
+pub mod metadata;
 pub mod minidiary;
+pub mod zip;
+
+/// Size limit for formats that must be read into memory in one piece
+pub const MAX_IMPORT_FILE_SIZE: u64 = 100 * 1024 * 1024; // 100 MB
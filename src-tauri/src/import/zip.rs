@@ -0,0 +1,391 @@
+//! Read-only ZIP support for importing export archives.
+//!
+//! Handles what export tools actually produce: stored and DEFLATE entries,
+//! with ZIP64 for archives over 4 GB. Entries are decompressed on demand, so
+//! opening an archive only reads its central directory.
+
+use flate2::read::DeflateDecoder;
+use std::io::{self, Read, Seek, SeekFrom};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+/// End-of-central-directory record size without the trailing comment
+const EOCD_LEN: u64 = 22;
+const MAX_COMMENT_LEN: u64 = u16::MAX as u64;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+/// One file in a ZIP archive
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    /// Path inside the archive, with `/` separators
+    pub name: String,
+    /// Uncompressed size in bytes, as recorded in the archive
+    pub size: u64,
+    method: u16,
+    compressed_size: u64,
+    header_offset: u64,
+    crc32: u32,
+}
+
+impl ZipEntry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// A ZIP archive opened for reading
+pub struct ZipReader<R> {
+    reader: R,
+    entries: Vec<ZipEntry>,
+}
+
+impl<R: Read + Seek> ZipReader<R> {
+    /// Reads the central directory of the archive in `reader`.
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let entries = read_central_directory(&mut reader)
+            .map_err(|e| format!("Not a valid ZIP file: {}", e))?;
+        Ok(Self { reader, entries })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Returns a reader over the decompressed contents of entry `index`.
+    ///
+    /// The checksum is verified when the reader reaches the end, and reading
+    /// past the recorded size fails, so a damaged or malicious archive cannot
+    /// expand beyond what its directory claims.
+    pub fn open(&mut self, index: usize) -> Result<impl Read + '_, String> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| format!("No ZIP entry at index {}", index))?
+            .clone();
+        let data_offset = local_data_offset(&mut self.reader, entry.header_offset)
+            .map_err(|e| format!("Failed to read '{}': {}", entry.name, e))?;
+        self.reader
+            .seek(SeekFrom::Start(data_offset))
+            .map_err(|e| format!("Failed to read '{}': {}", entry.name, e))?;
+
+        let raw = (&mut self.reader).take(entry.compressed_size);
+        let inner: Box<dyn Read + '_> = match entry.method {
+            METHOD_STORED => Box::new(raw),
+            METHOD_DEFLATE => Box::new(DeflateDecoder::new(raw)),
+            method => {
+                return Err(format!(
+                    "'{}' uses unsupported compression method {}",
+                    entry.name, method
+                ))
+            }
+        };
+        Ok(CheckedReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            remaining: entry.size,
+            expected_crc: entry.crc32,
+        })
+    }
+
+    /// Reads entry `index` into memory, refusing entries larger than `max_size`.
+    pub fn read(&mut self, index: usize, max_size: u64) -> Result<Vec<u8>, String> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| format!("No ZIP entry at index {}", index))?;
+        if entry.size > max_size {
+            return Err(format!(
+                "'{}' is too large ({} MB)",
+                entry.name,
+                entry.size / 1_048_576
+            ));
+        }
+        let name = entry.name.clone();
+        let mut data = Vec::with_capacity(entry.size as usize);
+        self.open(index)?
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read '{}': {}", name, e))?;
+        Ok(data)
+    }
+}
+
+/// Decompressed entry reader that enforces the recorded size and CRC-32.
+struct CheckedReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    hasher: crc32fast::Hasher,
+    remaining: u64,
+    expected_crc: u32,
+}
+
+impl Read for CheckedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Ask for one byte beyond the recorded size so overlong data is caught
+        let limit = buf.len().min(self.remaining.saturating_add(1) as usize);
+        let n = self.inner.read(&mut buf[..limit])?;
+        if n as u64 > self.remaining {
+            return Err(invalid_data("entry is larger than recorded"));
+        }
+        self.remaining -= n as u64;
+        self.hasher.update(&buf[..n]);
+        if n == 0 {
+            if self.remaining != 0 {
+                return Err(invalid_data("entry is truncated"));
+            }
+            if self.hasher.clone().finalize() != self.expected_crc {
+                return Err(invalid_data("checksum mismatch"));
+            }
+        }
+        Ok(n)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_central_directory<R: Read + Seek>(reader: &mut R) -> io::Result<Vec<ZipEntry>> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let eocd_pos = find_eocd(reader, file_len)?;
+
+    reader.seek(SeekFrom::Start(eocd_pos))?;
+    let mut eocd = [0u8; EOCD_LEN as usize];
+    reader.read_exact(&mut eocd)?;
+    let mut count = u64::from(le_u16(&eocd, 10));
+    let mut cd_size = u64::from(le_u32(&eocd, 12));
+    let mut cd_offset = u64::from(le_u32(&eocd, 16));
+
+    if count == 0xFFFF || cd_size == 0xFFFF_FFFF || cd_offset == 0xFFFF_FFFF {
+        (count, cd_size, cd_offset) = read_zip64_eocd(reader, eocd_pos)?;
+    }
+    if cd_offset.saturating_add(cd_size) > eocd_pos {
+        return Err(invalid_data("central directory is out of bounds"));
+    }
+
+    reader.seek(SeekFrom::Start(cd_offset))?;
+    let mut directory = Vec::new();
+    reader.take(cd_size).read_to_end(&mut directory)?;
+
+    // Each record is at least 46 bytes; don't trust `count` for the allocation
+    let mut entries = Vec::with_capacity((count as usize).min(directory.len() / 46));
+    let mut pos = 0;
+    for _ in 0..count {
+        let (entry, len) = parse_central_header(&directory[pos.min(directory.len())..])?;
+        entries.push(entry);
+        pos += len;
+    }
+    Ok(entries)
+}
+
+/// Finds the end-of-central-directory record, which sits before an optional
+/// comment of up to 64 KB at the end of the file.
+fn find_eocd<R: Read + Seek>(reader: &mut R, file_len: u64) -> io::Result<u64> {
+    if file_len < EOCD_LEN {
+        return Err(invalid_data("file is too small"));
+    }
+    let tail_len = file_len.min(EOCD_LEN + MAX_COMMENT_LEN);
+    let tail_start = file_len - tail_len;
+    reader.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.read_exact(&mut tail)?;
+
+    (0..=tail.len() - EOCD_LEN as usize)
+        .rev()
+        .find(|&i| le_u32(&tail, i) == EOCD_SIGNATURE)
+        .map(|i| tail_start + i as u64)
+        .ok_or_else(|| invalid_data("end of central directory not found"))
+}
+
+/// Reads entry count, directory size and directory offset from the ZIP64
+/// end-of-central-directory record.
+fn read_zip64_eocd<R: Read + Seek>(reader: &mut R, eocd_pos: u64) -> io::Result<(u64, u64, u64)> {
+    let locator_pos = eocd_pos
+        .checked_sub(20)
+        .ok_or_else(|| invalid_data("missing ZIP64 locator"))?;
+    reader.seek(SeekFrom::Start(locator_pos))?;
+    let mut locator = [0u8; 20];
+    reader.read_exact(&mut locator)?;
+    if le_u32(&locator, 0) != ZIP64_EOCD_LOCATOR_SIGNATURE {
+        return Err(invalid_data("missing ZIP64 locator"));
+    }
+
+    reader.seek(SeekFrom::Start(le_u64(&locator, 8)))?;
+    let mut record = [0u8; 56];
+    reader.read_exact(&mut record)?;
+    if le_u32(&record, 0) != ZIP64_EOCD_SIGNATURE {
+        return Err(invalid_data("invalid ZIP64 end of central directory"));
+    }
+    Ok((
+        le_u64(&record, 32),
+        le_u64(&record, 40),
+        le_u64(&record, 48),
+    ))
+}
+
+/// Parses one central directory record, returning it and its length.
+fn parse_central_header(buf: &[u8]) -> io::Result<(ZipEntry, usize)> {
+    if buf.len() < 46 || le_u32(buf, 0) != CENTRAL_HEADER_SIGNATURE {
+        return Err(invalid_data("corrupt central directory"));
+    }
+    let flags = le_u16(buf, 8);
+    let method = le_u16(buf, 10);
+    let crc32 = le_u32(buf, 16);
+    let mut compressed_size = u64::from(le_u32(buf, 20));
+    let mut size = u64::from(le_u32(buf, 24));
+    let name_len = usize::from(le_u16(buf, 28));
+    let extra_len = usize::from(le_u16(buf, 30));
+    let comment_len = usize::from(le_u16(buf, 32));
+    let mut header_offset = u64::from(le_u32(buf, 42));
+
+    let total = 46 + name_len + extra_len + comment_len;
+    if buf.len() < total {
+        return Err(invalid_data("corrupt central directory"));
+    }
+    if flags & 0x1 != 0 {
+        return Err(invalid_data("encrypted ZIP files are not supported"));
+    }
+    let name = String::from_utf8_lossy(&buf[46..46 + name_len]).into_owned();
+
+    // ZIP64 extended information replaces whichever fields overflowed, in order
+    let mut extra = &buf[46 + name_len..46 + name_len + extra_len];
+    while extra.len() >= 4 {
+        let id = le_u16(extra, 0);
+        let len = usize::from(le_u16(extra, 2)).min(extra.len() - 4);
+        if id == 0x0001 {
+            let mut field = &extra[4..4 + len];
+            for value in [&mut size, &mut compressed_size, &mut header_offset] {
+                if *value == 0xFFFF_FFFF && field.len() >= 8 {
+                    *value = le_u64(field, 0);
+                    field = &field[8..];
+                }
+            }
+        }
+        extra = &extra[4 + len..];
+    }
+
+    Ok((
+        ZipEntry {
+            name,
+            size,
+            method,
+            compressed_size,
+            header_offset,
+            crc32,
+        },
+        total,
+    ))
+}
+
+/// Returns where an entry's data starts, after its local header.
+fn local_data_offset<R: Read + Seek>(reader: &mut R, header_offset: u64) -> io::Result<u64> {
+    reader.seek(SeekFrom::Start(header_offset))?;
+    let mut header = [0u8; 30];
+    reader.read_exact(&mut header)?;
+    if le_u32(&header, 0) != LOCAL_HEADER_SIGNATURE {
+        return Err(invalid_data("corrupt local header"));
+    }
+    let name_len = u64::from(le_u16(&header, 26));
+    let extra_len = u64::from(le_u16(&header, 28));
+    Ok(header_offset + 30 + name_len + extra_len)
+}
+
+fn le_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn le_u64(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+
+    /// Builds a ZIP archive in memory, DEFLATE-compressing every file.
+    pub(crate) fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data) in files {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let crc = crc32fast::hash(data);
+            let offset = out.len() as u32;
+
+            let mut local = Vec::new();
+            local.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            local.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0, 0]);
+            local.extend_from_slice(&crc.to_le_bytes());
+            local.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            local.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            local.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&local);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&compressed);
+
+            central.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            central.extend_from_slice(&[20, 0]);
+            central.extend_from_slice(&local[4..30]);
+            central.extend_from_slice(&[0; 10]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let cd_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&cd_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_reads_entries() {
+        let zip = build_zip(&[
+            ("Journal.json", b"{\"entries\": []}"),
+            ("photos/a.jpeg", &[7; 1000]),
+        ]);
+        let mut reader = ZipReader::new(Cursor::new(zip)).unwrap();
+
+        let names: Vec<&str> = reader.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Journal.json", "photos/a.jpeg"]);
+        assert_eq!(reader.read(0, 1024).unwrap(), b"{\"entries\": []}");
+        assert_eq!(reader.read(1, 1024).unwrap(), vec![7; 1000]);
+        assert!(reader.read(1, 999).unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let mut zip = build_zip(&[("a.txt", b"hello hello hello")]);
+        // Flip the stored CRC in the central directory
+        let cd = zip.len() - 22 - (46 + 5);
+        zip[cd + 16] ^= 0xFF;
+        let mut reader = ZipReader::new(Cursor::new(zip)).unwrap();
+        assert!(reader.read(0, 1024).unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn test_rejects_non_zip() {
+        assert!(ZipReader::new(Cursor::new(b"not a zip file at all".to_vec())).is_err());
+        assert!(ZipReader::new(Cursor::new(Vec::new())).is_err());
+    }
+}
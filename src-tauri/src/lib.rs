@@ -1,5 +1,6 @@
 pub mod auth;
 pub mod backup;
+pub mod cli;
 pub mod commands;
 pub mod config;
 pub mod crypto;
@@ -151,6 +152,8 @@ pub fn run() {
             app.manage(lockable);
             app.manage(translatable);
 
+            commands::auth::spawn_backup_scheduler(app.handle().clone());
+
             if let Err(error) = screen_lock::init(app.handle()) {
                 warn!("Screen-lock listener initialization failed: {}", error);
             }
@@ -194,6 +197,14 @@ pub fn run() {
             commands::auth::remove_journal,
             commands::auth::rename_journal,
             commands::auth::switch_journal,
+            commands::auth::get_backup_retention,
+            commands::auth::set_backup_retention,
+            commands::auth::get_backup_mirrors,
+            commands::auth::set_backup_mirrors,
+            commands::auth::get_incremental_backups,
+            commands::auth::set_incremental_backups,
+            commands::auth::get_backup_interval,
+            commands::auth::set_backup_interval,
             // Auth - method management
             commands::auth::verify_password,
             commands::auth::list_auth_methods,
@@ -227,16 +238,28 @@ pub fn run() {
             commands::import::import_dayone_json,
             commands::import::import_dayone_txt,
             commands::import::import_jrnl_json,
+            commands::import::undo_last_import,
             // Export
             commands::export::export_json,
             commands::export::export_markdown,
             // Plugins
             commands::plugin::list_import_plugins,
             commands::plugin::list_export_plugins,
+            commands::plugin::preview_import,
             commands::plugin::run_import_plugin,
             commands::plugin::run_export_plugin,
             // Debug
             commands::debug::generate_debug_dump,
+            // Backups
+            commands::backups::list_backups,
+            commands::backups::preview_backup,
+            commands::backups::restore_backup,
+            commands::backups::restore_backup_entries,
+            commands::backups::list_incremental_backups,
+            commands::backups::restore_incremental_backup,
+            // Integrity
+            commands::integrity::verify_diary,
+            commands::integrity::salvage_diary,
             // Menu locale
             commands::menu::update_menu_locale,
         ])
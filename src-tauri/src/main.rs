@@ -2,5 +2,8 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    if let Some(code) = mini_diarium_lib::cli::run_from_args(std::env::args().skip(1)) {
+        std::process::exit(code);
+    }
     mini_diarium_lib::run()
 }
@@ -1,8 +1,17 @@
-use super::{ExportOutput, ExportPlugin, ImportPlugin, PluginInfo};
+use super::{ExportOptions, ExportOutput, ExportPlugin, ImportPlugin, PluginInfo};
+use crate::config::JournalSettings;
 use crate::db::queries::DiaryEntry;
-use crate::export::{json, markdown};
-use crate::import::{dayone, dayone_txt, jrnl, minidiary};
+use crate::export::{
+    archive as archive_export, dayone as dayone_export, epub, html_site, jrnl as jrnl_export, json,
+    markdown, markdown_folder as markdown_folder_export, pdf,
+};
+use crate::import::{
+    archive as archive_import, daylio, dayone, dayone_txt, dayone_zip, diarium, diaro, enex,
+    journey, jrnl, markdown_folder, minidiary,
+};
 use crate::plugin::registry::PluginRegistry;
+use std::io::Read;
+use std::path::Path;
 
 // --- Import plugins ---
 
@@ -15,12 +24,22 @@ impl ImportPlugin for MiniDiaryImporter {
             name: "Mini Diary JSON".into(),
             file_extensions: vec!["json".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
     fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
         minidiary::parse_minidiary_json(content)
     }
+
+    fn parse_reader(
+        &self,
+        reader: &mut dyn Read,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        minidiary::parse_minidiary_json_reader(reader, on_entry)
+    }
 }
 
 pub struct DayOneJsonImporter;
@@ -32,12 +51,45 @@ impl ImportPlugin for DayOneJsonImporter {
             name: "Day One JSON".into(),
             file_extensions: vec!["json".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
     fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
         dayone::parse_dayone_json(content)
     }
+
+    fn parse_reader(
+        &self,
+        reader: &mut dyn Read,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        dayone::parse_dayone_json_reader(reader, on_entry)
+    }
+}
+
+pub struct DayOneZipImporter;
+
+impl ImportPlugin for DayOneZipImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:dayone-zip".into(),
+            name: "Day One ZIP (with photos)".into(),
+            file_extensions: vec!["zip".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        dayone_zip::parse_dayone_zip(path, on_entry)
+    }
 }
 
 pub struct DayOneTxtImporter;
@@ -49,6 +101,8 @@ impl ImportPlugin for DayOneTxtImporter {
             name: "Day One TXT".into(),
             file_extensions: vec!["txt".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
@@ -66,12 +120,222 @@ impl ImportPlugin for JrnlImporter {
             name: "jrnl JSON".into(),
             file_extensions: vec!["json".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
     fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
         jrnl::parse_jrnl_json(content)
     }
+
+    fn parse_reader(
+        &self,
+        reader: &mut dyn Read,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        jrnl::parse_jrnl_json_reader(reader, on_entry)
+    }
+}
+
+pub struct JrnlTxtImporter;
+
+impl ImportPlugin for JrnlTxtImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:jrnl-txt".into(),
+            name: "jrnl text".into(),
+            file_extensions: vec!["txt".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
+        jrnl::parse_jrnl_txt(content)
+    }
+}
+
+pub struct EnexImporter;
+
+impl ImportPlugin for EnexImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:enex".into(),
+            name: "Evernote / Joplin (ENEX)".into(),
+            file_extensions: vec!["enex".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
+        enex::parse_enex(content)
+    }
+
+    fn parse_reader(
+        &self,
+        reader: &mut dyn Read,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        enex::parse_enex_reader(reader, on_entry)
+    }
+}
+
+pub struct JourneyImporter;
+
+impl ImportPlugin for JourneyImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:journey-zip".into(),
+            name: "Journey ZIP (with photos)".into(),
+            file_extensions: vec!["zip".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        journey::parse_journey_zip(path, on_entry)
+    }
+}
+
+pub struct DiaroImporter;
+
+impl ImportPlugin for DiaroImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:diaro".into(),
+            name: "Diaro backup".into(),
+            file_extensions: vec!["diaro".into(), "zip".into(), "xml".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        diaro::parse_diaro(path, on_entry)
+    }
+}
+
+pub struct DiariumImporter;
+
+impl ImportPlugin for DiariumImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:diarium-json".into(),
+            name: "Diarium (JSON or backup)".into(),
+            file_extensions: vec!["json".into(), "diary".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
+        diarium::parse_diarium_json(content)
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        // Backups are SQLite databases; anything else is read as a JSON export
+        if diarium::is_sqlite_file(path) {
+            return diarium::parse_diarium_sqlite(path, on_entry);
+        }
+        let file = std::fs::File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+        self.parse_reader(&mut std::io::BufReader::new(file), on_entry)
+    }
+}
+
+pub struct DaylioImporter;
+
+impl ImportPlugin for DaylioImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:daylio-csv".into(),
+            name: "Daylio CSV".into(),
+            file_extensions: vec!["csv".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
+        daylio::parse_daylio_csv(content)
+    }
+}
+
+pub struct MarkdownFolderImporter;
+
+impl ImportPlugin for MarkdownFolderImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:markdown-folder".into(),
+            name: "Markdown folder (Obsidian daily notes)".into(),
+            file_extensions: vec!["md".into()],
+            builtin: true,
+            directory: true,
+            options: vec![],
+        }
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        markdown_folder::parse_markdown_folder(path, on_entry)
+    }
+}
+
+pub struct ArchiveImporter;
+
+impl ImportPlugin for ArchiveImporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:mdarchive".into(),
+            name: "Mini Diarium Archive (encrypted)".into(),
+            file_extensions: vec!["mdarchive".into()],
+            builtin: true,
+            directory: false,
+            options: vec!["passphrase".into()],
+        }
+    }
+
+    fn parse(&self, _content: &str) -> Result<Vec<DiaryEntry>, String> {
+        Err("A passphrase is required to open this archive".to_string())
+    }
+
+    /// Archives are read whole, without the 100 MB limit of text formats:
+    /// the payload is a single encrypted block that cannot be streamed.
+    fn parse_path_with_passphrase(
+        &self,
+        path: &Path,
+        passphrase: &str,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<Option<JournalSettings>, String> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let (entries, journal) = archive_import::parse_archive(&content, passphrase)?;
+        entries.into_iter().try_for_each(on_entry)?;
+        Ok(journal)
+    }
 }
 
 // --- Export plugins ---
@@ -85,6 +349,8 @@ impl ExportPlugin for JsonExporter {
             name: "Mini Diary JSON".into(),
             file_extensions: vec!["json".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
@@ -92,7 +358,10 @@ impl ExportPlugin for JsonExporter {
         let content = json::export_entries_to_json(entries)?;
         Ok(ExportOutput {
             content,
+            binary: None,
             assets: vec![],
+            files: vec![],
+            overwrite: false,
         })
     }
 }
@@ -106,12 +375,20 @@ impl ExportPlugin for MarkdownExporter {
             name: "Markdown".into(),
             file_extensions: vec!["md".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
     fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
         let (content, assets) = markdown::export_entries_to_markdown_with_assets(entries);
-        Ok(ExportOutput { content, assets })
+        Ok(ExportOutput {
+            content,
+            binary: None,
+            assets,
+            files: vec![],
+            overwrite: false,
+        })
     }
 }
 
@@ -124,13 +401,220 @@ impl ExportPlugin for MarkdownInlineExporter {
             name: "Markdown (inline images)".into(),
             file_extensions: vec!["md".into()],
             builtin: true,
+            directory: false,
+            options: vec![],
         }
     }
 
     fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
         Ok(ExportOutput {
             content: markdown::export_entries_to_markdown_inline(entries),
+            binary: None,
             assets: vec![],
+            files: vec![],
+            overwrite: false,
+        })
+    }
+}
+
+pub struct MarkdownFolderExporter;
+
+impl ExportPlugin for MarkdownFolderExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:markdown-folder".into(),
+            name: "Markdown folder (Obsidian daily notes)".into(),
+            file_extensions: vec!["md".into()],
+            builtin: true,
+            directory: true,
+            options: vec![],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Ok(ExportOutput {
+            content: String::new(),
+            binary: None,
+            assets: vec![],
+            files: markdown_folder_export::export_entries_to_markdown_folder(entries),
+            overwrite: false,
+        })
+    }
+}
+
+pub struct DayOneExporter;
+
+impl ExportPlugin for DayOneExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:dayone-json".into(),
+            name: "Day One JSON (with photos)".into(),
+            file_extensions: vec!["json".into()],
+            builtin: true,
+            directory: true,
+            options: vec![],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Ok(ExportOutput {
+            content: String::new(),
+            binary: None,
+            assets: vec![],
+            files: dayone_export::export_entries_to_dayone(entries)?,
+            overwrite: false,
+        })
+    }
+}
+
+pub struct HtmlSiteExporter;
+
+impl ExportPlugin for HtmlSiteExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:html-site".into(),
+            name: "HTML website".into(),
+            file_extensions: vec!["html".into()],
+            builtin: true,
+            directory: true,
+            options: vec![],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Ok(ExportOutput {
+            content: String::new(),
+            binary: None,
+            assets: vec![],
+            files: html_site::export_entries_to_html_site(entries),
+            overwrite: true,
+        })
+    }
+}
+
+pub struct JrnlTxtExporter;
+
+impl ExportPlugin for JrnlTxtExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:jrnl-txt".into(),
+            name: "jrnl text".into(),
+            file_extensions: vec!["txt".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Ok(ExportOutput {
+            content: jrnl_export::export_entries_to_jrnl_txt(entries),
+            binary: None,
+            assets: vec![],
+            files: vec![],
+            overwrite: false,
+        })
+    }
+}
+
+pub struct EpubExporter;
+
+impl ExportPlugin for EpubExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:epub".into(),
+            name: "EPUB e-book".into(),
+            file_extensions: vec!["epub".into()],
+            builtin: true,
+            directory: false,
+            options: vec![],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Ok(ExportOutput {
+            content: String::new(),
+            binary: Some(epub::export_entries_to_epub(entries)?),
+            assets: vec![],
+            files: vec![],
+            overwrite: false,
+        })
+    }
+}
+
+pub struct PdfExporter;
+
+impl ExportPlugin for PdfExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:pdf".into(),
+            name: "PDF".into(),
+            file_extensions: vec!["pdf".into()],
+            builtin: true,
+            directory: false,
+            options: vec!["page_layout".into()],
+        }
+    }
+
+    fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        self.export_with_options(entries, &ExportOptions::default())
+    }
+
+    fn export_with_options(
+        &self,
+        entries: Vec<DiaryEntry>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput, String> {
+        let layout: pdf::PdfLayout =
+            serde_json::from_value(serde_json::Value::Object(options.settings.clone()))
+                .map_err(|e| format!("Invalid PDF settings: {}", e))?;
+        Ok(ExportOutput {
+            content: String::new(),
+            binary: Some(pdf::export_entries_to_pdf(entries, &layout)?),
+            assets: vec![],
+            files: vec![],
+            overwrite: false,
+        })
+    }
+}
+
+pub struct ArchiveExporter;
+
+impl ExportPlugin for ArchiveExporter {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: "builtin:mdarchive".into(),
+            name: "Mini Diarium Archive (encrypted)".into(),
+            file_extensions: vec!["mdarchive".into()],
+            builtin: true,
+            directory: false,
+            options: vec!["passphrase".into()],
+        }
+    }
+
+    fn export(&self, _entries: Vec<DiaryEntry>) -> Result<ExportOutput, String> {
+        Err("A passphrase is required to create an archive".to_string())
+    }
+
+    fn export_with_options(
+        &self,
+        entries: Vec<DiaryEntry>,
+        options: &ExportOptions,
+    ) -> Result<ExportOutput, String> {
+        let passphrase = options
+            .passphrase
+            .as_deref()
+            .ok_or("A passphrase is required to create an archive")?;
+        Ok(ExportOutput {
+            content: archive_export::export_entries_to_archive(
+                entries,
+                options.journal.clone(),
+                passphrase,
+            )?,
+            binary: None,
+            assets: vec![],
+            files: vec![],
+            overwrite: false,
         })
     }
 }
@@ -139,11 +623,27 @@ impl ExportPlugin for MarkdownInlineExporter {
 pub fn register_all(registry: &mut PluginRegistry) {
     registry.register_importer(Box::new(MiniDiaryImporter));
     registry.register_importer(Box::new(DayOneJsonImporter));
+    registry.register_importer(Box::new(DayOneZipImporter));
     registry.register_importer(Box::new(DayOneTxtImporter));
     registry.register_importer(Box::new(JrnlImporter));
+    registry.register_importer(Box::new(JrnlTxtImporter));
+    registry.register_importer(Box::new(EnexImporter));
+    registry.register_importer(Box::new(JourneyImporter));
+    registry.register_importer(Box::new(DiaroImporter));
+    registry.register_importer(Box::new(DiariumImporter));
+    registry.register_importer(Box::new(DaylioImporter));
+    registry.register_importer(Box::new(MarkdownFolderImporter));
+    registry.register_importer(Box::new(ArchiveImporter));
     registry.register_exporter(Box::new(JsonExporter));
     registry.register_exporter(Box::new(MarkdownExporter));
     registry.register_exporter(Box::new(MarkdownInlineExporter));
+    registry.register_exporter(Box::new(MarkdownFolderExporter));
+    registry.register_exporter(Box::new(JrnlTxtExporter));
+    registry.register_exporter(Box::new(DayOneExporter));
+    registry.register_exporter(Box::new(HtmlSiteExporter));
+    registry.register_exporter(Box::new(PdfExporter));
+    registry.register_exporter(Box::new(EpubExporter));
+    registry.register_exporter(Box::new(ArchiveExporter));
 }
 
 #[cfg(test)]
@@ -171,7 +671,39 @@ mod tests {
     fn test_register_all() {
         let mut registry = PluginRegistry::new();
         register_all(&mut registry);
-        assert_eq!(registry.list_importers().len(), 4);
-        assert_eq!(registry.list_exporters().len(), 3);
+        assert_eq!(registry.list_importers().len(), 13);
+        assert_eq!(registry.list_exporters().len(), 10);
+    }
+
+    #[test]
+    fn test_archive_plugins_require_passphrase() {
+        let importer = ArchiveImporter;
+        let exporter = ArchiveExporter;
+        assert_eq!(importer.info().options, vec!["passphrase"]);
+        assert_eq!(exporter.info().options, vec!["passphrase"]);
+        assert!(JsonExporter.info().options.is_empty());
+        assert!(importer.parse("{}").is_err());
+        assert!(exporter.export(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_pdf_exporter_reads_its_options() {
+        let options: ExportOptions = serde_json::from_value(serde_json::json!({
+            "passphrase": "ignored",
+            "page_size": "a5",
+            "font_size": 12,
+        }))
+        .unwrap();
+        assert_eq!(options.passphrase.as_deref(), Some("ignored"));
+        let output = PdfExporter.export_with_options(vec![], &options).unwrap();
+        assert!(output.binary.unwrap().starts_with(b"%PDF"));
+
+        let options: ExportOptions =
+            serde_json::from_value(serde_json::json!({ "page_size": "a3" })).unwrap();
+        let err = PdfExporter
+            .export_with_options(vec![], &options)
+            .err()
+            .unwrap();
+        assert!(err.contains("Invalid PDF settings"));
     }
 }
@@ -2,7 +2,11 @@ pub mod builtins;
 pub mod registry;
 pub mod rhai_loader;
 
+use crate::config::JournalSettings;
 use crate::db::queries::DiaryEntry;
+use crate::import::MAX_IMPORT_FILE_SIZE;
+use std::io::{BufReader, Read};
+use std::path::Path;
 
 /// Metadata about a plugin, returned to the frontend
 #[derive(Debug, Clone, serde::Serialize)]
@@ -11,12 +15,104 @@ pub struct PluginInfo {
     pub name: String,
     pub file_extensions: Vec<String>,
     pub builtin: bool,
+    /// True if the plugin reads or writes a folder instead of a single file
+    pub directory: bool,
+    /// Settings the frontend must ask for: `"passphrase"` for encrypted
+    /// formats, `"page_layout"` for page size and font
+    pub options: Vec<String>,
+}
+
+/// Settings chosen in the export dialog. Each plugin reads the fields it
+/// needs and ignores the rest.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ExportOptions {
+    pub passphrase: Option<String>,
+    /// Format-specific settings, such as the page size of a PDF
+    #[serde(flatten)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+    /// Name and backup settings of the journal being exported, filled in by
+    /// the export command
+    #[serde(skip)]
+    pub journal: Option<JournalSettings>,
 }
 
 /// A plugin that can parse file content into diary entries for import.
+///
+/// The import commands always call `parse_path`, or
+/// `parse_path_with_passphrase` when the user gave a passphrase. Plugins
+/// override the one
+/// method that fits their format: `parse` for text, `parse_bytes` for binary
+/// files, `parse_reader` to stream large files, or `parse_path` for archives
+/// and folders.
 pub trait ImportPlugin: Send + Sync {
     fn info(&self) -> PluginInfo;
-    fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String>;
+
+    /// Parses the text of a file.
+    fn parse(&self, _content: &str) -> Result<Vec<DiaryEntry>, String> {
+        Err(format!("{} cannot be imported from text", self.info().name))
+    }
+
+    /// Parses the raw bytes of a file. The default requires UTF-8 text and
+    /// calls `parse`.
+    fn parse_bytes(&self, bytes: &[u8]) -> Result<Vec<DiaryEntry>, String> {
+        let content = std::str::from_utf8(bytes)
+            .map_err(|e| format!("File is not valid UTF-8 text: {}", e))?;
+        self.parse(content)
+    }
+
+    /// Parses entries from `reader`, calling `on_entry` for each one as soon
+    /// as it is parsed and stopping at the first error it returns.
+    ///
+    /// The default reads the whole input, up to 100 MB, and calls
+    /// `parse_bytes`. Streaming plugins override this to import files of any
+    /// size.
+    fn parse_reader(
+        &self,
+        reader: &mut dyn Read,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let bytes = read_limited(reader)?;
+        self.parse_bytes(&bytes)?.into_iter().try_for_each(on_entry)
+    }
+
+    /// Parses entries from the file or folder at `path`, like `parse_reader`.
+    /// Formats that need random access to the file, such as ZIP archives, or
+    /// that read a folder override this instead of `parse_reader`.
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+        self.parse_reader(&mut BufReader::new(file), on_entry)
+    }
+
+    /// Parses a file protected by a passphrase, like `parse_path`, and
+    /// returns the settings of the journal it was exported from if the file
+    /// holds them. Only plugins with the `passphrase` option need to override
+    /// this.
+    fn parse_path_with_passphrase(
+        &self,
+        path: &Path,
+        _passphrase: &str,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<Option<JournalSettings>, String> {
+        self.parse_path(path, on_entry).map(|()| None)
+    }
+}
+
+/// Reads all of `reader` into memory, refusing input over
+/// `MAX_IMPORT_FILE_SIZE`.
+pub(crate) fn read_limited(reader: &mut dyn Read) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_IMPORT_FILE_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    if bytes.len() as u64 > MAX_IMPORT_FILE_SIZE {
+        return Err("File is too large. Maximum supported size is 100 MB.".to_string());
+    }
+    Ok(bytes)
 }
 
 /// Output from an export plugin: the formatted text content plus optional binary asset files.
@@ -24,13 +120,38 @@ pub trait ImportPlugin: Send + Sync {
 /// Most plugins return only `content`. The built-in Markdown exporter additionally
 /// returns `assets` — a list of `(filename, bytes)` pairs to be written to a
 /// sibling `assets/` directory alongside the main output file.
+///
+/// Exporters that write a folder tree return `files` — `(relative path, bytes)`
+/// pairs written inside the chosen folder for plugins that set `directory`,
+/// or next to the main output file otherwise.
+///
+/// Exporters of binary formats such as PDF set `binary`, which is written as
+/// the main output file instead of `content`.
+///
+/// `files` never replace existing files. With `overwrite` set, as the HTML
+/// site exporter does, the folder keeps a list of the files written, so a
+/// later export into it replaces those files and removes the ones it no
+/// longer writes. Files the user added to the folder are still never touched.
 pub struct ExportOutput {
     pub content: String,
+    pub binary: Option<Vec<u8>>,
     pub assets: Vec<(String, Vec<u8>)>,
+    pub files: Vec<(String, Vec<u8>)>,
+    pub overwrite: bool,
 }
 
 /// A plugin that can export diary entries to a formatted output.
 pub trait ExportPlugin: Send + Sync {
     fn info(&self) -> PluginInfo;
     fn export(&self, entries: Vec<DiaryEntry>) -> Result<ExportOutput, String>;
+
+    /// Exports entries with the settings chosen by the user. Only plugins
+    /// that declare `options` need to override this.
+    fn export_with_options(
+        &self,
+        entries: Vec<DiaryEntry>,
+        _options: &ExportOptions,
+    ) -> Result<ExportOutput, String> {
+        self.export(entries)
+    }
 }
@@ -56,6 +56,8 @@ mod tests {
                 name: "Dummy Importer".into(),
                 file_extensions: vec!["txt".into()],
                 builtin: false,
+                directory: false,
+                options: vec![],
             }
         }
         fn parse(&self, _content: &str) -> Result<Vec<DiaryEntry>, String> {
@@ -71,12 +73,17 @@ mod tests {
                 name: "Dummy Exporter".into(),
                 file_extensions: vec!["txt".into()],
                 builtin: false,
+                directory: false,
+                options: vec![],
             }
         }
         fn export(&self, _entries: Vec<DiaryEntry>) -> Result<crate::plugin::ExportOutput, String> {
             Ok(crate::plugin::ExportOutput {
                 content: String::new(),
+                binary: None,
                 assets: vec![],
+                files: vec![],
+                overwrite: false,
             })
         }
     }
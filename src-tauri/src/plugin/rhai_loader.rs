@@ -2,18 +2,51 @@ use super::registry::PluginRegistry;
 use super::{ExportOutput, ExportPlugin, ImportPlugin, PluginInfo};
 use crate::db::queries::DiaryEntry;
 use log::{info, warn};
-use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use rhai::{Array, Blob, Dynamic, Engine, Map, Scope, AST};
+use std::io::Read;
 use std::path::Path;
 
 // Keep plugin docs in one place: the generated `{diary_dir}/plugins/README.md`
 // is a direct copy of this repository guide.
 const PLUGINS_README: &str = include_str!("../../../docs/user-plugins/USER_PLUGIN_GUIDE.md");
 
+/// Limit on the total size of the files handed to an import script
+const MAX_INPUT_SIZE: u64 = 100 * 1024 * 1024; // 100 MB
+
+/// What an import script's `parse` function receives (`// @input:`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScriptInput {
+    /// The file as a string
+    Text,
+    /// The file as a blob
+    Bytes,
+    /// A map of file path to blob, for every file in a ZIP archive
+    Zip,
+    /// A map of file path to blob, for every file in a chosen folder
+    Folder,
+}
+
+impl ScriptInput {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(ScriptInput::Text),
+            "bytes" => Some(ScriptInput::Bytes),
+            "zip" => Some(ScriptInput::Zip),
+            "folder" => Some(ScriptInput::Folder),
+            _ => None,
+        }
+    }
+}
+
 /// Metadata parsed from the comment header of a .rhai script.
 struct ScriptMeta {
     name: String,
     plugin_type: String, // "import" or "export"
     extensions: Vec<String>,
+    /// Raw `@input:` value, for import scripts
+    input: Option<String>,
+    /// Raw `@output:` value, for export scripts
+    output: Option<String>,
 }
 
 /// Parse `// @key: value` lines from the top of a script.
@@ -21,6 +54,8 @@ fn parse_metadata(source: &str) -> Option<ScriptMeta> {
     let mut name = None;
     let mut plugin_type = None;
     let mut extensions = None;
+    let mut input = None;
+    let mut output = None;
 
     for line in source.lines() {
         let trimmed = line.trim();
@@ -42,6 +77,10 @@ fn parse_metadata(source: &str) -> Option<ScriptMeta> {
                     .filter(|s| !s.is_empty())
                     .collect::<Vec<_>>(),
             );
+        } else if let Some(val) = comment.strip_prefix("@input:") {
+            input = Some(val.trim().to_lowercase());
+        } else if let Some(val) = comment.strip_prefix("@output:") {
+            output = Some(val.trim().to_lowercase());
         }
     }
 
@@ -49,6 +88,8 @@ fn parse_metadata(source: &str) -> Option<ScriptMeta> {
         name: name?,
         plugin_type: plugin_type?,
         extensions: extensions.unwrap_or_default(),
+        input,
+        output,
     })
 }
 
@@ -120,6 +161,124 @@ fn convert_to_entries(arr: Array) -> Result<Vec<DiaryEntry>, String> {
     Ok(entries)
 }
 
+/// Reads every file in the ZIP archive at `path` into a map of path to blob.
+fn read_zip_files(path: &Path) -> Result<Map, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+    let mut zip = crate::import::zip::ZipReader::new(std::io::BufReader::new(file))?;
+    let mut files = Map::new();
+    let mut remaining = MAX_INPUT_SIZE;
+    for index in 0..zip.entries().len() {
+        let entry = &zip.entries()[index];
+        if entry.is_dir() {
+            continue;
+        }
+        if entry.size > remaining {
+            return Err("The archive is too large. Maximum supported size is 100 MB.".into());
+        }
+        let name = entry.name.clone();
+        let data = zip.read(index, remaining)?;
+        remaining -= data.len() as u64;
+        files.insert(name.into(), Dynamic::from_blob(data));
+    }
+    Ok(files)
+}
+
+/// Reads every file under the folder `root` into a map of `/`-separated
+/// relative path to blob. Hidden entries and symbolic links are skipped.
+fn read_folder_files(root: &Path) -> Result<Map, String> {
+    if !root.is_dir() {
+        return Err("Please choose a folder".to_string());
+    }
+    let mut files = Map::new();
+    let mut remaining = MAX_INPUT_SIZE;
+    collect_folder_files(root, "", &mut files, &mut remaining)?;
+    Ok(files)
+}
+
+fn collect_folder_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Map,
+    remaining: &mut u64,
+) -> Result<(), String> {
+    let read_dir = std::fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read folder '{}': {}", dir.display(), e))?;
+    for item in read_dir.flatten() {
+        let name = item.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = item.file_type() else {
+            continue;
+        };
+        let relative = format!("{}{}", prefix, name);
+        if file_type.is_dir() {
+            collect_folder_files(&item.path(), &format!("{}/", relative), files, remaining)?;
+        } else if file_type.is_file() {
+            let mut data = Vec::new();
+            std::fs::File::open(item.path())
+                .and_then(|file| file.take(*remaining + 1).read_to_end(&mut data))
+                .map_err(|e| format!("Failed to read '{}': {}", relative, e))?;
+            if data.len() as u64 > *remaining {
+                return Err("The folder is too large. Maximum supported size is 100 MB.".into());
+            }
+            *remaining -= data.len() as u64;
+            files.insert(relative.into(), Dynamic::from_blob(data));
+        }
+    }
+    Ok(())
+}
+
+/// Convert the value returned by `format_entries` into an `ExportOutput`.
+///
+/// Scripts return a string, a blob for binary formats, or a map with an
+/// optional `content` (string or blob) and `files` (path to string or blob).
+fn convert_export_output(value: Dynamic) -> Result<ExportOutput, String> {
+    let mut output = ExportOutput {
+        content: String::new(),
+        binary: None,
+        assets: vec![],
+        files: vec![],
+        overwrite: false,
+    };
+    let map = if value.is_map() {
+        value.cast::<Map>()
+    } else {
+        let mut map = Map::new();
+        map.insert("content".into(), value);
+        map
+    };
+    for (key, value) in map {
+        match key.as_str() {
+            "content" if value.is_string() => output.content = value.cast::<String>(),
+            "content" if value.is_blob() => output.binary = Some(value.cast::<Blob>()),
+            "files" if value.is_map() => {
+                for (path, data) in value.cast::<Map>() {
+                    let bytes = dynamic_to_bytes(data)
+                        .ok_or_else(|| format!("File '{}' is not a string or blob", path))?;
+                    output.files.push((path.to_string(), bytes));
+                }
+            }
+            "content" | "files" => {
+                return Err(format!(
+                    "format_entries returned an invalid '{}' (expected a string, blob or map)",
+                    key
+                ))
+            }
+            other => warn!("Ignoring unknown export output field '{}'", other),
+        }
+    }
+    Ok(output)
+}
+
+fn dynamic_to_bytes(value: Dynamic) -> Option<Vec<u8>> {
+    if value.is_blob() {
+        Some(value.cast::<Blob>())
+    } else {
+        value.into_string().ok().map(String::into_bytes)
+    }
+}
+
 /// Convert `Vec<DiaryEntry>` into a Rhai-compatible array of maps.
 fn entries_to_rhai_array(entries: Vec<DiaryEntry>) -> Array {
     entries
@@ -142,6 +301,18 @@ fn entries_to_rhai_array(entries: Vec<DiaryEntry>) -> Array {
 struct RhaiImportPlugin {
     info: PluginInfo,
     script: AST,
+    input: ScriptInput,
+}
+
+impl RhaiImportPlugin {
+    fn call_parse(&self, arg: Dynamic) -> Result<Vec<DiaryEntry>, String> {
+        let engine = create_sandboxed_engine();
+        let mut scope = Scope::new();
+        let result: Array = engine
+            .call_fn(&mut scope, &self.script, "parse", (arg,))
+            .map_err(|e| format!("Rhai script error: {}", e))?;
+        convert_to_entries(result)
+    }
 }
 
 // Safety: AST is immutable after compilation. Engine is created fresh per call_fn()
@@ -155,12 +326,45 @@ impl ImportPlugin for RhaiImportPlugin {
     }
 
     fn parse(&self, content: &str) -> Result<Vec<DiaryEntry>, String> {
-        let engine = create_sandboxed_engine();
-        let mut scope = Scope::new();
-        let result: Array = engine
-            .call_fn(&mut scope, &self.script, "parse", (content.to_string(),))
-            .map_err(|e| format!("Rhai script error: {}", e))?;
-        convert_to_entries(result)
+        match self.input {
+            ScriptInput::Text => self.call_parse(Dynamic::from(content.to_string())),
+            _ => self.parse_bytes(content.as_bytes()),
+        }
+    }
+
+    fn parse_bytes(&self, bytes: &[u8]) -> Result<Vec<DiaryEntry>, String> {
+        match self.input {
+            ScriptInput::Text => {
+                let content = std::str::from_utf8(bytes)
+                    .map_err(|e| format!("File is not valid UTF-8 text: {}", e))?;
+                self.parse(content)
+            }
+            ScriptInput::Bytes => self.call_parse(Dynamic::from_blob(bytes.to_vec())),
+            ScriptInput::Zip => Err(format!("{} must be imported from a file", self.info.name)),
+            ScriptInput::Folder => {
+                Err(format!("{} must be imported from a folder", self.info.name))
+            }
+        }
+    }
+
+    fn parse_path(
+        &self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(DiaryEntry) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let files = match self.input {
+            ScriptInput::Zip => read_zip_files(path)?,
+            ScriptInput::Folder => read_folder_files(path)?,
+            ScriptInput::Text | ScriptInput::Bytes => {
+                let mut file =
+                    std::fs::File::open(path).map_err(|e| format!("Cannot access file: {}", e))?;
+                let bytes = super::read_limited(&mut file)?;
+                return self.parse_bytes(&bytes)?.into_iter().try_for_each(on_entry);
+            }
+        };
+        self.call_parse(Dynamic::from_map(files))?
+            .into_iter()
+            .try_for_each(on_entry)
     }
 }
 
@@ -183,13 +387,10 @@ impl ExportPlugin for RhaiExportPlugin {
         let mut scope = Scope::new();
         let arr = entries_to_rhai_array(entries);
         // "export" is a reserved keyword in Rhai, so scripts use "format_entries" instead
-        let content: String = engine
+        let result: Dynamic = engine
             .call_fn(&mut scope, &self.script, "format_entries", (arr,))
             .map_err(|e| format!("Rhai script error: {}", e))?;
-        Ok(ExportOutput {
-            content,
-            assets: vec![],
-        })
+        convert_export_output(result)
     }
 }
 
@@ -264,11 +465,40 @@ pub fn load_plugins(plugins_dir: &Path, registry: &mut PluginRegistry) {
             }
         };
 
+        let input = match meta.input.as_deref() {
+            None => ScriptInput::Text,
+            Some(value) => match ScriptInput::parse(value) {
+                Some(input) => input,
+                None => {
+                    warn!(
+                        "Plugin '{}' has unknown @input '{}', skipping",
+                        path.display(),
+                        value
+                    );
+                    continue;
+                }
+            },
+        };
+        let directory = match meta.output.as_deref() {
+            None | Some("file") => false,
+            Some("folder") => true,
+            Some(value) => {
+                warn!(
+                    "Plugin '{}' has unknown @output '{}', skipping",
+                    path.display(),
+                    value
+                );
+                continue;
+            }
+        };
+
         let info = PluginInfo {
             id: plugin_id,
             name: meta.name,
             file_extensions: meta.extensions,
             builtin: false,
+            directory: directory || input == ScriptInput::Folder,
+            options: vec![],
         };
 
         match meta.plugin_type.as_str() {
@@ -278,7 +508,11 @@ pub fn load_plugins(plugins_dir: &Path, registry: &mut PluginRegistry) {
                     info.name,
                     path.display()
                 );
-                registry.register_importer(Box::new(RhaiImportPlugin { info, script: ast }));
+                registry.register_importer(Box::new(RhaiImportPlugin {
+                    info,
+                    script: ast,
+                    input,
+                }));
             }
             "export" => {
                 info!(
@@ -392,8 +626,11 @@ fn parse(content) {
                 name: "Test".into(),
                 file_extensions: vec!["json".into()],
                 builtin: false,
+                directory: false,
+                options: vec![],
             },
             script: ast,
+            input: ScriptInput::Text,
         };
 
         let entries = plugin.parse("ignored").unwrap();
@@ -426,6 +663,8 @@ fn format_entries(entries) {
                 name: "Test Export".into(),
                 file_extensions: vec!["txt".into()],
                 builtin: false,
+                directory: false,
+                options: vec![],
             },
             script: ast,
         };
@@ -472,8 +711,11 @@ fn parse(content) {
                 name: "JSON Test".into(),
                 file_extensions: vec!["json".into()],
                 builtin: false,
+                directory: false,
+                options: vec![],
             },
             script: ast,
+            input: ScriptInput::Text,
         };
 
         let input = r#"[{"date":"2024-03-01","title":"Test","text":"<p>Hi</p>"}]"#;
@@ -482,6 +724,161 @@ fn parse(content) {
         assert_eq!(entries[0].date, "2024-03-01");
     }
 
+    fn load_one(source: &str) -> PluginRegistry {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("script.rhai"), source).unwrap();
+        let mut registry = PluginRegistry::new();
+        load_plugins(dir.path(), &mut registry);
+        registry
+    }
+
+    #[test]
+    fn test_parse_metadata_input_and_output() {
+        let source = "// @name: Zip\n// @type: import\n// @input: ZIP\nfn parse(f) { [] }";
+        let meta = parse_metadata(source).unwrap();
+        assert_eq!(meta.input.as_deref(), Some("zip"));
+        assert_eq!(meta.output, None);
+
+        let source =
+            "// @name: Notes\n// @type: export\n// @output: folder\nfn format_entries(e) { #{} }";
+        let registry = load_one(source);
+        assert!(registry.list_exporters()[0].directory);
+
+        let source = "// @name: Bad\n// @type: import\n// @input: socket\nfn parse(c) { [] }";
+        assert!(load_one(source).list_importers().is_empty());
+    }
+
+    #[test]
+    fn test_rhai_import_plugin_bytes_input() {
+        let registry = load_one(
+            r#"
+// @name: Bytes
+// @type: import
+// @input: bytes
+
+fn parse(data) {
+    let title = data.extract(1, 2).as_string();
+    [#{ date: "2024-01-01", title: title, text: "" + data[0] }]
+}
+"#,
+        );
+        let plugin = registry.find_importer("rhai:script").unwrap();
+        let entries = plugin.parse_bytes(&[0xfe, b'h', b'i']).unwrap();
+        assert_eq!(entries[0].title, "hi");
+        assert_eq!(entries[0].text, "254");
+    }
+
+    const LIST_FILES_SCRIPT: &str = r#"
+fn parse(files) {
+    let entries = [];
+    for path in files.keys() {
+        entries += #{ date: "2024-01-01", title: path, text: files[path].as_string() };
+    }
+    entries.sort(|a, b| if a.title < b.title { -1 } else { 1 });
+    entries
+}
+"#;
+
+    fn parse_path_titles(plugin: &dyn ImportPlugin, path: &Path) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        plugin
+            .parse_path(path, &mut |entry| {
+                entries.push((entry.title, entry.text));
+                Ok(())
+            })
+            .unwrap();
+        entries
+    }
+
+    #[test]
+    fn test_rhai_import_plugin_zip_input() {
+        let registry = load_one(&format!(
+            "// @name: Zip\n// @type: import\n// @input: zip\n{}",
+            LIST_FILES_SCRIPT
+        ));
+        let plugin = registry.find_importer("rhai:script").unwrap();
+
+        let zip = crate::import::zip::tests::build_zip(&[
+            ("notes/", b""),
+            ("notes/a.txt", b"first"),
+            ("b.txt", b"second"),
+        ]);
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), zip).unwrap();
+        assert_eq!(
+            parse_path_titles(plugin, file.path()),
+            vec![
+                ("b.txt".to_string(), "second".to_string()),
+                ("notes/a.txt".to_string(), "first".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rhai_import_plugin_folder_input() {
+        let registry = load_one(&format!(
+            "// @name: Folder\n// @type: import\n// @input: folder\n{}",
+            LIST_FILES_SCRIPT
+        ));
+        let plugin = registry.find_importer("rhai:script").unwrap();
+        assert!(registry.list_importers()[0].directory);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("2024")).unwrap();
+        std::fs::write(dir.path().join("2024/jan.txt"), "first").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "skipped").unwrap();
+        assert_eq!(
+            parse_path_titles(plugin, dir.path()),
+            vec![("2024/jan.txt".to_string(), "first".to_string())]
+        );
+        assert!(plugin.parse("not a folder").is_err());
+    }
+
+    #[test]
+    fn test_rhai_export_plugin_binary_and_files() {
+        let registry = load_one(
+            r#"
+// @name: Binary
+// @type: export
+
+fn format_entries(entries) {
+    let data = blob(2, 0xff);
+    #{
+        content: data,
+        files: #{ "notes/readme.txt": "hello", "raw.bin": blob(1, 7) },
+    }
+}
+"#,
+        );
+        let plugin = registry.find_exporter("rhai:script").unwrap();
+        let mut output = plugin.export(sample_entries()).unwrap();
+        assert_eq!(output.binary, Some(vec![0xff, 0xff]));
+        output.files.sort();
+        assert_eq!(
+            output.files,
+            vec![
+                ("notes/readme.txt".to_string(), b"hello".to_vec()),
+                ("raw.bin".to_string(), vec![7]),
+            ]
+        );
+
+        let registry =
+            load_one("// @name: Blob\n// @type: export\nfn format_entries(e) { \"ab\".to_blob() }");
+        let output = registry
+            .find_exporter("rhai:script")
+            .unwrap()
+            .export(vec![]);
+        assert_eq!(output.unwrap().binary, Some(b"ab".to_vec()));
+
+        let registry =
+            load_one("// @name: Bad\n// @type: export\nfn format_entries(e) { #{ files: 1 } }");
+        let output = registry
+            .find_exporter("rhai:script")
+            .unwrap()
+            .export(vec![]);
+        assert!(output.is_err());
+    }
+
     #[test]
     fn test_load_plugins_from_dir() {
         let dir = tempfile::tempdir().unwrap();
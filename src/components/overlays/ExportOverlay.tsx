@@ -1,12 +1,14 @@
 import { createSignal, onMount, Show, For } from 'solid-js';
 import { Dialog } from '@kobalte/core/dialog';
-import { save as saveDialog } from '@tauri-apps/plugin-dialog';
+import { open as openDialog, save as saveDialog } from '@tauri-apps/plugin-dialog';
 import { createLogger } from '../../lib/logger';
 import {
   listExportPlugins,
   runExportPlugin,
   type PluginInfo,
   type ExportResult,
+  type PageSize,
+  type StandardFont,
 } from '../../lib/tauri';
 import { mapTauriError } from '../../lib/errors';
 import { useI18n } from '../../i18n';
@@ -28,6 +30,12 @@ export default function ExportOverlay(props: ExportOverlayProps) {
   const [exporting, setExporting] = createSignal(false);
   const [error, setError] = createSignal<string | null>(null);
   const [result, setResult] = createSignal<ExportResult | null>(null);
+  const [passphrase, setPassphrase] = createSignal('');
+  const [passphraseConfirm, setPassphraseConfirm] = createSignal('');
+  const [pageSize, setPageSize] = createSignal<PageSize>('a4');
+  const [font, setFont] = createSignal<StandardFont>('helvetica');
+  const [fontSize, setFontSize] = createSignal(11);
+  const [fontFile, setFontFile] = createSignal<string | null>(null);
 
   onMount(async () => {
     try {
@@ -47,10 +55,22 @@ export default function ExportOverlay(props: ExportOverlayProps) {
     if (!open) {
       setError(null);
       setResult(null);
+      setPassphrase('');
+      setPassphraseConfirm('');
       props.onClose();
     }
   };
 
+  const handleChooseFontFile = async () => {
+    const selected = await openDialog({
+      multiple: false,
+      filters: [{ name: 'TrueType', extensions: ['ttf'] }],
+    });
+    if (typeof selected === 'string') {
+      setFontFile(selected);
+    }
+  };
+
   const handleKeyDown = (e: KeyboardEvent) => {
     if (e.key === 'Escape' && !exporting()) {
       props.onClose();
@@ -61,6 +81,24 @@ export default function ExportOverlay(props: ExportOverlayProps) {
     const plugin = selectedPlugin();
     if (!plugin) return;
 
+    const needsPassphrase = plugin.options.includes('passphrase');
+    const needsPageLayout = plugin.options.includes('page_layout');
+    if (needsPassphrase) {
+      if (!passphrase()) {
+        setError(t('export.passphraseRequired'));
+        return;
+      }
+      if (passphrase() !== passphraseConfirm()) {
+        setError(t('export.passphraseMismatch'));
+        return;
+      }
+    }
+
+    if (needsPageLayout && !(fontSize() >= 6 && fontSize() <= 24)) {
+      setError(t('export.fontSizeInvalid'));
+      return;
+    }
+
     setExporting(true);
     setError(null);
     setResult(null);
@@ -69,22 +107,34 @@ export default function ExportOverlay(props: ExportOverlayProps) {
       const ext = plugin.file_extensions[0] ?? 'txt';
       const defaultPath = `mini-diarium-export.${ext}`;
 
-      const filePath = await saveDialog({
-        defaultPath,
-        filters: [
-          {
-            name: plugin.name,
-            extensions: plugin.file_extensions,
-          },
-        ],
-      });
+      const filePath = plugin.directory
+        ? await openDialog({ directory: true, multiple: false })
+        : await saveDialog({
+            defaultPath,
+            filters: [
+              {
+                name: plugin.name,
+                extensions: plugin.file_extensions,
+              },
+            ],
+          });
 
       if (!filePath) {
         setExporting(false);
         return;
       }
 
-      const exportResult = await runExportPlugin(plugin.id, filePath);
+      const exportResult = await runExportPlugin(plugin.id, filePath, {
+        ...(needsPassphrase ? { passphrase: passphrase() } : {}),
+        ...(needsPageLayout
+          ? {
+              page_size: pageSize(),
+              font: font(),
+              font_file: fontFile(),
+              font_size: fontSize(),
+            }
+          : {}),
+      });
       setResult(exportResult);
     } catch (err) {
       log.error('Export failed:', err);
@@ -134,11 +184,13 @@ export default function ExportOverlay(props: ExportOverlayProps) {
             </Dialog.Description>
 
             {/* Security Warning */}
-            <div class="mb-4 rounded-md bg-amber-50 border border-amber-200 p-3 dark:bg-amber-900/20 dark:border-amber-800">
-              <p class="text-sm text-amber-800 dark:text-amber-200">
-                {t('export.securityWarning')}
-              </p>
-            </div>
+            <Show when={!selectedPlugin()?.options.includes('passphrase')}>
+              <div class="mb-4 rounded-md bg-amber-50 border border-amber-200 p-3 dark:bg-amber-900/20 dark:border-amber-800">
+                <p class="text-sm text-amber-800 dark:text-amber-200">
+                  {t('export.securityWarning')}
+                </p>
+              </div>
+            </Show>
 
             {/* Format Selection */}
             <div class="mb-6">
@@ -162,6 +214,139 @@ export default function ExportOverlay(props: ExportOverlayProps) {
               </select>
             </div>
 
+            {/* Page Layout */}
+            <Show when={selectedPlugin()?.options.includes('page_layout')}>
+              <div class="mb-6 space-y-3">
+                <div class="grid grid-cols-3 gap-3">
+                  <div>
+                    <label
+                      for="export-page-size"
+                      class="block text-sm font-medium text-secondary mb-2"
+                    >
+                      {t('export.pageSizeLabel')}
+                    </label>
+                    <select
+                      id="export-page-size"
+                      value={pageSize()}
+                      onChange={(e) => setPageSize(e.currentTarget.value as PageSize)}
+                      disabled={exporting()}
+                      class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                    >
+                      <option value="a4">A4</option>
+                      <option value="a5">A5</option>
+                      <option value="letter">Letter</option>
+                    </select>
+                  </div>
+                  <div>
+                    <label
+                      for="export-font"
+                      class="block text-sm font-medium text-secondary mb-2"
+                    >
+                      {t('export.fontLabel')}
+                    </label>
+                    <select
+                      id="export-font"
+                      value={font()}
+                      onChange={(e) => setFont(e.currentTarget.value as StandardFont)}
+                      disabled={exporting() || fontFile() !== null}
+                      class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                    >
+                      <option value="helvetica">Helvetica</option>
+                      <option value="times">Times</option>
+                      <option value="courier">Courier</option>
+                    </select>
+                  </div>
+                  <div>
+                    <label
+                      for="export-font-size"
+                      class="block text-sm font-medium text-secondary mb-2"
+                    >
+                      {t('export.fontSizeLabel')}
+                    </label>
+                    <input
+                      id="export-font-size"
+                      type="number"
+                      min="6"
+                      max="24"
+                      step="0.5"
+                      value={fontSize()}
+                      onInput={(e) => setFontSize(e.currentTarget.valueAsNumber)}
+                      disabled={exporting()}
+                      class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                    />
+                  </div>
+                </div>
+                <div>
+                  <span class="block text-sm font-medium text-secondary mb-2">
+                    {t('export.fontFileLabel')}
+                  </span>
+                  <div class="flex items-center gap-2">
+                    <button
+                      type="button"
+                      onClick={handleChooseFontFile}
+                      disabled={exporting()}
+                      class="px-3 py-2 text-sm font-medium text-secondary border border-primary rounded-md hover:bg-hover transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+                    >
+                      {t('export.chooseFontFile')}
+                    </button>
+                    <Show when={fontFile()}>
+                      <span class="text-sm text-primary truncate">{getFileName(fontFile()!)}</span>
+                      <button
+                        type="button"
+                        onClick={() => setFontFile(null)}
+                        disabled={exporting()}
+                        class="text-sm text-tertiary hover:underline"
+                      >
+                        {t('export.clearFontFile')}
+                      </button>
+                    </Show>
+                  </div>
+                  <p class="text-xs text-tertiary mt-2">{t('export.fontFileHint')}</p>
+                </div>
+              </div>
+            </Show>
+
+            {/* Archive Passphrase */}
+            <Show when={selectedPlugin()?.options.includes('passphrase')}>
+              <div class="mb-6 space-y-3">
+                <div>
+                  <label
+                    for="export-passphrase"
+                    class="block text-sm font-medium text-secondary mb-2"
+                  >
+                    {t('export.passphraseLabel')}
+                  </label>
+                  <input
+                    id="export-passphrase"
+                    type="password"
+                    autocomplete="new-password"
+                    value={passphrase()}
+                    onInput={(e) => setPassphrase(e.currentTarget.value)}
+                    disabled={exporting()}
+                    class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                  />
+                </div>
+                <div>
+                  <label
+                    for="export-passphrase-confirm"
+                    class="block text-sm font-medium text-secondary mb-2"
+                  >
+                    {t('export.passphraseConfirmLabel')}
+                  </label>
+                  <input
+                    id="export-passphrase-confirm"
+                    type="password"
+                    autocomplete="new-password"
+                    value={passphraseConfirm()}
+                    onInput={(e) => setPassphraseConfirm(e.currentTarget.value)}
+                    disabled={exporting()}
+                    class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                  />
+                </div>
+                <p class="text-xs text-tertiary">{t('export.passphraseHint')}</p>
+              </div>
+            </Show>
+
             {/* Error Display */}
             <Show when={error()}>
               <div
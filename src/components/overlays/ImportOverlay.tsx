@@ -5,11 +5,17 @@ import { createLogger } from '../../lib/logger';
 import {
   listImportPlugins,
   runImportPlugin,
+  previewImport,
+  undoLastImport,
   type PluginInfo,
+  type ImportPreview,
   type ImportResult,
+  type ImportMode,
+  type UndoImportResult,
 } from '../../lib/tauri';
 import { useI18n } from '../../i18n';
 import { preferences } from '../../state/preferences';
+import { loadJournals } from '../../state/journals';
 import { X, FileUp, CheckCircle, AlertCircle } from 'lucide-solid';
 
 interface ImportOverlayProps {
@@ -29,6 +35,11 @@ export default function ImportOverlay(props: ImportOverlayProps) {
   const [importing, setImporting] = createSignal(false);
   const [error, setError] = createSignal<string | null>(null);
   const [result, setResult] = createSignal<ImportResult | null>(null);
+  const [passphrase, setPassphrase] = createSignal('');
+  const [mode, setMode] = createSignal<ImportMode>('append');
+  const [preview, setPreview] = createSignal<ImportPreview | null>(null);
+  const [previewing, setPreviewing] = createSignal(false);
+  const [undone, setUndone] = createSignal<UndoImportResult | null>(null);
 
   onMount(async () => {
     try {
@@ -49,6 +60,9 @@ export default function ImportOverlay(props: ImportOverlayProps) {
       setSelectedFile(null);
       setError(null);
       setResult(null);
+      setPreview(null);
+      setUndone(null);
+      setPassphrase('');
       props.onClose();
     }
   };
@@ -63,26 +77,68 @@ export default function ImportOverlay(props: ImportOverlayProps) {
     try {
       const plugin = selectedPlugin();
       const extensions = plugin?.file_extensions ?? ['json'];
-      const selected = await openDialog({
-        multiple: false,
-        filters: [
-          {
-            name: plugin?.name ?? 'File',
-            extensions,
-          },
-        ],
-      });
+      const selected = plugin?.directory
+        ? await openDialog({ directory: true, multiple: false })
+        : await openDialog({
+            multiple: false,
+            filters: [
+              {
+                name: plugin?.name ?? 'File',
+                extensions,
+              },
+            ],
+          });
 
       if (selected && typeof selected === 'string') {
         setSelectedFile(selected);
         setError(null);
         setResult(null);
+        setPreview(null);
       }
     } catch (err) {
       setError(err instanceof Error ? err.message : t('import.importFailed'));
     }
   };
 
+  const handlePreview = async () => {
+    const file = selectedFile();
+    const pluginId = selectedPluginId();
+    if (!file || !pluginId) return;
+    const needsPassphrase = selectedPlugin()?.options.includes('passphrase') ?? false;
+    if (needsPassphrase && !passphrase()) {
+      setError(t('import.passphraseRequired'));
+      return;
+    }
+
+    setPreviewing(true);
+    setError(null);
+    try {
+      setPreview(await previewImport(pluginId, file, needsPassphrase ? passphrase() : undefined));
+    } catch (err) {
+      log.error('Import preview failed:', err);
+      const errorMessage = err instanceof Error ? err.message : String(err);
+      setError(errorMessage || t('import.importFailed'));
+    } finally {
+      setPreviewing(false);
+    }
+  };
+
+  const handleUndo = async () => {
+    setImporting(true);
+    setError(null);
+    try {
+      const undoResult = await undoLastImport();
+      setUndone(undoResult);
+      props.onImportComplete?.();
+    } catch (err) {
+      log.error('Undo import failed:', err);
+      const errorMessage = err instanceof Error ? err.message : String(err);
+      setError(errorMessage || t('import.undoFailed'));
+    } finally {
+      setImporting(false);
+    }
+  };
+
   const handleImport = async () => {
     const file = selectedFile();
     const pluginId = selectedPluginId();
@@ -94,14 +150,29 @@ export default function ImportOverlay(props: ImportOverlayProps) {
       setError(t('import.selectFormatPlease'));
       return;
     }
+    const needsPassphrase = selectedPlugin()?.options.includes('passphrase') ?? false;
+    if (needsPassphrase && !passphrase()) {
+      setError(t('import.passphraseRequired'));
+      return;
+    }
 
     setImporting(true);
     setError(null);
     setResult(null);
+    setUndone(null);
 
     try {
-      const importResult = await runImportPlugin(pluginId, file);
+      const importResult = await runImportPlugin(
+        pluginId,
+        file,
+        needsPassphrase ? passphrase() : undefined,
+        mode(),
+      );
       setResult(importResult);
+      if (importResult.journal_restored) {
+        // The archive renamed the journal
+        await loadJournals();
+      }
       props.onImportComplete?.();
     } catch (err) {
       log.error('Import failed:', err);
@@ -165,6 +236,7 @@ export default function ImportOverlay(props: ImportOverlayProps) {
                   setSelectedFile(null);
                   setError(null);
                   setResult(null);
+                  setPreview(null);
                 }}
                 disabled={importing()}
                 class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
@@ -178,11 +250,15 @@ export default function ImportOverlay(props: ImportOverlayProps) {
             {/* File Selection */}
             <div class="mb-6">
               <label class="block text-sm font-medium text-secondary mb-2">
-                {t('import.fileLabel')}
+                {selectedPlugin()?.directory ? t('import.folderLabel') : t('import.fileLabel')}
               </label>
               <div class="flex gap-2">
                 <div class="flex-1 px-3 py-2 border border-primary rounded-md bg-tertiary text-sm text-secondary truncate">
-                  {selectedFile() ? getFileName(selectedFile()) : t('import.noFileSelected')}
+                  {selectedFile()
+                    ? getFileName(selectedFile())
+                    : selectedPlugin()?.directory
+                      ? t('import.noFolderSelected')
+                      : t('import.noFileSelected')}
                 </div>
                 <button
                   onClick={handleSelectFile}
@@ -194,6 +270,46 @@ export default function ImportOverlay(props: ImportOverlayProps) {
               </div>
             </div>
 
+            {/* Merge Mode */}
+            <div class="mb-6">
+              <label for="import-mode" class="block text-sm font-medium text-secondary mb-2">
+                {t('import.modeLabel')}
+              </label>
+              <select
+                id="import-mode"
+                value={mode()}
+                onChange={(e) => setMode(e.currentTarget.value as ImportMode)}
+                disabled={importing()}
+                class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+              >
+                <option value="append">{t('import.modeAppend')}</option>
+                <option value="skip_duplicates">{t('import.modeSkipDuplicates')}</option>
+                <option value="update_if_newer">{t('import.modeUpdateIfNewer')}</option>
+                <option value="replace_date">{t('import.modeReplaceDate')}</option>
+              </select>
+            </div>
+
+            {/* Archive Passphrase */}
+            <Show when={selectedPlugin()?.options.includes('passphrase')}>
+              <div class="mb-6">
+                <label
+                  for="import-passphrase"
+                  class="block text-sm font-medium text-secondary mb-2"
+                >
+                  {t('import.passphraseLabel')}
+                </label>
+                <input
+                  id="import-passphrase"
+                  type="password"
+                  autocomplete="off"
+                  value={passphrase()}
+                  onInput={(e) => setPassphrase(e.currentTarget.value)}
+                  disabled={importing()}
+                  class="w-full rounded-md border border-primary px-3 py-2 text-sm text-primary bg-primary focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500 disabled:bg-tertiary disabled:cursor-not-allowed"
+                />
+              </div>
+            </Show>
+
             {/* Error Display */}
             <Show when={error()}>
               <div
@@ -208,6 +324,42 @@ export default function ImportOverlay(props: ImportOverlayProps) {
               </div>
             </Show>
 
+            {/* Dry-run Preview */}
+            <Show when={preview() && !result()}>
+              <div class="mb-4 border border-primary rounded-md p-4 space-y-2 text-sm text-secondary">
+                <p class="font-medium text-primary">{t('import.previewTitle')}</p>
+                <div class="flex justify-between">
+                  <span>{t('import.previewEntries')}</span>
+                  <span class="font-semibold">{formatCount(preview()!.entry_count)}</span>
+                </div>
+                <Show when={preview()!.first_date}>
+                  <div class="flex justify-between">
+                    <span>{t('import.previewDateRange')}</span>
+                    <span class="font-semibold">
+                      {preview()!.first_date} – {preview()!.last_date}
+                    </span>
+                  </div>
+                </Show>
+                <div class="flex justify-between">
+                  <span>{t('import.previewCollisions')}</span>
+                  <span class="font-semibold">
+                    {formatCount(preview()!.colliding_dates.length)}
+                  </span>
+                </div>
+                <Show when={preview()!.sample_titles.length > 0}>
+                  <p>{t('import.previewSamples')}</p>
+                  <ul class="list-disc pl-5">
+                    <For each={preview()!.sample_titles}>{(title) => <li>{title}</li>}</For>
+                  </ul>
+                </Show>
+                <Show when={preview()!.warnings.length > 0}>
+                  <ul role="alert" class="list-disc pl-5 text-error">
+                    <For each={preview()!.warnings}>{(warning) => <li>{warning}</li>}</For>
+                  </ul>
+                </Show>
+              </div>
+            </Show>
+
             {/* Success Display */}
             <Show when={result() && !error()}>
               <div role="status" class="mb-4 bg-success border border-success rounded-md p-4">
@@ -220,13 +372,48 @@ export default function ImportOverlay(props: ImportOverlayProps) {
                     <span>{t('import.entriesImported')}</span>
                     <span class="font-semibold">{formatCount(result()!.entries_imported)}</span>
                   </div>
+                  <Show when={result()!.entries_updated > 0}>
+                    <div class="flex justify-between">
+                      <span>{t('import.entriesUpdated')}</span>
+                      <span class="font-semibold">{formatCount(result()!.entries_updated)}</span>
+                    </div>
+                  </Show>
+                  <Show when={result()!.entries_removed > 0}>
+                    <div class="flex justify-between">
+                      <span>{t('import.entriesRemoved')}</span>
+                      <span class="font-semibold">{formatCount(result()!.entries_removed)}</span>
+                    </div>
+                  </Show>
                   <Show when={result()!.entries_skipped > 0}>
                     <div class="flex justify-between">
                       <span>{t('import.entriesSkipped')}</span>
                       <span class="font-semibold">{formatCount(result()!.entries_skipped)}</span>
                     </div>
                   </Show>
+                  <Show when={result()!.journal_restored}>
+                    <p>{t('import.journalRestored')}</p>
+                  </Show>
                 </div>
+                <Show
+                  when={undone() === null}
+                  fallback={
+                    <p class="mt-3 text-sm text-success">
+                      {t('import.undone', { count: formatCount(undone()!.entries_removed) })}
+                      <Show when={undone()!.entries_kept > 0}>
+                        {' '}
+                        {t('import.undoKept', { count: formatCount(undone()!.entries_kept) })}
+                      </Show>
+                    </p>
+                  }
+                >
+                  <button
+                    onClick={handleUndo}
+                    disabled={importing()}
+                    class="mt-3 text-sm font-medium text-success underline disabled:opacity-50 disabled:cursor-not-allowed"
+                  >
+                    {t('import.undoImport')}
+                  </button>
+                </Show>
               </div>
             </Show>
 
@@ -253,6 +440,13 @@ export default function ImportOverlay(props: ImportOverlayProps) {
                 {result() ? t('common.close') : t('common.cancel')}
               </button>
               <Show when={!result()}>
+                <button
+                  onClick={handlePreview}
+                  disabled={!selectedFile() || importing() || previewing()}
+                  class="px-4 py-2 bg-tertiary text-secondary rounded-md hover:bg-hover transition-colors text-sm font-medium disabled:opacity-50 disabled:cursor-not-allowed"
+                >
+                  {t('import.preview')}
+                </button>
                 <button
                   onClick={handleImport}
                   disabled={!selectedFile() || importing()}
@@ -430,6 +430,21 @@ const en = {
     exporting: 'Exporting...',
     startExport: 'Start Export',
     exportFailed: 'Export failed',
+    passphraseLabel: 'Archive passphrase',
+    passphraseConfirmLabel: 'Confirm passphrase',
+    passphraseHint:
+      'The archive can only be opened with this passphrase. It cannot be recovered if you forget it.',
+    passphraseRequired: 'Please enter a passphrase for the archive',
+    passphraseMismatch: 'Passphrases do not match',
+    pageSizeLabel: 'Page size',
+    fontLabel: 'Font',
+    fontSizeLabel: 'Font size (pt)',
+    fontFileLabel: 'Embedded font (optional)',
+    chooseFontFile: 'Choose .ttf…',
+    clearFontFile: 'Remove',
+    fontFileHint:
+      'Standard fonts only cover Western European languages. Choose a TrueType font for other scripts.',
+    fontSizeInvalid: 'Font size must be between 6 and 24 points',
   },
 
   /** ImportOverlay */
@@ -439,16 +454,38 @@ const en = {
     description: 'Import journal entries from a file',
     formatLabel: 'Format',
     fileLabel: 'File',
+    folderLabel: 'Folder',
+    modeLabel: 'When entries already exist',
+    modeAppend: 'Add everything as new entries',
+    modeSkipDuplicates: 'Skip exact duplicates',
+    modeUpdateIfNewer: 'Update entries if the import is newer',
+    modeReplaceDate: 'Replace all entries on imported dates',
     noFileSelected: 'No file selected',
+    noFolderSelected: 'No folder selected',
     failedTitle: 'Import Failed',
     successTitle: 'Import Successful!',
     entriesImported: 'Entries imported:',
     entriesSkipped: 'Entries skipped:',
+    entriesUpdated: 'Entries updated:',
+    entriesRemoved: 'Entries replaced:',
     importing: 'Importing...',
     startImport: 'Start Import',
+    preview: 'Preview',
+    previewTitle: 'Preview (nothing imported yet)',
+    previewEntries: 'Entries in file:',
+    previewDateRange: 'Date range:',
+    previewCollisions: 'Dates that already have entries:',
+    previewSamples: 'First titles:',
     importFailed: 'Import failed',
+    undoImport: 'Undo this import',
+    undone: 'Import undone: {{ count }} entries removed',
+    undoKept: '{{ count }} entries you edited since the import were kept.',
+    journalRestored: 'The journal name and backup settings were restored from the archive.',
+    undoFailed: 'Undo failed',
     selectFilePlease: 'Please select a file first',
     selectFormatPlease: 'Please select an import format',
+    passphraseLabel: 'Archive passphrase',
+    passphraseRequired: 'Please enter the archive passphrase',
   },
 
   /** AboutOverlay */
@@ -19,6 +19,10 @@ describe('Import functions', () => {
       const mockResult: ImportResult = {
         entries_imported: 5,
         entries_skipped: 0,
+        entries_updated: 0,
+        entries_removed: 0,
+        batch_id: 1,
+        journal_restored: false,
       };
 
       mockInvoke.mockResolvedValueOnce(mockResult);
@@ -27,6 +31,7 @@ describe('Import functions', () => {
 
       expect(invoke).toHaveBeenCalledWith('import_minidiary_json', {
         filePath: '/path/to/file.json',
+        mode: 'append',
       });
       expect(result).toEqual(mockResult);
     });
@@ -43,6 +48,10 @@ describe('Import functions', () => {
       const mockResult: ImportResult = {
         entries_imported: 10,
         entries_skipped: 0,
+        entries_updated: 0,
+        entries_removed: 0,
+        batch_id: 1,
+        journal_restored: false,
       };
 
       mockInvoke.mockResolvedValueOnce(mockResult);
@@ -51,6 +60,7 @@ describe('Import functions', () => {
 
       expect(invoke).toHaveBeenCalledWith('import_dayone_json', {
         filePath: '/path/to/dayone.json',
+        mode: 'append',
       });
       expect(result).toEqual(mockResult);
     });
@@ -132,6 +132,55 @@ export async function switchJournal(id: string): Promise<void> {
   await invoke('switch_journal', { id });
 }
 
+export interface RetentionPolicy {
+  daily: number;
+  weekly: number;
+  monthly: number;
+}
+
+export async function getBackupRetention(id: string): Promise<RetentionPolicy> {
+  return await invoke('get_backup_retention', { id });
+}
+
+export async function setBackupRetention(id: string, policy: RetentionPolicy | null): Promise<void> {
+  await invoke('set_backup_retention', { id, policy });
+}
+
+export interface BackupMirror {
+  path: string;
+  retention?: RetentionPolicy | null;
+}
+
+export interface MirrorReport {
+  destination: string;
+  backup_path: string | null;
+  error: string | null;
+}
+
+export async function getBackupMirrors(id: string): Promise<BackupMirror[]> {
+  return await invoke('get_backup_mirrors', { id });
+}
+
+export async function setBackupMirrors(id: string, mirrors: BackupMirror[]): Promise<void> {
+  await invoke('set_backup_mirrors', { id, mirrors });
+}
+
+export async function getIncrementalBackups(id: string): Promise<boolean> {
+  return await invoke('get_incremental_backups', { id });
+}
+
+export async function setIncrementalBackups(id: string, enabled: boolean): Promise<void> {
+  await invoke('set_incremental_backups', { id, enabled });
+}
+
+export async function getBackupInterval(id: string): Promise<number | null> {
+  return await invoke('get_backup_interval', { id });
+}
+
+export async function setBackupInterval(id: string, minutes: number | null): Promise<void> {
+  await invoke('set_backup_interval', { id, minutes });
+}
+
 // Entry commands
 export interface DiaryEntry {
   id: number;
@@ -221,22 +270,53 @@ export async function getStatistics(): Promise<Statistics> {
 export interface ImportResult {
   entries_imported: number;
   entries_skipped: number;
+  entries_updated: number;
+  entries_removed: number;
+  batch_id: number;
+  journal_restored: boolean;
+}
+
+/** How imported entries are merged with the entries already in the journal. */
+export type ImportMode = 'append' | 'skip_duplicates' | 'update_if_newer' | 'replace_date';
+
+export async function importMiniDiaryJson(
+  filePath: string,
+  mode: ImportMode = 'append',
+): Promise<ImportResult> {
+  return await invoke('import_minidiary_json', { filePath, mode });
 }
 
-export async function importMiniDiaryJson(filePath: string): Promise<ImportResult> {
-  return await invoke('import_minidiary_json', { filePath });
+export async function importDayOneJson(
+  filePath: string,
+  mode: ImportMode = 'append',
+): Promise<ImportResult> {
+  return await invoke('import_dayone_json', { filePath, mode });
 }
 
-export async function importDayOneJson(filePath: string): Promise<ImportResult> {
-  return await invoke('import_dayone_json', { filePath });
+export async function importDayOneTxt(
+  filePath: string,
+  mode: ImportMode = 'append',
+): Promise<ImportResult> {
+  return await invoke('import_dayone_txt', { filePath, mode });
 }
 
-export async function importDayOneTxt(filePath: string): Promise<ImportResult> {
-  return await invoke('import_dayone_txt', { filePath });
+export async function importJrnlJson(
+  filePath: string,
+  mode: ImportMode = 'append',
+): Promise<ImportResult> {
+  return await invoke('import_jrnl_json', { filePath, mode });
 }
 
-export async function importJrnlJson(filePath: string): Promise<ImportResult> {
-  return await invoke('import_jrnl_json', { filePath });
+export interface UndoImportResult {
+  batch_id: number;
+  entries_removed: number;
+  /** Imported entries edited since the import, which were left in place */
+  entries_kept: number;
+}
+
+/** Removes the entries added by the most recent import. */
+export async function undoLastImport(): Promise<UndoImportResult> {
+  return await invoke('undo_last_import');
 }
 
 // Export commands
@@ -259,6 +339,25 @@ export interface PluginInfo {
   name: string;
   file_extensions: string[];
   builtin: boolean;
+  /** True if the plugin reads or writes a folder instead of a single file */
+  directory: boolean;
+  /** Settings the dialog must ask for and pass to the plugin */
+  options: PluginOption[];
+}
+
+export type PluginOption = 'passphrase' | 'page_layout';
+
+export type PageSize = 'a4' | 'a5' | 'letter';
+export type StandardFont = 'helvetica' | 'times' | 'courier';
+
+/** Page settings for exporters with the `page_layout` option */
+export interface PdfLayout {
+  page_size: PageSize;
+  font: StandardFont;
+  /** TrueType font to embed instead of `font` */
+  font_file: string | null;
+  /** Body text size in points (6–24) */
+  font_size: number;
 }
 
 export async function listImportPlugins(): Promise<PluginInfo[]> {
@@ -269,12 +368,51 @@ export async function listExportPlugins(): Promise<PluginInfo[]> {
   return await invoke('list_export_plugins');
 }
 
-export async function runImportPlugin(pluginId: string, filePath: string): Promise<ImportResult> {
-  return await invoke('run_import_plugin', { pluginId, filePath });
+export interface ImportPreview {
+  entry_count: number;
+  first_date: string | null;
+  last_date: string | null;
+  colliding_dates: string[];
+  sample_titles: string[];
+  warnings: string[];
+}
+
+/** Parses a file with an import plugin and summarizes it without importing anything. */
+export async function previewImport(
+  pluginId: string,
+  filePath: string,
+  passphrase?: string,
+): Promise<ImportPreview> {
+  return await invoke('preview_import', { pluginId, filePath, passphrase: passphrase ?? null });
 }
 
-export async function runExportPlugin(pluginId: string, filePath: string): Promise<ExportResult> {
-  return await invoke('run_export_plugin', { pluginId, filePath });
+export async function runImportPlugin(
+  pluginId: string,
+  filePath: string,
+  passphrase?: string,
+  mode: ImportMode = 'append',
+): Promise<ImportResult> {
+  return await invoke('run_import_plugin', {
+    pluginId,
+    filePath,
+    passphrase: passphrase ?? null,
+    mode,
+  });
+}
+
+/** Settings for an export; each plugin reads the fields it needs. */
+export type ExportOptions = { passphrase?: string } & Partial<PdfLayout>;
+
+export async function runExportPlugin(
+  pluginId: string,
+  filePath: string,
+  options?: ExportOptions,
+): Promise<ExportResult> {
+  return await invoke('run_export_plugin', {
+    pluginId,
+    filePath,
+    options: options ?? null,
+  });
 }
 
 // File utility commands
@@ -299,6 +437,107 @@ export async function generateDebugDump(
   return await invoke<DebugDumpResult>('generate_debug_dump', { filePath, preferencesJson });
 }
 
+// Backup commands
+export interface BackupInfo {
+  file_name: string;
+  created_at: string;
+  size_bytes: number;
+}
+
+export interface RestoreResult {
+  safety_backup: string;
+}
+
+export interface RestoreEntriesResult {
+  entries_restored: number;
+  entries_skipped: number;
+}
+
+export async function listBackups(): Promise<BackupInfo[]> {
+  return await invoke<BackupInfo[]>('list_backups');
+}
+
+export async function previewBackup(fileName: string): Promise<DiaryEntry[]> {
+  return await invoke<DiaryEntry[]>('preview_backup', { fileName });
+}
+
+export async function restoreBackup(fileName: string): Promise<RestoreResult> {
+  return await invoke<RestoreResult>('restore_backup', { fileName });
+}
+
+export async function restoreBackupEntries(
+  fileName: string,
+  entryIds: number[],
+): Promise<RestoreEntriesResult> {
+  return await invoke<RestoreEntriesResult>('restore_backup_entries', { fileName, entryIds });
+}
+
+export interface SnapshotInfo {
+  name: string;
+  created_at: string;
+  entries: number;
+  new_chunks: number;
+}
+
+export async function listIncrementalBackups(): Promise<SnapshotInfo[]> {
+  return await invoke<SnapshotInfo[]>('list_incremental_backups');
+}
+
+export async function restoreIncrementalBackup(name: string, outputPath: string): Promise<number> {
+  return await invoke<number>('restore_incremental_backup', { name, outputPath });
+}
+
+// Integrity commands
+export type IntegrityIssueKind =
+  | 'unreadable_row'
+  | 'missing_data'
+  | 'decryption_failed'
+  | 'invalid_utf8'
+  | 'invalid_date'
+  | 'malformed_auth_slot'
+  | 'orphaned_row';
+
+export interface IntegrityIssue {
+  table: string;
+  row_id: number;
+  date: string | null;
+  field: string | null;
+  kind: IntegrityIssueKind;
+  message: string;
+}
+
+export interface IntegrityReport {
+  sqlite_integrity: string[];
+  entries_checked: number;
+  entries_ok: number;
+  auth_slots_checked: number;
+  issues: IntegrityIssue[];
+}
+
+export async function verifyDiary(password?: string): Promise<IntegrityReport> {
+  return await invoke<IntegrityReport>('verify_diary', { password: password ?? null });
+}
+
+export interface QuarantinedRow {
+  row_id: number;
+  date: string | null;
+  title_encrypted: string | null;
+  text_encrypted: string | null;
+}
+
+export interface SalvageReport {
+  output_path: string;
+  quarantine_path: string | null;
+  entries_recovered: number;
+  entries_quarantined: number;
+  issues: IntegrityIssue[];
+  quarantined_rows: QuarantinedRow[];
+}
+
+export async function salvageDiary(outputPath: string, password: string): Promise<SalvageReport> {
+  return await invoke<SalvageReport>('salvage_diary', { outputPath, password });
+}
+
 // Menu commands
 export async function updateMenuLocale(locale: string): Promise<void> {
   await invoke('update_menu_locale', { locale });